@@ -0,0 +1,44 @@
+/* C interface of the todo store, built with `cargo build --release --features ffi` as
+ * libtodo.so, libtodo.dylib or todo.dll.
+ *
+ * Strings are utf-8. Strings returned are freed with todo_string_free, and calls that fail
+ * leave their message for todo_last_error. Todos come as json objects like the ones of the
+ * json export. */
+
+#ifndef TODO_H
+#define TODO_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TodoStore TodoStore;
+
+/* The message of the last call that failed on this thread, NULL when none did. It stays
+ * valid until the next call fails. */
+const char *todo_last_error(void);
+
+/* Open the todos at location like --db does, NULL on failure */
+TodoStore *todo_store_open(const char *location);
+
+void todo_store_free(TodoStore *store);
+
+/* Every todo as a json array, NULL on failure */
+char *todo_store_list(TodoStore *store);
+
+/* Add a todo, fields is a json object like {"due": "tomorrow"} or NULL. Returns the id of
+ * the todo, or 0 on failure. */
+size_t todo_store_add(TodoStore *store, const char *body, const char *fields);
+
+/* Mark a todo done, returns 0 or -1 on failure */
+int todo_store_complete(TodoStore *store, size_t id);
+
+void todo_string_free(char *text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
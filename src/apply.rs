@@ -0,0 +1,138 @@
+use crate::ids::short;
+use crate::query::parse_query;
+use crate::store::set_field;
+use crate::{Todo, collect_todos_all};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::error::Error;
+
+/// What `apply` does to every todo it matches
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Changes {
+  /// Set a field, e.g. priority=high, due=3d or project=none to clear it
+  #[arg(long, value_parser = parse_assignment)]
+  pub set: Vec<(String, String)>,
+
+  #[arg(long, value_parser = crate::tags::parse_tag)]
+  pub add_tag: Vec<String>,
+
+  #[arg(long, value_parser = crate::tags::parse_tag)]
+  pub remove_tag: Vec<String>,
+}
+
+fn parse_assignment(input: &str) -> Result<(String, String), String> {
+  match input.split_once('=') {
+    Some((field, value)) if !field.is_empty() => Ok((field.to_string(), value.to_string())),
+    _ => Err(format!("Expected field=value, got '{}'", input)),
+  }
+}
+
+impl Changes {
+  pub fn is_empty(&self) -> bool {
+    self.set.is_empty() && self.add_tag.is_empty() && self.remove_tag.is_empty()
+  }
+
+  pub fn write(&self, todo: &Todo, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    for (field, value) in &self.set {
+      let value = match value.as_str() {
+        "none" | "" => Value::Null,
+        value => Value::from(value),
+      };
+      set_field(todo.id, field, value, conn)?;
+    }
+    if !self.add_tag.is_empty() || !self.remove_tag.is_empty() {
+      let mut tags = todo.tags.clone();
+      tags.extend(&self.add_tag);
+      for tag in &self.remove_tag {
+        tags.remove(tag);
+      }
+      if tags != todo.tags {
+        set_field(todo.id, "tags", serde_json::to_value(&tags)?, conn)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Change every todo matching the filter expression at once, all of them or none
+pub fn apply(
+  filter: &str,
+  changes: &Changes,
+  dry_run: bool,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  if changes.is_empty() {
+    return Err("Nothing to change, use --set, --add-tag or --remove-tag".into());
+  }
+  let todos = parse_query(filter)?.apply(collect_todos_all(conn)?);
+  if todos.is_empty() {
+    println!("No todo matches '{}'", filter);
+    return Ok(());
+  }
+
+  // A dry run makes the changes too so mistakes show up, and then rolls them back
+  let tx = conn.unchecked_transaction()?;
+  for todo in &todos {
+    changes.write(todo, &tx)?;
+    match dry_run {
+      true => println!("Would change {}: {}", short(todo.id), todo.body),
+      false => println!("Changed {}: {}", short(todo.id), todo.body),
+    }
+  }
+  if dry_run {
+    println!("Dry run, nothing changed!");
+  } else {
+    tx.commit()?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::priority::Priority;
+  use crate::tags::Tags;
+  use crate::{AddOptions, add};
+
+  #[test]
+  fn apply_changes_every_match_or_nothing() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let work = AddOptions {
+      tags: vec!["work".to_string()],
+      ..Default::default()
+    };
+    _ = add(
+      vec!["Write report".to_string(), "Call bank".to_string()],
+      &work,
+      &conn,
+    );
+    _ = add(vec!["Buy milk".to_string()], &AddOptions::default(), &conn);
+    let changes = Changes {
+      set: vec![("priority".to_string(), "high".to_string())],
+      add_tag: vec!["q3".to_string()],
+      remove_tag: vec!["work".to_string()],
+    };
+
+    apply("tag:work", &changes, true, &conn).unwrap();
+    assert!(collect_todos_all(&conn).unwrap()[0].priority.is_none());
+    apply("tag:work and report", &changes, false, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(Some(Priority::High), todos[0].priority);
+    assert_eq!(Tags(vec!["q3".to_string()]), todos[0].tags);
+    assert_eq!(None, todos[1].priority);
+
+    // A change that fails for one todo leaves the others alone too
+    let bad = Changes {
+      set: vec![
+        ("project".to_string(), "House".to_string()),
+        ("bucket".to_string(), "next".to_string()),
+      ],
+      ..Default::default()
+    };
+    assert!(apply("all", &bad, false, &conn).is_err());
+    assert!(collect_todos_all(&conn).unwrap()[0].project.is_none());
+    assert!(apply("all", &Changes::default(), false, &conn).is_err());
+  }
+}
@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether glyphs, box drawing and emoji are written in plain ASCII, for legacy terminals and
+/// log files
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// What each glyph of the output becomes, wider ones are only used where nothing is aligned
+const GLYPHS: [(char, &str); 32] = [
+  ('○', "o"),
+  ('◐', "~"),
+  ('◷', "?"),
+  ('✔', "x"),
+  ('✘', "-"),
+  ('★', "*"),
+  ('⛔', "[blocked]"),
+  ('⚑', "[blocking]"),
+  ('■', "#"),
+  ('·', "."),
+  ('█', "#"),
+  ('░', "."),
+  ('→', "->"),
+  ('…', "..."),
+  ('•', "*"),
+  ('☐', "[ ]"),
+  ('☑', "[x]"),
+  ('×', "x"),
+  ('—', "-"),
+  ('▸', ">"),
+  ('▾', "v"),
+  ('─', "-"),
+  ('│', "|"),
+  ('┌', "+"),
+  ('┬', "+"),
+  ('┐', "+"),
+  ('├', "+"),
+  ('┼', "+"),
+  ('┤', "+"),
+  ('└', "+"),
+  ('┴', "+"),
+  ('┘', "+"),
+];
+
+pub fn init(ascii: bool) {
+  ASCII.store(ascii, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+  ASCII.load(Ordering::Relaxed)
+}
+
+fn replace(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| match GLYPHS.iter().find(|(glyph, _)| *glyph == c) {
+      Some((_, ascii)) => ascii.to_string(),
+      None => c.to_string(),
+    })
+    .collect()
+}
+
+/// `text` with its glyphs in ASCII when `--ascii` asks for it, emoji go through
+/// `emoji::display`
+pub fn text(text: &str) -> String {
+  match enabled() {
+    true => replace(text),
+    false => text.to_string(),
+  }
+}
+
+/// What truncated text ends with
+pub fn ellipsis() -> &'static str {
+  match enabled() {
+    true => "...",
+    false => "…",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn glyphs_become_ascii() {
+    assert_eq!("+- 2/5 ##... 40%", replace("└─ 2/5 ██░░░ 40%"));
+    assert_eq!("[x] Café -> [ ] 3x/week", replace("☑ Café → ☐ 3×/week"));
+    // The bottom border of the matrix
+    assert_eq!("+--+--+", replace("└──┴──┘"));
+  }
+}
@@ -0,0 +1,306 @@
+use crate::config::Confirm;
+use crate::date::parse_date;
+use crate::dedupe;
+use crate::duration::{format_duration, parse_duration};
+use crate::priority::Priority;
+use crate::prompt;
+use crate::status::Status;
+use crate::{AddOptions, Todo, insert};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use dialoguer::Editor;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+
+const HEADER: &str = "# One todo per line, [id] keeps an existing todo, lines without one are added
+# and removed lines are deleted. Trailing tokens: status: pri: due: est: project:
+";
+
+/// The parts of a todo that can be edited in the buffer
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Line {
+  id: Option<usize>,
+  body: String,
+  status: Status,
+  priority: Option<Priority>,
+  due: Option<NaiveDate>,
+  estimate: Option<u32>,
+  project: Option<String>,
+}
+
+impl Line {
+  fn from_todo(todo: &Todo) -> Line {
+    Line {
+      id: Some(todo.id),
+      body: todo.body.clone(),
+      status: todo.status,
+      priority: todo.priority,
+      due: todo.due,
+      estimate: todo.estimate,
+      project: todo.project.clone(),
+    }
+  }
+
+  fn render(&self) -> String {
+    let mut line = match self.id {
+      Some(id) => format!("[{}] {}", id, self.body),
+      None => self.body.clone(),
+    };
+    if self.status != Status::Todo {
+      line.push_str(&format!(" status:{}", self.status));
+    }
+    if let Some(priority) = self.priority {
+      line.push_str(&format!(" pri:{}", priority));
+    }
+    if let Some(due) = self.due {
+      line.push_str(&format!(" due:{}", due));
+    }
+    if let Some(estimate) = self.estimate {
+      line.push_str(&format!(" est:{}", format_duration(estimate)));
+    }
+    if let Some(project) = &self.project {
+      line.push_str(&format!(" project:{}", project));
+    }
+    line
+  }
+
+  /// Apply a trailing `key:value` token, false when it is just part of the body
+  fn apply_token(&mut self, token: &str) -> bool {
+    let Some((key, value)) = token.split_once(':') else {
+      return false;
+    };
+    match key {
+      "status" => Status::from_str(value, true).map(|status| self.status = status),
+      "pri" => Priority::from_str(value, true).map(|priority| self.priority = Some(priority)),
+      "due" => parse_date(value).map(|due| self.due = Some(due)),
+      "est" => parse_duration(value).map(|estimate| self.estimate = Some(estimate)),
+      "project" if !value.is_empty() => {
+        self.project = Some(value.to_string());
+        Ok(())
+      }
+      _ => return false,
+    }
+    .is_ok()
+  }
+
+  fn parse(text: &str) -> Option<Line> {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('#') {
+      return None;
+    }
+
+    let mut line = Line::default();
+    let mut rest = text;
+    if let Some(after) = text.strip_prefix('[')
+      && let Some((id, body)) = after.split_once(']')
+      && let Ok(id) = id.trim().parse()
+    {
+      line.id = Some(id);
+      rest = body.trim_start();
+    }
+
+    // Tokens are only read from the end so bodies can still mention "due:" and alike
+    let mut words = rest.split_whitespace().collect::<Vec<&str>>();
+    while words.len() > 1 && line.apply_token(words[words.len() - 1]) {
+      words.pop();
+    }
+    line.body = words.join(" ");
+    Some(line)
+  }
+}
+
+/// What saving the buffer changes
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+  added: Vec<Line>,
+  edited: Vec<Line>,
+  deleted: Vec<usize>,
+}
+
+fn diff(original: &[Todo], buffer: &str) -> Result<Changes, Box<dyn Error>> {
+  let mut remaining = original
+    .iter()
+    .map(|todo| (todo.id, Line::from_todo(todo)))
+    .collect::<HashMap<usize, Line>>();
+  let mut changes = Changes::default();
+
+  for line in buffer.lines().filter_map(Line::parse) {
+    match line.id {
+      None => changes.added.push(line),
+      Some(id) => {
+        let before = remaining
+          .remove(&id)
+          .ok_or(format!("Unknown or repeated id [{}] in buffer", id))?;
+        if before != line {
+          changes.edited.push(line);
+        }
+      }
+    }
+  }
+
+  let mut deleted = remaining.into_keys().collect::<Vec<usize>>();
+  deleted.sort();
+  changes.deleted = deleted;
+  Ok(changes)
+}
+
+fn apply(changes: &Changes, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  for line in &changes.added {
+    let options = AddOptions {
+      priority: line.priority,
+      due: line.due,
+      estimate: line.estimate,
+      project: line.project.clone(),
+      ..Default::default()
+    };
+    insert(&line.body, &options, &tx)?;
+    tx.execute(
+      "UPDATE todos SET status = ?1 WHERE id = last_insert_rowid()",
+      (line.status,),
+    )?;
+  }
+  for line in &changes.edited {
+    tx.execute(
+      "UPDATE todos SET body = ?1, status = ?2, priority = ?3, due = ?4, estimate = ?5, project = ?6,
+         body_hash = ?7 WHERE id = ?8",
+      (
+        &line.body,
+        line.status,
+        line.priority,
+        line.due,
+        line.estimate,
+        &line.project,
+        dedupe::body_hash(&line.body),
+        line.id,
+      ),
+    )?;
+  }
+  for id in &changes.deleted {
+    tx.execute("DELETE FROM todos WHERE id = ?1", (id,))?;
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+/// Edit many todos at once as lines of text
+pub fn edit_all(
+  todos: Vec<Todo>,
+  confirm: Confirm,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let buffer = todos
+    .iter()
+    .map(|todo| Line::from_todo(todo).render() + "\n")
+    .collect::<String>();
+
+  prompt::require("edit todos one at a time with `todo edit <id>`")?;
+  // Untested segment starts, this part needs interactivity
+  let Some(edited) = Editor::new()
+    .extension(".txt")
+    .edit(&format!("{}{}", HEADER, buffer))?
+  else {
+    println!("Nothing changed!");
+    return Ok(());
+  };
+  // Untested segment ends
+
+  let changes = diff(&todos, &edited)?;
+  let deleted = todos
+    .iter()
+    .filter(|todo| changes.deleted.contains(&todo.id))
+    .map(|todo| todo.body.clone())
+    .collect::<Vec<String>>();
+  if !confirm.ask("Delete", &deleted)? {
+    return Ok(());
+  }
+  apply(&changes, conn)?;
+  println!(
+    "Added {}, edited {}, deleted {}",
+    changes.added.len(),
+    changes.edited.len(),
+    changes.deleted.len()
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{add, collect_todos_all};
+
+  #[test]
+  fn line_round_trip() {
+    let line = Line {
+      id: Some(3),
+      body: "Taxes due: soon".to_string(),
+      status: Status::InProgress,
+      priority: Some(Priority::High),
+      due: NaiveDate::from_ymd_opt(2024, 4, 30),
+      estimate: Some(90),
+      project: Some("Home".to_string()),
+    };
+    assert_eq!(
+      "[3] Taxes due: soon status:in-progress pri:high due:2024-04-30 est:1h30m project:Home",
+      line.render()
+    );
+    assert_eq!(Some(line.clone()), Line::parse(&line.render()));
+  }
+
+  #[test]
+  fn parse_skips_comments_and_keeps_unknown_tokens() {
+    assert_eq!(None, Line::parse("# comment"));
+    assert_eq!(None, Line::parse("   "));
+    assert_eq!(
+      Some(Line {
+        body: "Call at 10:30 re:taxes".to_string(),
+        ..Default::default()
+      }),
+      Line::parse("Call at 10:30 re:taxes")
+    );
+  }
+
+  #[test]
+  fn diff_and_apply() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+
+    let buffer = "# header\n[1] Milk\n[3] Katia pri:low\nBread status:done\n";
+    let changes = diff(&todos, buffer).unwrap();
+    assert_eq!(
+      (1, 1, vec![2]),
+      (
+        changes.added.len(),
+        changes.edited.len(),
+        changes.deleted.clone()
+      )
+    );
+
+    apply(&changes, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(
+      vec![
+        ("Milk", Status::Todo, None),
+        ("Katia", Status::Todo, Some(Priority::Low)),
+        ("Bread", Status::Done, None)
+      ],
+      todos
+        .iter()
+        .map(|todo| (todo.body.as_str(), todo.status, todo.priority))
+        .collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn diff_rejects_unknown_ids() {
+    assert!(diff(&[], "[7] Ghost").is_err());
+  }
+}
@@ -0,0 +1,357 @@
+use crate::config::{self, Config, LockFile};
+use crate::db::create_db;
+use crate::lock::Lock;
+use crate::status::Status;
+use crate::storage::Storage;
+use crate::{AddOptions, collect_todos_incomplete, find_targets, insert, undo};
+use rusqlite::Connection;
+use serde_json::{Value, json};
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Which chat to answer in
+#[derive(clap::Args, Clone, Debug)]
+#[group(required = true, multiple = false)]
+pub struct Network {
+  /// The room in the `[matrix]` table of the config
+  #[arg(long)]
+  matrix: bool,
+
+  /// The channel in the `[discord]` table of the config
+  #[arg(long)]
+  discord: bool,
+}
+
+const HELP: &str = "Use `!todo add <todo>`, `!todo list` or `!todo done <id>`";
+
+/// A message from someone else in the room
+struct Message {
+  sender: String,
+  text: String,
+}
+
+trait Chat {
+  /// Wait for new messages, the ones sent before the bot started are skipped
+  fn receive(&mut self) -> Result<Vec<Message>, Box<dyn Error>>;
+
+  fn send(&mut self, text: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// The answer to a `!todo` command, `None` for every other message
+fn respond(text: &str, conn: &Connection) -> Result<Option<String>, Box<dyn Error>> {
+  let Some(command) = text.trim().strip_prefix("!todo") else {
+    return Ok(None);
+  };
+  let command = command.trim();
+  let (action, rest) = command.split_once(' ').unwrap_or((command, ""));
+  let rest = rest.trim();
+  let answer = match action {
+    "add" if !rest.is_empty() => {
+      insert(rest, &AddOptions::default(), conn)?;
+      format!("Added [{}]: {}", conn.last_insert_rowid(), rest)
+    }
+    "list" => {
+      let todos = collect_todos_incomplete(conn)?;
+      if todos.is_empty() {
+        "Nothing to do!".to_string()
+      } else {
+        todos
+          .iter()
+          .map(|todo| format!("[{}] {}", todo.id, todo.body))
+          .collect::<Vec<String>>()
+          .join("\n")
+      }
+    }
+    "done" if !rest.is_empty() => match find_targets(&[rest.to_string()], conn) {
+      Ok(targets) => {
+        for target in &targets {
+          conn.execute(
+            "UPDATE todos SET status = ?1 WHERE id = ?2",
+            (Status::Done, target.id),
+          )?;
+        }
+        targets
+          .iter()
+          .map(|target| format!("Done: {}", target.body))
+          .collect::<Vec<String>>()
+          .join("\n")
+      }
+      Err(e) => e.to_string(),
+    },
+    _ => HELP.to_string(),
+  };
+  Ok(Some(answer))
+}
+
+/// Every command gets a fresh connection so changes by others are seen and shared
+/// storages are written back right away, the lock is only held meanwhile
+fn handle(
+  message: &Message,
+  lock_file: LockFile,
+  storage: &dyn Storage,
+) -> Result<Option<String>, Box<dyn Error>> {
+  let _lock = Lock::acquire(lock_file, storage.file())?;
+  let conn = storage.open()?;
+  create_db(&conn)?;
+  let answer = respond(&message.text, &conn)?;
+  if answer.is_some() {
+    undo::record(
+      &format!("bot {}: {}", message.sender, message.text),
+      true,
+      &conn,
+    )?;
+    storage.save(&conn)?;
+  }
+  Ok(answer)
+}
+
+struct Matrix<'a> {
+  config: &'a config::Matrix,
+  token: String,
+  user_id: String,
+  room_id: String,
+  since: Option<String>,
+}
+
+impl<'a> Matrix<'a> {
+  fn connect(config: &'a config::Matrix) -> Result<Matrix<'a>, Box<dyn Error>> {
+    let mut matrix = Matrix {
+      config,
+      token: config.secret.get("matrix")?,
+      user_id: String::new(),
+      room_id: config.room.clone(),
+      since: None,
+    };
+    matrix.user_id = matrix.get("account/whoami", &[])?["user_id"]
+      .as_str()
+      .ok_or("Matrix did not say who the bot is")?
+      .to_string();
+    if config.room.starts_with('#') {
+      let path = format!("directory/room/{}", config.room.replace('#', "%23"));
+      matrix.room_id = matrix.get(&path, &[])?["room_id"]
+        .as_str()
+        .ok_or("Unknown Matrix room alias")?
+        .to_string();
+    }
+    // The first sync only finds where to start from
+    matrix.receive()?;
+    Ok(matrix)
+  }
+
+  fn url(&self, path: &str) -> String {
+    format!(
+      "{}/_matrix/client/v3/{}",
+      self.config.homeserver.trim_end_matches('/'),
+      path
+    )
+  }
+
+  fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Value, Box<dyn Error>> {
+    let mut response = ureq::get(&self.url(path))
+      .header("Authorization", &format!("Bearer {}", self.token))
+      .query_pairs(query.iter().copied())
+      .call()?;
+    Ok(response.body_mut().read_json()?)
+  }
+}
+
+impl Chat for Matrix<'_> {
+  fn receive(&mut self) -> Result<Vec<Message>, Box<dyn Error>> {
+    let filter = json!({
+      "room": { "rooms": [self.room_id], "timeline": { "types": ["m.room.message"] } }
+    })
+    .to_string();
+    let mut query = vec![("timeout", "30000"), ("filter", filter.as_str())];
+    let since = self.since.clone();
+    if let Some(since) = &since {
+      query.push(("since", since));
+    }
+    let sync = self.get("sync", &query)?;
+    let first = self.since.is_none();
+    self.since = sync["next_batch"].as_str().map(str::to_string);
+    if first {
+      return Ok(vec![]);
+    }
+    let messages = sync["rooms"]["join"][&self.room_id]["timeline"]["events"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter(|event| event["sender"].as_str() != Some(&self.user_id))
+      .filter_map(|event| {
+        Some(Message {
+          sender: event["sender"].as_str()?.to_string(),
+          text: event["content"]["body"].as_str()?.to_string(),
+        })
+      })
+      .collect();
+    Ok(messages)
+  }
+
+  fn send(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+    let transaction = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let path = format!(
+      "rooms/{}/send/m.room.message/todo{}",
+      self.room_id, transaction
+    );
+    ureq::put(&self.url(&path))
+      .header("Authorization", &format!("Bearer {}", self.token))
+      .send_json(json!({ "msgtype": "m.notice", "body": text }))?;
+    Ok(())
+  }
+}
+
+/// Discord without a gateway connection, the channel is polled every few seconds
+struct Discord<'a> {
+  config: &'a config::Discord,
+  token: String,
+  last: Option<String>,
+}
+
+impl<'a> Discord<'a> {
+  const POLL: Duration = Duration::from_secs(3);
+
+  fn connect(config: &'a config::Discord) -> Result<Discord<'a>, Box<dyn Error>> {
+    let mut discord = Discord {
+      config,
+      token: config.secret.get("discord")?,
+      last: None,
+    };
+    let latest = discord.messages(&[("limit", "1")])?;
+    discord.last = latest[0]["id"].as_str().map(str::to_string);
+    Ok(discord)
+  }
+
+  fn url(&self) -> String {
+    format!(
+      "https://discord.com/api/v10/channels/{}/messages",
+      self.config.channel
+    )
+  }
+
+  fn messages(&self, query: &[(&str, &str)]) -> Result<Value, Box<dyn Error>> {
+    let mut response = ureq::get(&self.url())
+      .header("Authorization", &format!("Bot {}", self.token))
+      .query_pairs(query.iter().copied())
+      .call()?;
+    Ok(response.body_mut().read_json()?)
+  }
+}
+
+impl Chat for Discord<'_> {
+  fn receive(&mut self) -> Result<Vec<Message>, Box<dyn Error>> {
+    thread::sleep(Discord::POLL);
+    let last = self.last.clone().unwrap_or_else(|| "0".to_string());
+    let batch = self.messages(&[("after", &last), ("limit", "50")])?;
+    // Newest come first
+    let mut events = batch.as_array().cloned().unwrap_or_default();
+    events.reverse();
+    if let Some(newest) = events.last() {
+      self.last = newest["id"].as_str().map(str::to_string);
+    }
+    let messages = events
+      .iter()
+      .filter(|event| event["author"]["bot"].as_bool() != Some(true))
+      .filter_map(|event| {
+        Some(Message {
+          sender: event["author"]["username"].as_str()?.to_string(),
+          text: event["content"].as_str()?.to_string(),
+        })
+      })
+      .collect();
+    Ok(messages)
+  }
+
+  fn send(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+    ureq::post(&self.url())
+      .header("Authorization", &format!("Bot {}", self.token))
+      .send_json(json!({ "content": text }))?;
+    Ok(())
+  }
+}
+
+// Untested segment starts, this part needs a chat server
+fn serve(
+  chat: &mut dyn Chat,
+  lock_file: LockFile,
+  storage: &dyn Storage,
+) -> Result<(), Box<dyn Error>> {
+  println!("Listening for !todo commands, stop with Ctrl-C");
+  loop {
+    let messages = match chat.receive() {
+      Ok(messages) => messages,
+      Err(e) => {
+        log::warn!("Could not receive messages: {}", e);
+        thread::sleep(Duration::from_secs(10));
+        continue;
+      }
+    };
+    for message in messages {
+      let answer = match handle(&message, lock_file, storage) {
+        Ok(answer) => answer,
+        Err(e) => Some(format!("Something went wrong: {}", e)),
+      };
+      if let Some(answer) = answer {
+        log::info!("{}: {}", message.sender, message.text);
+        chat.send(&answer)?;
+      }
+    }
+  }
+}
+
+pub fn bot(
+  network: &Network,
+  config: &Config,
+  storage: &dyn Storage,
+) -> Result<(), Box<dyn Error>> {
+  let lock_file = config.lock_file;
+  if network.matrix {
+    let config = config
+      .matrix
+      .as_ref()
+      .ok_or("Add a [matrix] table with the homeserver, room and token to the config first")?;
+    serve(&mut Matrix::connect(config)?, lock_file, storage)
+  } else {
+    let config = config
+      .discord
+      .as_ref()
+      .ok_or("Add a [discord] table with the channel and token to the config first")?;
+    serve(&mut Discord::connect(config)?, lock_file, storage)
+  }
+}
+// Untested segment ends
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::collect_todos_all;
+
+  #[test]
+  fn respond_to_commands() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let answer = |text: &str| respond(text, &conn).unwrap();
+
+    assert_eq!(None, answer("good morning"));
+    assert_eq!(Some("Nothing to do!".to_string()), answer("!todo list"));
+    assert_eq!(
+      Some("Added [1]: Buy milk".to_string()),
+      answer("!todo add Buy milk")
+    );
+    assert_eq!(
+      Some("Added [2]: Call Carl".to_string()),
+      answer("!todo add Call Carl")
+    );
+    assert_eq!(
+      Some("[1] Buy milk\n[2] Call Carl".to_string()),
+      answer("!todo list")
+    );
+    assert_eq!(Some("Done: Buy milk".to_string()), answer("!todo done 1"));
+    assert_eq!(
+      Some("No todo with id 9".to_string()),
+      answer("!todo done 9")
+    );
+    assert_eq!(Some(HELP.to_string()), answer("!todo add"));
+    assert_eq!(Status::Done, collect_todos_all(&conn).unwrap()[0].status);
+  }
+}
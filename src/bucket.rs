@@ -0,0 +1,50 @@
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+#[cfg(feature = "cli")]
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+
+/// Where a todo lives in the GTD flow
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+  /// Captured but not looked at yet
+  #[default]
+  Inbox,
+  /// Processed and actionable
+  Next,
+  /// Deferred until further notice
+  Someday,
+}
+
+impl Bucket {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Bucket::Inbox => "inbox",
+      Bucket::Next => "next",
+      Bucket::Someday => "someday",
+    }
+  }
+}
+
+#[cfg(feature = "cli")]
+impl ToSql for Bucket {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(self.as_str()))
+  }
+}
+
+#[cfg(feature = "cli")]
+impl FromSql for Bucket {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    match value.as_str()? {
+      "inbox" => Ok(Bucket::Inbox),
+      "next" => Ok(Bucket::Next),
+      "someday" => Ok(Bucket::Someday),
+      other => Err(FromSqlError::Other(
+        format!("Unknown bucket '{}'", other).into(),
+      )),
+    }
+  }
+}
@@ -0,0 +1,153 @@
+use crate::Todo;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// A markdown `- [ ]` line inside the notes of a todo
+#[derive(Debug, PartialEq)]
+pub struct Item {
+  pub checked: bool,
+  pub text: String,
+  /// Line of the notes the item is on
+  line: usize,
+}
+
+/// Split a line into its checkbox state and text, if it is a checklist item
+pub fn parse_line(line: &str) -> Option<(bool, &str)> {
+  let rest = line.trim_start();
+  let rest = rest
+    .strip_prefix("- ")
+    .or_else(|| rest.strip_prefix("* "))?;
+  let (checked, text) = if let Some(text) = rest.strip_prefix("[ ]") {
+    (false, text)
+  } else if let Some(text) = rest
+    .strip_prefix("[x]")
+    .or_else(|| rest.strip_prefix("[X]"))
+  {
+    (true, text)
+  } else {
+    return None;
+  };
+  Some((checked, text.trim()))
+}
+
+pub fn items(notes: &str) -> Vec<Item> {
+  notes
+    .lines()
+    .enumerate()
+    .filter_map(|(line, text)| {
+      parse_line(text).map(|(checked, text)| Item {
+        checked,
+        text: text.to_string(),
+        line,
+      })
+    })
+    .collect()
+}
+
+/// Checked and total items, `None` when the notes have no checklist
+pub fn progress(notes: &str) -> Option<(usize, usize)> {
+  let items = items(notes);
+  if items.is_empty() {
+    return None;
+  }
+  let checked = items.iter().filter(|item| item.checked).count();
+  Some((checked, items.len()))
+}
+
+/// Flip the checkbox of the item at the 1-based `index`
+fn toggle_item(notes: &str, index: usize) -> Result<String, Box<dyn Error>> {
+  let items = items(notes);
+  let item = index
+    .checked_sub(1)
+    .and_then(|index| items.get(index))
+    .ok_or(format!(
+      "No checklist item {}, there are {}",
+      index,
+      items.len()
+    ))?;
+
+  let lines = notes
+    .lines()
+    .enumerate()
+    .map(|(number, line)| {
+      if number != item.line {
+        return line.to_string();
+      }
+      // The checkbox comes right after the indentation and the "- " bullet
+      let from = line.len() - line.trim_start().len() + 2;
+      let to = if item.checked { "[ ]" } else { "[x]" };
+      format!("{}{}{}", &line[..from], to, &line[from + 3..])
+    })
+    .collect::<Vec<String>>();
+
+  let mut toggled = lines.join("\n");
+  if notes.ends_with('\n') {
+    toggled.push('\n');
+  }
+  Ok(toggled)
+}
+
+fn print_items(todo: &Todo, notes: &str) {
+  println!("{}", todo.body);
+  for (number, item) in items(notes).iter().enumerate() {
+    let mark = if item.checked { "x" } else { " " };
+    println!("{}. [{}] {}", number + 1, mark, item.text);
+  }
+}
+
+/// Toggle a checklist item, or show the checklist when no item is given
+pub fn check(target: Todo, index: Option<usize>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let notes = target.notes.clone().unwrap_or_default();
+  let Some(index) = index else {
+    print_items(&target, &notes);
+    return Ok(());
+  };
+
+  let notes = toggle_item(&notes, index)?;
+  conn.execute(
+    "UPDATE todos SET notes = ?1 where id is ?2",
+    (&notes, target.id),
+  )?;
+  print_items(&target, &notes);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const NOTES: &str =
+    "Groceries for the week\n- [ ] milk\n- [x] eggs\n  * [X] flour\n- not an item\n";
+
+  #[test]
+  fn items_and_progress() {
+    let items = items(NOTES);
+    assert_eq!(
+      vec![(false, "milk"), (true, "eggs"), (true, "flour")],
+      items
+        .iter()
+        .map(|item| (item.checked, item.text.as_str()))
+        .collect::<Vec<(bool, &str)>>()
+    );
+    assert_eq!(Some((2, 3)), progress(NOTES));
+    assert_eq!(None, progress("Just some notes"));
+  }
+
+  #[test]
+  fn toggle_item_flips_only_that_line() {
+    let notes = toggle_item(NOTES, 1).unwrap();
+    assert_eq!(
+      "Groceries for the week\n- [x] milk\n- [x] eggs\n  * [X] flour\n- not an item\n",
+      notes
+    );
+    let notes = toggle_item(&notes, 3).unwrap();
+    assert!(notes.contains("  * [ ] flour"));
+    assert_eq!(Some((2, 3)), progress(&notes));
+  }
+
+  #[test]
+  fn toggle_item_out_of_range() {
+    assert!(toggle_item(NOTES, 0).is_err());
+    assert!(toggle_item(NOTES, 4).is_err());
+  }
+}
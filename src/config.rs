@@ -0,0 +1,401 @@
+use crate::cron::Job;
+use crate::date::{DateFormat, Dates, WeekStart};
+use crate::filter::Matcher;
+use crate::notify::Window;
+use crate::search::Tokenizer;
+use crate::sort::Sort;
+use crate::tags::Color;
+use dialoguer::Confirm as Prompt;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs};
+
+/// When to ask before deleting todos
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Confirm {
+  Always,
+  Never,
+  /// Only when more than one todo goes at once
+  #[default]
+  Bulk,
+}
+
+impl Confirm {
+  fn applies_to(self, count: usize) -> bool {
+    match self {
+      Confirm::Always => count > 0,
+      Confirm::Never => false,
+      Confirm::Bulk => count > 1,
+    }
+  }
+
+  /// Summarize what is about to be removed and ask, true when it may go ahead
+  pub fn ask(self, action: &str, bodies: &[String]) -> Result<bool, Box<dyn Error>> {
+    if !self.applies_to(bodies.len()) {
+      return Ok(true);
+    }
+    println!("{} {} todos:", action, bodies.len());
+    for body in bodies {
+      println!("  {}", body);
+    }
+    crate::prompt::require("set confirm = \"never\" to go ahead without asking")?;
+    // Untested segment starts, this part needs interactivity
+    let proceed = Prompt::new().with_prompt("Continue?").interact()?;
+    if !proceed {
+      println!("Cancelled, nothing changed!");
+    }
+    Ok(proceed)
+    // Untested segment ends
+  }
+}
+
+/// What happens to open todos once their `--until` date has passed
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Expired {
+  /// Mark them cancelled, `clean` removes them with the other completed todos
+  #[default]
+  Cancel,
+  Delete,
+}
+
+/// What happens to `:rocket:` style shortcodes in bodies
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Emoji {
+  /// Leave them as typed
+  Off,
+  /// Show them as emoji but keep the shortcodes in the todos
+  #[default]
+  Display,
+  /// Replace them when todos are added or edited
+  Store,
+}
+
+/// Which keys the terminal ui responds to
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Keymap {
+  /// Arrow keys, space to complete
+  #[default]
+  Default,
+  /// `j`/`k`, `gg`/`G`, `dd` to delete, `x` to complete and `u` to undo
+  Vim,
+}
+
+/// Keys of an action in the `[keys]` table, in vim notation like `gg` or `<c-d>`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Bindings {
+  One(String),
+  /// An empty list leaves the action without keys
+  Many(Vec<String>),
+}
+
+impl Bindings {
+  pub fn keys(&self) -> Vec<&str> {
+    match self {
+      Bindings::One(keys) => vec![keys.as_str()],
+      Bindings::Many(keys) => keys.iter().map(|keys| keys.as_str()).collect(),
+    }
+  }
+}
+
+/// A credential written in the config, or printed by a command so it can live in a
+/// keyring, e.g. `secret-tool lookup service jira`
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Secret {
+  token: Option<String>,
+  token_command: Option<String>,
+}
+
+impl Secret {
+  pub fn get(&self, name: &str) -> Result<String, Box<dyn Error>> {
+    if let Some(token) = &self.token {
+      return Ok(token.clone());
+    }
+    let Some(command) = &self.token_command else {
+      return Err(format!("Set token or token_command for {} in the config", name).into());
+    };
+    let output = Command::new("sh").arg("-c").arg(command).output()?;
+    if !output.status.success() {
+      return Err(format!("token_command for {} failed: {}", name, output.status).into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+  }
+}
+
+/// The `[jira]` table, issues are linked by their key
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Jira {
+  /// e.g. `https://example.atlassian.net`
+  pub url: String,
+  /// Cloud logs in with the email and an API token, without one the token is a bearer token
+  pub email: Option<String>,
+  #[serde(flatten)]
+  pub secret: Secret,
+  /// Transition applied when a linked todo is done
+  #[serde(default = "Jira::default_transition")]
+  pub done_transition: String,
+  /// Which issues `jira pull` imports
+  #[serde(default = "Jira::default_jql")]
+  pub jql: String,
+}
+
+impl Jira {
+  fn default_transition() -> String {
+    "Done".to_string()
+  }
+
+  fn default_jql() -> String {
+    "assignee = currentUser() AND statusCategory != Done ORDER BY created".to_string()
+  }
+}
+
+/// The `[gitlab]` table, issues are linked by their full reference like `group/project#12`
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Gitlab {
+  /// The instance, self-hosted ones included
+  #[serde(default = "Gitlab::default_url")]
+  pub url: String,
+  #[serde(flatten)]
+  pub secret: Secret,
+}
+
+impl Gitlab {
+  fn default_url() -> String {
+    "https://gitlab.com".to_string()
+  }
+}
+
+/// The `[slack]` table
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Slack {
+  /// Incoming webhook url of the channel to post to
+  pub webhook: String,
+}
+
+/// The `[matrix]` table for `bot --matrix`
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Matrix {
+  /// e.g. `https://matrix.org`
+  pub homeserver: String,
+  /// Id like `!abc:matrix.org` or alias like `#team:matrix.org` of a room the bot has joined
+  pub room: String,
+  /// Access token of the bot's account
+  #[serde(flatten)]
+  pub secret: Secret,
+}
+
+/// The `[discord]` table for `bot --discord`
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Discord {
+  /// Id of the channel to answer in
+  pub channel: String,
+  /// Bot token, the bot needs the message content intent
+  #[serde(flatten)]
+  pub secret: Secret,
+}
+
+/// When commands take a `<file>.lock` next to the todos
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockFile {
+  /// When the file is on an NFS or SMB share, which is only detected on Linux
+  #[default]
+  Auto,
+  Always,
+  Never,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+  pub confirm: Confirm,
+  pub expired: Expired,
+  pub emoji: Emoji,
+  /// Like `--accessible` on every command
+  pub accessible: bool,
+  /// Like `--ascii` on every command
+  pub ascii: bool,
+  /// How list and show write dates, `--dates` overrides it
+  pub dates: Dates,
+  /// strftime pattern of the dates shown, or `locale`
+  pub date_format: DateFormat,
+  /// First day of the week in week views, or `locale`
+  pub week_start: WeekStart,
+  pub keymap: Keymap,
+  /// Actions of the terminal ui bound to other keys than the keymap has for them
+  pub keys: BTreeMap<String, Bindings>,
+  /// Lua scripts that handle the changes of every command with `todo.on`
+  pub scripts: Vec<PathBuf>,
+  /// Used when `--db` is not given
+  pub db: Option<String>,
+  pub lock_file: LockFile,
+  /// How pickers and the terminal ui's filter match what is typed
+  pub matcher: Matcher,
+  /// How list sorts when `--sort` isn't given, like `priority:desc,due:asc`
+  pub sort: Option<Sort>,
+  /// Colors of tag badges like `shopping = "green"`, `todo tag color` sets them too
+  pub tag_colors: BTreeMap<String, Color>,
+  /// How `search` splits todos into words, `unicode61` or `trigram` for other languages than
+  /// English
+  pub search_tokenizer: Tokenizer,
+  /// Where verbose output goes instead of stderr
+  pub log_file: Option<PathBuf>,
+  /// The hash `todo lock --hash` prints as the token, which `todo unlock` asks the
+  /// passphrase of
+  pub passphrase: Option<Secret>,
+  pub jira: Option<Jira>,
+  pub gitlab: Option<Gitlab>,
+  pub slack: Option<Slack>,
+  pub matrix: Option<Matrix>,
+  pub discord: Option<Discord>,
+  /// Commands `schedule run` runs on cron schedules
+  pub schedule: Vec<Job>,
+  /// Windows like `22:00-07:00` that `notify send` holds notifications back in
+  pub quiet_hours: Vec<Window>,
+}
+
+impl Config {
+  /// `$TODO_CONFIG`, or `todo/config.toml` in the XDG config directory
+  fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("TODO_CONFIG") {
+      return Some(path.into());
+    }
+    let base = env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("todo").join("config.toml"))
+  }
+
+  /// Read the config file, falling back to the defaults when there is none
+  pub fn load() -> Result<Config, Box<dyn Error>> {
+    let Some(path) = Config::path().filter(|path| path.exists()) else {
+      return Ok(Config::default());
+    };
+    let text = fs::read_to_string(&path)?;
+    Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+  }
+
+  fn parse(text: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(text)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_config() {
+    assert_eq!(Config::default(), Config::parse("").unwrap());
+    assert_eq!(
+      Confirm::Never,
+      Config::parse("confirm = \"never\"").unwrap().confirm
+    );
+    assert!(Config::parse("confirm = \"sometimes\"").is_err());
+    assert_eq!(
+      Expired::Delete,
+      Config::parse("expired = \"delete\"").unwrap().expired
+    );
+    assert_eq!(
+      Emoji::Store,
+      Config::parse("emoji = \"store\"").unwrap().emoji
+    );
+    assert_eq!(
+      Dates::Relative,
+      Config::parse("dates = \"relative\"").unwrap().dates
+    );
+    assert_eq!(
+      chrono::Weekday::Sun,
+      Config::parse("week_start = \"Sun\"").unwrap().week_start.0
+    );
+    assert!(Config::parse("date_format = \"%d.%m.%Y\"").is_ok());
+    assert_eq!(
+      Keymap::Vim,
+      Config::parse("keymap = \"vim\"").unwrap().keymap
+    );
+    assert_eq!(
+      vec!["x"],
+      Config::parse("[keys]\ntoggle = \"x\"\nundo = []")
+        .unwrap()
+        .keys["toggle"]
+        .keys()
+    );
+    assert_eq!(
+      Some(PathBuf::from("/tmp/todo.log")),
+      Config::parse("log_file = \"/tmp/todo.log\"")
+        .unwrap()
+        .log_file
+    );
+    let matcher = Config::parse("[matcher]\nmode = \"substring\"\ncase = \"ignore\"")
+      .unwrap()
+      .matcher;
+    assert_eq!(crate::filter::Mode::Substring, matcher.mode);
+    assert!(matcher.highlight);
+    assert_eq!(
+      Tokenizer::Trigram,
+      Config::parse("search_tokenizer = \"trigram\"")
+        .unwrap()
+        .search_tokenizer
+    );
+    assert_eq!(
+      LockFile::Always,
+      Config::parse("lock_file = \"always\"").unwrap().lock_file
+    );
+  }
+
+  #[test]
+  fn parse_jira() {
+    let config = Config::parse(
+      "[jira]\nurl = \"https://example.atlassian.net\"\ntoken_command = \"echo secret\"",
+    )
+    .unwrap();
+    let jira = config.jira.unwrap();
+    assert_eq!("Done", jira.done_transition);
+    assert_eq!("secret", jira.secret.get("jira").unwrap());
+    assert!(Secret::default().get("jira").is_err());
+
+    let config = Config::parse("[gitlab]\ntoken = \"secret\"").unwrap();
+    assert_eq!("https://gitlab.com", config.gitlab.unwrap().url);
+  }
+
+  #[test]
+  fn parse_schedule() {
+    let config = Config::parse(
+      "[[schedule]]\ncron = \"@daily\"\ncommand = \"snapshot create\"\n\n\
+       [[schedule]]\ncron = \"0 9 * * mon\"\ncommand = [\"add\", \"Plan the week\"]",
+    )
+    .unwrap();
+    assert_eq!(
+      vec!["snapshot", "create"],
+      config.schedule[0].command.args()
+    );
+    assert_eq!(
+      vec!["add", "Plan the week"],
+      config.schedule[1].command.args()
+    );
+    assert_eq!(
+      1,
+      Config::parse("quiet_hours = [\"22:00-07:00\"]")
+        .unwrap()
+        .quiet_hours
+        .len()
+    );
+    assert!(Config::parse("[[schedule]]\ncron = \"daily\"\ncommand = \"clean\"").is_err());
+  }
+
+  #[test]
+  fn confirm_applies_to() {
+    assert!(!Confirm::Bulk.applies_to(1));
+    assert!(Confirm::Bulk.applies_to(2));
+    assert!(Confirm::Always.applies_to(1));
+    assert!(!Confirm::Never.applies_to(9));
+    assert!(Confirm::Never.ask("Remove", &["Milk".to_string()]).unwrap());
+  }
+}
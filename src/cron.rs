@@ -0,0 +1,316 @@
+use chrono::{Datelike, Duration, Local, NaiveDateTime, Timelike};
+use clap::Subcommand;
+use serde::Deserialize;
+use std::error::Error;
+use std::process::Command;
+use std::{env, thread};
+
+const MONTHS: [&str; 12] = [
+  "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Enough days to reach the next February 29th
+const HORIZON: i64 = 366 * 8;
+
+/// A cron expression like `30 9 * * mon-fri` or `@daily`, in local time. Each field is a
+/// set of allowed values as bits.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Cron {
+  text: String,
+  minutes: u64,
+  hours: u64,
+  days: u64,
+  months: u64,
+  weekdays: u64,
+  /// With both day of month and weekday restricted either one matching is enough, like cron
+  either_day: bool,
+}
+
+/// One comma separated field, `*`, `5`, `1-5`, `*/15` and `mon-fri` style parts
+fn field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+  let value = |text: &str| {
+    names
+      .iter()
+      .position(|name| name.eq_ignore_ascii_case(text))
+      .map(|index| index as u32 + min)
+      .or_else(|| text.parse().ok())
+      .filter(|value| (min..=max).contains(value))
+      .ok_or_else(|| format!("{} isn't between {} and {}", text, min, max))
+  };
+  let mut bits = 0;
+  for part in text.split(',') {
+    let (range, step) = match part.split_once('/') {
+      Some((range, step)) => (
+        range,
+        step
+          .parse::<u32>()
+          .ok()
+          .filter(|step| *step > 0)
+          .ok_or_else(|| format!("Bad step {}", step))?,
+      ),
+      None => (part, 1),
+    };
+    let (start, end) = match range.split_once('-') {
+      _ if range == "*" => (min, max),
+      Some((start, end)) => (value(start)?, value(end)?),
+      // `5/10` counts from 5
+      None if step > 1 => (value(range)?, max),
+      None => (value(range)?, value(range)?),
+    };
+    if start > end {
+      return Err(format!("{} runs backwards", range));
+    }
+    for value in (start..=end).step_by(step as usize) {
+      bits |= 1 << value;
+    }
+  }
+  Ok(bits)
+}
+
+impl TryFrom<String> for Cron {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Cron, String> {
+    let expanded = match text.as_str() {
+      "@hourly" => "0 * * * *",
+      "@daily" | "@midnight" => "0 0 * * *",
+      "@weekly" => "0 0 * * 0",
+      "@monthly" => "0 0 1 * *",
+      "@yearly" | "@annually" => "0 0 1 1 *",
+      text => text,
+    };
+    let fields = expanded.split_whitespace().collect::<Vec<_>>();
+    let [minutes, hours, days, months, weekdays] = fields[..] else {
+      return Err(format!(
+        "{} needs minute, hour, day, month and weekday",
+        text
+      ));
+    };
+    let weekdays_bits = field(weekdays, 0, 7, &WEEKDAYS)?;
+    Ok(Cron {
+      minutes: field(minutes, 0, 59, &[])?,
+      hours: field(hours, 0, 23, &[])?,
+      days: field(days, 1, 31, &[])?,
+      months: field(months, 1, 12, &MONTHS)?,
+      // 7 is Sunday as well
+      weekdays: (weekdays_bits | weekdays_bits >> 7) & 0x7f,
+      either_day: !days.starts_with('*') && !weekdays.starts_with('*'),
+      text,
+    })
+  }
+}
+
+impl Cron {
+  fn day_matches(&self, time: NaiveDateTime) -> bool {
+    let day = self.days & 1 << time.day() != 0;
+    let weekday = self.weekdays & 1 << time.weekday().num_days_from_sunday() != 0;
+    self.months & 1 << time.month() != 0
+      && match self.either_day {
+        true => day || weekday,
+        false => day && weekday,
+      }
+  }
+
+  /// The first minute after `after` the expression matches, None for ones like `0 0 30 2 *`
+  pub fn next(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+    let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+    for days in 0..HORIZON {
+      let date = start.date() + Duration::days(days);
+      let midnight = date.and_hms_opt(0, 0, 0)?;
+      if !self.day_matches(midnight) {
+        continue;
+      }
+      // The day of `after` only counts from then on
+      let first = match days {
+        0 => start.hour() * 60 + start.minute(),
+        _ => 0,
+      };
+      let found = (first..24 * 60).find(|minute| {
+        self.hours & 1 << (minute / 60) != 0 && self.minutes & 1 << (minute % 60) != 0
+      });
+      if let Some(minute) = found {
+        return Some(midnight + Duration::minutes(minute as i64));
+      }
+    }
+    None
+  }
+}
+
+/// The arguments of a todo command, a string is split at whitespace
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Words {
+  Line(String),
+  Args(Vec<String>),
+}
+
+impl Words {
+  pub fn args(&self) -> Vec<&str> {
+    match self {
+      Words::Line(line) => line.split_whitespace().collect(),
+      Words::Args(args) => args.iter().map(|arg| arg.as_str()).collect(),
+    }
+  }
+}
+
+/// A `[[schedule]]` table, e.g. `cron = "0 18 * * fri"` with `command = "clean"`
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Job {
+  pub cron: Cron,
+  /// What to run, like `slack post`, `snapshot create` or `maintain`
+  pub command: Words,
+}
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Show the `[[schedule]]` jobs of the config and when they run next
+  List {},
+
+  /// Run the jobs whenever they are due until stopped, `install-service --user schedule
+  /// run` keeps this running in the background
+  Run {},
+}
+
+/// When the next jobs are due after `after` and which ones those are
+fn due(jobs: &[Job], after: NaiveDateTime) -> Option<(NaiveDateTime, Vec<&Job>)> {
+  let next = jobs.iter().filter_map(|job| job.cron.next(after)).min()?;
+  let due = jobs
+    .iter()
+    .filter(|job| job.cron.next(after) == Some(next))
+    .collect();
+  Some((next, due))
+}
+
+fn list(jobs: &[Job]) {
+  if jobs.is_empty() {
+    println!("Nothing scheduled, add [[schedule]] tables with cron and command to the config!");
+    return;
+  }
+  let width = jobs
+    .iter()
+    .map(|job| job.cron.text.len())
+    .max()
+    .unwrap_or(0);
+  let now = Local::now().naive_local();
+  for job in jobs {
+    let next = job.cron.next(now).map_or("never".to_string(), |next| {
+      next.format("%Y-%m-%d %H:%M").to_string()
+    });
+    println!(
+      "{:<width$}  {:<16}  next {}",
+      job.cron.text,
+      job.command.args().join(" "),
+      next
+    );
+  }
+}
+
+// Untested segment starts, this part waits for the clock
+fn run(jobs: &[Job], db: &str) -> Result<(), Box<dyn Error>> {
+  let exe = env::current_exe()?;
+  let mut after = Local::now().naive_local();
+  while let Some((next, due)) = due(jobs, after) {
+    if let Ok(wait) = (next - Local::now().naive_local()).to_std() {
+      thread::sleep(wait);
+    }
+    for job in due {
+      let args = job.command.args();
+      // Jobs run as commands of their own, so each one locks and saves like any other
+      let status = Command::new(&exe)
+        .args(["--db", db, "--no-input"])
+        .args(&args)
+        .status();
+      match status {
+        Ok(status) if status.success() => log::info!("Ran {}", args.join(" ")),
+        Ok(status) => log::warn!("{} failed: {}", args.join(" "), status),
+        Err(e) => log::warn!("Could not run {}: {}", args.join(" "), e),
+      }
+    }
+    // Runs missed while asleep are skipped rather than caught up on
+    after = next.max(Local::now().naive_local());
+  }
+  Err("None of the jobs can run again".into())
+}
+// Untested segment ends
+
+pub fn schedule(action: &Action, jobs: &[Job], db: &str) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::List {} => {
+      list(jobs);
+      Ok(())
+    }
+    Action::Run {} if jobs.is_empty() => Err("Nothing is scheduled in the config".into()),
+    Action::Run {} => run(jobs, db),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::NaiveDate;
+
+  fn cron(text: &str) -> Cron {
+    Cron::try_from(text.to_string()).unwrap()
+  }
+
+  fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    // 2024-01-01 is a Monday
+    NaiveDate::from_ymd_opt(2024, 1, day)
+      .unwrap()
+      .and_hms_opt(hour, minute, 0)
+      .unwrap()
+  }
+
+  #[test]
+  fn cron_finds_the_next_run() {
+    assert_eq!(
+      Some(at(1, 9, 30)),
+      cron("30 9 * * mon-fri").next(at(1, 8, 0))
+    );
+    assert_eq!(Some(at(2, 9, 30)), cron("30 9 * * 1-5").next(at(1, 9, 30)));
+    assert_eq!(Some(at(8, 9, 30)), cron("30 9 * * 1-5").next(at(5, 10, 0)));
+    assert_eq!(Some(at(1, 8, 45)), cron("*/15 * * * *").next(at(1, 8, 44)));
+    assert_eq!(Some(at(7, 0, 0)), cron("@weekly").next(at(1, 0, 0)));
+    assert_eq!(Some(at(7, 0, 0)), cron("0 0 * * 7").next(at(1, 0, 0)));
+    // The 15th or any Friday
+    assert_eq!(Some(at(5, 12, 0)), cron("0 12 15 * fri").next(at(1, 0, 0)));
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 2, 29)
+        .unwrap()
+        .and_hms_opt(0, 0, 0),
+      cron("0 0 29 feb *").next(at(1, 0, 0))
+    );
+    assert_eq!(None, cron("0 0 30 2 *").next(at(1, 0, 0)));
+    for bad in [
+      "* * * *",
+      "60 * * * *",
+      "5-1 * * * *",
+      "*/0 * * * *",
+      "0 0 * * fun",
+    ] {
+      assert!(Cron::try_from(bad.to_string()).is_err(), "{}", bad);
+    }
+  }
+
+  #[test]
+  fn jobs_due_at_the_same_minute_run_together() {
+    let job = |cron: &str, command: &str| Job {
+      cron: super::tests::cron(cron),
+      command: Words::Line(command.to_string()),
+    };
+    let jobs = [
+      job("0 18 * * fri", "clean"),
+      job("@daily", "snapshot create"),
+      job("0 0 * * 6", "maintain"),
+    ];
+    let (next, due) = due(&jobs, at(5, 19, 0)).unwrap();
+    assert_eq!(at(6, 0, 0), next);
+    assert_eq!(
+      vec![vec!["snapshot", "create"], vec!["maintain"]],
+      due.iter().map(|job| job.command.args()).collect::<Vec<_>>()
+    );
+  }
+}
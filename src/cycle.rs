@@ -0,0 +1,201 @@
+use crate::status::Status;
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Percentiles shown for every state
+const PERCENTILES: [usize; 3] = [50, 75, 90];
+
+/// A creation or status change of one todo, in the order they happened
+#[derive(Debug, PartialEq)]
+struct Change {
+  todo_id: usize,
+  at: DateTime<Utc>,
+  /// None for the creation
+  old: Option<String>,
+  new: Option<String>,
+}
+
+/// History is kept for removed todos too, so they count as well
+fn collect_changes(conn: &Connection) -> Result<Vec<Change>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT todo_id, changed_at, field, old, new FROM history
+     WHERE field in ('created', 'status') ORDER BY todo_id, id",
+  )?;
+  let changes = stmt
+    .query_map([], |row| {
+      let field: String = row.get(2)?;
+      let status = |index| match field.as_str() {
+        "status" => row.get(index),
+        _ => Ok(None),
+      };
+      Ok(Change {
+        todo_id: row.get(0)?,
+        at: row.get(1)?,
+        old: status(3)?,
+        new: status(4)?,
+      })
+    })?
+    .collect::<Result<Vec<Change>, _>>()?;
+  Ok(changes)
+}
+
+/// Seconds spent per visit of each state, and from creation to the last completion. Only
+/// visits that ended count, time in the current state is still growing.
+#[derive(Debug, Default, PartialEq)]
+struct Durations {
+  states: HashMap<String, Vec<i64>>,
+  lead: Vec<i64>,
+}
+
+fn durations(changes: &[Change]) -> Durations {
+  let mut durations = Durations::default();
+  for todo in changes.chunk_by(|a, b| a.todo_id == b.todo_id) {
+    // Todos from before history was kept have no creation to start from
+    let Some(created) = todo
+      .iter()
+      .find(|change| change.old.is_none() && change.new.is_none())
+    else {
+      continue;
+    };
+    let mut since = created.at;
+    let mut completed = None;
+    for change in todo.iter().filter(|change| change.new.is_some()) {
+      if let Some(old) = &change.old {
+        durations
+          .states
+          .entry(old.clone())
+          .or_default()
+          .push((change.at - since).num_seconds());
+      }
+      since = change.at;
+      completed = match change.new.as_deref() {
+        Some("done") => Some(change.at),
+        _ => None,
+      };
+    }
+    if let Some(completed) = completed {
+      durations.lead.push((completed - created.at).num_seconds());
+    }
+  }
+  durations
+}
+
+/// Nearest rank percentile of sorted seconds
+fn percentile(sorted: &[i64], percent: usize) -> i64 {
+  let rank = (sorted.len() * percent).div_ceil(100).max(1);
+  sorted[rank - 1]
+}
+
+/// `45m`, `5.5h` or `3.2d`
+fn format_span(seconds: i64) -> String {
+  match seconds {
+    ..3_600 => format!("{}m", seconds / 60),
+    3_600..86_400 => format!("{:.1}h", seconds as f64 / 3_600.0),
+    _ => format!("{:.1}d", seconds as f64 / 86_400.0),
+  }
+}
+
+fn row(name: &str, seconds: &[i64]) -> String {
+  let mut sorted = seconds.to_vec();
+  sorted.sort();
+  let percentiles = PERCENTILES
+    .iter()
+    .map(|percent| format!("{:>8}", format_span(percentile(&sorted, *percent))))
+    .collect::<String>();
+  format!("{:<12}{:>6}{}", name, sorted.len(), percentiles)
+}
+
+fn render(durations: &Durations) -> Vec<String> {
+  let header = PERCENTILES
+    .iter()
+    .map(|percent| format!("{:>8}", format!("p{}", percent)))
+    .collect::<String>();
+  let mut lines = vec![format!("{:<12}{:>6}{}", "State", "Count", header)];
+  for status in Status::ALL {
+    if let Some(seconds) = durations.states.get(status.as_str()) {
+      lines.push(row(status.as_str(), seconds));
+    }
+  }
+  if !durations.lead.is_empty() {
+    lines.push(String::new());
+    lines.push(row("lead time", &durations.lead));
+  }
+  lines
+}
+
+pub fn cycle_time(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let durations = durations(&collect_changes(conn)?);
+  if durations == Durations::default() {
+    println!("No status changes yet!");
+    return Ok(());
+  }
+  for line in render(&durations) {
+    println!("{}", line);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::TimeZone;
+
+  fn change(todo_id: usize, hour: u32, old: Option<&str>, new: Option<&str>) -> Change {
+    Change {
+      todo_id,
+      at: Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap(),
+      old: old.map(str::to_string),
+      new: new.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn time_in_each_state_and_lead_time() {
+    let changes = vec![
+      change(1, 0, None, None),
+      change(1, 2, Some("todo"), Some("in-progress")),
+      change(1, 5, Some("in-progress"), Some("done")),
+      change(2, 1, None, None),
+      change(2, 2, Some("todo"), Some("done")),
+      change(2, 3, Some("done"), Some("todo")),
+      change(3, 4, None, None),
+    ];
+    let durations = durations(&changes);
+    assert_eq!(Some(&vec![7_200, 3_600]), durations.states.get("todo"));
+    assert_eq!(Some(&vec![10_800]), durations.states.get("in-progress"));
+    // Reopened todos are not done, so only the first one has a lead time
+    assert_eq!(vec![18_000], durations.lead);
+
+    let lines = render(&durations);
+    assert_eq!("State        Count     p50     p75     p90", lines[0]);
+    assert_eq!("todo             2    1.0h    2.0h    2.0h", lines[1]);
+    assert_eq!("lead time        1    5.0h    5.0h    5.0h", lines[5]);
+  }
+
+  #[test]
+  fn percentiles_use_nearest_rank() {
+    let sorted = (1..=10).collect::<Vec<i64>>();
+    assert_eq!(5, percentile(&sorted, 50));
+    assert_eq!(9, percentile(&sorted, 90));
+    assert_eq!(1, percentile(&[1], 90));
+  }
+
+  #[test]
+  fn changes_come_from_history() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = crate::db::create_db(&conn);
+    _ = crate::add(
+      vec!["Milk".to_string()],
+      &crate::AddOptions::default(),
+      &conn,
+    );
+    let todos = crate::collect_todos_all(&conn).unwrap();
+    _ = crate::set_status(todos, Status::Done, &conn);
+    let changes = collect_changes(&conn).unwrap();
+    assert_eq!(2, changes.len());
+    assert_eq!(Some("done".to_string()), changes[1].new);
+    assert_eq!(1, durations(&changes).lead.len());
+  }
+}
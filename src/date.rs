@@ -0,0 +1,334 @@
+#[cfg(feature = "cli")]
+use chrono::Local;
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+#[cfg(feature = "cli")]
+use serde::Deserialize;
+#[cfg(feature = "cli")]
+use std::env;
+#[cfg(feature = "cli")]
+use std::sync::OnceLock;
+#[cfg(feature = "cli")]
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The `week_start` and `date_format` that follow the locale, like `en_US.UTF-8` in `LC_TIME`
+#[cfg(feature = "cli")]
+const LOCALE: &str = "locale";
+
+/// Regions whose weeks start on Sunday
+#[cfg(feature = "cli")]
+const SUNDAY_REGIONS: [&str; 12] = [
+  "US", "CA", "MX", "BR", "JP", "KR", "TW", "HK", "PH", "IN", "IL", "ZA",
+];
+
+/// How list and show write dates
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dates {
+  /// Like `2024-03-05`
+  #[default]
+  Absolute,
+  /// Like `in 3 days` or `2 weeks ago`
+  Relative,
+  /// Like `2024-03-05, in 3 days`
+  Both,
+}
+
+/// The `Dates` setting, from the config or a command's `--dates`
+#[cfg(feature = "cli")]
+static DATES: AtomicU8 = AtomicU8::new(Dates::Absolute as u8);
+
+#[cfg(feature = "cli")]
+pub fn init(dates: Dates) {
+  DATES.store(dates as u8, Ordering::Relaxed);
+}
+
+/// Language and region of the locale dates are written in, like `("en", "US")`
+#[cfg(feature = "cli")]
+fn locale() -> Option<(String, String)> {
+  let locale = ["LC_ALL", "LC_TIME", "LANG"]
+    .iter()
+    .filter_map(|name| env::var(name).ok())
+    .find(|locale| !locale.is_empty())
+    .filter(|locale| locale != "C" && locale != "POSIX")?;
+  let locale = locale.split(['.', '@']).next()?;
+  let (language, region) = locale.split_once('_').unwrap_or((locale, ""));
+  Some((language.to_lowercase(), region.to_uppercase()))
+}
+
+/// The `week_start` setting, a weekday like `sunday` or `locale`
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub struct WeekStart(pub Weekday);
+
+#[cfg(feature = "cli")]
+impl Default for WeekStart {
+  fn default() -> Self {
+    WeekStart(Weekday::Mon)
+  }
+}
+
+#[cfg(feature = "cli")]
+impl TryFrom<String> for WeekStart {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<WeekStart, String> {
+    if text == LOCALE {
+      let sunday = locale().is_some_and(|(_, region)| SUNDAY_REGIONS.contains(&region.as_str()));
+      return Ok(WeekStart(if sunday { Weekday::Sun } else { Weekday::Mon }));
+    }
+    text
+      .parse()
+      .map(WeekStart)
+      .map_err(|_| format!("{} isn't a weekday or locale", text))
+  }
+}
+
+/// The `date_format` setting, a strftime pattern like `%d.%m.%Y` or `locale`
+#[cfg(feature = "cli")]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub struct DateFormat(String);
+
+#[cfg(feature = "cli")]
+impl Default for DateFormat {
+  fn default() -> Self {
+    DateFormat("%Y-%m-%d".to_string())
+  }
+}
+
+#[cfg(feature = "cli")]
+impl TryFrom<String> for DateFormat {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<DateFormat, String> {
+    if text == LOCALE {
+      let format = match locale() {
+        Some((_, region)) if region == "US" || region == "PH" => "%m/%d/%Y",
+        Some((language, _)) => match language.as_str() {
+          "de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "da" | "tr" | "uk" => "%d.%m.%Y",
+          "en" | "fr" | "es" | "it" | "pt" | "el" => "%d/%m/%Y",
+          "nl" => "%d-%m-%Y",
+          _ => "%Y-%m-%d",
+        },
+        None => "%Y-%m-%d",
+      };
+      return Ok(DateFormat(format.to_string()));
+    }
+    let items = chrono::format::StrftimeItems::new(&text);
+    if items
+      .clone()
+      .any(|item| item == chrono::format::Item::Error)
+      || items.count() == 0
+    {
+      return Err(format!("{} isn't a date format like %d.%m.%Y", text));
+    }
+    Ok(DateFormat(text))
+  }
+}
+
+/// Pattern and weekday the calendar settings chose
+#[cfg(feature = "cli")]
+static CALENDAR: OnceLock<(String, Weekday)> = OnceLock::new();
+
+/// Use the `date_format` and `week_start` of the config from now on
+#[cfg(feature = "cli")]
+pub fn init_calendar(format: &DateFormat, week_start: WeekStart) {
+  _ = CALENDAR.set((format.0.clone(), week_start.0));
+}
+
+/// The first day of the week `day` is in
+#[cfg(feature = "cli")]
+pub fn start_of_week(day: NaiveDate) -> NaiveDate {
+  let start = CALENDAR.get().map_or(Weekday::Mon, |(_, start)| *start);
+  day - Days::new(day.weekday().days_since(start) as u64)
+}
+
+/// A date in the configured format, ISO like `2024-03-05` unless set
+#[cfg(feature = "cli")]
+pub fn format(date: NaiveDate) -> String {
+  match CALENDAR.get() {
+    Some((format, _)) => date.format(format).to_string(),
+    None => date.to_string(),
+  }
+}
+
+/// How far `date` is from `today` in words, like `tomorrow`, `in 3 days` or `2 months ago`
+#[cfg(feature = "cli")]
+pub fn relative(date: NaiveDate, today: NaiveDate) -> String {
+  let days = (date - today).num_days();
+  let (amount, unit) = match days.abs() {
+    0 => return "today".to_string(),
+    1 if days > 0 => return "tomorrow".to_string(),
+    1 => return "yesterday".to_string(),
+    days @ ..14 => (days, "day"),
+    days @ ..60 => (days / 7, "week"),
+    days @ ..730 => (days / 30, "month"),
+    days => (days / 365, "year"),
+  };
+  let plural = if amount == 1 { "" } else { "s" };
+  match days > 0 {
+    true => format!("in {} {}{}", amount, unit, plural),
+    false => format!("{} {}{} ago", amount, unit, plural),
+  }
+}
+
+/// A date the way `dates` shows them, `absolute` is how it's written out in full
+#[cfg(feature = "cli")]
+fn shown(dates: Dates, absolute: String, date: NaiveDate, today: NaiveDate) -> String {
+  match dates {
+    Dates::Absolute => absolute,
+    Dates::Relative => relative(date, today),
+    Dates::Both => format!("{}, {}", absolute, relative(date, today)),
+  }
+}
+
+/// A date the way the setting shows them, `absolute` is how it's written out in full
+#[cfg(feature = "cli")]
+pub fn display_with(absolute: String, date: NaiveDate, today: NaiveDate) -> String {
+  let dates = match DATES.load(Ordering::Relaxed) {
+    dates if dates == Dates::Relative as u8 => Dates::Relative,
+    dates if dates == Dates::Both as u8 => Dates::Both,
+    _ => Dates::Absolute,
+  };
+  shown(dates, absolute, date, today)
+}
+
+#[cfg(feature = "cli")]
+pub fn display(date: NaiveDate, today: NaiveDate) -> String {
+  display_with(format(date), date, today)
+}
+
+/// Parse a date given on the command line
+///
+/// Accepts `YYYY-MM-DD`, `today`, `tomorrow`, a weekday like `friday` for the next one, or
+/// an offset from today such as `3d` or `2w`.
+#[cfg(feature = "cli")]
+pub fn parse_date(input: &str) -> Result<NaiveDate, String> {
+  parse_date_from(input, Local::now().date_naive())
+}
+
+pub fn parse_date_from(input: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+  let input = input.trim().to_lowercase();
+  let offset = |days: u64| {
+    today
+      .checked_add_days(Days::new(days))
+      .ok_or(format!("Date out of range: {}", input))
+  };
+
+  match input.as_str() {
+    "today" => return Ok(today),
+    "tomorrow" => return offset(1),
+    _ => {}
+  }
+
+  if let Ok(weekday) = input.parse::<Weekday>() {
+    let days = 7 - today.weekday().days_since(weekday);
+    return offset(days as u64);
+  }
+
+  if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+    return Ok(date);
+  }
+
+  // Offsets like 3d or 2w
+  if let Some((amount, unit)) = input.split_at_checked(input.len().saturating_sub(1))
+    && let Ok(amount) = amount.parse::<u64>()
+  {
+    match unit {
+      "d" => return offset(amount),
+      "w" => return offset(amount * 7),
+      _ => {}
+    }
+  }
+
+  Err(format!(
+    "Unknown date '{}', use YYYY-MM-DD, today, tomorrow, a weekday or an offset like 3d/2w",
+    input
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 1, 30).unwrap()
+  }
+
+  #[test]
+  fn parse_date_iso() {
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+      parse_date_from("2024-03-01", today()).unwrap()
+    );
+  }
+
+  #[test]
+  fn parse_date_relative() {
+    assert_eq!(today(), parse_date_from("Today", today()).unwrap());
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+      parse_date_from("tomorrow", today()).unwrap()
+    );
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+      parse_date_from("3d", today()).unwrap()
+    );
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 2, 13).unwrap(),
+      parse_date_from("2w", today()).unwrap()
+    );
+    // The next one, a week away on the day itself
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+      parse_date_from("Friday", today()).unwrap()
+    );
+    assert_eq!(
+      NaiveDate::from_ymd_opt(2024, 2, 6).unwrap(),
+      parse_date_from("tue", today()).unwrap()
+    );
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn relative_dates_in_words() {
+    let day = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+    assert_eq!("tomorrow", relative(day(31), today()));
+    assert_eq!("yesterday", relative(day(29), today()));
+    assert_eq!("in 3 days", relative(day(30) + Days::new(3), today()));
+    assert_eq!("2 weeks ago", relative(day(10), today()));
+    assert_eq!("in 6 weeks", relative(day(30) + Days::new(45), today()));
+    assert_eq!("in 2 months", relative(day(30) + Days::new(75), today()));
+    assert_eq!("in 2 years", relative(day(30) + Days::new(800), today()));
+    assert_eq!("2024-01-10", display(day(10), today()));
+    let both = shown(Dates::Both, format(day(10)), day(10), today());
+    assert_eq!("2024-01-10, 2 weeks ago", both);
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn calendar_settings_parse() {
+    assert_eq!(
+      Ok(WeekStart(Weekday::Sun)),
+      WeekStart::try_from("sunday".to_string())
+    );
+    assert!(WeekStart::try_from("someday".to_string()).is_err());
+    assert_eq!(
+      Ok(DateFormat("%d.%m.%Y".to_string())),
+      DateFormat::try_from("%d.%m.%Y".to_string())
+    );
+    assert!(DateFormat::try_from("%Q".to_string()).is_err());
+    assert!(DateFormat::try_from(LOCALE.to_string()).is_ok());
+  }
+
+  #[test]
+  fn parse_date_invalid() {
+    assert!(parse_date_from("someday", today()).is_err());
+    assert!(parse_date_from("d", today()).is_err());
+    assert!(parse_date_from("2024-13-01", today()).is_err());
+  }
+}
@@ -0,0 +1,748 @@
+use crate::dedupe;
+use rusqlite::Connection;
+use rusqlite::functions::FunctionFlags;
+use std::error::Error;
+
+/// Schema changes in the order they were introduced
+///
+/// The index of the last applied migration is kept in `PRAGMA user_version`, so only append to
+/// this list and never change an entry that has already shipped.
+const MIGRATIONS: &[&str] = &[
+  "CREATE TABLE IF NOT EXISTS todos (
+            id          INTEGER PRIMARY KEY,
+            body        TEXT NOT NULL,
+            incomplete  BOOL
+        );",
+  "ALTER TABLE todos ADD COLUMN waiting_on TEXT;
+   ALTER TABLE todos ADD COLUMN follow_up TEXT;",
+  "ALTER TABLE todos ADD COLUMN status TEXT NOT NULL DEFAULT 'todo';
+   UPDATE todos SET status = CASE
+     WHEN NOT incomplete THEN 'done'
+     WHEN waiting_on IS NOT NULL THEN 'waiting'
+     ELSE 'todo'
+   END;
+   ALTER TABLE todos DROP COLUMN incomplete;",
+  "ALTER TABLE todos ADD COLUMN estimate INTEGER;",
+  "ALTER TABLE todos ADD COLUMN priority INTEGER;
+   ALTER TABLE todos ADD COLUMN due TEXT;",
+  "ALTER TABLE todos ADD COLUMN bucket TEXT NOT NULL DEFAULT 'inbox';
+   ALTER TABLE todos ADD COLUMN project TEXT;
+   UPDATE todos SET bucket = 'next';",
+  "ALTER TABLE todos ADD COLUMN snoozed_until TEXT;
+   ALTER TABLE todos ADD COLUMN created_at TEXT;
+   ALTER TABLE todos ADD COLUMN updated_at TEXT;
+   UPDATE todos SET created_at = datetime('now'), updated_at = datetime('now');
+   CREATE TRIGGER todos_created AFTER INSERT ON todos
+   WHEN NEW.created_at IS NULL OR NEW.updated_at IS NULL BEGIN
+     UPDATE todos SET
+       created_at = coalesce(NEW.created_at, datetime('now')),
+       updated_at = coalesce(NEW.updated_at, datetime('now'))
+     WHERE id = NEW.id;
+   END;
+   CREATE TRIGGER todos_updated AFTER UPDATE ON todos
+   WHEN NEW.updated_at IS OLD.updated_at AND NEW.created_at IS OLD.created_at BEGIN
+     UPDATE todos SET updated_at = datetime('now') WHERE id = NEW.id;
+   END;",
+  "ALTER TABLE todos ADD COLUMN focused BOOL NOT NULL DEFAULT false;",
+  "CREATE TABLE dependencies (
+     todo_id     INTEGER NOT NULL,
+     depends_on  INTEGER NOT NULL,
+     PRIMARY KEY (todo_id, depends_on)
+   );
+   CREATE TRIGGER todos_deleted_dependencies AFTER DELETE ON todos BEGIN
+     DELETE FROM dependencies WHERE todo_id = OLD.id OR depends_on = OLD.id;
+   END;",
+  "ALTER TABLE todos ADD COLUMN notes TEXT;",
+  "ALTER TABLE todos ADD COLUMN parent_id INTEGER;
+   CREATE TRIGGER todos_deleted_subtasks AFTER DELETE ON todos BEGIN
+     UPDATE todos SET parent_id = NULL WHERE parent_id = OLD.id;
+   END;",
+  "CREATE TABLE history (
+     id INTEGER PRIMARY KEY,
+     todo_id INTEGER NOT NULL,
+     field TEXT NOT NULL,
+     old,
+     new,
+     changed_at TEXT NOT NULL DEFAULT (datetime('now'))
+   );
+   CREATE TRIGGER todos_history_created AFTER INSERT ON todos BEGIN
+     INSERT INTO history (todo_id, field, new) VALUES (NEW.id, 'created', NEW.body);
+   END;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', OLD.body);
+   END;
+   CREATE TRIGGER todos_history_updated AFTER UPDATE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old, new)
+     SELECT NEW.id, field, old, new FROM (
+       SELECT 'body' AS field, OLD.body AS old, NEW.body AS new
+       UNION ALL SELECT 'status', OLD.status, NEW.status
+       UNION ALL SELECT 'waiting_on', OLD.waiting_on, NEW.waiting_on
+       UNION ALL SELECT 'follow_up', OLD.follow_up, NEW.follow_up
+       UNION ALL SELECT 'estimate', OLD.estimate, NEW.estimate
+       UNION ALL SELECT 'priority', OLD.priority, NEW.priority
+       UNION ALL SELECT 'due', OLD.due, NEW.due
+       UNION ALL SELECT 'bucket', OLD.bucket, NEW.bucket
+       UNION ALL SELECT 'project', OLD.project, NEW.project
+       UNION ALL SELECT 'snoozed_until', OLD.snoozed_until, NEW.snoozed_until
+       UNION ALL SELECT 'focused', OLD.focused, NEW.focused
+       UNION ALL SELECT 'notes', OLD.notes, NEW.notes
+       UNION ALL SELECT 'parent_id', OLD.parent_id, NEW.parent_id
+     ) WHERE old IS NOT new;
+   END;",
+  "CREATE TABLE operations (
+     id INTEGER PRIMARY KEY,
+     command TEXT NOT NULL,
+     undoable BOOL NOT NULL DEFAULT true,
+     undone BOOL NOT NULL DEFAULT false,
+     created_at TEXT NOT NULL DEFAULT (datetime('now'))
+   );
+   ALTER TABLE history ADD COLUMN operation_id INTEGER;
+   INSERT INTO operations (command, undoable)
+   SELECT 'before the operation log', false WHERE EXISTS (SELECT 1 FROM history);
+   UPDATE history SET operation_id = last_insert_rowid();
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id
+       ));
+   END;",
+  "ALTER TABLE todos ADD COLUMN tags TEXT;
+   ALTER TABLE todos ADD COLUMN scheduled TEXT;
+   DROP TRIGGER todos_history_updated;
+   CREATE TRIGGER todos_history_updated AFTER UPDATE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old, new)
+     SELECT NEW.id, field, old, new FROM (
+       SELECT 'body' AS field, OLD.body AS old, NEW.body AS new
+       UNION ALL SELECT 'status', OLD.status, NEW.status
+       UNION ALL SELECT 'waiting_on', OLD.waiting_on, NEW.waiting_on
+       UNION ALL SELECT 'follow_up', OLD.follow_up, NEW.follow_up
+       UNION ALL SELECT 'estimate', OLD.estimate, NEW.estimate
+       UNION ALL SELECT 'priority', OLD.priority, NEW.priority
+       UNION ALL SELECT 'due', OLD.due, NEW.due
+       UNION ALL SELECT 'bucket', OLD.bucket, NEW.bucket
+       UNION ALL SELECT 'project', OLD.project, NEW.project
+       UNION ALL SELECT 'snoozed_until', OLD.snoozed_until, NEW.snoozed_until
+       UNION ALL SELECT 'focused', OLD.focused, NEW.focused
+       UNION ALL SELECT 'notes', OLD.notes, NEW.notes
+       UNION ALL SELECT 'parent_id', OLD.parent_id, NEW.parent_id
+       UNION ALL SELECT 'tags', OLD.tags, NEW.tags
+       UNION ALL SELECT 'scheduled', OLD.scheduled, NEW.scheduled
+     ) WHERE old IS NOT new;
+   END;
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id,
+         'tags', OLD.tags,
+         'scheduled', OLD.scheduled
+       ));
+   END;",
+  "CREATE TABLE links (
+     todo_id    INTEGER NOT NULL,
+     source     TEXT NOT NULL,
+     reference  TEXT NOT NULL,
+     PRIMARY KEY (source, reference)
+   );
+   CREATE TRIGGER todos_deleted_links AFTER DELETE ON todos BEGIN
+     DELETE FROM links WHERE todo_id = OLD.id;
+   END;",
+  "ALTER TABLE links ADD COLUMN closed BOOL NOT NULL DEFAULT false;",
+  "ALTER TABLE todos ADD COLUMN position REAL;
+   UPDATE todos SET position = id;
+   CREATE TRIGGER todos_position AFTER INSERT ON todos WHEN NEW.position IS NULL BEGIN
+     UPDATE todos SET position = (SELECT coalesce(max(position), 0) + 1 FROM todos)
+     WHERE id = NEW.id;
+   END;",
+  "ALTER TABLE todos ADD COLUMN pinned BOOL NOT NULL DEFAULT false;
+   DROP TRIGGER todos_history_updated;
+   CREATE TRIGGER todos_history_updated AFTER UPDATE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old, new)
+     SELECT NEW.id, field, old, new FROM (
+       SELECT 'body' AS field, OLD.body AS old, NEW.body AS new
+       UNION ALL SELECT 'status', OLD.status, NEW.status
+       UNION ALL SELECT 'waiting_on', OLD.waiting_on, NEW.waiting_on
+       UNION ALL SELECT 'follow_up', OLD.follow_up, NEW.follow_up
+       UNION ALL SELECT 'estimate', OLD.estimate, NEW.estimate
+       UNION ALL SELECT 'priority', OLD.priority, NEW.priority
+       UNION ALL SELECT 'due', OLD.due, NEW.due
+       UNION ALL SELECT 'bucket', OLD.bucket, NEW.bucket
+       UNION ALL SELECT 'project', OLD.project, NEW.project
+       UNION ALL SELECT 'snoozed_until', OLD.snoozed_until, NEW.snoozed_until
+       UNION ALL SELECT 'focused', OLD.focused, NEW.focused
+       UNION ALL SELECT 'notes', OLD.notes, NEW.notes
+       UNION ALL SELECT 'parent_id', OLD.parent_id, NEW.parent_id
+       UNION ALL SELECT 'tags', OLD.tags, NEW.tags
+       UNION ALL SELECT 'scheduled', OLD.scheduled, NEW.scheduled
+       UNION ALL SELECT 'pinned', OLD.pinned, NEW.pinned
+     ) WHERE old IS NOT new;
+   END;
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id,
+         'tags', OLD.tags,
+         'scheduled', OLD.scheduled,
+         'pinned', OLD.pinned
+       ));
+   END;",
+  "ALTER TABLE todos ADD COLUMN expires TEXT;
+   DROP TRIGGER todos_history_updated;
+   CREATE TRIGGER todos_history_updated AFTER UPDATE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old, new)
+     SELECT NEW.id, field, old, new FROM (
+       SELECT 'body' AS field, OLD.body AS old, NEW.body AS new
+       UNION ALL SELECT 'status', OLD.status, NEW.status
+       UNION ALL SELECT 'waiting_on', OLD.waiting_on, NEW.waiting_on
+       UNION ALL SELECT 'follow_up', OLD.follow_up, NEW.follow_up
+       UNION ALL SELECT 'estimate', OLD.estimate, NEW.estimate
+       UNION ALL SELECT 'priority', OLD.priority, NEW.priority
+       UNION ALL SELECT 'due', OLD.due, NEW.due
+       UNION ALL SELECT 'bucket', OLD.bucket, NEW.bucket
+       UNION ALL SELECT 'project', OLD.project, NEW.project
+       UNION ALL SELECT 'snoozed_until', OLD.snoozed_until, NEW.snoozed_until
+       UNION ALL SELECT 'focused', OLD.focused, NEW.focused
+       UNION ALL SELECT 'notes', OLD.notes, NEW.notes
+       UNION ALL SELECT 'parent_id', OLD.parent_id, NEW.parent_id
+       UNION ALL SELECT 'tags', OLD.tags, NEW.tags
+       UNION ALL SELECT 'scheduled', OLD.scheduled, NEW.scheduled
+       UNION ALL SELECT 'pinned', OLD.pinned, NEW.pinned
+       UNION ALL SELECT 'expires', OLD.expires, NEW.expires
+     ) WHERE old IS NOT new;
+   END;
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id,
+         'tags', OLD.tags,
+         'scheduled', OLD.scheduled,
+         'pinned', OLD.pinned,
+         'expires', OLD.expires
+       ));
+   END;",
+  "ALTER TABLE todos ADD COLUMN habit TEXT;
+   CREATE TABLE checkins (
+     todo_id  INTEGER NOT NULL,
+     day      TEXT NOT NULL,
+     PRIMARY KEY (todo_id, day)
+   );
+   CREATE TRIGGER todos_deleted_checkins AFTER DELETE ON todos BEGIN
+     DELETE FROM checkins WHERE todo_id = OLD.id;
+   END;
+   DROP TRIGGER todos_history_updated;
+   CREATE TRIGGER todos_history_updated AFTER UPDATE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old, new)
+     SELECT NEW.id, field, old, new FROM (
+       SELECT 'body' AS field, OLD.body AS old, NEW.body AS new
+       UNION ALL SELECT 'status', OLD.status, NEW.status
+       UNION ALL SELECT 'waiting_on', OLD.waiting_on, NEW.waiting_on
+       UNION ALL SELECT 'follow_up', OLD.follow_up, NEW.follow_up
+       UNION ALL SELECT 'estimate', OLD.estimate, NEW.estimate
+       UNION ALL SELECT 'priority', OLD.priority, NEW.priority
+       UNION ALL SELECT 'due', OLD.due, NEW.due
+       UNION ALL SELECT 'bucket', OLD.bucket, NEW.bucket
+       UNION ALL SELECT 'project', OLD.project, NEW.project
+       UNION ALL SELECT 'snoozed_until', OLD.snoozed_until, NEW.snoozed_until
+       UNION ALL SELECT 'focused', OLD.focused, NEW.focused
+       UNION ALL SELECT 'notes', OLD.notes, NEW.notes
+       UNION ALL SELECT 'parent_id', OLD.parent_id, NEW.parent_id
+       UNION ALL SELECT 'tags', OLD.tags, NEW.tags
+       UNION ALL SELECT 'scheduled', OLD.scheduled, NEW.scheduled
+       UNION ALL SELECT 'pinned', OLD.pinned, NEW.pinned
+       UNION ALL SELECT 'expires', OLD.expires, NEW.expires
+       UNION ALL SELECT 'habit', OLD.habit, NEW.habit
+     ) WHERE old IS NOT new;
+   END;
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id,
+         'tags', OLD.tags,
+         'scheduled', OLD.scheduled,
+         'pinned', OLD.pinned,
+         'expires', OLD.expires,
+         'habit', OLD.habit
+       ));
+   END;",
+  "CREATE TABLE collapsed (
+     grouping TEXT NOT NULL,
+     name     TEXT NOT NULL,
+     PRIMARY KEY (grouping, name)
+   );",
+  // Random version 4 uuids, assigning one isn't an update of the todo
+  "ALTER TABLE todos ADD COLUMN uuid TEXT;
+   CREATE UNIQUE INDEX todos_uuid ON todos (uuid);
+   CREATE TRIGGER todos_uuid AFTER INSERT ON todos WHEN NEW.uuid IS NULL BEGIN
+     UPDATE todos SET uuid = lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' ||
+       substr(lower(hex(randomblob(2))), 2) || '-' || substr('89ab', 1 + abs(random()) % 4, 1) ||
+       substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6)))
+     WHERE id = NEW.id;
+   END;
+   DROP TRIGGER todos_updated;
+   CREATE TRIGGER todos_updated AFTER UPDATE ON todos
+   WHEN NEW.updated_at IS OLD.updated_at AND NEW.created_at IS OLD.created_at
+     AND OLD.uuid IS NOT NULL BEGIN
+     UPDATE todos SET updated_at = datetime('now') WHERE id = NEW.id;
+   END;
+   UPDATE todos SET uuid = lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' ||
+     substr(lower(hex(randomblob(2))), 2) || '-' || substr('89ab', 1 + abs(random()) % 4, 1) ||
+     substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6)));
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id,
+         'tags', OLD.tags,
+         'scheduled', OLD.scheduled,
+         'pinned', OLD.pinned,
+         'expires', OLD.expires,
+         'habit', OLD.habit,
+         'uuid', OLD.uuid
+       ));
+   END;",
+  // Only content changes count as an update, not what triggers fill in
+  "ALTER TABLE todos ADD COLUMN body_hash TEXT;
+   CREATE INDEX todos_body_hash ON todos (body_hash);
+   DROP TRIGGER todos_updated;
+   CREATE TRIGGER todos_updated AFTER UPDATE OF body, status, waiting_on, follow_up, estimate,
+     priority, due, bucket, project, snoozed_until, focused, notes, parent_id, tags, scheduled,
+     pinned, expires, habit, position ON todos
+   WHEN NEW.updated_at IS OLD.updated_at AND NEW.created_at IS OLD.created_at BEGIN
+     UPDATE todos SET updated_at = datetime('now') WHERE id = NEW.id;
+   END;
+   UPDATE todos SET body_hash = body_hash(body);
+   CREATE TRIGGER todos_body_hash AFTER INSERT ON todos BEGIN
+     UPDATE todos SET body_hash = body_hash(NEW.body) WHERE id = NEW.id;
+   END;
+   CREATE TRIGGER todos_body_hash_updated AFTER UPDATE OF body ON todos BEGIN
+     UPDATE todos SET body_hash = body_hash(NEW.body) WHERE id = NEW.id;
+   END;",
+  // New todos look up the last position, and lists and pickers page in this order. The
+  // others are for checks every command makes, on the few todos they concern.
+  "CREATE INDEX todos_position ON todos (position);
+   CREATE INDEX todos_order ON todos (pinned DESC, position, id);
+   CREATE INDEX todos_focused ON todos (focused) WHERE focused;
+   CREATE INDEX todos_expires ON todos (expires) WHERE expires IS NOT NULL;
+   CREATE INDEX todos_parent ON todos (parent_id) WHERE parent_id IS NOT NULL;",
+  // Open todos in list order, `incomplete` became `status`. Tags have no index, they are
+  // a json array in the todo.
+  "CREATE INDEX todos_incomplete ON todos (pinned DESC, position, id)
+     WHERE status not in ('done', 'cancelled');
+   CREATE INDEX todos_status ON todos (status);
+   CREATE INDEX todos_due ON todos (due) WHERE due IS NOT NULL;
+   CREATE INDEX todos_project ON todos (project) WHERE project IS NOT NULL;",
+  // What the todo is about elsewhere, integrations find their todos by it
+  "ALTER TABLE todos ADD COLUMN reference TEXT;
+   CREATE INDEX todos_reference ON todos (reference) WHERE reference IS NOT NULL;
+   DROP TRIGGER todos_updated;
+   CREATE TRIGGER todos_updated AFTER UPDATE OF body, status, waiting_on, follow_up, estimate,
+     priority, due, bucket, project, snoozed_until, focused, notes, parent_id, tags, scheduled,
+     pinned, expires, habit, position, reference ON todos
+   WHEN NEW.updated_at IS OLD.updated_at AND NEW.created_at IS OLD.created_at BEGIN
+     UPDATE todos SET updated_at = datetime('now') WHERE id = NEW.id;
+   END;
+   DROP TRIGGER todos_history_updated;
+   CREATE TRIGGER todos_history_updated AFTER UPDATE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old, new)
+     SELECT NEW.id, field, old, new FROM (
+       SELECT 'body' AS field, OLD.body AS old, NEW.body AS new
+       UNION ALL SELECT 'status', OLD.status, NEW.status
+       UNION ALL SELECT 'waiting_on', OLD.waiting_on, NEW.waiting_on
+       UNION ALL SELECT 'follow_up', OLD.follow_up, NEW.follow_up
+       UNION ALL SELECT 'estimate', OLD.estimate, NEW.estimate
+       UNION ALL SELECT 'priority', OLD.priority, NEW.priority
+       UNION ALL SELECT 'due', OLD.due, NEW.due
+       UNION ALL SELECT 'bucket', OLD.bucket, NEW.bucket
+       UNION ALL SELECT 'project', OLD.project, NEW.project
+       UNION ALL SELECT 'snoozed_until', OLD.snoozed_until, NEW.snoozed_until
+       UNION ALL SELECT 'focused', OLD.focused, NEW.focused
+       UNION ALL SELECT 'notes', OLD.notes, NEW.notes
+       UNION ALL SELECT 'parent_id', OLD.parent_id, NEW.parent_id
+       UNION ALL SELECT 'tags', OLD.tags, NEW.tags
+       UNION ALL SELECT 'scheduled', OLD.scheduled, NEW.scheduled
+       UNION ALL SELECT 'pinned', OLD.pinned, NEW.pinned
+       UNION ALL SELECT 'expires', OLD.expires, NEW.expires
+       UNION ALL SELECT 'habit', OLD.habit, NEW.habit
+       UNION ALL SELECT 'reference', OLD.reference, NEW.reference
+     ) WHERE old IS NOT new;
+   END;
+   DROP TRIGGER todos_history_deleted;
+   CREATE TRIGGER todos_history_deleted AFTER DELETE ON todos BEGIN
+     INSERT INTO history (todo_id, field, old) VALUES (OLD.id, 'deleted', json_object(
+         'id', OLD.id,
+         'body', OLD.body,
+         'status', OLD.status,
+         'waiting_on', OLD.waiting_on,
+         'follow_up', OLD.follow_up,
+         'estimate', OLD.estimate,
+         'priority', OLD.priority,
+         'due', OLD.due,
+         'bucket', OLD.bucket,
+         'project', OLD.project,
+         'snoozed_until', OLD.snoozed_until,
+         'created_at', OLD.created_at,
+         'updated_at', OLD.updated_at,
+         'focused', OLD.focused,
+         'notes', OLD.notes,
+         'parent_id', OLD.parent_id,
+         'tags', OLD.tags,
+         'scheduled', OLD.scheduled,
+         'pinned', OLD.pinned,
+         'expires', OLD.expires,
+         'habit', OLD.habit,
+         'uuid', OLD.uuid,
+         'reference', OLD.reference
+       ));
+   END;",
+  // Which due date a todo was notified about and until when that is snoozed
+  "CREATE TABLE notifications (
+     todo_id        INTEGER PRIMARY KEY,
+     due            TEXT,
+     snoozed_until  TEXT
+   );
+   CREATE TRIGGER todos_deleted_notifications AFTER DELETE ON todos BEGIN
+     DELETE FROM notifications WHERE todo_id = OLD.id;
+   END;",
+  // Hashes leave accents out since then
+  "UPDATE todos SET body_hash = body_hash(body);",
+  // Recent searches and filters, the latest has the highest id
+  "CREATE TABLE searches (
+     id    INTEGER PRIMARY KEY,
+     kind  TEXT NOT NULL,
+     text  TEXT NOT NULL,
+     UNIQUE (kind, text)
+   );",
+  // Colors set with `todo tag color`, over those of the config
+  "CREATE TABLE tag_colors (
+     tag    TEXT PRIMARY KEY COLLATE NOCASE,
+     color  TEXT NOT NULL
+   );",
+  // Defaults of a project, for todos added to it and for listing it
+  "CREATE TABLE list_settings (
+     project       TEXT PRIMARY KEY,
+     tags          TEXT,
+     priority      INTEGER,
+     sort          TEXT,
+     archive_days  INTEGER
+   );",
+  // The project `todo use` works in, a single row
+  "CREATE TABLE current_list (
+     id       INTEGER PRIMARY KEY CHECK (id = 1),
+     project  TEXT NOT NULL
+   );",
+  // The app hashes the bodies it writes, so other tools can still write to the file without
+  // knowing `body_hash()`. Their edits clear the hash, it is filled in again when looking
+  // for duplicates.
+  "DROP TRIGGER todos_body_hash;
+   DROP TRIGGER todos_body_hash_updated;
+   CREATE TRIGGER todos_body_hash_updated AFTER UPDATE OF body ON todos
+   WHEN NEW.body_hash IS OLD.body_hash BEGIN
+     UPDATE todos SET body_hash = NULL WHERE id = NEW.id;
+   END;",
+];
+
+fn version(conn: &Connection) -> Result<usize, Box<dyn Error>> {
+  Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// Whether `create_db` has nothing left to migrate
+pub fn is_current(conn: &Connection) -> Result<bool, Box<dyn Error>> {
+  Ok(version(conn)? >= MIGRATIONS.len())
+}
+
+/// SQL functions for queries and triggers, `lowercase` folds case like Rust does, beyond
+/// the ASCII that `lower` handles, and keeps null null
+fn register(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  dedupe::register(conn)?;
+  conn.create_scalar_function(
+    "lowercase",
+    1,
+    FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+    |ctx| {
+      Ok(
+        ctx
+          .get::<Option<String>>(0)?
+          .map(|text| text.to_lowercase()),
+      )
+    },
+  )?;
+  Ok(())
+}
+
+/// Create the tables and bring an existing database up to the latest schema, also registers
+/// the functions the triggers call, so every connection comes through here
+pub fn create_db(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  register(conn)?;
+  let version = version(conn)?;
+
+  for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(migration)?;
+    tx.pragma_update(None, "user_version", index + 1)?;
+    tx.commit()?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::status::Status;
+  use crate::{ListOptions, listed_query};
+
+  #[test]
+  fn create_db_migrates_old_schema() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(MIGRATIONS[0]).unwrap();
+    conn
+      .execute(
+        "INSERT INTO todos (body, incomplete) VALUES ('Milk', true)",
+        (),
+      )
+      .unwrap();
+
+    create_db(&conn).unwrap();
+
+    let version: usize = conn
+      .query_row("PRAGMA user_version", [], |row| row.get(0))
+      .unwrap();
+    let body: String = conn
+      .query_row(
+        "SELECT body FROM todos WHERE waiting_on IS NULL",
+        [],
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(MIGRATIONS.len(), version);
+    assert_eq!("Milk", body);
+  }
+
+  #[test]
+  fn create_db_migrates_incomplete_to_status() {
+    let conn = Connection::open_in_memory().unwrap();
+    for migration in &MIGRATIONS[..2] {
+      conn.execute_batch(migration).unwrap();
+    }
+    conn.pragma_update(None, "user_version", 2).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO todos (body, incomplete) VALUES ('Milk', true);
+         INSERT INTO todos (body, incomplete) VALUES ('Carl', false);
+         INSERT INTO todos (body, incomplete, waiting_on) VALUES ('Katia', true, 'Bob');",
+      )
+      .unwrap();
+
+    create_db(&conn).unwrap();
+
+    let mut stmt = conn
+      .prepare("SELECT status FROM todos ORDER BY id")
+      .unwrap();
+    let statuses = stmt
+      .query_map([], |row| row.get::<_, String>(0))
+      .unwrap()
+      .map(|s| s.unwrap())
+      .collect::<Vec<String>>();
+    assert_eq!(vec!["todo", "done", "waiting"], statuses);
+  }
+
+  #[test]
+  fn create_db_tracks_activity() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_db(&conn).unwrap();
+    conn
+      .execute_batch(
+        "INSERT INTO todos (body) VALUES ('Milk');
+         UPDATE todos SET updated_at = '2020-01-01 00:00:00';",
+      )
+      .unwrap();
+    let stamps = || -> (String, String) {
+      conn
+        .query_row("SELECT created_at, updated_at FROM todos", [], |row| {
+          Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap()
+    };
+    assert_eq!("2020-01-01 00:00:00", stamps().1);
+
+    conn
+      .execute("UPDATE todos SET body = 'Oat milk'", ())
+      .unwrap();
+
+    let (created, updated) = stamps();
+    assert!(updated.as_str() > "2020-01-01 00:00:00");
+    assert!(updated >= created);
+  }
+
+  #[test]
+  fn create_db_gives_todos_uuids() {
+    let conn = Connection::open_in_memory().unwrap();
+    for migration in &MIGRATIONS[..21] {
+      conn.execute_batch(migration).unwrap();
+    }
+    conn.pragma_update(None, "user_version", 21).unwrap();
+    conn
+      .execute(
+        "INSERT INTO todos (body, updated_at) VALUES ('Milk', '2020-01-01 00:00:00')",
+        (),
+      )
+      .unwrap();
+
+    create_db(&conn).unwrap();
+    conn
+      .execute("INSERT INTO todos (body) VALUES ('Bread')", ())
+      .unwrap();
+
+    let mut stmt = conn
+      .prepare("SELECT uuid, updated_at FROM todos ORDER BY id")
+      .unwrap();
+    let rows = stmt
+      .query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+      })
+      .unwrap()
+      .map(|s| s.unwrap())
+      .collect::<Vec<(String, String)>>();
+    assert_eq!(36, rows[0].0.len());
+    assert_eq!(Some('4'), rows[0].0.chars().nth(14));
+    assert_ne!(rows[0].0, rows[1].0);
+    assert_eq!("2020-01-01 00:00:00", rows[0].1);
+    assert!(
+      conn
+        .execute("UPDATE todos SET uuid = ?1 WHERE id = 2", (&rows[0].0,))
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn indexes_serve_common_queries() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_db(&conn).unwrap();
+    let plan = |query: &str| {
+      let mut stmt = conn
+        .prepare(&format!("EXPLAIN QUERY PLAN {}", query))
+        .unwrap();
+      stmt
+        .query_map([], |row| row.get::<_, String>(3))
+        .unwrap()
+        .map(|s| s.unwrap())
+        .collect::<Vec<String>>()
+        .join("\n")
+    };
+    let listed = |options: ListOptions| plan(&listed_query(&options, &conn).unwrap().0);
+
+    assert!(listed(ListOptions::default()).contains("todos_order"));
+    let incomplete = ListOptions {
+      incomplete: true,
+      ..Default::default()
+    };
+    assert!(listed(incomplete).contains("todos_incomplete"));
+    let waiting = ListOptions {
+      status: Some(Status::Waiting),
+      ..Default::default()
+    };
+    assert!(listed(waiting).contains("todos_status"));
+    assert!(
+      plan("SELECT * FROM todos WHERE due < '2024-03-01' ORDER BY due").contains("todos_due")
+    );
+    assert!(
+      plan("SELECT DISTINCT project FROM todos WHERE project IS NOT NULL ORDER BY project")
+        .contains("todos_project")
+    );
+  }
+
+  #[test]
+  fn create_db_is_idempotent() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_db(&conn).unwrap();
+    create_db(&conn).unwrap();
+  }
+}
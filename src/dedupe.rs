@@ -0,0 +1,205 @@
+use crate::ids::short;
+use crate::{TODO_SELECT, Todo, collect_todos, wizard};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::error::Error;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Lowercase words without punctuation or accents, so `Call Mom!` and `call mom` are the
+/// same todo and so are `Café` and `cafe`
+pub fn normalize(body: &str) -> String {
+  body
+    .nfd()
+    .filter(|c| !is_combining_mark(*c))
+    .collect::<String>()
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(str::to_lowercase)
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+/// FNV-1a of the normalized body, kept in the indexed `body_hash` column. It is stored, so
+/// changing it needs a migration that hashes every todo again.
+pub fn body_hash(body: &str) -> String {
+  let hash = normalize(body)
+    .bytes()
+    .fold(0xcbf29ce484222325_u64, |hash, byte| {
+      (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+  format!("{:016x}", hash)
+}
+
+/// Make `body_hash` callable from SQL, for migrations and `hash_missing`
+pub fn register(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  conn.create_scalar_function(
+    "body_hash",
+    1,
+    FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+    |ctx| Ok(body_hash(&ctx.get::<String>(0)?)),
+  )?;
+  Ok(())
+}
+
+/// Hash the todos other tools added or edited since the last look for duplicates
+fn hash_missing(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  conn
+    .prepare_cached("UPDATE todos SET body_hash = body_hash(body) WHERE body_hash IS NULL")?
+    .execute(())?;
+  Ok(())
+}
+
+/// The oldest todo with the same body, case and punctuation aside
+pub fn find_same(body: &str, conn: &Connection) -> Result<Option<usize>, Box<dyn Error>> {
+  hash_missing(conn)?;
+  Ok(
+    conn
+      .prepare_cached("SELECT id FROM todos WHERE body_hash = ?1 ORDER BY id LIMIT 1")?
+      .query_row((body_hash(body),), |row| row.get(0))
+      .optional()?,
+  )
+}
+
+pub fn find_uuid(uuid: &str, conn: &Connection) -> Result<Option<usize>, Box<dyn Error>> {
+  Ok(
+    conn
+      .prepare_cached("SELECT id FROM todos WHERE uuid = ?1")?
+      .query_row((uuid,), |row| row.get(0))
+      .optional()?,
+  )
+}
+
+/// Fold `duplicate` into `into`, its subtasks and dependencies move over
+fn fold(into: &Todo, duplicate: &Todo, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  wizard::merge(into, duplicate, conn)?;
+  conn.execute(
+    "UPDATE todos SET parent_id = ?1 WHERE parent_id = ?2",
+    (into.id, duplicate.id),
+  )?;
+  conn.execute(
+    "UPDATE OR IGNORE dependencies SET todo_id = ?1 WHERE todo_id = ?2",
+    (into.id, duplicate.id),
+  )?;
+  conn.execute(
+    "UPDATE OR IGNORE dependencies SET depends_on = ?1 WHERE depends_on = ?2",
+    (into.id, duplicate.id),
+  )?;
+  conn.execute("DELETE FROM dependencies WHERE todo_id = depends_on", ())?;
+  conn.execute("DELETE FROM todos WHERE id = ?1", (duplicate.id,))?;
+  Ok(())
+}
+
+/// Merge open todos with the same body into the oldest of them
+pub fn dedupe(dry_run: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  hash_missing(conn)?;
+  let todos = collect_todos(
+    format!(
+      "{TODO_SELECT} WHERE body_hash IN
+         (SELECT body_hash FROM todos GROUP BY body_hash HAVING count(*) > 1)
+       ORDER BY id;"
+    ),
+    conn,
+  )?;
+  let mut groups: HashMap<String, Vec<Todo>> = HashMap::new();
+  for todo in todos.into_iter().filter(|todo| todo.status.is_open()) {
+    groups.entry(body_hash(&todo.body)).or_default().push(todo);
+  }
+  let mut groups = groups
+    .into_values()
+    .filter(|group| group.len() > 1)
+    .collect::<Vec<Vec<Todo>>>();
+  groups.sort_by_key(|group| group[0].id);
+  if groups.is_empty() {
+    println!("No duplicates!");
+    return Ok(());
+  }
+
+  let tx = conn.unchecked_transaction()?;
+  for group in &groups {
+    let (into, duplicates) = group.split_first().unwrap();
+    for duplicate in duplicates {
+      if dry_run {
+        println!("Would merge {}: {}", short(duplicate.id), duplicate.body);
+      } else {
+        // Merging fills in fields, later duplicates see what earlier ones added
+        let into = collect_todos(format!("{TODO_SELECT} WHERE id = {};", into.id), &tx)?
+          .pop()
+          .unwrap_or_else(|| into.clone());
+        fold(&into, duplicate, &tx)?;
+        println!("Merged {}: {}", short(duplicate.id), duplicate.body);
+      }
+    }
+    println!("  into {}: {}", short(into.id), into.body);
+  }
+  if dry_run {
+    println!("Dry run, nothing merged!");
+  } else {
+    tx.commit()?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, collect_todos_all};
+
+  #[test]
+  fn hashes_ignore_case_and_punctuation() {
+    assert_eq!(body_hash("Call mom"), body_hash("call  MOM!"));
+    assert_ne!(body_hash("Call mom"), body_hash("Call dad"));
+    assert_eq!(body_hash("Café"), body_hash("cafe\u{301}"));
+    assert_eq!(body_hash("Café"), body_hash("cafe"));
+    // Stored hashes must stay the same across versions
+    assert_eq!("cbf29ce484222325", body_hash(""));
+  }
+
+  #[test]
+  fn dedupe_merges_into_the_oldest() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let bodies = ["Call mom", "Milk", "call mom!", "Call Mom"];
+    _ = add(
+      bodies.iter().map(|body| body.to_string()).collect(),
+      &AddOptions::default(),
+      &conn,
+    );
+    conn
+      .execute_batch(
+        "UPDATE todos SET priority = 3 WHERE id = 4;
+         UPDATE todos SET parent_id = 4 WHERE id = 2;
+         UPDATE todos SET body = 'Call dad' WHERE id = 1;",
+      )
+      .unwrap();
+    // Added todos are hashed, the body changed behind the app's back is when looked for
+    let hashed: usize = conn
+      .query_row(
+        "SELECT count(*) FROM todos WHERE body_hash IS NOT NULL",
+        [],
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(3, hashed);
+    assert_eq!(Some(3), find_same("CALL MOM", &conn).unwrap());
+    // Tools without the function can still write, no trigger calls it
+    let calls: usize = conn
+      .query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'trigger' AND sql LIKE '%body_hash(%'",
+        [],
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(0, calls);
+
+    dedupe(true, &conn).unwrap();
+    assert_eq!(4, collect_todos_all(&conn).unwrap().len());
+    dedupe(false, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(vec!["Call dad", "Milk", "call mom!"], crate::bodies(&todos));
+    assert_eq!(Some(3), todos[1].parent_id);
+    assert!(todos[2].priority.is_some());
+  }
+}
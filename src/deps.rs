@@ -0,0 +1,183 @@
+use crate::Todo;
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Condition on `todos` that holds when one of its dependencies is still open
+pub const BLOCKED: &str = "exists (select 1 from dependencies d join todos t on t.id = d.depends_on
+  where d.todo_id = todos.id and t.status not in ('done', 'cancelled'))";
+
+/// Condition on `todos` that holds when an open todo depends on it
+pub const BLOCKING: &str = "exists (select 1 from dependencies d join todos t on t.id = d.todo_id
+  where d.depends_on = todos.id and t.status not in ('done', 'cancelled'))";
+
+/// Ids of open todos that are blocked and that are blocking others
+#[derive(Debug, Default)]
+pub struct Markers {
+  blocked: HashSet<usize>,
+  blocking: HashSet<usize>,
+}
+
+impl Markers {
+  pub fn load(conn: &Connection) -> Result<Markers, Box<dyn Error>> {
+    let ids = |condition: &str| -> Result<HashSet<usize>, Box<dyn Error>> {
+      // Only todos with dependencies are looked at, not every todo
+      let mut stmt = conn.prepare(&format!(
+        "SELECT id FROM todos WHERE id in (SELECT todo_id FROM dependencies
+           UNION SELECT depends_on FROM dependencies)
+         and status not in ('done', 'cancelled') and {}",
+        condition
+      ))?;
+      let ids = stmt
+        .query_map([], |row| row.get(0))?
+        .filter_map(|s| s.ok())
+        .collect::<HashSet<usize>>();
+      Ok(ids)
+    };
+    Ok(Markers {
+      blocked: ids(BLOCKED)?,
+      blocking: ids(BLOCKING)?,
+    })
+  }
+
+  /// ⛔ when blocked and ⚑ when blocking, prefixed with a space
+  pub fn for_todo(&self, todo: &Todo) -> String {
+    let mut markers = String::new();
+    if self.blocked.contains(&todo.id) {
+      markers.push_str(" ⛔");
+    }
+    if self.blocking.contains(&todo.id) {
+      markers.push_str(" ⚑");
+    }
+    markers
+  }
+}
+
+/// Whether `from` already depends on `to`, directly or through other todos
+fn depends_on(from: usize, to: usize, conn: &Connection) -> Result<bool, Box<dyn Error>> {
+  Ok(conn.query_row(
+    "WITH RECURSIVE reachable(id) AS (
+       SELECT ?1
+       UNION SELECT d.depends_on FROM dependencies d JOIN reachable r ON d.todo_id = r.id
+     )
+     SELECT EXISTS(SELECT 1 FROM reachable WHERE id = ?2)",
+    (from, to),
+    |row| row.get(0),
+  )?)
+}
+
+/// Make `target` wait for each of `on`, refusing anything that would form a cycle
+pub fn depend(target: &Todo, on: &[Todo], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for dependency in on {
+    if depends_on(dependency.id, target.id, conn)? {
+      return Err(
+        format!(
+          "'{}' already depends on '{}', that would be a cycle",
+          dependency.body, target.body
+        )
+        .into(),
+      );
+    }
+    conn.execute(
+      "INSERT OR IGNORE INTO dependencies (todo_id, depends_on) VALUES (?1, ?2)",
+      (target.id, dependency.id),
+    )?;
+    println!("{} now depends on {}", target.body, dependency.body);
+  }
+  Ok(())
+}
+
+pub fn undepend(target: &Todo, on: &[Todo], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for dependency in on {
+    conn.execute(
+      "DELETE FROM dependencies WHERE todo_id = ?1 and depends_on = ?2",
+      (target.id, dependency.id),
+    )?;
+    println!("{} no longer depends on {}", target.body, dependency.body);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{
+    AddOptions, ListOptions, add, collect_todos_all, collect_todos_listed, rm, set_status,
+  };
+
+  fn setup() -> (Connection, Vec<Todo>) {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    (conn, todos)
+  }
+
+  #[test]
+  fn markers_follow_open_dependencies() {
+    let (conn, todos) = setup();
+    depend(&todos[0], &[todos[1].clone()], &conn).unwrap();
+
+    let markers = Markers::load(&conn).unwrap();
+    assert_eq!(" ⛔", markers.for_todo(&todos[0]));
+    assert_eq!(" ⚑", markers.for_todo(&todos[1]));
+    assert_eq!("", markers.for_todo(&todos[2]));
+
+    _ = set_status(vec![todos[1].clone()], Status::Done, &conn);
+    let markers = Markers::load(&conn).unwrap();
+    assert_eq!("", markers.for_todo(&todos[0]));
+  }
+
+  #[test]
+  fn depend_refuses_cycles() {
+    let (conn, todos) = setup();
+    depend(&todos[0], &[todos[1].clone()], &conn).unwrap();
+    depend(&todos[1], &[todos[2].clone()], &conn).unwrap();
+
+    assert!(depend(&todos[2], &[todos[0].clone()], &conn).is_err());
+    assert!(depend(&todos[0], &[todos[0].clone()], &conn).is_err());
+  }
+
+  #[test]
+  fn list_blocked_and_unblocked() {
+    let (conn, todos) = setup();
+    depend(&todos[2], &[todos[0].clone()], &conn).unwrap();
+    let listed = |blocked: bool, unblocked: bool| {
+      collect_todos_listed(
+        &ListOptions {
+          blocked,
+          unblocked,
+          ..Default::default()
+        },
+        &conn,
+      )
+      .unwrap()
+      .into_iter()
+      .map(|todo| todo.id)
+      .collect::<Vec<usize>>()
+    };
+
+    assert_eq!(vec![3], listed(true, false));
+    assert_eq!(vec![1, 2], listed(false, true));
+  }
+
+  #[test]
+  fn removing_a_todo_drops_its_dependencies() {
+    let (conn, todos) = setup();
+    depend(&todos[0], &[todos[1].clone()], &conn).unwrap();
+    undepend(&todos[0], &[todos[1].clone()], &conn).unwrap();
+    depend(&todos[0], &[todos[2].clone()], &conn).unwrap();
+    _ = rm(vec![todos[2].clone()], &conn);
+
+    let count: usize = conn
+      .query_row("SELECT count(*) FROM dependencies", [], |row| row.get(0))
+      .unwrap();
+    assert_eq!(0, count);
+  }
+}
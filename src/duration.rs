@@ -0,0 +1,109 @@
+const HOUR: u32 = 60;
+const DAY: u32 = 24 * HOUR;
+const WEEK: u32 = 7 * DAY;
+
+/// Parse a duration such as `90m`, `2h30m`, `1.5d` or `2w` into minutes, a day is 24 hours
+pub fn parse_duration(input: &str) -> Result<u32, String> {
+  let input = input.trim().to_lowercase();
+  let invalid = || format!("Unknown duration '{}', use e.g. 90m, 2h30m or 1.5d", input);
+
+  let mut minutes = 0.0;
+  let mut number = String::new();
+  for c in input.chars() {
+    match c {
+      '0'..='9' | '.' => number.push(c),
+      'w' | 'd' | 'h' | 'm' if !number.is_empty() => {
+        let amount: f64 = number.parse().map_err(|_| invalid())?;
+        let unit = match c {
+          'w' => WEEK,
+          'd' => DAY,
+          'h' => HOUR,
+          _ => 1,
+        };
+        minutes += amount * unit as f64;
+        number.clear();
+      }
+      _ => return Err(invalid()),
+    }
+  }
+
+  let minutes = minutes.round();
+  if !number.is_empty() || minutes < 1.0 || minutes > u32::MAX as f64 {
+    return Err(invalid());
+  }
+  Ok(minutes as u32)
+}
+
+/// A number of days like the `90` of `--keep-days 90`, or a duration of whole days like `12w`
+#[cfg(feature = "cli")]
+pub fn parse_days(input: &str) -> Result<i64, String> {
+  if let Ok(days) = input.trim().parse::<i64>() {
+    return Ok(days);
+  }
+  let minutes = parse_duration(input)?;
+  match minutes % DAY {
+    0 => Ok((minutes / DAY) as i64),
+    _ => Err(format!("'{}' isn't a number of whole days", input.trim())),
+  }
+}
+
+/// Format minutes the way `parse_duration` reads them, like `1d2h30m`
+pub fn format_duration(minutes: u32) -> String {
+  let parts = [
+    (minutes / DAY, 'd'),
+    (minutes % DAY / HOUR, 'h'),
+    (minutes % HOUR, 'm'),
+  ];
+  let text = parts
+    .iter()
+    .filter(|(amount, _)| *amount > 0)
+    .map(|(amount, unit)| format!("{}{}", amount, unit))
+    .collect::<String>();
+  match text.is_empty() {
+    true => "0m".to_string(),
+    false => text,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_duration_units() {
+    assert_eq!(Ok(90), parse_duration("90m"));
+    assert_eq!(Ok(120), parse_duration("2h"));
+    assert_eq!(Ok(150), parse_duration("2H30m"));
+    assert_eq!(Ok(2160), parse_duration("1.5d"));
+    assert_eq!(Ok(90), parse_duration("1.5h"));
+    assert_eq!(Ok(20160), parse_duration("2w"));
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn parse_days_whole_days() {
+    assert_eq!(Ok(84), parse_days("12w"));
+    assert_eq!(Ok(90), parse_days("90"));
+    assert!(parse_days("36h").is_err());
+  }
+
+  #[test]
+  fn parse_duration_invalid() {
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("2").is_err());
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("0m").is_err());
+    assert!(parse_duration("2 days").is_err());
+    assert!(parse_duration("1.2.3h").is_err());
+    assert!(parse_duration("0.2m").is_err());
+  }
+
+  #[test]
+  fn format_duration_round_trip() {
+    assert_eq!("1d12h", format_duration(2160));
+    assert_eq!("0m", format_duration(0));
+    for minutes in [5, 60, 90, 600, 1440, 1530, 20160] {
+      assert_eq!(Ok(minutes), parse_duration(&format_duration(minutes)));
+    }
+  }
+}
@@ -0,0 +1,106 @@
+use crate::ascii;
+use crate::config::Emoji;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether shortcodes are shown as emoji, set once from the config
+static DISPLAY: AtomicBool = AtomicBool::new(false);
+
+pub fn init(mode: Emoji) {
+  DISPLAY.store(mode != Emoji::Off, Ordering::Relaxed);
+}
+
+/// Replace shortcodes like `:rocket:` with their emoji, unknown ones stay as typed
+pub fn expand(text: &str) -> String {
+  let mut expanded = String::new();
+  let mut rest = text;
+  while let Some(start) = rest.find(':') {
+    expanded.push_str(&rest[..start]);
+    let after = &rest[start + 1..];
+    let emoji = after
+      .find(':')
+      .map(|end| &after[..end])
+      .filter(|code| {
+        !code.is_empty()
+          && code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-'))
+      })
+      .and_then(|code| Some((code, emojis::get_by_shortcode(code)?)));
+    match emoji {
+      Some((code, emoji)) => {
+        expanded.push_str(emoji.as_str());
+        rest = &after[code.len() + 1..];
+      }
+      // The closing colon may open the next shortcode
+      None => {
+        expanded.push(':');
+        rest = after;
+      }
+    }
+  }
+  expanded.push_str(rest);
+  expanded
+}
+
+/// A body as it is stored, shortcodes are only replaced when the config asks for it
+pub fn store(mode: Emoji, text: &str) -> String {
+  match mode {
+    Emoji::Store => expand(text),
+    _ => text.to_string(),
+  }
+}
+
+/// Emoji back to their shortcodes, the longest sequence that is one emoji at a time
+pub fn collapse(text: &str) -> String {
+  let mut collapsed = String::new();
+  let mut rest = text;
+  while let Some(c) = rest.chars().next() {
+    let emoji = match c.is_ascii() {
+      true => None,
+      false => rest
+        .char_indices()
+        .map(|(index, c)| index + c.len_utf8())
+        .take(10)
+        .collect::<Vec<usize>>()
+        .into_iter()
+        .rev()
+        .find_map(|end| Some((end, emojis::get(&rest[..end])?.shortcode()?))),
+    };
+    match emoji {
+      Some((end, code)) => {
+        collapsed.push_str(&format!(":{}:", code));
+        rest = &rest[end..];
+      }
+      None => {
+        collapsed.push(c);
+        rest = &rest[c.len_utf8()..];
+      }
+    }
+  }
+  collapsed
+}
+
+/// A body as it is shown, with emoji unless the config turned them off. `--ascii` shows
+/// shortcodes even for emoji that were stored.
+pub fn display(text: &str) -> String {
+  match (ascii::enabled(), DISPLAY.load(Ordering::Relaxed)) {
+    (true, _) => collapse(text),
+    (false, true) => expand(text),
+    (false, false) => text.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shortcodes_become_emoji() {
+    assert_eq!("Ship it 🚀!", expand("Ship it :rocket:!"));
+    assert_eq!("at 10:30 👍", expand("at 10:30 :+1:"));
+    assert_eq!(":nope: 🎉", expand(":nope: :tada:"));
+    assert_eq!("a::zz: c:", expand("a::zz: c:"));
+    assert_eq!("Ship it :rocket:! Café", collapse("Ship it 🚀! Café"));
+    assert_eq!(":+1: 1", collapse("👍 1"));
+  }
+}
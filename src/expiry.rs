@@ -0,0 +1,83 @@
+use crate::config::Expired;
+use crate::{TODO_SELECT, Todo, collect_todos, undo};
+use chrono::{Local, NaiveDate};
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Open todos whose `--until` date is before `today`
+fn collect_todos_expired(today: NaiveDate, conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} where status not in ('done', 'cancelled') and expires < '{}' ORDER BY id;",
+      today
+    ),
+    conn,
+  )
+}
+
+/// Runs before every command, expired todos go in an operation of their own so undo can bring
+/// them back
+pub fn expire(expired: Expired, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  expire_before(Local::now().date_naive(), expired, conn)
+}
+
+fn expire_before(
+  today: NaiveDate,
+  expired: Expired,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos_expired(today, conn)?;
+  if todos.is_empty() {
+    return Ok(());
+  }
+  for todo in todos {
+    let query = match expired {
+      Expired::Cancel => "UPDATE todos SET status = 'cancelled' where id is ?1",
+      Expired::Delete => "DELETE FROM todos where id is ?1",
+    };
+    conn.execute(query, (todo.id,))?;
+    eprintln!("Expired: {}", todo.body);
+  }
+  undo::record("expire", true, conn)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, add, bodies, collect_todos_all};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let until = AddOptions {
+      until: NaiveDate::from_ymd_opt(2024, 3, 1),
+      ..Default::default()
+    };
+    _ = add(vec!["Buy tickets".to_string()], &until, &conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    conn
+  }
+
+  #[test]
+  fn expired_todos_are_cancelled() {
+    let conn = setup();
+    let day = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    expire_before(day(1), Expired::Cancel, &conn).unwrap();
+    assert_eq!(Status::Todo, collect_todos_all(&conn).unwrap()[0].status);
+
+    expire_before(day(2), Expired::Cancel, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(Status::Cancelled, todos[0].status);
+    assert_eq!(Status::Todo, todos[1].status);
+  }
+
+  #[test]
+  fn expired_todos_are_deleted() {
+    let conn = setup();
+    let day = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+    expire_before(day, Expired::Delete, &conn).unwrap();
+    assert_eq!(vec!["Milk"], bodies(&collect_todos_all(&conn).unwrap()));
+  }
+}
@@ -0,0 +1,173 @@
+use crate::TodoStore;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+thread_local! {
+  static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(e: Box<dyn Error>) {
+  let message = CString::new(e.to_string().replace('\0', "")).unwrap_or_default();
+  LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// The value of a call, or `failed` with the error kept
+fn report<T>(result: Result<T, Box<dyn Error>>, failed: T) -> T {
+  result.unwrap_or_else(|e| {
+    set_error(e);
+    failed
+  })
+}
+
+unsafe fn text<'a>(text: *const c_char) -> Result<&'a str, Box<dyn Error>> {
+  if text.is_null() {
+    return Err("Expected a string, got NULL".into());
+  }
+  Ok(unsafe { CStr::from_ptr(text) }.to_str()?)
+}
+
+unsafe fn store<'a>(store: *mut TodoStore) -> Result<&'a mut TodoStore, Box<dyn Error>> {
+  unsafe { store.as_mut() }.ok_or_else(|| "Expected a store, got NULL".into())
+}
+
+/// Fields of a json object, NULL for none
+unsafe fn fields(json: *const c_char) -> Result<Map<String, Value>, Box<dyn Error>> {
+  if json.is_null() {
+    return Ok(Map::new());
+  }
+  match serde_json::from_str(unsafe { text(json) }?)? {
+    Value::Object(fields) => Ok(fields),
+    _ => Err("Expected the fields as a json object".into()),
+  }
+}
+
+/// The message of the last call that failed on this thread, NULL when none did. It stays
+/// valid until the next call fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn todo_last_error() -> *const c_char {
+  LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Open the todos at `location` like `--db` does, NULL on failure
+///
+/// # Safety
+/// `location` is a NUL terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn todo_store_open(location: *const c_char) -> *mut TodoStore {
+  report(
+    unsafe { text(location) }
+      .and_then(TodoStore::open)
+      .map(|store| Box::into_raw(Box::new(store))),
+    ptr::null_mut(),
+  )
+}
+
+/// # Safety
+/// `store` comes from `todo_store_open` and isn't used afterwards, or is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn todo_store_free(store: *mut TodoStore) {
+  if !store.is_null() {
+    drop(unsafe { Box::from_raw(store) });
+  }
+}
+
+/// Every todo as a json array of objects like the ones of the json export, NULL on failure
+///
+/// # Safety
+/// `store` comes from `todo_store_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn todo_store_list(store: *mut TodoStore) -> *mut c_char {
+  let list = || -> Result<*mut c_char, Box<dyn Error>> {
+    let todos = unsafe { self::store(store) }?.todos()?;
+    Ok(CString::new(serde_json::to_string(&todos)?)?.into_raw())
+  };
+  report(list(), ptr::null_mut())
+}
+
+/// Add a todo, `fields` is a json object like `{"due": "tomorrow"}` or NULL. Returns the
+/// id of the todo, or 0 on failure.
+///
+/// # Safety
+/// `store` comes from `todo_store_open`, `body` and `fields` are NUL terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn todo_store_add(
+  store: *mut TodoStore,
+  body: *const c_char,
+  fields: *const c_char,
+) -> usize {
+  let add = || unsafe {
+    let (body, fields) = (text(body)?, self::fields(fields)?);
+    self::store(store)?.add(body, fields)
+  };
+  report(add(), 0)
+}
+
+/// Mark a todo done, returns 0 or -1 on failure
+///
+/// # Safety
+/// `store` comes from `todo_store_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn todo_store_complete(store: *mut TodoStore, id: usize) -> i32 {
+  report(
+    unsafe { self::store(store) }
+      .and_then(|store| store.complete(id))
+      .map(|_| 0),
+    -1,
+  )
+}
+
+/// # Safety
+/// `text` comes from this library and isn't used afterwards, or is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn todo_string_free(text: *mut c_char) {
+  if !text.is_null() {
+    drop(unsafe { CString::from_raw(text) });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn c(text: &str) -> CString {
+    CString::new(text).unwrap()
+  }
+
+  #[test]
+  fn c_callers_add_list_and_complete() {
+    let path = std::env::temp_dir().join(format!("todo-ffi-{}.json", std::process::id()));
+    let location = c(path.to_str().unwrap());
+    unsafe {
+      let store = todo_store_open(location.as_ptr());
+      assert!(!store.is_null());
+      let id = todo_store_add(
+        store,
+        c("Milk").as_ptr(),
+        c(r#"{"tags": ["shop"]}"#).as_ptr(),
+      );
+      assert_eq!(1, id);
+      assert_eq!(2, todo_store_add(store, c("Bread").as_ptr(), ptr::null()));
+      assert_eq!(0, todo_store_complete(store, id));
+
+      assert_eq!(-1, todo_store_complete(store, 9));
+      let error = CStr::from_ptr(todo_last_error()).to_str().unwrap();
+      assert_eq!("No todo with id 9", error);
+      assert_eq!(
+        0,
+        todo_store_add(store, c("Eggs").as_ptr(), c("[]").as_ptr())
+      );
+
+      let list = todo_store_list(store);
+      let todos: Value = serde_json::from_str(CStr::from_ptr(list).to_str().unwrap()).unwrap();
+      todo_string_free(list);
+      assert_eq!(2, todos.as_array().unwrap().len());
+      assert_eq!("done", todos[0]["status"]);
+      assert_eq!("shop", todos[0]["tags"][0]);
+      todo_store_free(store);
+    }
+    _ = std::fs::remove_file(path);
+  }
+}
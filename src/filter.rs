@@ -0,0 +1,284 @@
+use crate::Todo;
+use crate::status::Status;
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use serde::{Deserialize, Serialize};
+use std::iter;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+static MATCHER: OnceLock<Matcher> = OnceLock::new();
+
+/// How typed text is looked for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+  /// Skim style, the letters in order with anything between them
+  #[default]
+  Fuzzy,
+  /// The text as typed, somewhere in the todo
+  Substring,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Case {
+  /// Case only matters once the text has a capital
+  #[default]
+  Smart,
+  Ignore,
+  Respect,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Accents {
+  /// `cafe` only finds `café` typed with the accent
+  #[default]
+  Respect,
+  /// `cafe` finds `café` and `Ça` finds `ca`
+  Ignore,
+}
+
+/// The chars of `text` normalized, each with the index of the char it came from, so `é` as
+/// one char and as `e` with a combining accent match alike. Ignored accents are left out.
+pub fn normalize(text: &str, accents: Accents) -> Vec<(char, usize)> {
+  if accents == Accents::Ignore {
+    return text
+      .chars()
+      .enumerate()
+      .flat_map(|(index, c)| iter::once(c).nfd().map(move |c| (c, index)))
+      .filter(|(c, _)| !is_combining_mark(*c))
+      .collect();
+  }
+  // Letters are composed with the accents following them
+  let mut clusters: Vec<(String, usize)> = vec![];
+  for (index, c) in text.chars().enumerate() {
+    match clusters.last_mut() {
+      Some((cluster, _)) if is_combining_mark(c) => cluster.push(c),
+      _ => clusters.push((c.to_string(), index)),
+    }
+  }
+  clusters
+    .into_iter()
+    .flat_map(|(cluster, index)| cluster.nfc().map(|c| (c, index)).collect::<Vec<_>>())
+    .collect()
+}
+
+/// The `[matcher]` table, used by pickers and the filter of the terminal ui
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Matcher {
+  pub mode: Mode,
+  pub case: Case,
+  pub accents: Accents,
+  /// Show the matched letters in pickers
+  pub highlight: bool,
+}
+
+impl Default for Matcher {
+  fn default() -> Matcher {
+    Matcher {
+      mode: Mode::Fuzzy,
+      case: Case::Smart,
+      accents: Accents::Respect,
+      highlight: true,
+    }
+  }
+}
+
+impl Matcher {
+  /// The one from the config, the default until `init`
+  pub fn current() -> Matcher {
+    MATCHER.get().copied().unwrap_or_default()
+  }
+
+  #[cfg(feature = "cli")]
+  pub fn init(self) {
+    _ = MATCHER.set(self);
+  }
+
+  /// Where `query` matches in `text`, as indices of the matched chars
+  pub fn find(&self, text: &str, query: &str) -> Option<Vec<usize>> {
+    let respect = match self.case {
+      Case::Smart => query.chars().any(char::is_uppercase),
+      Case::Ignore => false,
+      Case::Respect => true,
+    };
+    let normalized = normalize(text, self.accents);
+    let query = normalize(query, self.accents)
+      .into_iter()
+      .map(|(c, _)| c)
+      .collect::<String>();
+    let found: Vec<usize> = match self.mode {
+      Mode::Fuzzy => {
+        let matcher = SkimMatcherV2::default();
+        let matcher = match respect {
+          true => matcher.respect_case(),
+          false => matcher.ignore_case(),
+        };
+        let text = normalized.iter().map(|(c, _)| c).collect::<String>();
+        matcher
+          .fuzzy_indices(&text, &query)
+          .map(|(_, indices)| indices)?
+      }
+      Mode::Substring => {
+        let fold = |c: char| match respect {
+          true => c,
+          false => c.to_lowercase().next().unwrap_or(c),
+        };
+        let text = normalized
+          .iter()
+          .map(|(c, _)| fold(*c))
+          .collect::<Vec<char>>();
+        let query = query.chars().map(fold).collect::<Vec<char>>();
+        (0..=text.len().checked_sub(query.len())?)
+          .find(|start| text[*start..].starts_with(&query))
+          .map(|start| (start..start + query.len()).collect())?
+      }
+    };
+    // Back to the chars of `text`, a letter and its accent are one
+    let mut indices = found
+      .into_iter()
+      .map(|index| normalized[index].1)
+      .collect::<Vec<usize>>();
+    indices.dedup();
+    Some(indices)
+  }
+}
+
+/// Which todos to show, for views that hold the todos themselves like the tui, scripts or
+/// a viewer over an export
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+  /// Only todos that still need attention
+  pub incomplete: bool,
+  pub status: Option<Status>,
+  pub project: Option<String>,
+  pub tag: Option<String>,
+  /// Matched against the body, tags and project
+  pub text: String,
+}
+
+/// Whether `query` matches the body, tags or project of the todo the way the config asks,
+/// anything matches an empty query
+pub fn text_matches(todo: &Todo, query: &str) -> bool {
+  if query.is_empty() {
+    return true;
+  }
+  let text = format!(
+    "{} {} {}",
+    todo.body,
+    todo.tags,
+    todo.project.as_deref().unwrap_or("")
+  );
+  Matcher::current().find(&text, query).is_some()
+}
+
+impl Filter {
+  pub fn matches(&self, todo: &Todo) -> bool {
+    (!self.incomplete || todo.status.is_open())
+      && self.status.is_none_or(|status| todo.status == status)
+      && (self.project.is_none() || todo.project == self.project)
+      && self.tag.as_ref().is_none_or(|tag| todo.tags.contains(tag))
+      && text_matches(todo, &self.text)
+  }
+
+  pub fn apply(&self, todos: Vec<Todo>) -> Vec<Todo> {
+    todos
+      .into_iter()
+      .filter(|todo| self.matches(todo))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tags::Tags;
+
+  #[test]
+  fn filters_combine() {
+    let todo = |body: &str, status, project: Option<&str>, tags: &[&str]| Todo {
+      body: body.to_string(),
+      status,
+      project: project.map(str::to_string),
+      tags: Tags(tags.iter().map(|tag| tag.to_string()).collect()),
+      ..Default::default()
+    };
+    let todos = vec![
+      todo("Buy milk", Status::Todo, Some("House"), &["shop"]),
+      todo("Paint fence", Status::Done, Some("House"), &[]),
+      todo("Call bank", Status::Waiting, None, &["Phone"]),
+    ];
+    let bodies = |filter: Filter| {
+      filter
+        .apply(todos.clone())
+        .into_iter()
+        .map(|todo| todo.body)
+        .collect::<Vec<String>>()
+    };
+
+    assert_eq!(3, bodies(Filter::default()).len());
+    let house = Filter {
+      project: Some("House".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(vec!["Buy milk", "Paint fence"], bodies(house.clone()));
+    assert_eq!(
+      vec!["Buy milk"],
+      bodies(Filter {
+        incomplete: true,
+        ..house
+      })
+    );
+    assert_eq!(
+      vec!["Call bank"],
+      bodies(Filter {
+        tag: Some("phone".to_string()),
+        ..Default::default()
+      })
+    );
+    // The project and tags are matched too
+    assert_eq!(
+      vec!["Buy milk"],
+      bodies(Filter {
+        text: "milkhouse".to_string(),
+        ..Default::default()
+      })
+    );
+    let filter: Filter = serde_json::from_str(r#"{"status": "done"}"#).unwrap();
+    assert_eq!(vec!["Paint fence"], bodies(filter));
+  }
+
+  #[test]
+  fn matchers_find_the_matched_letters() {
+    let fuzzy = Matcher::default();
+    assert_eq!(Some(vec![0, 4]), fuzzy.find("Buy milk", "bm"));
+    assert_eq!(None, fuzzy.find("Buy milk", "bM"));
+    let substring = Matcher {
+      mode: Mode::Substring,
+      ..Default::default()
+    };
+    assert_eq!(Some(vec![4, 5]), substring.find("Buy milk", "mi"));
+    assert_eq!(None, substring.find("Buy milk", "bm"));
+    assert_eq!(Some(vec![0, 1]), substring.find("Émile", "ém"));
+    let respect = Matcher {
+      case: Case::Respect,
+      ..substring
+    };
+    assert_eq!(None, respect.find("Buy milk", "buy"));
+    assert_eq!(Some(vec![]), respect.find("Buy milk", ""));
+    let accents = Matcher {
+      accents: Accents::Ignore,
+      ..substring
+    };
+    assert_eq!(None, substring.find("Café au lait", "cafe"));
+    assert_eq!(Some(vec![0, 1, 2, 3]), accents.find("Café au lait", "cafe"));
+    // Decomposed as `e` and an accent, like macOS file names
+    assert_eq!(Some(vec![3]), substring.find("Cafe\u{301}", "é"));
+    assert_eq!(Some(vec![0, 1]), accents.find("Ça va", "ca"));
+  }
+}
@@ -0,0 +1,117 @@
+use crate::Todo;
+use console::style;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Above this many todos a focus set stops being a focus
+const FOCUS_LIMIT: usize = 5;
+
+pub fn is_focusing(conn: &Connection) -> Result<bool, Box<dyn Error>> {
+  Ok(conn.query_row(
+    "SELECT EXISTS(SELECT 1 FROM todos WHERE focused)",
+    [],
+    |row| row.get(0),
+  )?)
+}
+
+/// Add todos to the focus set
+pub fn focus(targets: Vec<Todo>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for target in &targets {
+    conn.execute(
+      "UPDATE todos SET focused = true where id is ?1",
+      (target.id,),
+    )?;
+    println!("Focusing on: {}", target.body);
+  }
+
+  let count: usize = conn.query_row("SELECT count(*) FROM todos WHERE focused", [], |row| {
+    row.get(0)
+  })?;
+  if count > FOCUS_LIMIT {
+    println!(
+      "{}",
+      style(format!("Focusing on {} todos, consider fewer!", count)).yellow()
+    );
+  }
+  Ok(())
+}
+
+pub fn unfocus(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  conn.execute("UPDATE todos SET focused = false WHERE focused", ())?;
+  println!("Focus cleared, listing everything again!");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{
+    AddOptions, ListOptions, add, collect_todos_all, collect_todos_listed, find_targets,
+  };
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec![
+        "Milk".to_string(),
+        "Carl".to_string(),
+        "Buy milk".to_string(),
+      ],
+      &AddOptions::default(),
+      &conn,
+    );
+    conn
+  }
+
+  fn bodies(todos: Vec<Todo>) -> Vec<String> {
+    todos.into_iter().map(|todo| todo.body).collect()
+  }
+
+  #[test]
+  fn focus_limits_default_list() {
+    let conn = setup();
+    let everything = ListOptions {
+      all: true,
+      ..Default::default()
+    };
+
+    _ = focus(find_targets(&["2".to_string()], &conn).unwrap(), &conn);
+
+    assert!(is_focusing(&conn).unwrap());
+    assert_eq!(
+      vec!["Carl"],
+      bodies(collect_todos_listed(&ListOptions::default(), &conn).unwrap())
+    );
+    assert_eq!(3, collect_todos_listed(&everything, &conn).unwrap().len());
+  }
+
+  #[test]
+  fn unfocus_lists_everything() {
+    let conn = setup();
+    _ = focus(collect_todos_all(&conn).unwrap(), &conn);
+    _ = unfocus(&conn);
+
+    assert!(!is_focusing(&conn).unwrap());
+    assert_eq!(
+      3,
+      collect_todos_listed(&ListOptions::default(), &conn)
+        .unwrap()
+        .len()
+    );
+  }
+
+  #[test]
+  fn find_targets_by_id_and_text() {
+    let conn = setup();
+    let targets = ["milk".to_string(), "1".to_string()];
+
+    assert_eq!(
+      vec!["Milk", "Buy milk"],
+      bodies(find_targets(&targets, &conn).unwrap())
+    );
+    assert!(find_targets(&["Katia".to_string()], &conn).is_err());
+    assert!(find_targets(&["9".to_string()], &conn).is_err());
+  }
+}
@@ -0,0 +1,594 @@
+use crate::ids::short;
+use crate::prompt;
+use crate::query::Query;
+use crate::redact;
+use crate::storage::{collect_todos_stored, insert_todo};
+use crate::strategy::{Strategy, Summary};
+use crate::table::{self, Table};
+use crate::wizard::{self, Resolution};
+use crate::{Bucket, Todo, json, org, todotxt};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Formats todos can be exported to and imported from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  Org,
+  Csv,
+  Json,
+  TodoTxt,
+}
+
+#[derive(clap::Args, Clone, Debug)]
+#[group(required = true, multiple = false)]
+pub struct FormatArgs {
+  /// Emacs org-mode headlines
+  #[arg(long)]
+  org: bool,
+
+  /// Comma separated values with a header row, columns are matched by name
+  #[arg(long)]
+  csv: bool,
+
+  /// The json storage format, or a list of objects read like csv rows
+  #[arg(long)]
+  json: bool,
+
+  /// A todo.txt file
+  #[arg(long)]
+  todotxt: bool,
+}
+
+impl FormatArgs {
+  pub fn format(&self) -> Format {
+    match self {
+      FormatArgs { org: true, .. } => Format::Org,
+      FormatArgs { csv: true, .. } => Format::Csv,
+      FormatArgs { json: true, .. } => Format::Json,
+      FormatArgs { todotxt: true, .. } => Format::TodoTxt,
+      FormatArgs { .. } => unreachable!("clap requires one format"),
+    }
+  }
+}
+
+pub fn render(format: Format, todos: &[Todo]) -> Result<String, Box<dyn Error>> {
+  match format {
+    Format::Org => Ok(org::render(todos)),
+    Format::Csv => table::render_csv(todos),
+    Format::Json => json::render(todos),
+    Format::TodoTxt => Ok(todotxt::render(todos)),
+  }
+}
+
+/// What of `todos` comes back when they are exported to `format` and imported into an empty
+/// list: the fields the format holds, ids counted from 1 in the given order and no timestamps
+/// or positions. Org files move todos under their projects, so they have no such form.
+pub fn canonical(format: Format, todos: &[Todo]) -> Option<Vec<Todo>> {
+  if format == Format::Org {
+    return None;
+  }
+  let ids = todos
+    .iter()
+    .enumerate()
+    .map(|(index, todo)| (todo.id, index + 1))
+    .collect::<HashMap<usize, usize>>();
+  let kept = |todo: &Todo| -> Todo {
+    let todo = Todo {
+      id: ids[&todo.id],
+      parent_id: todo.parent_id.and_then(|parent| ids.get(&parent).copied()),
+      position: None,
+      created_at: None,
+      updated_at: None,
+      ..todo.clone()
+    };
+    match format {
+      Format::Json | Format::Org => todo,
+      // Words are split at spaces
+      Format::TodoTxt => Todo {
+        body: todo
+          .body
+          .split_whitespace()
+          .collect::<Vec<&str>>()
+          .join(" "),
+        notes: None,
+        reference: todo
+          .reference
+          .filter(|reference| !reference.contains(char::is_whitespace)),
+        ..todo
+      },
+      // Only the columns of `table::render_csv`, trimmed like cells are read
+      Format::Csv => Todo {
+        id: todo.id,
+        body: todo.body.trim().to_string(),
+        status: todo.status,
+        due: todo.due,
+        priority: todo.priority,
+        tags: todo.tags,
+        project: todo
+          .project
+          .map(|p| p.trim().to_string())
+          .filter(|p| !p.is_empty()),
+        notes: todo
+          .notes
+          .map(|n| n.trim().to_string())
+          .filter(|n| !n.is_empty()),
+        estimate: todo.estimate,
+        bucket: Bucket::default(),
+        ..Default::default()
+      },
+    }
+  };
+  Some(todos.iter().map(kept).collect())
+}
+
+/// What a file holds, the columns of a table still need to be mapped to fields
+#[derive(Debug, PartialEq)]
+pub enum Parsed {
+  Todos(Vec<Todo>),
+  Table(Table),
+}
+
+impl Parsed {
+  /// Tables are read with the mapping their column names suggest
+  pub fn todos(self) -> Vec<Todo> {
+    match self {
+      Parsed::Todos(todos) => todos,
+      Parsed::Table(table) => table.todos(&table.guess_mapping()),
+    }
+  }
+}
+
+pub fn read(format: Format, text: &str) -> Result<Parsed, Box<dyn Error>> {
+  match format {
+    Format::Org => Ok(Parsed::Todos(org::parse(text))),
+    Format::Csv => Ok(Parsed::Table(table::parse_csv(text)?)),
+    Format::Json => match serde_json::from_str(text)? {
+      Value::Array(objects) => Ok(Parsed::Table(table::parse_json(&objects)?)),
+      document => Ok(Parsed::Todos(json::parse(document)?)),
+    },
+    Format::TodoTxt => Ok(Parsed::Todos(todotxt::parse(text))),
+  }
+}
+
+/// Write every todo, or those matching `filter`, to `output`, or stdout without one.
+/// Subtasks whose parent is left out are written on their own.
+pub fn export(
+  format: Format,
+  filter: Option<&Query>,
+  redact: bool,
+  output: Option<&Path>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let mut todos = collect_todos_stored(conn)?;
+  if let Some(filter) = filter {
+    todos = filter.apply(todos);
+    let ids = todos.iter().map(|todo| todo.id).collect::<Vec<usize>>();
+    for todo in &mut todos {
+      todo.parent_id = todo.parent_id.filter(|parent| ids.contains(parent));
+    }
+  }
+  if redact {
+    todos = redact::redact(&todos);
+  }
+  let text = render(format, &todos)?;
+  match output {
+    Some(path) => {
+      fs::write(path, text)?;
+      println!("Exported todos to {}", path.display());
+      if let (Some(kept), Some(all)) = (canonical(format, &todos), canonical(Format::Json, &todos))
+      {
+        let lossy = kept
+          .iter()
+          .zip(&all)
+          .filter(|(kept, all)| kept != all || kept.uuid != all.uuid)
+          .count();
+        if lossy > 0 {
+          println!(
+            "Some fields of {} todos were left out, --json keeps them all",
+            lossy
+          );
+        }
+      }
+    }
+    None => print!("{}", text),
+  }
+  Ok(())
+}
+
+/// Add the todos as new ones or merge them into existing ones, subtasks stay under the parents
+/// they were imported with, even those listed before them. A copy of a todo that is already
+/// there gets a uuid of its own.
+fn insert_imported(
+  plan: Vec<(Todo, Resolution)>,
+  existing: &[Todo],
+  conn: &Connection,
+) -> Result<Summary, Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  let mut ids = HashMap::new();
+  let mut parents = vec![];
+  let mut summary = Summary::default();
+  for (todo, resolution) in plan {
+    summary.count(&resolution);
+    match resolution {
+      Resolution::Create => {
+        let imported = Todo {
+          id: 0,
+          parent_id: None,
+          uuid: match wizard::same_uuid(&todo, &tx)? {
+            Some(_) => None,
+            None => todo.uuid.clone(),
+          },
+          ..todo.clone()
+        };
+        let id = insert_todo(&imported, &tx)?;
+        ids.insert(todo.id, id);
+        parents.extend(todo.parent_id.map(|parent| (id, parent)));
+        println!("Imported: {}", todo.body);
+      }
+      Resolution::Merge(id) => {
+        let Some(into) = existing.iter().find(|own| own.id == id) else {
+          continue;
+        };
+        wizard::merge(into, &todo, &tx)?;
+        ids.insert(todo.id, id);
+        println!("Merged: {}", into.body);
+      }
+      Resolution::Overwrite(id) => {
+        let Some(into) = existing.iter().find(|own| own.id == id) else {
+          continue;
+        };
+        wizard::overwrite(into, &todo, &tx)?;
+        ids.insert(todo.id, id);
+        println!("Overwrote: {}", into.body);
+      }
+      Resolution::Skip => println!("Skipped: {}", todo.body),
+    }
+  }
+  for (id, parent) in parents {
+    if let Some(parent) = ids.get(&parent) {
+      tx.execute(
+        "UPDATE todos SET parent_id = ?1 WHERE id = ?2",
+        (parent, id),
+      )?;
+    }
+  }
+  tx.commit()?;
+  Ok(summary)
+}
+
+/// What to do with an imported todo without asking. Without a strategy todos exported from
+/// this list before are merged back into themselves and the others are added.
+fn resolve(
+  todo: &Todo,
+  strategy: Option<Strategy>,
+  conn: &Connection,
+) -> Result<Resolution, Box<dyn Error>> {
+  let existing = match strategy {
+    Some(_) => wizard::find_existing(todo, conn)?,
+    None => wizard::same_uuid(todo, conn)?,
+  };
+  Ok(match existing {
+    Some(id) => strategy.unwrap_or(Strategy::Merge).resolve(id),
+    None => Resolution::Create,
+  })
+}
+
+/// `strategy` settles the todos the list already has, with skip importing twice adds nothing
+pub fn import(
+  format: Format,
+  file: &Path,
+  dry_run: bool,
+  interactive: bool,
+  strategy: Option<Strategy>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let text = fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+  let parsed = read(format, &text).map_err(|e| format!("{}: {}", file.display(), e))?;
+  let existing = collect_todos_stored(conn)?;
+  let plan = match interactive {
+    true => {
+      prompt::require("import without --interactive")?;
+      wizard::wizard(parsed, &existing, strategy, conn)?
+    }
+    false => parsed
+      .todos()
+      .into_iter()
+      .map(|todo| {
+        let resolution = resolve(&todo, strategy, conn)?;
+        Ok((todo, resolution))
+      })
+      .collect::<Result<Vec<(Todo, Resolution)>, Box<dyn Error>>>()?,
+  };
+  if plan.is_empty() {
+    println!("Nothing to import!");
+  } else if dry_run {
+    let todos = plan
+      .iter()
+      .map(|(todo, _)| todo.clone())
+      .collect::<Vec<Todo>>();
+    let duplicates = wizard::find_duplicates(&todos, conn)?;
+    let mut summary = Summary::default();
+    for ((todo, resolution), duplicate) in plan.iter().zip(duplicates) {
+      summary.count(resolution);
+      let duplicate = match duplicate {
+        Some(id) => format!(" (duplicate of todo {})", short(id)),
+        None => String::new(),
+      };
+      match resolution {
+        Resolution::Create => println!("Would import todo: {}{}", todo.body, duplicate),
+        Resolution::Merge(_) => println!("Would merge todo: {}{}", todo.body, duplicate),
+        Resolution::Overwrite(_) => {
+          println!("Would overwrite todo: {}{}", todo.body, duplicate)
+        }
+        Resolution::Skip => println!("Would skip todo: {}{}", todo.body, duplicate),
+      }
+    }
+    println!("Dry run, nothing imported! It would be {}", summary);
+  } else {
+    println!("Import done, {}", insert_imported(plan, &existing, conn)?);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::frequency::parse_frequency;
+  use crate::status::Status;
+  use crate::{AddOptions, Priority, Tags, add, collect_todos_all};
+  use chrono::NaiveDate;
+  use std::env;
+
+  #[test]
+  fn import_keeps_subtasks() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    let path = env::temp_dir().join(format!("todo-import-{}.org", std::process::id()));
+    fs::write(&path, "* TODO Shed\n** DONE Wood\n").unwrap();
+
+    import(Format::Org, &path, true, false, None, &conn).unwrap();
+    assert_eq!(1, collect_todos_all(&conn).unwrap().len());
+
+    import(Format::Org, &path, false, false, None, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(vec!["Milk", "Shed", "Wood"], crate::bodies(&todos));
+    assert_eq!(Some(todos[1].id), todos[2].parent_id);
+
+    export(Format::Org, None, false, Some(&path), &conn).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    let headlines = text
+      .lines()
+      .filter(|line| line.starts_with('*'))
+      .collect::<Vec<&str>>();
+    assert_eq!(
+      vec!["* TODO Milk", "* TODO Shed", "** DONE Wood"],
+      headlines
+    );
+    assert!(text.contains(&format!(":ID: {}", todos[0].uuid.as_ref().unwrap())));
+
+    // Importing the export again finds the same todos
+    import(Format::Org, &path, false, false, None, &conn).unwrap();
+    assert_eq!(3, collect_todos_all(&conn).unwrap().len());
+
+    let filter = crate::parse_query("complete").unwrap();
+    export(Format::TodoTxt, Some(&filter), false, Some(&path), &conn).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("x ") && text.contains("Wood") && !text.contains("parent:"));
+    assert_eq!(1, text.lines().count());
+
+    fs::write(&path, "milk!\nBread\n").unwrap();
+    for _ in 0..2 {
+      import(
+        Format::TodoTxt,
+        &path,
+        false,
+        false,
+        Some(Strategy::Skip),
+        &conn,
+      )
+      .unwrap();
+    }
+    let todos = collect_todos_stored(&conn).unwrap();
+    assert_eq!(vec!["Milk", "Shed", "Wood", "Bread"], crate::bodies(&todos));
+
+    fs::write(&path, "(A) bread\nmilk +Home\n").unwrap();
+    import(
+      Format::TodoTxt,
+      &path,
+      false,
+      false,
+      Some(Strategy::Overwrite),
+      &conn,
+    )
+    .unwrap();
+    import(
+      Format::TodoTxt,
+      &path,
+      false,
+      false,
+      Some(Strategy::Merge),
+      &conn,
+    )
+    .unwrap();
+    import(
+      Format::TodoTxt,
+      &path,
+      false,
+      false,
+      Some(Strategy::Duplicate),
+      &conn,
+    )
+    .unwrap();
+    let todos = collect_todos_stored(&conn).unwrap();
+    assert_eq!(
+      vec!["milk", "Shed", "Wood", "bread", "bread", "milk"],
+      crate::bodies(&todos)
+    );
+    assert_eq!(Some(crate::Priority::High), todos[3].priority);
+    _ = fs::remove_file(path);
+  }
+
+  /// Seeded xorshift, every run tries the same todos
+  struct Random(u64);
+
+  impl Random {
+    fn below(&mut self, n: usize) -> usize {
+      self.0 ^= self.0 << 13;
+      self.0 ^= self.0 >> 7;
+      self.0 ^= self.0 << 17;
+      (self.0 % n as u64) as usize
+    }
+
+    fn pick<T: Clone>(&mut self, values: &[T]) -> T {
+      values[self.below(values.len())].clone()
+    }
+
+    fn maybe<T: Clone>(&mut self, values: &[T]) -> Option<T> {
+      match self.below(3) {
+        0 => Some(self.pick(values)),
+        _ => None,
+      }
+    }
+  }
+
+  fn random_todos(random: &mut Random) -> Vec<Todo> {
+    let day = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    let count = random.below(8);
+    // Ids out of order put some subtasks before their parents
+    let mut ids = (1..=count * 2).collect::<Vec<usize>>();
+    for index in (1..ids.len()).rev() {
+      ids.swap(index, random.below(index + 1));
+    }
+    let mut todos: Vec<Todo> = vec![];
+    for (index, id) in ids.into_iter().take(count).enumerate() {
+      let words = random.below(3) + 1;
+      let body = (0..words)
+        .map(|_| {
+          random.pick(&[
+            "Call",
+            "mom",
+            "Pay  bills",
+            " fix",
+            "ünïcode",
+            "a,b",
+            "\"quoted\"",
+          ])
+        })
+        .collect::<Vec<&str>>()
+        .join(" ");
+      let tags = (0..random.below(3))
+        .map(|_| random.pick(&["home", "work", "Errand-2", "ça"]).to_string())
+        .collect::<Vec<String>>();
+      let mut own_tags = Tags::default();
+      own_tags.extend(&tags);
+      todos.push(Todo {
+        id,
+        body: body.trim().to_string(),
+        status: random.pick(&Status::ALL),
+        waiting_on: random
+          .maybe(&["Bob", "the landlord", "ops_team"])
+          .map(String::from),
+        follow_up: random.maybe(&[day(4)]),
+        estimate: random.maybe(&[5, 90, 1440, 2000]),
+        priority: random.maybe(&[Priority::Low, Priority::Medium, Priority::High]),
+        due: random.maybe(&[day(1), day(29)]),
+        bucket: random.pick(&[Bucket::Inbox, Bucket::Next, Bucket::Someday]),
+        project: random
+          .maybe(&["House", "Side project", "q3", "side_quest"])
+          .map(String::from),
+        snoozed_until: random.maybe(&[day(9)]),
+        focused: random.below(4) == 0,
+        notes: random
+          .maybe(&[
+            "Form 1040",
+            "  - [ ] first\n- [x] second\n",
+            "line, \"quoted\"\r",
+          ])
+          .map(String::from),
+        parent_id: match index {
+          0 => None,
+          _ => random.maybe(&todos).map(|parent| parent.id),
+        },
+        tags: own_tags,
+        scheduled: random.maybe(&[day(2)]),
+        pinned: random.below(4) == 0,
+        expires: random.maybe(&[day(30)]),
+        habit: random.maybe(&[
+          parse_frequency("3/week").unwrap(),
+          parse_frequency("daily").unwrap(),
+        ]),
+        uuid: Some(format!("0000000{}-98fc-4c14-9afb-f4c8996fb924", index)),
+        reference: random
+          .maybe(&["SHOP-12", "https://example.com/7", "two words"])
+          .map(String::from),
+        ..Default::default()
+      });
+    }
+    todos
+  }
+
+  #[test]
+  fn exports_import_back_to_the_same_todos() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    let path = env::temp_dir().join(format!("todo-round-trip-{}", std::process::id()));
+    for _ in 0..32 {
+      let conn = Connection::open_in_memory().unwrap();
+      _ = create_db(&conn);
+      for todo in random_todos(&mut random) {
+        insert_todo(&todo, &conn).unwrap();
+      }
+      let todos = collect_todos_stored(&conn).unwrap();
+      for format in [Format::Json, Format::TodoTxt, Format::Csv] {
+        fs::write(&path, render(format, &todos).unwrap()).unwrap();
+        let copy = Connection::open_in_memory().unwrap();
+        _ = create_db(&copy);
+        import(format, &path, false, false, None, &copy).unwrap();
+        let imported = collect_todos_stored(&copy).unwrap();
+        let expected = canonical(format, &todos).unwrap();
+        assert_eq!(
+          expected,
+          canonical(format, &imported).unwrap(),
+          "{:?}",
+          format
+        );
+        if format != Format::Csv {
+          let uuids = |todos: &[Todo]| {
+            todos
+              .iter()
+              .map(|todo| todo.uuid.clone())
+              .collect::<Vec<_>>()
+          };
+          assert_eq!(uuids(&todos), uuids(&imported));
+        }
+        // Canonical todos are what they export to
+        assert_eq!(Some(expected.clone()), canonical(format, &expected));
+      }
+    }
+    assert_eq!(None, canonical(Format::Org, &[]));
+    _ = fs::remove_file(path);
+  }
+
+  #[test]
+  fn json_reads_the_storage_format_and_plain_lists() {
+    let todos = vec![Todo {
+      id: 1,
+      body: "Milk".to_string(),
+      ..Default::default()
+    }];
+    let text = render(Format::Json, &todos).unwrap();
+    assert_eq!(Parsed::Todos(todos), read(Format::Json, &text).unwrap());
+
+    let todos = read(Format::Json, r#"[{"title": "Carl", "done": true}]"#)
+      .unwrap()
+      .todos();
+    assert_eq!("Carl", todos[0].body);
+    assert_eq!(Status::Done, todos[0].status);
+    assert!(read(Format::Json, "[1, 2]").is_err());
+  }
+}
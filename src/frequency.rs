@@ -0,0 +1,121 @@
+#[cfg(feature = "cli")]
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+  Day,
+  Week,
+  Month,
+}
+
+impl Period {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Period::Day => "day",
+      Period::Week => "week",
+      Period::Month => "month",
+    }
+  }
+}
+
+/// How often a habit should be done, stored as `3/week`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Frequency {
+  pub times: u32,
+  pub per: Period,
+}
+
+/// `3/week`, `3x/week`, `daily`, `weekly` or `monthly`
+pub fn parse_frequency(input: &str) -> Result<Frequency, String> {
+  let input = input.trim().to_lowercase();
+  let (times, per) = match input.as_str() {
+    "daily" => ("1", "day"),
+    "weekly" => ("1", "week"),
+    "monthly" => ("1", "month"),
+    _ => input
+      .split_once('/')
+      .ok_or_else(|| format!("Invalid frequency '{}', use e.g. 3/week or daily", input))?,
+  };
+  let times = times
+    .trim_end_matches(['x', '×'])
+    .parse::<u32>()
+    .ok()
+    .filter(|times| *times > 0)
+    .ok_or_else(|| format!("Invalid number of times in '{}'", input))?;
+  let per = match per {
+    "day" => Period::Day,
+    "week" => Period::Week,
+    "month" => Period::Month,
+    _ => {
+      return Err(format!(
+        "Invalid period in '{}', use day, week or month",
+        input
+      ));
+    }
+  };
+  Ok(Frequency { times, per })
+}
+
+impl fmt::Display for Frequency {
+  /// `3×/week`
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}×/{}", self.times, self.per.as_str())
+  }
+}
+
+impl From<Frequency> for String {
+  fn from(frequency: Frequency) -> String {
+    format!("{}/{}", frequency.times, frequency.per.as_str())
+  }
+}
+
+impl TryFrom<String> for Frequency {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Frequency, String> {
+    parse_frequency(&text)
+  }
+}
+
+#[cfg(feature = "cli")]
+impl ToSql for Frequency {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(String::from(*self)))
+  }
+}
+
+#[cfg(feature = "cli")]
+impl FromSql for Frequency {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    parse_frequency(value.as_str()?).map_err(|e| FromSqlError::Other(e.into()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_frequencies() {
+    let three_a_week = Frequency {
+      times: 3,
+      per: Period::Week,
+    };
+    assert_eq!(Ok(three_a_week), parse_frequency("3/week"));
+    assert_eq!(Ok(three_a_week), parse_frequency("3x/week"));
+    assert_eq!(
+      Ok(Frequency {
+        times: 1,
+        per: Period::Day
+      }),
+      parse_frequency("daily")
+    );
+    assert!(parse_frequency("0/week").is_err());
+    assert!(parse_frequency("3/year").is_err());
+    assert!(parse_frequency("often").is_err());
+    assert_eq!("3×/week", three_a_week.to_string());
+  }
+}
@@ -0,0 +1,86 @@
+use crate::ids::short;
+use crate::query::parse_query;
+use crate::{Todo, collect_todos_incomplete};
+use rusqlite::Connection;
+use std::error::Error;
+
+/// The open todos any of the filters match, `overdue|tag:blocking` holds two of them
+fn blockers(filters: &[String], todos: Vec<Todo>) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let queries = filters
+    .iter()
+    .flat_map(|filter| filter.split('|'))
+    .map(parse_query)
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(
+    todos
+      .into_iter()
+      .filter(|todo| queries.iter().any(|query| query.matches(todo)))
+      .collect(),
+  )
+}
+
+/// `todo check --fail-on`, fails when open todos match so git hooks stop. The todos
+/// in the way are listed first.
+pub fn gate(filters: &[String], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let blockers = blockers(filters, collect_todos_incomplete(conn)?)?;
+  if blockers.is_empty() {
+    return Ok(());
+  }
+  for todo in &blockers {
+    println!("{}. {}", short(todo.id), todo.body);
+  }
+  Err(
+    format!(
+      "{} todos match --fail-on {}",
+      blockers.len(),
+      filters.join(" ")
+    )
+    .into(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add};
+  use chrono::{Duration, Local};
+
+  #[test]
+  fn gate_fails_on_matching_open_todos() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let blocking = AddOptions {
+      tags: vec!["blocking".to_string()],
+      ..Default::default()
+    };
+    _ = add(vec!["Fix the build".to_string()], &blocking, &conn);
+    let late = AddOptions {
+      due: Some(Local::now().date_naive() - Duration::days(1)),
+      ..Default::default()
+    };
+    _ = add(vec!["Send invoice".to_string()], &late, &conn);
+    _ = add(vec!["Buy milk".to_string()], &AddOptions::default(), &conn);
+
+    let fail_on = |filters: &[&str]| {
+      let filters = filters.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+      blockers(&filters, collect_todos_incomplete(&conn).unwrap())
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.body)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      vec!["Fix the build", "Send invoice"],
+      fail_on(&["overdue|tag:blocking"])
+    );
+    assert_eq!(vec!["Send invoice"], fail_on(&["overdue"]));
+    assert!(gate(&["tag:blocking".to_string()], &conn).is_err());
+    assert!(gate(&["tag:someday".to_string()], &conn).is_ok());
+
+    conn
+      .execute("UPDATE todos SET status = 'done'", ())
+      .unwrap();
+    assert!(gate(&["overdue|tag:blocking".to_string()], &conn).is_ok());
+  }
+}
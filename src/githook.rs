@@ -0,0 +1,175 @@
+use crate::ids;
+use crate::status::Status;
+use crate::{TODO_SELECT, collect_todos, set_status};
+use clap::Subcommand;
+use rusqlite::Connection;
+use std::error::Error;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs};
+
+/// The trailer of a commit message that completes a todo
+const TRAILER: &str = "closes-todo";
+
+/// Marks hooks this command wrote, others are never overwritten
+const MARK: &str = "# Installed by todo git-hook install";
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Install a post-commit hook in the current repository, committing with
+  /// `closes-todo: <id>` in the message completes the todo
+  Install {
+    /// Replace a post-commit hook that is already there
+    #[arg(long)]
+    force: bool,
+  },
+
+  /// Complete the todos a commit message names, read from stdin. The hook runs this.
+  Close {},
+}
+
+/// The ids of `closes-todo: 12, a7, 3-5` lines, in any case. Words that could be text as
+/// well as short ids are left out, `closes-todo: 4 and 5` closes no third todo.
+fn closed_ids(message: &str) -> Vec<usize> {
+  let mut closed = vec![];
+  let words = message
+    .lines()
+    .filter_map(|line| line.trim().split_once(':'))
+    .filter(|(key, _)| key.trim().eq_ignore_ascii_case(TRAILER))
+    .flat_map(|(_, ids)| ids.split([',', ' ']).map(str::trim))
+    .filter(|word| !word.is_empty());
+  for word in words {
+    match ids::parse_ids(word) {
+      Ok(ids) if ids::is_unambiguous(word) => closed.extend(ids),
+      _ => log::warn!("'{}' in {} is not an id, left out", word, TRAILER),
+    }
+  }
+  closed
+}
+
+/// The hook runs after the commit is made, ids that are gone are reported without failing it
+fn close(message: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut open = vec![];
+  for id in closed_ids(message) {
+    match collect_todos(format!("{TODO_SELECT} WHERE id = {};", id), conn)?.pop() {
+      Some(todo) if todo.status.is_open() => open.push(todo),
+      Some(_) => {}
+      None => log::warn!("No todo has id {}", ids::short(id)),
+    }
+  }
+  if open.is_empty() {
+    return Ok(());
+  }
+  set_status(open, Status::Done, conn)
+}
+
+/// Quoted for sh, nothing inside single quotes is special but the quote itself
+fn quote(text: &str) -> String {
+  format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// The hook runs this binary on the same todos, wherever the commit happens
+fn script(exe: &Path, db: &str) -> String {
+  format!(
+    "#!/bin/sh\n{}\ngit log -1 --format=%B | {} --db {} git-hook close\n",
+    MARK,
+    quote(&exe.display().to_string()),
+    quote(db)
+  )
+}
+
+fn install(force: bool, db: &str) -> Result<(), Box<dyn Error>> {
+  // Also finds the hooks of worktrees and a configured core.hooksPath
+  let output = Command::new("git")
+    .args(["rev-parse", "--git-path", "hooks"])
+    .output()
+    .map_err(|e| format!("Can't run git: {}", e))?;
+  if !output.status.success() {
+    return Err("Not inside a git repository".into());
+  }
+  let hooks = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+  let hook = hooks.join("post-commit");
+  if let Ok(existing) = fs::read_to_string(&hook)
+    && !existing.contains(MARK)
+    && !force
+  {
+    return Err(
+      format!(
+        "{} exists already, use --force to replace it or add `todo git-hook close` to it",
+        hook.display()
+      )
+      .into(),
+    );
+  }
+
+  // Relative files would be looked up from the repository
+  let db = match fs::canonicalize(db) {
+    Ok(path) => path.display().to_string(),
+    Err(_) => db.to_string(),
+  };
+  fs::create_dir_all(&hooks)?;
+  fs::write(&hook, script(&env::current_exe()?, &db))?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+  }
+  println!("Installed {}", hook.display());
+  Ok(())
+}
+
+pub fn git_hook(action: &Action, db: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Install { force } => install(*force, db),
+    Action::Close {} => {
+      let mut message = String::new();
+      io::stdin().read_to_string(&mut message)?;
+      close(&message, conn)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, collect_todos_all};
+
+  #[test]
+  fn commit_messages_close_todos() {
+    let message = "Fix the login\n\nCloses-Todo: 1, 3\ncloses-todo:2 and 4-5\nSee: 6\n";
+    assert_eq!(vec![1, 3, 2, 4, 5], closed_ids(message));
+
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec![
+        "Login".to_string(),
+        "Logout".to_string(),
+        "Docs".to_string(),
+      ],
+      &AddOptions::default(),
+      &conn,
+    );
+    // A missing id doesn't keep the others open
+    close("Fix the login\n\ncloses-todo: 1 3 9", &conn).unwrap();
+    let statuses = collect_todos_all(&conn)
+      .unwrap()
+      .iter()
+      .map(|todo| todo.status)
+      .collect::<Vec<_>>();
+    assert_eq!(vec![Status::Done, Status::Todo, Status::Done], statuses);
+
+    assert_eq!(
+      "'/usr/bin/todo' --db '/home/o'\\''neil/todos.db' git-hook close",
+      script(Path::new("/usr/bin/todo"), "/home/o'neil/todos.db")
+        .lines()
+        .last()
+        .unwrap()
+        .split(" | ")
+        .nth(1)
+        .unwrap()
+    );
+  }
+}
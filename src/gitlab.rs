@@ -0,0 +1,220 @@
+use crate::bucket::Bucket;
+use crate::config;
+use crate::strategy::{self, Strategy, Summary};
+use crate::tags::{Tags, parse_tag};
+use crate::{Todo, find_targets, links};
+use chrono::NaiveDate;
+use clap::Subcommand;
+use rusqlite::Connection;
+use serde_json::Value;
+use std::error::Error;
+
+const GITLAB: &str = "gitlab";
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Link a todo to an issue, completing the todo closes the issue
+  Link {
+    /// Id of the todo
+    #[arg(value_parser = crate::ids::parse_id)]
+    id: usize,
+
+    /// Full reference of the issue, e.g. group/project#12
+    #[arg(value_parser = parse_reference)]
+    issue: String,
+  },
+
+  /// Import the open issues assigned to me
+  Pull {
+    /// What to do with issues a todo is linked to or has the ref of already
+    #[arg(long, value_enum, default_value = "skip")]
+    strategy: Strategy,
+  },
+}
+
+/// Split `group/project#12` into the project path and the issue number
+fn split_reference(reference: &str) -> Option<(&str, u64)> {
+  let (project, iid) = reference.rsplit_once('#')?;
+  let valid = project.contains('/')
+    && project
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || "/._-".contains(c));
+  valid.then_some((project, iid.parse().ok()?))
+}
+
+fn parse_reference(input: &str) -> Result<String, String> {
+  match split_reference(input.trim()) {
+    Some(_) => Ok(input.trim().to_string()),
+    None => Err(format!(
+      "Invalid issue '{}', use e.g. group/project#12",
+      input
+    )),
+  }
+}
+
+struct Client<'a> {
+  config: &'a config::Gitlab,
+  token: String,
+}
+
+impl<'a> Client<'a> {
+  fn new(config: &'a config::Gitlab) -> Result<Client<'a>, Box<dyn Error>> {
+    Ok(Client {
+      config,
+      token: config.secret.get(GITLAB)?,
+    })
+  }
+
+  fn url(&self, path: &str) -> String {
+    format!("{}/api/v4/{}", self.config.url.trim_end_matches('/'), path)
+  }
+
+  fn assigned_issues(&self) -> Result<Value, Box<dyn Error>> {
+    let mut response = ureq::get(&self.url("issues"))
+      .header("PRIVATE-TOKEN", &self.token)
+      .query("scope", "assigned_to_me")
+      .query("state", "opened")
+      .query("per_page", "100")
+      .call()?;
+    Ok(response.body_mut().read_json()?)
+  }
+
+  fn close(&self, reference: &str) -> Result<(), Box<dyn Error>> {
+    let (project, iid) = split_reference(reference).ok_or("Invalid issue reference")?;
+    // Projects can be addressed by their path with the slashes encoded
+    let path = format!("projects/{}/issues/{}", project.replace('/', "%2F"), iid);
+    ureq::put(&self.url(&path))
+      .header("PRIVATE-TOKEN", &self.token)
+      .query("state_event", "close")
+      .send_empty()?;
+    Ok(())
+  }
+}
+
+/// The full reference of an issue from the api and the todo it becomes, labels turn into tags
+fn issue_todo(issue: &Value) -> Option<(String, Todo)> {
+  let reference = issue["references"]["full"].as_str()?;
+  let (project, _) = split_reference(reference)?;
+  let tags = issue["labels"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(|label| parse_tag(&label.as_str()?.replace(char::is_whitespace, "-")).ok())
+    .collect();
+  let todo = Todo {
+    body: format!("{} {}", reference, issue["title"].as_str()?),
+    due: issue["due_date"]
+      .as_str()
+      .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok()),
+    project: Some(project.to_string()),
+    tags: Tags(tags),
+    // Issues are already triaged by whoever assigned them
+    bucket: Bucket::Next,
+    reference: Some(reference.to_string()),
+    ..Default::default()
+  };
+  Some((reference.to_string(), todo))
+}
+
+fn pull(client: &Client, strategy: Strategy, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut summary = Summary::default();
+  for (reference, todo) in client
+    .assigned_issues()?
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(issue_todo)
+  {
+    strategy::pull(GITLAB, &reference, &todo, strategy, &mut summary, conn)?;
+  }
+  match summary.changed() {
+    true => println!("Pulled from GitLab, {}", summary),
+    false => println!("Nothing new from GitLab!"),
+  }
+  Ok(())
+}
+
+fn link(id: usize, issue: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let target = find_targets(&[id.to_string()], conn)?.remove(0);
+  links::link(target.id, GITLAB, issue, conn)?;
+  links::set_reference(target.id, issue, conn)?;
+  println!("Linked {} to: {}", issue, target.body);
+  Ok(())
+}
+
+pub fn gitlab(
+  action: &Action,
+  config: Option<&config::Gitlab>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Link { id, issue } => link(*id, issue, conn),
+    Action::Pull { strategy } => {
+      let config = config.ok_or("Add a [gitlab] table with a token to the config first")?;
+      pull(&Client::new(config)?, *strategy, conn)
+    }
+  }
+}
+
+/// Close the issues of todos that were completed, failures are retried next time
+pub fn close_completed(
+  config: Option<&config::Gitlab>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let unclosed = links::collect_unclosed(GITLAB, conn)?;
+  let Some(config) = config.filter(|_| !unclosed.is_empty()) else {
+    return Ok(());
+  };
+  let client = Client::new(config)?;
+  for (todo, reference) in unclosed {
+    match client.close(&reference) {
+      Ok(()) => {
+        links::mark_closed(GITLAB, &reference, conn)?;
+        println!("Closed {}: {}", reference, todo.body);
+      }
+      Err(e) => log::warn!("Could not close {}: {}", reference, e),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn parse_references() {
+    assert_eq!(
+      Some(("group/sub/app", 12)),
+      split_reference("group/sub/app#12")
+    );
+    assert!(parse_reference("app#12").is_err());
+    assert!(parse_reference("group/app#x").is_err());
+    assert!(parse_reference("group/app!12").is_err());
+  }
+
+  #[test]
+  fn issues_become_todos() {
+    let issue = json!({
+      "title": "Fix login",
+      "due_date": "2024-03-05",
+      "labels": ["bug", "needs review"],
+      "references": { "full": "acme/web#42" }
+    });
+    let (reference, todo) = issue_todo(&issue).unwrap();
+    assert_eq!("acme/web#42", reference);
+    assert_eq!(
+      Todo {
+        body: "acme/web#42 Fix login".to_string(),
+        due: NaiveDate::from_ymd_opt(2024, 3, 5),
+        project: Some("acme/web".to_string()),
+        tags: Tags(vec!["bug".to_string(), "needs-review".to_string()]),
+        bucket: Bucket::Next,
+        reference: Some("acme/web#42".to_string()),
+        ..Default::default()
+      },
+      todo
+    );
+  }
+}
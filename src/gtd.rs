@@ -0,0 +1,266 @@
+use crate::bucket::Bucket;
+use crate::prompt;
+use crate::status::Status;
+use crate::{
+  AddOptions, TODO_SELECT, Todo, annotations, collect_todos, ids, insert, rm, set_status, wait,
+};
+use chrono::Local;
+use dialoguer::{Input, Select, theme::ColorfulTheme};
+use rusqlite::Connection;
+use std::error::Error;
+
+/// What to do with an inbox item
+#[derive(Debug, PartialEq)]
+enum Decision {
+  Next,
+  Project(String),
+  Someday,
+  Waiting(String),
+  Done,
+  Delete,
+  Skip,
+}
+
+/// Open todos in a bucket, oldest first
+fn collect_todos_bucket(bucket: Bucket, conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} where bucket is '{}' and status not in ('done', 'cancelled');",
+      bucket.as_str()
+    ),
+    conn,
+  )
+}
+
+/// Projects that already have todos, to offer while processing
+fn projects(conn: &Connection) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut stmt = conn
+    .prepare("SELECT DISTINCT project FROM todos WHERE project IS NOT NULL ORDER BY project")?;
+  let projects = stmt
+    .query_map([], |row| row.get(0))?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<String>>();
+  Ok(projects)
+}
+
+fn set_bucket(
+  targets: &[Todo],
+  bucket: Bucket,
+  project: Option<&str>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  for target in targets {
+    conn.execute(
+      "UPDATE todos SET bucket = ?1, project = coalesce(?2, project) where id is ?3",
+      (bucket, project, target.id),
+    )?;
+  }
+  Ok(())
+}
+
+fn apply(decision: Decision, todo: Todo, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = [todo];
+  match decision {
+    Decision::Next => set_bucket(&todos, Bucket::Next, None, conn)?,
+    Decision::Project(project) => set_bucket(&todos, Bucket::Next, Some(&project), conn)?,
+    Decision::Someday => set_bucket(&todos, Bucket::Someday, None, conn)?,
+    Decision::Waiting(on) => {
+      set_bucket(&todos, Bucket::Next, None, conn)?;
+      let [todo] = todos;
+      wait(todo, Some(on), None, conn)?;
+    }
+    Decision::Done => {
+      set_bucket(&todos, Bucket::Next, None, conn)?;
+      set_status(todos.to_vec(), Status::Done, conn)?;
+    }
+    Decision::Delete => rm(todos.to_vec(), conn)?,
+    Decision::Skip => {}
+  }
+  Ok(())
+}
+
+fn print_todos(todos: &[Todo]) {
+  let today = Local::now().date_naive();
+  for todo in todos {
+    println!(
+      "{}. {}{}",
+      ids::short(todo.id),
+      todo.body,
+      annotations(todo, today)
+    );
+  }
+}
+
+pub fn inbox(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos_bucket(Bucket::Inbox, conn)?;
+  if todos.is_empty() {
+    println!("Inbox zero!");
+  }
+  print_todos(&todos);
+  Ok(())
+}
+
+pub fn someday(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  print_todos(&collect_todos_bucket(Bucket::Someday, conn)?);
+  Ok(())
+}
+
+/// Move picked todos into or out of the someday bucket
+pub fn defer(targets: Vec<Todo>, bucket: Bucket, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  set_bucket(&targets, bucket, None, conn)?;
+  for target in targets {
+    println!("Moved to {}: {}", bucket.as_str(), target.body);
+  }
+  Ok(())
+}
+
+/// Append a single line to the inbox, prompting for it when not given
+pub fn capture(body: String, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let body = if body.trim().is_empty() {
+    prompt::require("pass the line to capture")?;
+    // Untested segment starts, this part needs interactivity
+    Input::<String>::new()
+      .with_prompt("Capture")
+      .allow_empty(true)
+      .interact_text()?
+    // Untested segment ends
+  } else {
+    body
+  };
+
+  let body = body.trim();
+  if !body.is_empty() {
+    insert(body, &AddOptions::default(), conn)?;
+  }
+  Ok(())
+}
+
+/// Walk through the inbox asking what each item is
+pub fn process(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  prompt::require("file inbox todos with `todo <id> modify proj:<name>`")?;
+  // Untested segment starts, this part needs interactivity
+  let theme = ColorfulTheme::default();
+  let choices = [
+    "Next action",
+    "Next action in a project",
+    "Someday/maybe",
+    "Waiting for someone",
+    "Done already",
+    "Delete",
+    "Skip",
+  ];
+
+  for todo in collect_todos_bucket(Bucket::Inbox, conn)? {
+    let choice = Select::with_theme(&theme)
+      .with_prompt(format!("What is \"{}\"?", todo.body))
+      .default(0)
+      .items(&choices)
+      .interact()?;
+
+    let decision = match choice {
+      0 => Decision::Next,
+      1 => {
+        let known = projects(conn)?;
+        let project: String = Input::with_theme(&theme)
+          .with_prompt(format!("Project ({})", known.join(", ")))
+          .interact_text()?;
+        Decision::Project(project)
+      }
+      2 => Decision::Someday,
+      3 => Decision::Waiting(
+        Input::with_theme(&theme)
+          .with_prompt("Waiting on")
+          .interact_text()?,
+      ),
+      4 => Decision::Done,
+      5 => Decision::Delete,
+      _ => Decision::Skip,
+    };
+    apply(decision, todo, conn)?;
+  }
+  // Untested segment ends
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, collect_todos_all};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    conn
+  }
+
+  #[test]
+  fn added_todos_land_in_inbox() {
+    let conn = setup();
+    assert_eq!(3, collect_todos_bucket(Bucket::Inbox, &conn).unwrap().len());
+  }
+
+  #[test]
+  fn capture_appends_to_inbox() {
+    let conn = setup();
+    _ = capture("  Call mum ".to_string(), &conn);
+
+    let inbox = collect_todos_bucket(Bucket::Inbox, &conn).unwrap();
+    assert_eq!("Call mum", inbox[3].body);
+  }
+
+  #[test]
+  fn apply_decisions() {
+    let conn = setup();
+    let todos = collect_todos_all(&conn).unwrap();
+
+    _ = apply(
+      Decision::Project("Home".to_string()),
+      todos[0].clone(),
+      &conn,
+    );
+    _ = apply(Decision::Someday, todos[1].clone(), &conn);
+    _ = apply(
+      Decision::Waiting("Bob".to_string()),
+      todos[2].clone(),
+      &conn,
+    );
+
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(
+      (Bucket::Next, Some("Home".to_string())),
+      (todos[0].bucket, todos[0].project.clone())
+    );
+    assert_eq!(Bucket::Someday, todos[1].bucket);
+    assert_eq!(
+      (Bucket::Next, Status::Waiting),
+      (todos[2].bucket, todos[2].status)
+    );
+    assert!(
+      collect_todos_bucket(Bucket::Inbox, &conn)
+        .unwrap()
+        .is_empty()
+    );
+    assert_eq!(vec!["Home".to_string()], projects(&conn).unwrap());
+  }
+
+  #[test]
+  fn apply_done_and_delete() {
+    let conn = setup();
+    let todos = collect_todos_all(&conn).unwrap();
+
+    _ = apply(Decision::Done, todos[0].clone(), &conn);
+    _ = apply(Decision::Delete, todos[1].clone(), &conn);
+    _ = apply(Decision::Skip, todos[2].clone(), &conn);
+
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(2, todos.len());
+    assert_eq!(Status::Done, todos[0].status);
+    assert_eq!(Bucket::Inbox, todos[1].bucket);
+  }
+}
@@ -0,0 +1,185 @@
+use crate::ascii;
+use crate::frequency::{Frequency, Period};
+use crate::{TODO_SELECT, Todo, collect_todos};
+use chrono::{Days, Local, NaiveDate};
+use console::{pad_str, style};
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Adherence is measured over this many days, four whole weeks
+const WINDOW_DAYS: u64 = 28;
+
+impl Period {
+  /// How many of these fit in the adherence window
+  fn in_window(&self) -> u32 {
+    match self {
+      Period::Day => WINDOW_DAYS as u32,
+      Period::Week => WINDOW_DAYS as u32 / 7,
+      Period::Month => 1,
+    }
+  }
+}
+
+impl Frequency {
+  /// Check-ins that keep the habit over the whole window
+  fn target(&self) -> u32 {
+    self.times * self.per.in_window()
+  }
+}
+
+fn is_checked_in(todo: &Todo, day: NaiveDate, conn: &Connection) -> Result<bool, Box<dyn Error>> {
+  Ok(conn.query_row(
+    "SELECT EXISTS(SELECT 1 FROM checkins WHERE todo_id = ?1 and day = ?2)",
+    (todo.id, day),
+    |row| row.get(0),
+  )?)
+}
+
+/// Record today for the habits among `targets` instead of completing them, when `toggle` is
+/// set a check-in from today is taken back. Returns the todos that are not habits.
+pub fn check_in(
+  targets: Vec<Todo>,
+  toggle: bool,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  check_in_on(Local::now().date_naive(), targets, toggle, conn)
+}
+
+fn check_in_on(
+  today: NaiveDate,
+  targets: Vec<Todo>,
+  toggle: bool,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let (habits, others): (Vec<Todo>, Vec<Todo>) =
+    targets.into_iter().partition(|todo| todo.habit.is_some());
+  for habit in habits {
+    if toggle && is_checked_in(&habit, today, conn)? {
+      conn.execute(
+        "DELETE FROM checkins WHERE todo_id = ?1 and day = ?2",
+        (habit.id, today),
+      )?;
+      println!("Unchecked: {}", habit.body);
+    } else {
+      conn.execute(
+        "INSERT OR IGNORE INTO checkins (todo_id, day) VALUES (?1, ?2)",
+        (habit.id, today),
+      )?;
+      println!("Checked in: {}", habit.body);
+    }
+  }
+  Ok(others)
+}
+
+/// Days within the window ending `today` that the habit was done on
+fn checkins(
+  todo: &Todo,
+  today: NaiveDate,
+  conn: &Connection,
+) -> Result<HashSet<NaiveDate>, Box<dyn Error>> {
+  let start = today - Days::new(WINDOW_DAYS - 1);
+  let mut stmt =
+    conn.prepare("SELECT day FROM checkins WHERE todo_id = ?1 and day between ?2 and ?3")?;
+  let days = stmt
+    .query_map((todo.id, start, today), |row| row.get(0))?
+    .collect::<Result<HashSet<NaiveDate>, _>>()?;
+  Ok(days)
+}
+
+/// One line per open habit: frequency, check-ins against the target and a cell per day, oldest
+/// first
+fn report(today: NaiveDate, conn: &Connection) -> Result<Vec<String>, Box<dyn Error>> {
+  let habits = collect_todos(
+    format!(
+      "{TODO_SELECT} where habit is not null and status not in ('done', 'cancelled') ORDER BY id;"
+    ),
+    conn,
+  )?;
+  let width = habits
+    .iter()
+    .map(|habit| console::measure_text_width(&habit.body))
+    .max()
+    .unwrap_or(0);
+
+  let mut lines = vec![];
+  for habit in habits {
+    let Some(frequency) = habit.habit else {
+      continue;
+    };
+    let days = checkins(&habit, today, conn)?;
+    let done = days.len() as u32;
+    let target = frequency.target();
+    let percent = (done * 100 / target).min(100);
+    let cells = (0..WINDOW_DAYS)
+      .rev()
+      .map(|back| match days.contains(&(today - Days::new(back))) {
+        true => style('■').green().to_string(),
+        false => style('·').dim().to_string(),
+      })
+      .collect::<String>();
+    let adherence = format!("{:>3}%", percent);
+    let adherence = match percent {
+      80.. => style(adherence).green(),
+      50..80 => style(adherence).yellow(),
+      _ => style(adherence).red(),
+    };
+    lines.push(format!(
+      "{} {:<9} {:>3}/{:<3} {} {}",
+      pad_str(&habit.body, width, console::Alignment::Left, None),
+      frequency.to_string(),
+      done,
+      target,
+      adherence,
+      cells
+    ));
+  }
+  Ok(lines)
+}
+
+pub fn habits(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let lines = report(Local::now().date_naive(), conn)?;
+  if lines.is_empty() {
+    println!("No habits yet, add one with --habit 3/week!");
+    return Ok(());
+  }
+  println!("Habits over the last {} days:", WINDOW_DAYS);
+  for line in lines {
+    println!("{}", ascii::text(&line));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::frequency::parse_frequency;
+  use crate::{AddOptions, add, collect_todos_all};
+
+  #[test]
+  fn check_ins_count_towards_the_target() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let habit = AddOptions {
+      habit: parse_frequency("2/week").ok(),
+      ..Default::default()
+    };
+    _ = add(vec!["Run".to_string()], &habit, &conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    let todos = collect_todos_all(&conn).unwrap();
+
+    let day = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    let others = check_in_on(day(1), todos.clone(), false, &conn).unwrap();
+    assert_eq!(vec![todos[1].clone()], others);
+    _ = check_in_on(day(3), vec![todos[0].clone()], true, &conn);
+    _ = check_in_on(day(4), vec![todos[0].clone()], true, &conn);
+    _ = check_in_on(day(4), vec![todos[0].clone()], true, &conn);
+    assert_eq!(2, checkins(&todos[0], day(10), &conn).unwrap().len());
+
+    let lines = report(day(10), &conn).unwrap();
+    assert_eq!(1, lines.len());
+    assert!(lines[0].starts_with("Run 2×/week     2/8"));
+    assert!(lines[0].contains("25%"));
+  }
+}
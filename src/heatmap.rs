@@ -0,0 +1,150 @@
+use crate::ascii;
+use crate::date;
+use chrono::{Datelike, Days, Local, NaiveDate};
+use console::{Term, style};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Width of the weekday labels in front of the rows
+const LABEL_WIDTH: usize = 4;
+
+/// Completed todos per local day, habit check-ins count too
+fn completions(conn: &Connection) -> Result<HashMap<NaiveDate, usize>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT day, count(*) FROM (
+       SELECT date(changed_at, 'localtime') AS day FROM history
+       WHERE field = 'status' and new = 'done'
+       UNION ALL SELECT day FROM checkins
+     ) GROUP BY day",
+  )?;
+  let counts = stmt
+    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect::<Result<HashMap<NaiveDate, usize>, _>>()?;
+  Ok(counts)
+}
+
+/// A cell shaded by how busy the day was compared to the busiest one
+fn cell(count: usize, busiest: usize) -> String {
+  let level = (count * 4).div_ceil(busiest.max(1));
+  let color = match level {
+    0 => 237,
+    1 => 22,
+    2 => 28,
+    3 => 34,
+    _ => 40,
+  };
+  style("■").color256(color).to_string()
+}
+
+/// A row per weekday and a column per week, the last column holds `today`
+fn render(counts: &HashMap<NaiveDate, usize>, today: NaiveDate, weeks: usize) -> Vec<String> {
+  let start = date::start_of_week(today) - Days::new(7 * (weeks as u64 - 1));
+  let busiest = counts
+    .iter()
+    .filter(|(day, _)| **day >= start && **day <= today)
+    .map(|(_, count)| *count)
+    .max()
+    .unwrap_or(0);
+
+  // Month names above the first week starting in them, unless the previous name is in the way
+  let mut months = String::new();
+  let mut month = None;
+  for week in 0..weeks {
+    let first = start + Days::new(7 * week as u64);
+    if month != Some(first.month()) {
+      month = Some(first.month());
+      if months.is_empty() || week * 2 > months.len() {
+        months = format!("{:<width$}{}", months, first.format("%b"), width = week * 2);
+      }
+    }
+  }
+  let mut lines = vec![format!("{:width$}{}", "", months, width = LABEL_WIDTH)];
+
+  for weekday in 0..7 {
+    // Every other row is labelled, starting with the first day of the week
+    let label = match weekday {
+      0 | 2 | 4 => (start + Days::new(weekday as u64)).format("%a").to_string(),
+      _ => String::new(),
+    };
+    let cells = (0..weeks)
+      .map(|week| start + Days::new((7 * week + weekday) as u64))
+      .filter(|day| *day <= today)
+      .map(|day| cell(counts.get(&day).copied().unwrap_or(0), busiest))
+      .collect::<Vec<String>>();
+    lines.push(format!(
+      "{:<width$}{}",
+      label,
+      cells.join(" "),
+      width = LABEL_WIDTH
+    ));
+  }
+
+  let total = counts
+    .iter()
+    .filter(|(day, _)| **day >= start && **day <= today)
+    .map(|(_, count)| count)
+    .sum::<usize>();
+  let legend = [0, 1, 2, 3, 4].map(|level| cell(level, 4)).join(" ");
+  lines.push(String::new());
+  lines.push(format!(
+    "{:width$}{} completions in {} weeks   Less {} More",
+    "",
+    total,
+    weeks,
+    legend,
+    width = LABEL_WIDTH
+  ));
+  lines
+}
+
+pub fn heatmap(weeks: usize, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  // Every week takes two columns, keep to what fits in the terminal
+  let (_, width) = Term::stdout().size();
+  let fits = (width as usize).saturating_sub(LABEL_WIDTH) / 2;
+  let weeks = weeks.min(fits).max(1);
+  for line in render(&completions(conn)?, Local::now().date_naive(), weeks) {
+    println!("{}", ascii::text(&line));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, add, collect_todos_all, set_status};
+
+  #[test]
+  fn completions_come_from_history() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_status(todos.clone(), Status::Done, &conn);
+    _ = set_status(vec![todos[0].clone()], Status::Todo, &conn);
+    let today = Local::now().date_naive();
+    assert_eq!(Some(&2), completions(&conn).unwrap().get(&today));
+  }
+
+  #[test]
+  fn render_weeks() {
+    // A Wednesday
+    let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+    let counts = HashMap::from([
+      (today, 3),
+      (NaiveDate::from_ymd_opt(2024, 2, 26).unwrap(), 1),
+    ]);
+    let lines = render(&counts, today, 3);
+    assert_eq!("    Feb Mar", lines[0]);
+    // Every Monday has a cell, the current week stops at today
+    assert_eq!(5, console::measure_text_width(&lines[1][LABEL_WIDTH..]));
+    assert_eq!(3, console::measure_text_width(&lines[4][LABEL_WIDTH..]));
+    assert!(lines[9].contains("4 completions in 3 weeks"));
+  }
+}
@@ -0,0 +1,154 @@
+use crate::ascii;
+use crate::duration::format_duration;
+use crate::priority::Priority;
+use chrono::{DateTime, Local, Utc};
+use console::style;
+use rusqlite::types::{FromSql, Value, ValueRef};
+use rusqlite::{Connection, Row};
+use std::error::Error;
+
+/// One changed field of a todo, `created` holds the body and `deleted` the whole row as json
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+  pub id: usize,
+  pub todo_id: usize,
+  pub field: String,
+  pub old: Value,
+  pub new: Value,
+  pub changed_at: DateTime<Utc>,
+  pub operation_id: Option<usize>,
+}
+
+impl Entry {
+  pub fn from_row(row: &Row) -> Result<Entry, rusqlite::Error> {
+    Ok(Entry {
+      id: row.get("id")?,
+      todo_id: row.get("todo_id")?,
+      field: row.get("field")?,
+      old: row.get("old")?,
+      new: row.get("new")?,
+      changed_at: row.get("changed_at")?,
+      operation_id: row.get("operation_id")?,
+    })
+  }
+}
+
+/// Changes to a todo, oldest first
+pub fn collect_history(todo_id: usize, conn: &Connection) -> Result<Vec<Entry>, Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT * FROM history WHERE todo_id = ?1 ORDER BY id")?;
+  let entries = stmt
+    .query_map((todo_id,), Entry::from_row)?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<Entry>>();
+  Ok(entries)
+}
+
+/// Show stored values the way they are entered, e.g. priorities by name
+fn format_value(field: &str, value: &Value) -> String {
+  let value_ref = ValueRef::from(value);
+  match (field, value) {
+    (_, Value::Null) => "-".to_string(),
+    ("priority", _) => Priority::column_result(value_ref)
+      .map(|priority| priority.to_string())
+      .unwrap_or_else(|_| format!("{:?}", value)),
+    ("estimate", Value::Integer(minutes)) => format_duration(*minutes as u32),
+    ("focused" | "pinned", Value::Integer(focused)) => {
+      (if *focused == 0 { "no" } else { "yes" }).to_string()
+    }
+    (_, Value::Text(text)) => text.clone(),
+    (_, Value::Integer(number)) => number.to_string(),
+    (_, Value::Real(number)) => number.to_string(),
+    (_, Value::Blob(blob)) => format!("{} bytes", blob.len()),
+  }
+}
+
+/// Body of a deleted todo, older entries only stored the body itself
+fn deleted_body(old: &Value) -> String {
+  let text = format_value("body", old);
+  serde_json::from_str::<serde_json::Value>(&text)
+    .ok()
+    .and_then(|row| row["body"].as_str().map(str::to_string))
+    .unwrap_or(text)
+}
+
+pub fn format_entry(entry: &Entry) -> String {
+  let change = match entry.field.as_str() {
+    "created" => format_value("body", &entry.new),
+    "deleted" => deleted_body(&entry.old),
+    field => format!(
+      "{} → {}",
+      format_value(field, &entry.old),
+      format_value(field, &entry.new)
+    ),
+  };
+  format!(
+    "{}  {:<13} {}",
+    entry
+      .changed_at
+      .with_timezone(&Local)
+      .format("%Y-%m-%d %H:%M"),
+    entry.field,
+    change
+  )
+}
+
+pub fn history(todo_id: usize, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let entries = collect_history(todo_id, conn)?;
+  if entries.is_empty() {
+    return Err(format!("No history for todo {}", todo_id).into());
+  }
+  for entry in &entries {
+    let line = ascii::text(&format_entry(entry));
+    if entry.field == "deleted" {
+      println!("{}", style(line).red());
+    } else {
+      println!("{}", line);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, add, collect_todos_all, rm, set_priority, set_status};
+
+  #[test]
+  fn history_records_changes() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_status(todos.clone(), Status::Done, &conn);
+    _ = set_priority(todos.clone(), Some(Priority::High), &conn);
+    _ = rm(todos, &conn);
+
+    let changes = collect_history(1, &conn)
+      .unwrap()
+      .iter()
+      .map(|entry| {
+        (
+          entry.field.clone(),
+          format_entry(entry).split_once("  ").unwrap().1.to_string(),
+        )
+      })
+      .collect::<Vec<(String, String)>>();
+    let expected = [
+      ("created", "created       Milk"),
+      ("status", "status        todo → done"),
+      ("priority", "priority      - → high"),
+      ("deleted", "deleted       Milk"),
+    ]
+    .map(|(field, line)| (field.to_string(), line.to_string()));
+    assert_eq!(expected.to_vec(), changes);
+  }
+
+  #[test]
+  fn format_values() {
+    assert_eq!("1h30m", format_value("estimate", &Value::Integer(90)));
+    assert_eq!("yes", format_value("focused", &Value::Integer(1)));
+    assert_eq!("-", format_value("due", &Value::Null));
+  }
+}
@@ -0,0 +1,131 @@
+use console::Term;
+use std::env;
+
+/// Terminals that are known to show OSC 8 links by `TERM_PROGRAM`
+const PROGRAMS: [&str; 7] = [
+  "iTerm.app",
+  "WezTerm",
+  "vscode",
+  "ghostty",
+  "Hyper",
+  "Tabby",
+  "rio",
+];
+
+/// Terminals that are known to show OSC 8 links by `TERM`
+const TERMS: [&str; 5] = [
+  "xterm-kitty",
+  "alacritty",
+  "xterm-ghostty",
+  "foot",
+  "wezterm",
+];
+
+/// Whether the terminal described by the environment shows OSC 8 links.
+/// `FORCE_HYPERLINK=1` or `FORCE_HYPERLINK=0` overrides the guess.
+fn supported(var: impl Fn(&str) -> Option<String>) -> bool {
+  if let Some(force) = var("FORCE_HYPERLINK") {
+    return force != "0";
+  }
+  if [
+    "DOMTERM",
+    "WT_SESSION",
+    "KONSOLE_VERSION",
+    "KITTY_WINDOW_ID",
+  ]
+  .iter()
+  .any(|name| var(name).is_some())
+  {
+    return true;
+  }
+  if var("VTE_VERSION").and_then(|version| version.parse::<u32>().ok()) >= Some(5000) {
+    return true;
+  }
+  var("TERM_PROGRAM").is_some_and(|program| PROGRAMS.contains(&program.as_str()))
+    || var("TERM").is_some_and(|term| TERMS.contains(&term.as_str()))
+}
+
+/// Links are only written to a terminal that shows them, anything else gets plain text
+pub fn enabled() -> bool {
+  Term::stdout().is_term() && supported(|name| env::var(name).ok())
+}
+
+/// `text` that opens `url` when clicked
+pub fn link(text: &str, url: &str) -> String {
+  format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+/// Byte length of the url at the start of `text`, punctuation at the end belongs to the
+/// sentence around it
+fn url_length(text: &str) -> Option<usize> {
+  if !text.starts_with("https://") && !text.starts_with("http://") {
+    return None;
+  }
+  let end = text
+    .find(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | '"'))
+    .unwrap_or(text.len());
+  let url = text[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '\'']);
+  Some(url.len()).filter(|length| *length > "https://".len())
+}
+
+/// Turn the urls in `text` into links when `enabled`
+pub fn linkify(text: &str, enabled: bool) -> String {
+  if !enabled {
+    return text.to_string();
+  }
+  let mut linked = String::new();
+  let mut rest = text;
+  while let Some(start) = rest.find("http") {
+    linked.push_str(&rest[..start]);
+    rest = &rest[start..];
+    let length = url_length(rest).unwrap_or(0);
+    // Not a url after all, move past the `h`
+    let (url, after) = rest.split_at(length.max(1));
+    match length {
+      0 => linked.push_str(url),
+      _ => linked.push_str(&link(url, url)),
+    }
+    rest = after;
+  }
+  linked.push_str(rest);
+  linked
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn urls_become_links() {
+    assert_eq!(
+      format!(
+        "See {}, or http",
+        link("https://example.com/a?b=1", "https://example.com/a?b=1")
+      ),
+      linkify("See https://example.com/a?b=1, or http", true)
+    );
+    assert_eq!(
+      "See https://example.com",
+      linkify("See https://example.com", false)
+    );
+    assert_eq!("https://", linkify("https://", true));
+  }
+
+  #[test]
+  fn support_is_guessed_from_the_environment() {
+    let with = |vars: &'static [(&str, &str)]| {
+      supported(move |name| {
+        vars
+          .iter()
+          .find(|(own, _)| *own == name)
+          .map(|(_, value)| value.to_string())
+      })
+    };
+    assert!(with(&[("TERM_PROGRAM", "WezTerm")]));
+    assert!(with(&[("VTE_VERSION", "6003")]));
+    assert!(!with(&[("VTE_VERSION", "4200")]));
+    assert!(!with(&[("TERM", "xterm-256color")]));
+    assert!(!with(&[("TERM", "xterm-kitty"), ("FORCE_HYPERLINK", "0")]));
+    assert!(with(&[("FORCE_HYPERLINK", "1")]));
+  }
+}
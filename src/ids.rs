@@ -0,0 +1,149 @@
+/// Ranges longer than this are most likely a typo
+const MAX_RANGE: usize = 1000;
+
+/// Short ids start with one of these, so they never look like full ids
+const LETTERS: &[u8; 26] = b"abcdefghijklmnopqrstuvwxyz";
+
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// The short id shown for a todo, base36 that starts with a letter: 1 is `a`, 26 is `z`
+/// and 27 is `a0`. It follows from the id, so it stays the same while todos come and go.
+pub fn short(id: usize) -> String {
+  let Some(mut rest) = id.checked_sub(1) else {
+    return id.to_string();
+  };
+  let (mut length, mut count) = (1, LETTERS.len());
+  while rest >= count {
+    rest -= count;
+    count *= DIGITS.len();
+    length += 1;
+  }
+  let mut short = vec![];
+  for _ in 1..length {
+    short.push(DIGITS[rest % DIGITS.len()]);
+    rest /= DIGITS.len();
+  }
+  short.push(LETTERS[rest]);
+  short.iter().rev().map(|c| *c as char).collect()
+}
+
+/// The id a short id stands for, in any case
+pub fn parse_short(input: &str) -> Option<usize> {
+  let input = input.to_ascii_lowercase();
+  let mut chars = input.chars();
+  let first = chars.next().filter(|c| c.is_ascii_lowercase())?;
+  let (mut value, mut offset, mut count) = (first as usize - 'a' as usize, 0usize, LETTERS.len());
+  for c in chars {
+    value = value
+      .checked_mul(DIGITS.len())?
+      .checked_add(c.to_digit(36)? as usize)?;
+    offset = offset.checked_add(count)?;
+    count = count.checked_mul(DIGITS.len())?;
+  }
+  offset.checked_add(value)?.checked_add(1)
+}
+
+/// An id written in full like `34` or short like `a7`
+pub fn parse_id(input: &str) -> Result<usize, String> {
+  let input = input.trim();
+  input
+    .parse::<usize>()
+    .ok()
+    .or_else(|| parse_short(input))
+    .ok_or_else(|| format!("Invalid id '{}', use e.g. 34 or a7", input))
+}
+
+/// Ids written as a list of ids and ranges, `1-5,8,a7`, in the order given without repeats
+pub fn parse_ids(input: &str) -> Result<Vec<usize>, String> {
+  let invalid = || format!("Invalid ids '{}', use e.g. 3, a7 or 1-5,8,12", input);
+  let mut ids: Vec<usize> = vec![];
+  for part in input.split(',') {
+    let (start, end) = match part.split_once('-') {
+      Some((start, end)) => (start, end),
+      None => (part, part),
+    };
+    let start = parse_id(start).map_err(|_| invalid())?;
+    let end = parse_id(end).map_err(|_| invalid())?;
+    if start > end {
+      return Err(format!("Range {} runs backwards", part.trim()));
+    }
+    if end - start >= MAX_RANGE {
+      return Err(format!("Range {} is too long", part.trim()));
+    }
+    for id in start..=end {
+      if !ids.contains(&id) {
+        ids.push(id);
+      }
+    }
+  }
+  Ok(ids)
+}
+
+/// Whether the target is meant as ids rather than text to look for
+pub fn is_id_expression(target: &str) -> bool {
+  target.starts_with(|c: char| c.is_ascii_digit())
+    && target
+      .chars()
+      .all(|c| c.is_ascii_digit() || c == ',' || c == '-')
+}
+
+/// Whether the target can only be meant as ids: full ids, or short ids that are a single
+/// letter or have a digit like `c` and `a7`. Words like `bed` are short ids too.
+pub fn is_unambiguous(target: &str) -> bool {
+  is_id_expression(target)
+    || (parse_ids(target).is_ok()
+      && target.split([',', '-']).all(|part| {
+        let part = part.trim();
+        part.chars().count() == 1 || part.contains(|c: char| c.is_ascii_digit())
+      }))
+}
+
+/// Clap parser for arguments that only take ids, checked here and expanded later
+pub fn parse_id_expression(input: &str) -> Result<String, String> {
+  parse_ids(input)?;
+  Ok(input.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_id_lists() {
+    assert_eq!(Ok(vec![3]), parse_ids("3"));
+    assert_eq!(Ok(vec![1, 2, 3, 8, 12]), parse_ids("1-3,8,12"));
+    assert_eq!(Ok(vec![2, 3, 1]), parse_ids("2-3,1-2"));
+    assert!(parse_ids("5-1").is_err());
+    assert!(parse_ids("1-").is_err());
+    assert!(parse_ids("1,,2").is_err());
+    assert!(parse_ids("1-100000").is_err());
+    assert!(is_unambiguous("3,1-2") && is_unambiguous("c,a7") && is_unambiguous("b-d"));
+    assert!(!is_unambiguous("bed") && !is_unambiguous("a7,tea") && !is_unambiguous("milk!"));
+  }
+
+  #[test]
+  fn short_ids_round_trip() {
+    assert_eq!("a", short(1));
+    assert_eq!("z", short(26));
+    assert_eq!("a0", short(27));
+    assert_eq!("a7", short(34));
+    assert_eq!("zz", short(962));
+    assert_eq!("a00", short(963));
+    for id in 1..2000 {
+      assert_eq!(Some(id), parse_short(&short(id)));
+    }
+    assert_eq!(Ok(34), parse_id("A7"));
+    assert_eq!(Ok(34), parse_id("34"));
+    assert_eq!(None, parse_short("7a"));
+    assert_eq!(None, parse_short("a-7"));
+    assert_eq!(None, parse_short("supercalifragilistic"));
+    assert_eq!(Ok(vec![34, 35, 36, 2]), parse_ids("a7-a9,b"));
+  }
+
+  #[test]
+  fn id_expressions() {
+    assert!(is_id_expression("1-5,8"));
+    assert!(!is_id_expression("milk"));
+    assert!(!is_id_expression("-5"));
+  }
+}
@@ -0,0 +1,279 @@
+use crate::bucket::Bucket;
+use crate::config;
+use crate::priority::Priority;
+use crate::strategy::{self, Strategy, Summary};
+use crate::{Todo, find_targets, links};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::NaiveDate;
+use clap::Subcommand;
+use rusqlite::Connection;
+use serde_json::{Value, json};
+use std::error::Error;
+
+const JIRA: &str = "jira";
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Link a todo to an issue, completing the todo transitions the issue
+  Link {
+    /// Id of the todo
+    #[arg(value_parser = crate::ids::parse_id)]
+    id: usize,
+
+    /// Key of the issue, e.g. SHOP-123
+    #[arg(value_parser = parse_key)]
+    issue: String,
+  },
+
+  /// Import the open issues assigned to me
+  Pull {
+    /// What to do with issues a todo is linked to or has the ref of already
+    #[arg(long, value_enum, default_value = "skip")]
+    strategy: Strategy,
+  },
+}
+
+/// Issue keys are a project key, a dash and a number
+fn parse_key(input: &str) -> Result<String, String> {
+  let key = input.trim().to_uppercase();
+  let valid = key.split_once('-').is_some_and(|(project, number)| {
+    project.starts_with(|c: char| c.is_ascii_alphabetic())
+      && project
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+      && !number.is_empty()
+      && number.chars().all(|c| c.is_ascii_digit())
+  });
+  if !valid {
+    return Err(format!("Invalid issue key '{}', use e.g. SHOP-123", input));
+  }
+  Ok(key)
+}
+
+struct Client<'a> {
+  config: &'a config::Jira,
+  authorization: String,
+}
+
+impl<'a> Client<'a> {
+  fn new(config: &'a config::Jira) -> Result<Client<'a>, Box<dyn Error>> {
+    let token = config.secret.get(JIRA)?;
+    let authorization = match &config.email {
+      Some(email) => format!("Basic {}", STANDARD.encode(format!("{}:{}", email, token))),
+      None => format!("Bearer {}", token),
+    };
+    Ok(Client {
+      config,
+      authorization,
+    })
+  }
+
+  fn url(&self, path: &str) -> String {
+    format!(
+      "{}/rest/api/3/{}",
+      self.config.url.trim_end_matches('/'),
+      path
+    )
+  }
+
+  fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Value, Box<dyn Error>> {
+    let mut response = ureq::get(&self.url(path))
+      .header("Authorization", &self.authorization)
+      .header("Accept", "application/json")
+      .query_pairs(query.iter().copied())
+      .call()?;
+    Ok(response.body_mut().read_json()?)
+  }
+
+  fn post(&self, path: &str, body: Value) -> Result<(), Box<dyn Error>> {
+    ureq::post(&self.url(path))
+      .header("Authorization", &self.authorization)
+      .send_json(body)?;
+    Ok(())
+  }
+
+  /// Move the issue through the transition named in the config, or the one leading to a
+  /// status of that name
+  fn transition(&self, key: &str) -> Result<(), Box<dyn Error>> {
+    let path = format!("issue/{}/transitions", key);
+    let transitions = self.get(&path, &[])?;
+    let wanted = &self.config.done_transition;
+    let id = transitions["transitions"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .find(|transition| {
+        [&transition["name"], &transition["to"]["name"]]
+          .iter()
+          .any(|name| {
+            name
+              .as_str()
+              .is_some_and(|name| name.eq_ignore_ascii_case(wanted))
+          })
+      })
+      .and_then(|transition| transition["id"].as_str())
+      .ok_or_else(|| format!("{} has no '{}' transition", key, wanted))?;
+    self.post(&path, json!({ "transition": { "id": id } }))
+  }
+}
+
+fn parse_priority(name: &str) -> Option<Priority> {
+  match name {
+    "Highest" | "High" => Some(Priority::High),
+    "Medium" => Some(Priority::Medium),
+    "Low" | "Lowest" => Some(Priority::Low),
+    _ => None,
+  }
+}
+
+/// The key of an issue from a search and the todo it becomes
+fn issue_todo(issue: &Value) -> Option<(String, Todo)> {
+  let key = issue["key"].as_str()?;
+  let fields = &issue["fields"];
+  let todo = Todo {
+    body: format!("{} {}", key, fields["summary"].as_str()?),
+    due: fields["duedate"]
+      .as_str()
+      .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok()),
+    priority: fields["priority"]["name"].as_str().and_then(parse_priority),
+    project: fields["project"]["name"].as_str().map(str::to_string),
+    // Issues are already triaged by whoever assigned them
+    bucket: Bucket::Next,
+    reference: Some(key.to_string()),
+    ..Default::default()
+  };
+  Some((key.to_string(), todo))
+}
+
+fn pull(client: &Client, strategy: Strategy, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let result = client.get(
+    "search/jql",
+    &[
+      ("jql", &client.config.jql),
+      ("fields", "summary,duedate,priority,project"),
+      ("maxResults", "100"),
+    ],
+  )?;
+  let mut summary = Summary::default();
+  for (key, todo) in result["issues"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(issue_todo)
+  {
+    strategy::pull(JIRA, &key, &todo, strategy, &mut summary, conn)?;
+  }
+  match summary.changed() {
+    true => println!("Pulled from Jira, {}", summary),
+    false => println!("Nothing new from Jira!"),
+  }
+  Ok(())
+}
+
+fn link(id: usize, issue: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let target = find_targets(&[id.to_string()], conn)?.remove(0);
+  links::link(target.id, JIRA, issue, conn)?;
+  links::set_reference(target.id, issue, conn)?;
+  println!("Linked {} to: {}", issue, target.body);
+  Ok(())
+}
+
+fn missing_config() -> Box<dyn Error> {
+  "Add a [jira] table with the url and a token to the config first".into()
+}
+
+pub fn jira(
+  action: &Action,
+  config: Option<&config::Jira>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Link { id, issue } => link(*id, issue, conn),
+    Action::Pull { strategy } => pull(
+      &Client::new(config.ok_or_else(missing_config)?)?,
+      *strategy,
+      conn,
+    ),
+  }
+}
+
+/// Transition the issues of todos that were completed, failures are retried next time
+pub fn close_completed(
+  config: Option<&config::Jira>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let unclosed = links::collect_unclosed(JIRA, conn)?;
+  let Some(config) = config.filter(|_| !unclosed.is_empty()) else {
+    return Ok(());
+  };
+  let client = Client::new(config)?;
+  for (todo, key) in unclosed {
+    match client.transition(&key) {
+      Ok(()) => {
+        links::mark_closed(JIRA, &key, conn)?;
+        println!(
+          "Transitioned {} to {}: {}",
+          key, config.done_transition, todo.body
+        );
+      }
+      Err(e) => log::warn!("Could not transition {}: {}", key, e),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add};
+
+  #[test]
+  fn parse_keys() {
+    assert_eq!(Ok("SHOP-12".to_string()), parse_key("shop-12"));
+    assert!(parse_key("SHOP").is_err());
+    assert!(parse_key("12-SHOP").is_err());
+    assert!(parse_key("SHOP-1a").is_err());
+  }
+
+  #[test]
+  fn issues_become_todos() {
+    let issue = json!({
+      "key": "SHOP-7",
+      "fields": {
+        "summary": "Restock milk",
+        "duedate": "2024-03-05",
+        "priority": { "name": "Highest" },
+        "project": { "name": "Shop" }
+      }
+    });
+    let (key, todo) = issue_todo(&issue).unwrap();
+    assert_eq!("SHOP-7", key);
+    assert_eq!(
+      Todo {
+        body: "SHOP-7 Restock milk".to_string(),
+        due: NaiveDate::from_ymd_opt(2024, 3, 5),
+        priority: Some(Priority::High),
+        project: Some("Shop".to_string()),
+        bucket: Bucket::Next,
+        reference: Some("SHOP-7".to_string()),
+        ..Default::default()
+      },
+      todo
+    );
+    assert!(issue_todo(&json!({ "key": "SHOP-8" })).is_none());
+  }
+
+  #[test]
+  fn link_needs_a_todo() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    assert!(link(9, "SHOP-1", &conn).is_err());
+    link(1, "SHOP-1", &conn).unwrap();
+    assert!(links::is_linked(JIRA, "SHOP-1", &conn).unwrap());
+    // Nothing to transition, so no config is needed
+    close_completed(None, &conn).unwrap();
+  }
+}
@@ -0,0 +1,501 @@
+use crate::Todo;
+#[cfg(feature = "cli")]
+use crate::storage::{Storage, collect_todos_stored, open_in_memory, write_atomic};
+#[cfg(feature = "cli")]
+use rusqlite::types::{Value, ValueRef};
+#[cfg(feature = "cli")]
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+#[cfg(feature = "cli")]
+use std::fs;
+#[cfg(feature = "cli")]
+use std::io::ErrorKind;
+#[cfg(feature = "cli")]
+use std::path::{Path, PathBuf};
+
+/// A single pretty printed json file, meant to be read and diffed by people
+#[cfg(feature = "cli")]
+pub struct JsonFile {
+  path: PathBuf,
+}
+
+#[cfg(feature = "cli")]
+impl JsonFile {
+  pub fn new(path: PathBuf) -> JsonFile {
+    JsonFile { path }
+  }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Document {
+  todos: Vec<Todo>,
+  /// Pairs of a todo and the todo it depends on
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  dependencies: Vec<(usize, usize)>,
+  /// Pairs of a habit and a day it was checked in on
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  checkins: Vec<(usize, String)>,
+  /// Colors set with `todo tag color`
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  tag_colors: BTreeMap<String, String>,
+  /// Defaults set with `todo project`
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  projects: Vec<Project>,
+  /// The project picked with `todo use`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  current_project: Option<String>,
+  /// What changed in each todo, for `todo history` and undo
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  history: Vec<Change>,
+  /// The commands the changes were made by
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  operations: Vec<Operation>,
+  /// Pairs of a kind and what was searched for, the latest last
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  searches: Vec<(String, String)>,
+  /// Due dates notified about and until when they are snoozed
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  notifications: Vec<Notification>,
+  /// Issues and tasks elsewhere the todos are synced with
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  links: Vec<Link>,
+  /// Pairs of a grouping and a group collapsed in the terminal ui
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  collapsed: Vec<(String, String)>,
+}
+
+/// A row of the project defaults, as the database keeps it
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Project {
+  name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  tags: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  priority: Option<i64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  sort: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  archive_days: Option<i64>,
+}
+
+/// A row of the history, `old` and `new` are whatever SQLite kept in them
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Change {
+  id: usize,
+  todo_id: usize,
+  field: String,
+  #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+  old: serde_json::Value,
+  #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+  new: serde_json::Value,
+  changed_at: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  operation_id: Option<usize>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Operation {
+  id: usize,
+  command: String,
+  undoable: bool,
+  undone: bool,
+  created_at: String,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Notification {
+  todo_id: usize,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  due: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  snoozed_until: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Link {
+  todo_id: usize,
+  source: String,
+  reference: String,
+  closed: bool,
+}
+
+#[cfg(feature = "cli")]
+fn from_sql(value: ValueRef) -> serde_json::Value {
+  match value {
+    ValueRef::Null => serde_json::Value::Null,
+    ValueRef::Integer(integer) => integer.into(),
+    ValueRef::Real(real) => real.into(),
+    ValueRef::Text(text) | ValueRef::Blob(text) => String::from_utf8_lossy(text).into(),
+  }
+}
+
+#[cfg(feature = "cli")]
+fn to_sql(value: serde_json::Value) -> Value {
+  match value {
+    serde_json::Value::Null => Value::Null,
+    serde_json::Value::Bool(bool) => Value::Integer(bool.into()),
+    serde_json::Value::Number(number) => match number.as_i64() {
+      Some(integer) => Value::Integer(integer),
+      None => Value::Real(number.as_f64().unwrap_or_default()),
+    },
+    serde_json::Value::String(text) => Value::Text(text),
+    value => Value::Text(value.to_string()),
+  }
+}
+
+/// The todos of a document in the storage format, as written by `render`
+pub fn parse(document: serde_json::Value) -> Result<Vec<Todo>, Box<dyn Error>> {
+  Ok(serde_json::from_value::<Document>(document)?.todos)
+}
+
+/// Todos in the storage format, without their dependencies
+pub fn render(todos: &[Todo]) -> Result<String, Box<dyn Error>> {
+  let document = Document {
+    todos: todos.to_vec(),
+    ..Default::default()
+  };
+  Ok(serde_json::to_string_pretty(&document)? + "\n")
+}
+
+#[cfg(feature = "cli")]
+impl Storage for JsonFile {
+  fn open(&self) -> Result<Connection, Box<dyn Error>> {
+    let document = match fs::read_to_string(&self.path) {
+      Ok(text) => serde_json::from_str::<Document>(&text)
+        .map_err(|e| format!("{}: {}", self.path.display(), e))?,
+      Err(e) if e.kind() == ErrorKind::NotFound => Document::default(),
+      Err(e) => return Err(format!("{}: {}", self.path.display(), e).into()),
+    };
+    let conn = open_in_memory(&document.todos)?;
+    for (todo_id, depends_on) in document.dependencies {
+      conn.execute(
+        "INSERT OR IGNORE INTO dependencies (todo_id, depends_on) VALUES (?1, ?2)",
+        (todo_id, depends_on),
+      )?;
+    }
+    for (todo_id, day) in document.checkins {
+      conn.execute(
+        "INSERT OR IGNORE INTO checkins (todo_id, day) VALUES (?1, ?2)",
+        (todo_id, day),
+      )?;
+    }
+    for (tag, color) in document.tag_colors {
+      conn.execute(
+        "INSERT OR REPLACE INTO tag_colors (tag, color) VALUES (?1, ?2)",
+        (tag, color),
+      )?;
+    }
+    for project in document.projects {
+      conn.execute(
+        "INSERT OR REPLACE INTO list_settings (project, tags, priority, sort, archive_days)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        (
+          project.name,
+          project.tags,
+          project.priority,
+          project.sort,
+          project.archive_days,
+        ),
+      )?;
+    }
+    if let Some(project) = document.current_project {
+      conn.execute(
+        "INSERT INTO current_list (id, project) VALUES (1, ?1)",
+        (project,),
+      )?;
+    }
+    for change in document.history {
+      conn.execute(
+        "INSERT INTO history (id, todo_id, field, old, new, changed_at, operation_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+          change.id,
+          change.todo_id,
+          change.field,
+          to_sql(change.old),
+          to_sql(change.new),
+          change.changed_at,
+          change.operation_id,
+        ),
+      )?;
+    }
+    for operation in document.operations {
+      conn.execute(
+        "INSERT INTO operations (id, command, undoable, undone, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        (
+          operation.id,
+          operation.command,
+          operation.undoable,
+          operation.undone,
+          operation.created_at,
+        ),
+      )?;
+    }
+    for (kind, text) in document.searches {
+      conn.execute(
+        "INSERT OR IGNORE INTO searches (kind, text) VALUES (?1, ?2)",
+        (kind, text),
+      )?;
+    }
+    for notification in document.notifications {
+      conn.execute(
+        "INSERT OR REPLACE INTO notifications (todo_id, due, snoozed_until) VALUES (?1, ?2, ?3)",
+        (
+          notification.todo_id,
+          notification.due,
+          notification.snoozed_until,
+        ),
+      )?;
+    }
+    for link in document.links {
+      conn.execute(
+        "INSERT OR REPLACE INTO links (todo_id, source, reference, closed)
+         VALUES (?1, ?2, ?3, ?4)",
+        (link.todo_id, link.source, link.reference, link.closed),
+      )?;
+    }
+    for (grouping, name) in document.collapsed {
+      conn.execute(
+        "INSERT OR IGNORE INTO collapsed (grouping, name) VALUES (?1, ?2)",
+        (grouping, name),
+      )?;
+    }
+    Ok(conn)
+  }
+
+  fn save(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let mut stmt =
+      conn.prepare("SELECT todo_id, depends_on FROM dependencies ORDER BY todo_id, depends_on")?;
+    let dependencies = stmt
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .filter_map(|s| s.ok())
+      .collect::<Vec<(usize, usize)>>();
+    let mut stmt = conn.prepare("SELECT todo_id, day FROM checkins ORDER BY todo_id, day")?;
+    let checkins = stmt
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<Vec<(usize, String)>, _>>()?;
+    let mut stmt = conn.prepare("SELECT tag, color FROM tag_colors")?;
+    let tag_colors = stmt
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<BTreeMap<String, String>, _>>()?;
+    let mut stmt = conn.prepare(
+      "SELECT project, tags, priority, sort, archive_days FROM list_settings ORDER BY project",
+    )?;
+    let projects = stmt
+      .query_map([], |row| {
+        Ok(Project {
+          name: row.get(0)?,
+          tags: row.get(1)?,
+          priority: row.get(2)?,
+          sort: row.get(3)?,
+          archive_days: row.get(4)?,
+        })
+      })?
+      .collect::<Result<Vec<Project>, _>>()?;
+    let current_project = conn
+      .query_row("SELECT project FROM current_list", [], |row| row.get(0))
+      .optional()?;
+    let mut stmt = conn.prepare(
+      "SELECT id, todo_id, field, old, new, changed_at, operation_id FROM history ORDER BY id",
+    )?;
+    let history = stmt
+      .query_map([], |row| {
+        Ok(Change {
+          id: row.get(0)?,
+          todo_id: row.get(1)?,
+          field: row.get(2)?,
+          old: from_sql(row.get_ref(3)?),
+          new: from_sql(row.get_ref(4)?),
+          changed_at: row.get(5)?,
+          operation_id: row.get(6)?,
+        })
+      })?
+      .collect::<Result<Vec<Change>, _>>()?;
+    let mut stmt = conn
+      .prepare("SELECT id, command, undoable, undone, created_at FROM operations ORDER BY id")?;
+    let operations = stmt
+      .query_map([], |row| {
+        Ok(Operation {
+          id: row.get(0)?,
+          command: row.get(1)?,
+          undoable: row.get(2)?,
+          undone: row.get(3)?,
+          created_at: row.get(4)?,
+        })
+      })?
+      .collect::<Result<Vec<Operation>, _>>()?;
+    let mut stmt = conn.prepare("SELECT kind, text FROM searches ORDER BY id")?;
+    let searches = stmt
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<Vec<(String, String)>, _>>()?;
+    let mut stmt =
+      conn.prepare("SELECT todo_id, due, snoozed_until FROM notifications ORDER BY todo_id")?;
+    let notifications = stmt
+      .query_map([], |row| {
+        Ok(Notification {
+          todo_id: row.get(0)?,
+          due: row.get(1)?,
+          snoozed_until: row.get(2)?,
+        })
+      })?
+      .collect::<Result<Vec<Notification>, _>>()?;
+    let mut stmt = conn
+      .prepare("SELECT todo_id, source, reference, closed FROM links ORDER BY source, reference")?;
+    let links = stmt
+      .query_map([], |row| {
+        Ok(Link {
+          todo_id: row.get(0)?,
+          source: row.get(1)?,
+          reference: row.get(2)?,
+          closed: row.get(3)?,
+        })
+      })?
+      .collect::<Result<Vec<Link>, _>>()?;
+    let mut stmt = conn.prepare("SELECT grouping, name FROM collapsed ORDER BY grouping, name")?;
+    let collapsed = stmt
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<Vec<(String, String)>, _>>()?;
+    let document = Document {
+      todos: collect_todos_stored(conn)?,
+      dependencies,
+      checkins,
+      tag_colors,
+      projects,
+      current_project,
+      history,
+      operations,
+      searches,
+      notifications,
+      links,
+      collapsed,
+    };
+    write_atomic(
+      &self.path,
+      &(serde_json::to_string_pretty(&document)? + "\n"),
+    )
+  }
+
+  fn file(&self) -> Option<&Path> {
+    Some(&self.path)
+  }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+  use super::*;
+  use crate::deps::depend;
+  use crate::priority::Priority;
+  use crate::{AddOptions, add, collect_todos_all, searches, undo};
+  use std::env;
+
+  #[test]
+  fn storage_round_trip() {
+    let path = env::temp_dir().join(format!("todo-{}.json", std::process::id()));
+    _ = fs::remove_file(&path);
+    let storage = JsonFile::new(path.clone());
+
+    let conn = storage.open().unwrap();
+    let options = AddOptions {
+      priority: Some(Priority::High),
+      notes: Some("- [ ] oat\n- [x] soy".to_string()),
+      ..Default::default()
+    };
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &options,
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = depend(&todos[0], &[todos[1].clone()], &conn);
+    storage.save(&conn).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.contains("\"priority\": \"high\""));
+    assert!(!text.contains("null"));
+    let conn = storage.open().unwrap();
+    assert_eq!(todos, collect_todos_all(&conn).unwrap());
+    let dependencies: usize = conn
+      .query_row("SELECT count(*) FROM dependencies", [], |row| row.get(0))
+      .unwrap();
+    assert_eq!(1, dependencies);
+
+    // What is kept beside the todos comes back too
+    conn
+      .execute_batch(&format!(
+        "INSERT INTO checkins (todo_id, day) VALUES ({}, '2026-10-01');
+         INSERT INTO tag_colors (tag, color) VALUES ('home', 'green');
+         INSERT INTO list_settings (project, priority, archive_days) VALUES ('House', 1, 30);
+         INSERT INTO current_list (id, project) VALUES (1, 'House');",
+        todos[0].id
+      ))
+      .unwrap();
+    storage.save(&conn).unwrap();
+    let conn = storage.open().unwrap();
+    let kept: (usize, String, i64, String) = conn
+      .query_row(
+        "SELECT (SELECT count(*) FROM checkins), (SELECT color FROM tag_colors),
+           (SELECT archive_days FROM list_settings), (SELECT project FROM current_list)",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+      )
+      .unwrap();
+    assert_eq!((1, "green".to_string(), 30, "House".to_string()), kept);
+    _ = fs::remove_file(path);
+  }
+
+  #[test]
+  fn history_and_searches_are_kept() {
+    let path = env::temp_dir().join(format!("todo-history-{}.json", std::process::id()));
+    _ = fs::remove_file(&path);
+    let storage = JsonFile::new(path.clone());
+    let conn = storage.open().unwrap();
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    conn
+      .execute_batch(
+        "UPDATE todos SET priority = 1, estimate = 1.5;
+         INSERT INTO notifications (todo_id, due, snoozed_until) VALUES (1, '2026-10-01', NULL);
+         INSERT INTO links (todo_id, source, reference) VALUES (1, 'jira', 'SHOP-1');
+         INSERT INTO collapsed (grouping, name) VALUES ('project', 'House');",
+      )
+      .unwrap();
+    undo::record("add Milk", true, &conn).unwrap();
+    searches::record(searches::SEARCH, "milk", &conn).unwrap();
+    searches::record(searches::SEARCH, "bread", &conn).unwrap();
+    let kept = |conn: &Connection| {
+      conn
+        .query_row(
+          "SELECT (SELECT group_concat(id || field || quote(old) || quote(new) || operation_id)
+              FROM history),
+             (SELECT group_concat(command || undoable || undone) FROM operations),
+             (SELECT count(*) FROM notifications) + (SELECT count(*) FROM links)
+               + (SELECT count(*) FROM collapsed)",
+          [],
+          |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap()
+    };
+    let before: (String, String, usize) = kept(&conn);
+    storage.save(&conn).unwrap();
+    let conn = storage.open().unwrap();
+    assert_eq!(before, kept(&conn));
+    assert_eq!(3, before.2);
+    assert_eq!(
+      vec!["bread", "milk"],
+      searches::recent(searches::SEARCH, &conn).unwrap()
+    );
+    _ = fs::remove_file(path);
+  }
+
+  #[test]
+  fn open_reports_broken_files() {
+    let path = env::temp_dir().join(format!("todo-broken-{}.json", std::process::id()));
+    fs::write(&path, "{ not json").unwrap();
+    assert!(JsonFile::new(path.clone()).open().is_err());
+    _ = fs::remove_file(path);
+    // Files that can't be read aren't taken as empty either
+    assert!(JsonFile::new(env::temp_dir()).open().is_err());
+  }
+}
@@ -0,0 +1,488 @@
+use crate::config::{Bindings, Keymap};
+use console::style;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the fuzzy picker has a normal mode with `j`/`k`, set once from the config
+static PICKER_VIM: AtomicBool = AtomicBool::new(false);
+
+/// Something a key does in the terminal ui
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+  Up,
+  Down,
+  PageUp,
+  PageDown,
+  Top,
+  Bottom,
+  /// Complete or reopen the selected todo, or fold the selected group
+  Toggle,
+  Collapse,
+  Expand,
+  Filter,
+  Pane,
+  Group,
+  /// Let the terminal have the mouse back, so text can be selected and copied
+  Mouse,
+  Delete,
+  /// Swap the selected todo with the one above or below it, for good
+  MoveUp,
+  MoveDown,
+  /// Revert the last change, made here or by a command
+  Undo,
+  Quit,
+}
+
+impl Action {
+  const ALL: [Action; 18] = [
+    Action::Up,
+    Action::Down,
+    Action::PageUp,
+    Action::PageDown,
+    Action::Top,
+    Action::Bottom,
+    Action::Toggle,
+    Action::Collapse,
+    Action::Expand,
+    Action::Filter,
+    Action::Pane,
+    Action::Group,
+    Action::Mouse,
+    Action::Delete,
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::Undo,
+    Action::Quit,
+  ];
+
+  /// Name of the action in the `[keys]` table
+  fn as_str(&self) -> &'static str {
+    match self {
+      Action::Up => "up",
+      Action::Down => "down",
+      Action::PageUp => "page-up",
+      Action::PageDown => "page-down",
+      Action::Top => "top",
+      Action::Bottom => "bottom",
+      Action::Toggle => "toggle",
+      Action::Collapse => "collapse",
+      Action::Expand => "expand",
+      Action::Filter => "filter",
+      Action::Pane => "pane",
+      Action::Group => "group",
+      Action::Mouse => "mouse",
+      Action::Delete => "delete",
+      Action::MoveUp => "move-up",
+      Action::MoveDown => "move-down",
+      Action::Undo => "undo",
+      Action::Quit => "quit",
+    }
+  }
+}
+
+/// Keys every keymap has, in vim notation
+const COMMON: [(&str, Action); 16] = [
+  ("<up>", Action::Up),
+  ("<down>", Action::Down),
+  ("<pageup>", Action::PageUp),
+  ("<pagedown>", Action::PageDown),
+  ("<home>", Action::Top),
+  ("<end>", Action::Bottom),
+  ("<space>", Action::Toggle),
+  ("<enter>", Action::Toggle),
+  ("<left>", Action::Collapse),
+  ("<right>", Action::Expand),
+  ("/", Action::Filter),
+  ("<tab>", Action::Pane),
+  ("m", Action::Mouse),
+  ("K", Action::MoveUp),
+  ("J", Action::MoveDown),
+  ("<c-c>", Action::Quit),
+];
+
+const DEFAULT: [(&str, Action); 5] = [
+  ("g", Action::Group),
+  ("<del>", Action::Delete),
+  ("u", Action::Undo),
+  ("q", Action::Quit),
+  ("<esc>", Action::Quit),
+];
+
+const VIM: [(&str, Action); 13] = [
+  ("x", Action::Toggle),
+  ("j", Action::Down),
+  ("k", Action::Up),
+  ("gg", Action::Top),
+  ("G", Action::Bottom),
+  ("<c-d>", Action::PageDown),
+  ("<c-u>", Action::PageUp),
+  ("h", Action::Collapse),
+  ("l", Action::Expand),
+  ("gr", Action::Group),
+  ("dd", Action::Delete),
+  ("u", Action::Undo),
+  ("q", Action::Quit),
+];
+
+/// Keys written inside angle brackets, besides `<c-x>` for control
+const NAMED: [&str; 17] = [
+  "space", "lt", "up", "down", "left", "right", "pageup", "pagedown", "home", "end", "enter",
+  "tab", "esc", "bs", "del", "insert", "backtab",
+];
+
+/// The pickers of commands like `notes` and `done` are not configurable, binding these in
+/// `[keys]` is refused
+const PICKER: [(&str, &str); 5] = [
+  (
+    "move",
+    "<up> <down> <tab> <backtab>, j k outside the fuzzy search",
+  ),
+  ("select", "<enter>"),
+  ("mark", "<space>, a marks all"),
+  ("cancel", "<esc> q"),
+  (
+    "search",
+    "type to narrow the fuzzy search, <bs> <del> <left> <right> edit",
+  ),
+];
+
+/// A key press in vim notation, like `j`, `<c-d>` or `<space>`
+fn key_name(key: KeyEvent) -> Option<String> {
+  let name = match key.code {
+    KeyCode::Char(' ') => "space".to_string(),
+    KeyCode::Char('<') => "lt".to_string(),
+    KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => format!("c-{}", c),
+    KeyCode::Char(c) => return Some(c.to_string()),
+    KeyCode::Up => "up".to_string(),
+    KeyCode::Down => "down".to_string(),
+    KeyCode::Left => "left".to_string(),
+    KeyCode::Right => "right".to_string(),
+    KeyCode::PageUp => "pageup".to_string(),
+    KeyCode::PageDown => "pagedown".to_string(),
+    KeyCode::Home => "home".to_string(),
+    KeyCode::End => "end".to_string(),
+    KeyCode::Enter => "enter".to_string(),
+    KeyCode::Tab => "tab".to_string(),
+    KeyCode::BackTab => "backtab".to_string(),
+    KeyCode::Esc => "esc".to_string(),
+    KeyCode::Backspace => "bs".to_string(),
+    KeyCode::Delete => "del".to_string(),
+    KeyCode::Insert => "insert".to_string(),
+    _ => return None,
+  };
+  Some(format!("<{}>", name))
+}
+
+/// A sequence in vim notation written the way key presses are named, `<C-D>` becomes
+/// `<c-D>` and `<Space>` becomes `<space>`
+fn parse_sequence(text: &str) -> Result<String, String> {
+  let mut keys = String::new();
+  let mut rest = text;
+  while let Some(c) = rest.chars().next() {
+    if c == '<'
+      && let Some(end) = rest.find('>')
+      && end > 1
+    {
+      let name = &rest[1..end];
+      let lower = name.to_lowercase();
+      let name = if lower.starts_with("c-") && name.chars().count() == 3 {
+        format!("c-{}", &name[2..])
+      } else if NAMED.contains(&lower.as_str()) {
+        lower
+      } else {
+        return Err(format!("unknown key <{}>", name));
+      };
+      keys.push_str(&format!("<{}>", name));
+      rest = &rest[end + 1..];
+    } else if c == '<' {
+      return Err("write < as <lt>".to_string());
+    } else if c.is_whitespace() || c.is_control() {
+      return Err("write a space as <space>".to_string());
+    } else {
+      keys.push(c);
+      rest = &rest[c.len_utf8()..];
+    }
+  }
+  match keys.is_empty() {
+    true => Err("no keys given".to_string()),
+    false => Ok(keys),
+  }
+}
+
+/// Keys as shown, named keys without their brackets
+pub fn display(keys: &str) -> &str {
+  match keys.starts_with('<') && keys.ends_with('>') && keys.matches('<').count() == 1 {
+    true => &keys[1..keys.len() - 1],
+    false => keys,
+  }
+}
+
+/// Turns key presses into actions, the keys of a sequence like `gg` are collected until
+/// they can only mean one thing
+pub struct Keys {
+  keymap: Keymap,
+  /// Earlier bindings win when the same keys are bound twice
+  bindings: Vec<(String, Action)>,
+  pending: String,
+}
+
+impl Keys {
+  /// The bindings of `keymap`, an action in `overrides` keeps only the keys given there
+  pub fn new(
+    keymap: Keymap,
+    overrides: &BTreeMap<String, Bindings>,
+  ) -> Result<Keys, Box<dyn Error>> {
+    let mut bindings = vec![];
+    for (name, keys) in overrides {
+      if PICKER.iter().any(|(picker, _)| picker == name) || name.starts_with("picker") {
+        return Err(
+          format!(
+            "The keys of the pickers are built in, {} in [keys] can't change them. \
+             keymap = \"vim\" gives them j and k, todo keys lists them all",
+            name
+          )
+          .into(),
+        );
+      }
+      let Some(action) = Action::ALL
+        .into_iter()
+        .find(|action| action.as_str() == name)
+      else {
+        let names = Action::ALL.map(|action| action.as_str());
+        return Err(
+          format!(
+            "Unknown action {} in [keys], expected one of {}",
+            name,
+            names.join(", ")
+          )
+          .into(),
+        );
+      };
+      for keys in keys.keys() {
+        let keys =
+          parse_sequence(keys).map_err(|e| format!("Bad keys \"{}\" for {}: {}", keys, name, e))?;
+        bindings.push((keys, action));
+      }
+    }
+    let own = match keymap {
+      Keymap::Default => DEFAULT.as_slice(),
+      Keymap::Vim => VIM.as_slice(),
+    };
+    for (keys, action) in own.iter().chain(&COMMON) {
+      if !overrides.contains_key(action.as_str()) {
+        bindings.push((keys.to_string(), *action));
+      }
+    }
+    Ok(Keys {
+      keymap,
+      bindings,
+      pending: String::new(),
+    })
+  }
+
+  pub fn press(&mut self, key: KeyEvent) -> Option<Action> {
+    let name = key_name(key)?;
+    let sequence = format!("{}{}", self.pending, name);
+    self.pending.clear();
+    if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| *keys == sequence) {
+      return Some(*action);
+    }
+    if self
+      .bindings
+      .iter()
+      .any(|(keys, _)| keys.starts_with(&sequence))
+    {
+      self.pending = sequence;
+      return None;
+    }
+    // A sequence that went nowhere, the last key may start a new one
+    match sequence == name {
+      true => None,
+      false => self.press(key),
+    }
+  }
+
+  /// Keys bound to `action`, in the order they are looked up
+  pub fn bound(&self, action: Action) -> Vec<&str> {
+    self
+      .bindings
+      .iter()
+      .filter(|(_, own)| *own == action)
+      .map(|(keys, _)| keys.as_str())
+      .collect()
+  }
+
+  /// Bindings that can never be used, because the same keys or the start of them do
+  /// something else first
+  fn conflicts(&self) -> Vec<String> {
+    let mut conflicts = vec![];
+    for (index, (keys, action)) in self.bindings.iter().enumerate() {
+      let earlier = self.bindings[..index]
+        .iter()
+        .find(|(other, own)| other == keys && own != action);
+      let shorter = self
+        .bindings
+        .iter()
+        .find(|(other, _)| other != keys && keys.starts_with(other.as_str()));
+      match (earlier, shorter) {
+        (Some((_, own)), _) => conflicts.push(format!(
+          "{} is bound to {} and {}, {} wins",
+          keys,
+          own.as_str(),
+          action.as_str(),
+          own.as_str()
+        )),
+        (None, Some((other, own))) => conflicts.push(format!(
+          "{} ({}) can't be typed, {} ({}) runs first",
+          keys,
+          action.as_str(),
+          other,
+          own.as_str()
+        )),
+        (None, None) => {}
+      }
+    }
+    conflicts
+  }
+
+  /// Tell the pickers about the keymap, they only have a vim mode to switch on
+  pub fn init(&self) {
+    PICKER_VIM.store(self.keymap == Keymap::Vim, Ordering::Relaxed);
+  }
+}
+
+/// Whether the fuzzy picker starts its normal mode on escape
+pub fn picker_vim_mode() -> bool {
+  PICKER_VIM.load(Ordering::Relaxed)
+}
+
+/// Lines of `todo keys`, every action with its keys and what gets in the way
+fn lines(keys: &Keys) -> Vec<String> {
+  let keymap = match keys.keymap {
+    Keymap::Default => "default",
+    Keymap::Vim => "vim",
+  };
+  let mut lines = vec![
+    style(format!("Terminal ui, {} keymap:", keymap))
+      .bold()
+      .to_string(),
+  ];
+  for action in Action::ALL {
+    let bound = keys.bound(action);
+    let bound = match bound.is_empty() {
+      true => style("unbound".to_string()).dim().to_string(),
+      false => bound.join(" "),
+    };
+    lines.push(format!("  {:<10} {}", action.as_str(), bound));
+  }
+  let conflicts = keys.conflicts();
+  if !conflicts.is_empty() {
+    lines.push(String::new());
+    lines.push(style("Conflicts:").bold().to_string());
+    lines.extend(
+      conflicts
+        .into_iter()
+        .map(|conflict| format!("  {}", conflict)),
+    );
+  }
+  lines.push(String::new());
+  lines.push(style("Pickers, built in:").bold().to_string());
+  for (name, keys) in PICKER {
+    lines.push(format!("  {:<10} {}", name, keys));
+  }
+  if keys.keymap == Keymap::Vim {
+    lines.push(format!(
+      "  {:<10} <esc> leaves the fuzzy search for j k h l, i or a goes back",
+      "vim"
+    ));
+  }
+  lines
+}
+
+/// Show the current bindings
+pub fn keys(keys: &Keys) -> Result<(), Box<dyn Error>> {
+  for line in lines(keys) {
+    println!("{}", line);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn overrides(bindings: &[(&str, &[&str])]) -> BTreeMap<String, Bindings> {
+    bindings
+      .iter()
+      .map(|(name, keys)| {
+        (
+          name.to_string(),
+          Bindings::Many(keys.iter().map(|keys| keys.to_string()).collect()),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn sequences_are_written_like_key_presses() {
+    assert_eq!(Ok("gg".to_string()), parse_sequence("gg"));
+    assert_eq!(Ok("<c-d>".to_string()), parse_sequence("<C-d>"));
+    assert_eq!(Ok("<space>x".to_string()), parse_sequence("<Space>x"));
+    assert!(parse_sequence("<nope>").is_err());
+    assert!(parse_sequence("a b").is_err());
+    assert!(parse_sequence("<").is_err());
+    assert!(parse_sequence("").is_err());
+
+    let mut keys = Keys::new(Keymap::Default, &overrides(&[("top", &["<C-a>"])])).unwrap();
+    let ctrl_a = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+    assert_eq!(Some(Action::Top), keys.press(ctrl_a));
+    assert_eq!(vec!["<c-a>"], keys.bound(Action::Top));
+  }
+
+  #[test]
+  fn overrides_replace_an_action_and_are_checked() {
+    let keys = Keys::new(
+      Keymap::Vim,
+      &overrides(&[("delete", &["x"]), ("undo", &[]), ("pane", &["g"])]),
+    )
+    .unwrap();
+    assert_eq!(vec!["x"], keys.bound(Action::Delete));
+    assert!(keys.bound(Action::Undo).is_empty());
+    assert_eq!(
+      vec![
+        "x is bound to delete and toggle, delete wins",
+        "gg (top) can't be typed, g (pane) runs first",
+        "gr (group) can't be typed, g (pane) runs first",
+      ],
+      keys.conflicts()
+    );
+    assert!(
+      Keys::new(Keymap::Default, &BTreeMap::new())
+        .unwrap()
+        .conflicts()
+        .is_empty()
+    );
+    assert!(
+      Keys::new(Keymap::Vim, &BTreeMap::new())
+        .unwrap()
+        .conflicts()
+        .is_empty()
+    );
+
+    assert!(Keys::new(Keymap::Vim, &overrides(&[("fly", &["f"])])).is_err());
+    let picker = Keys::new(Keymap::Vim, &overrides(&[("select", &["<tab>"])]));
+    assert!(picker.is_err_and(|e| e.to_string().contains("pickers are built in")));
+    assert!(Keys::new(Keymap::Vim, &overrides(&[("quit", &["<hyper-q>"])])).is_err());
+  }
+
+  #[test]
+  fn keys_lists_pickers_too() {
+    let keys = Keys::new(Keymap::Default, &overrides(&[("mouse", &[])])).unwrap();
+    let lines = lines(&keys);
+    assert_eq!("  up         <up>", lines[1]);
+    assert_eq!("  mouse      unbound", lines[13]);
+    assert!(lines.contains(&"  select     <enter>".to_string()));
+  }
+}
@@ -0,0 +1,461 @@
+use crate::duration::format_duration;
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::{Todo, ascii, date, emoji, ids};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use console::{
+  Alignment, Term, measure_text_width, pad_str, strip_ansi_codes, style, truncate_str,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Room the pickers take for their cursor and checkbox in front of each item
+const PICKER_MARGIN: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+
+/// The body column is never made narrower than this to fit other columns in
+const MIN_BODY_WIDTH: usize = 20;
+
+/// Space between table columns
+const GAP: &str = "  ";
+
+/// Whether what colors and strikethrough show is written out too, screen readers skip styles
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+pub fn init(accessible: bool) {
+  ACCESSIBLE.store(accessible, Ordering::Relaxed);
+}
+
+pub fn accessible() -> bool {
+  ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// What a column of the list table shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+  Id,
+  Status,
+  #[value(name = "pri", alias = "priority")]
+  Priority,
+  Due,
+  Tags,
+  Project,
+  Estimate,
+  Body,
+}
+
+impl Column {
+  /// Columns shown when none are asked for, in this order
+  const AUTO: [Column; 8] = [
+    Column::Id,
+    Column::Status,
+    Column::Priority,
+    Column::Due,
+    Column::Tags,
+    Column::Project,
+    Column::Estimate,
+    Column::Body,
+  ];
+
+  /// Which columns go first when the terminal is too narrow for all of them
+  const DROPPED: [Column; 6] = [
+    Column::Project,
+    Column::Estimate,
+    Column::Tags,
+    Column::Due,
+    Column::Priority,
+    Column::Status,
+  ];
+
+  fn title(&self) -> &'static str {
+    match self {
+      Column::Id => "Id",
+      Column::Status => "Status",
+      Column::Priority => "Pri",
+      Column::Due => "Due",
+      Column::Tags => "Tags",
+      Column::Project => "Project",
+      Column::Estimate => "Est",
+      Column::Body => "Body",
+    }
+  }
+
+  /// The title written out, for labelled lines
+  fn label(&self) -> &'static str {
+    match self {
+      Column::Priority => "Priority",
+      Column::Estimate => "Estimate",
+      column => column.title(),
+    }
+  }
+
+  fn cell(&self, todo: &Todo, today: NaiveDate) -> String {
+    match self {
+      Column::Id => ids::short(todo.id),
+      Column::Status => todo.status.to_string(),
+      Column::Priority => match todo.priority {
+        Some(priority @ Priority::High) => style(priority.marker()).red().bold().to_string(),
+        Some(priority @ Priority::Medium) => style(priority.marker()).yellow().to_string(),
+        Some(priority @ Priority::Low) => style(priority.marker()).dim().to_string(),
+        None => String::new(),
+      },
+      Column::Due => match todo.due {
+        Some(due) if due < today && todo.status.is_open() => {
+          style(date::display(due, today)).red().to_string()
+        }
+        Some(due) => date::display(due, today),
+        None => String::new(),
+      },
+      Column::Tags => todo.tags.badges(),
+      Column::Project => todo.project.clone().unwrap_or_default(),
+      Column::Estimate => todo.estimate.map(format_duration).unwrap_or_default(),
+      Column::Body => {
+        let body = emoji::display(&todo.body);
+        match todo.status {
+          Status::InProgress => style(body).yellow().bold().to_string(),
+          Status::Done => style(body).strikethrough().to_string(),
+          Status::Cancelled => style(body).strikethrough().dim().to_string(),
+          Status::Todo | Status::Waiting => body,
+        }
+      }
+    }
+  }
+}
+
+/// `[done] ` and `[overdue] ` in front of todos whose status or due date is only styled
+fn signal_words(todo: &Todo, today: NaiveDate) -> String {
+  let mut words = String::new();
+  if todo.status != Status::Todo {
+    words.push_str(&format!("[{}] ", todo.status));
+  }
+  if todo.status.is_open() && todo.due.is_some_and(|due| due < today) {
+    words.push_str("[overdue] ");
+  }
+  words
+}
+
+/// The signal words with `--accessible`, nothing otherwise
+pub fn signals(todo: &Todo, today: NaiveDate) -> String {
+  match accessible() {
+    true => signal_words(todo, today),
+    false => String::new(),
+  }
+}
+
+/// What `table` shows as one line per todo with a label before each cell and no styles,
+/// screen readers lose track of aligned columns. The status is in the signal words.
+pub fn labelled(todos: &[Todo], columns: &[Column], today: NaiveDate) -> Vec<String> {
+  let columns = match columns.is_empty() {
+    true => auto_columns(todos, None, today),
+    false => columns.to_vec(),
+  };
+  todos
+    .iter()
+    .map(|todo| {
+      let cells = columns
+        .iter()
+        .filter(|column| **column != Column::Status)
+        .map(|column| {
+          let cell = match (column, todo.priority) {
+            (Column::Priority, Some(priority)) => priority.as_str().to_string(),
+            _ => strip_ansi_codes(&column.cell(todo, today)).to_string(),
+          };
+          (column, cell)
+        })
+        .filter(|(_, cell)| !cell.is_empty())
+        .map(|(column, cell)| format!("{}: {}", column.label(), cell))
+        .collect::<Vec<String>>();
+      format!("{}{}", signal_words(todo, today), cells.join(", "))
+    })
+    .collect()
+}
+
+/// The automatic columns that have something to show, starting with the least needed ones
+/// gone until they fit `width`
+fn auto_columns(todos: &[Todo], width: Option<usize>, today: NaiveDate) -> Vec<Column> {
+  let mut columns = Column::AUTO
+    .into_iter()
+    .filter(|column| {
+      matches!(column, Column::Id | Column::Status | Column::Body)
+        || todos
+          .iter()
+          .any(|todo| !column.cell(todo, today).is_empty())
+    })
+    .collect::<Vec<Column>>();
+  let Some(width) = width else {
+    return columns;
+  };
+  for dropped in Column::DROPPED {
+    let used = columns
+      .iter()
+      .filter(|column| **column != Column::Body)
+      .map(|column| column_width(*column, todos, today) + GAP.len())
+      .sum::<usize>();
+    if used + MIN_BODY_WIDTH <= width {
+      break;
+    }
+    columns.retain(|column| *column != dropped);
+  }
+  columns
+}
+
+fn column_width(column: Column, todos: &[Todo], today: NaiveDate) -> usize {
+  todos
+    .iter()
+    .map(|todo| measure_text_width(&column.cell(todo, today)))
+    .chain([column.title().len()])
+    .max()
+    .unwrap_or(0)
+}
+
+/// The todos as aligned rows under a header, the body is cut off so rows fit `width`.
+/// Without `columns` the ones that fit are picked.
+pub fn table(
+  todos: &[Todo],
+  columns: &[Column],
+  width: Option<usize>,
+  today: NaiveDate,
+) -> Vec<String> {
+  let columns = match columns.is_empty() {
+    true => auto_columns(todos, width, today),
+    false => columns.to_vec(),
+  };
+  let mut widths = columns
+    .iter()
+    .map(|column| column_width(*column, todos, today))
+    .collect::<Vec<usize>>();
+  if let (Some(width), Some(body)) = (
+    width,
+    columns.iter().position(|column| *column == Column::Body),
+  ) {
+    let others = widths.iter().sum::<usize>() - widths[body] + GAP.len() * (widths.len() - 1);
+    widths[body] = widths[body].min(width.saturating_sub(others).max(MIN_BODY_WIDTH));
+  }
+
+  let row = |cells: Vec<String>| {
+    cells
+      .iter()
+      .zip(&columns)
+      .zip(&widths)
+      .map(|((cell, column), width)| {
+        let alignment = match column {
+          Column::Id => Alignment::Right,
+          _ => Alignment::Left,
+        };
+        pad_str(cell, *width, alignment, Some(ascii::ellipsis())).to_string()
+      })
+      .collect::<Vec<String>>()
+      .join(GAP)
+      .trim_end()
+      .to_string()
+  };
+  let header = columns
+    .iter()
+    .map(|column| style(column.title()).bold().underlined().to_string())
+    .collect();
+  let mut lines = vec![row(header)];
+  for todo in todos {
+    lines.push(row(
+      columns
+        .iter()
+        .map(|column| column.cell(todo, today))
+        .collect(),
+    ));
+  }
+  lines
+}
+
+/// Columns of the terminal list output goes to, `None` when it goes elsewhere
+pub fn stdout_width() -> Option<usize> {
+  Term::stdout()
+    .size_checked()
+    .map(|(_, width)| width as usize)
+}
+
+/// Columns left for an item of a picker, pickers draw on stderr
+pub fn picker_width() -> Option<usize> {
+  Term::stderr()
+    .size_checked()
+    .map(|(_, width)| (width as usize).saturating_sub(PICKER_MARGIN))
+}
+
+/// Cut `line` off at `width` columns with an ellipsis, styles are kept
+pub fn truncate(line: &str, width: usize) -> String {
+  truncate_str(line, width, ascii::ellipsis()).to_string()
+}
+
+/// Styles a word turns on, a reset turns all of them off again
+fn track_styles(word: &str, active: &mut Vec<String>) {
+  let mut rest = word;
+  while let Some(start) = rest.find("\x1b[") {
+    let Some(end) = rest[start..].find('m') else {
+      break;
+    };
+    let code = &rest[start..start + end + 1];
+    match code {
+      RESET => active.clear(),
+      code => active.push(code.to_string()),
+    }
+    rest = &rest[start + end + 1..];
+  }
+}
+
+/// Break `line` between words so no line is wider than `width`, continuation lines are
+/// indented by `indent` columns. Styles are ended before each break and picked up again
+/// after the indent, so it stays unstyled. Words wider than a line are left whole.
+pub fn wrap(line: &str, width: usize, indent: usize) -> Vec<String> {
+  if measure_text_width(line) <= width {
+    return vec![line.to_string()];
+  }
+  // Narrow terminals need the room more than the indent
+  let indent = match indent * 2 > width {
+    true => 0,
+    false => indent,
+  };
+  let mut lines = vec![];
+  let mut current = String::new();
+  let mut current_width = 0;
+  let mut empty = true;
+  let mut active = vec![];
+  for word in line.split(' ') {
+    let word_width = measure_text_width(word);
+    if !empty && current_width + 1 + word_width > width {
+      if !active.is_empty() {
+        current.push_str(RESET);
+      }
+      lines.push(current);
+      current = format!("{}{}", " ".repeat(indent), active.concat());
+      current_width = indent;
+      empty = true;
+    }
+    if !empty {
+      current.push(' ');
+      current_width += 1;
+    }
+    current.push_str(word);
+    current_width += word_width;
+    empty = empty && word_width == 0;
+    track_styles(word, &mut active);
+  }
+  lines.push(current);
+  lines
+}
+
+/// `line` made to fit `width`, when there is one, by cutting it off or wrapping it
+pub fn fit(line: &str, width: Option<usize>, indent: usize, cut: bool) -> Vec<String> {
+  match width {
+    Some(width) if cut => vec![truncate(line, width)],
+    Some(width) => wrap(line, width, indent),
+    None => vec![line.to_string()],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn long_lines_wrap_with_a_hanging_indent() {
+    let line = "12. Write the quarterly report for the team and send it";
+    assert_eq!(
+      vec![
+        "12. Write the quarterly report",
+        "    for the team and send it",
+      ],
+      wrap(line, 30, 4)
+    );
+    assert_eq!(vec!["1. Milk"], wrap("1. Milk", 30, 3));
+    // Wide characters take two columns
+    assert_eq!(
+      vec!["1. 日本語", "   日本語"],
+      wrap("1. 日本語 日本語", 10, 3)
+    );
+  }
+
+  #[test]
+  fn styles_do_not_spill_into_the_indent() {
+    let line = "\x1b[9m1. Pay all of the bills\x1b[0m";
+    assert_eq!(
+      vec!["\x1b[9m1. Pay all of\x1b[0m", "   \x1b[9mthe bills\x1b[0m",],
+      wrap(line, 13, 3)
+    );
+  }
+
+  fn todos() -> Vec<Todo> {
+    vec![
+      Todo {
+        id: 3,
+        body: "Pay the bills before the end of the month".to_string(),
+        priority: Some(Priority::High),
+        due: NaiveDate::from_ymd_opt(2024, 3, 5),
+        project: Some("House".to_string()),
+        ..Default::default()
+      },
+      Todo {
+        id: 34,
+        body: "Milk".to_string(),
+        status: Status::InProgress,
+        tags: crate::tags::Tags(vec!["shop".to_string()]),
+        ..Default::default()
+      },
+    ]
+  }
+
+  #[test]
+  fn table_drops_columns_that_do_not_fit() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+    assert_eq!(
+      vec![
+        "Id  Status       Pri  Due         Tags   Project  Body",
+        " c  todo         !!!  2024-03-05         House    Pay the bills before the end of the month",
+        "a7  in-progress                   #shop           Milk",
+      ],
+      table(&todos(), &[], None, today)
+    );
+    assert_eq!(
+      vec![
+        "Id  Status       Pri  Due         Body",
+        " c  todo         !!!  2024-03-05  Pay the bills before…",
+        "a7  in-progress                   Milk",
+      ],
+      table(&todos(), &[], Some(55), today)
+    );
+  }
+
+  #[test]
+  fn table_shows_the_columns_asked_for() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+    assert_eq!(
+      vec![
+        "Body                                       Id",
+        "Pay the bills before the end of the month   c",
+        "Milk                                       a7"
+      ],
+      table(&todos(), &[Column::Body, Column::Id], None, today)
+    );
+  }
+
+  #[test]
+  fn accessible_lines_say_what_styles_show() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+    assert_eq!(
+      vec![
+        "[overdue] Id: c, Priority: high, Due: 2024-03-05, Project: House, Body: Pay the bills before the end of the month",
+        "[in-progress] Id: a7, Tags: #shop, Body: Milk",
+      ],
+      labelled(&todos(), &[], today)
+    );
+    let done = Todo {
+      status: Status::Done,
+      ..todos().remove(0)
+    };
+    assert_eq!("[done] ", signal_words(&done, today));
+  }
+
+  #[test]
+  fn truncate_adds_an_ellipsis() {
+    assert_eq!("1. Pay the…", truncate("1. Pay the bills", 11));
+    assert_eq!(vec!["1. Milk"], fit("1. Milk", None, 3, true));
+  }
+}
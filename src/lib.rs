@@ -1,167 +1,2003 @@
+#[cfg(feature = "cli")]
+use chrono::Local;
+use chrono::{DateTime, NaiveDate, Utc};
+#[cfg(feature = "cli")]
 use clap::Parser;
+#[cfg(feature = "cli")]
 use clap::Subcommand;
-use console::style;
+#[cfg(feature = "cli")]
+use config::Config;
+#[cfg(feature = "cli")]
+use console::{measure_text_width, style};
+#[cfg(feature = "cli")]
+use date::parse_date;
+#[cfg(feature = "cli")]
+use db::create_db;
+#[cfg(feature = "cli")]
 use dialoguer::Editor;
+#[cfg(feature = "cli")]
 use dialoguer::MultiSelect;
-use dialoguer::{theme::ColorfulTheme, FuzzySelect};
-use rusqlite::{Connection, Result};
+#[cfg(feature = "cli")]
+use dialoguer::theme::ColorfulTheme;
+#[cfg(feature = "cli")]
+use duration::{parse_days, parse_duration};
+#[cfg(feature = "cli")]
+use picker::PickerFilter;
+#[cfg(feature = "cli")]
+use rusqlite::{Connection, Result, Row};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cli")]
+use std::env;
 use std::error::Error;
+#[cfg(feature = "cli")]
+use std::io::{IsTerminal, Read};
+#[cfg(feature = "cli")]
+use std::path::PathBuf;
+#[cfg(feature = "cli")]
+use tags::parse_tag;
 
-#[derive(Clone, Debug)]
-struct Todo {
-  body: String,
-  id: usize,
-  incomplete: bool,
+#[cfg(feature = "cli")]
+mod apply;
+#[cfg(feature = "cli")]
+mod ascii;
+#[cfg(feature = "cli")]
+mod batch;
+#[cfg(feature = "cli")]
+mod bot;
+mod bucket;
+#[cfg(feature = "cli")]
+mod checklist;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod cron;
+#[cfg(feature = "cli")]
+mod cycle;
+mod date;
+#[cfg(feature = "cli")]
+mod db;
+#[cfg(feature = "cli")]
+mod dedupe;
+#[cfg(feature = "cli")]
+mod deps;
+mod duration;
+#[cfg(feature = "cli")]
+mod emoji;
+#[cfg(feature = "cli")]
+mod expiry;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod filter;
+#[cfg(feature = "cli")]
+mod focus;
+#[cfg(feature = "cli")]
+mod formats;
+mod frequency;
+#[cfg(feature = "cli")]
+mod gate;
+#[cfg(feature = "cli")]
+mod githook;
+#[cfg(feature = "cli")]
+mod gitlab;
+#[cfg(feature = "cli")]
+mod gtd;
+#[cfg(feature = "cli")]
+mod habits;
+#[cfg(feature = "cli")]
+mod heatmap;
+#[cfg(feature = "cli")]
+mod history;
+#[cfg(feature = "cli")]
+mod hyperlink;
+#[cfg(feature = "cli")]
+mod ids;
+#[cfg(feature = "cli")]
+mod jira;
+mod json;
+#[cfg(feature = "cli")]
+mod keys;
+#[cfg(feature = "cli")]
+mod layout;
+#[cfg(feature = "cli")]
+mod links;
+#[cfg(feature = "cli")]
+mod lock;
+#[cfg(feature = "cli")]
+mod logging;
+#[cfg(feature = "cli")]
+mod maintain;
+#[cfg(feature = "cli")]
+mod markdown;
+#[cfg(feature = "cli")]
+mod matrix;
+#[cfg(feature = "cli")]
+mod modify;
+#[cfg(feature = "cli")]
+mod notify;
+#[cfg(feature = "cli")]
+mod order;
+mod org;
+#[cfg(feature = "cli")]
+mod pager;
+#[cfg(feature = "cli")]
+mod passphrase;
+#[cfg(feature = "postgres")]
+mod pg;
+#[cfg(feature = "cli")]
+mod picker;
+#[cfg(feature = "cli")]
+mod plan;
+mod priority;
+#[cfg(feature = "cli")]
+mod project;
+#[cfg(feature = "cli")]
+mod prompt;
+#[cfg(feature = "python")]
+mod python;
+mod query;
+#[cfg(feature = "cli")]
+mod redact;
+#[cfg(feature = "cli")]
+mod removed;
+#[cfg(feature = "cli")]
+mod report;
+#[cfg(feature = "cli")]
+mod review;
+#[cfg(feature = "cli")]
+mod scan;
+#[cfg(feature = "cli")]
+mod script;
+#[cfg(feature = "cli")]
+mod search;
+#[cfg(feature = "cli")]
+mod searches;
+#[cfg(feature = "cli")]
+mod service;
+#[cfg(feature = "cli")]
+mod slack;
+#[cfg(feature = "cli")]
+mod snapshot;
+#[cfg(feature = "cli")]
+mod sort;
+#[cfg(feature = "cli")]
+mod stats;
+mod status;
+#[cfg(feature = "cli")]
+mod storage;
+#[cfg(feature = "cli")]
+mod store;
+#[cfg(feature = "cli")]
+mod strategy;
+#[cfg(feature = "cli")]
+mod subtasks;
+#[cfg(feature = "cli")]
+mod sync;
+#[cfg(feature = "cli")]
+mod table;
+mod tags;
+#[cfg(feature = "cli")]
+mod todotxt;
+#[cfg(feature = "cli")]
+mod triage;
+#[cfg(feature = "cli")]
+mod tui;
+#[cfg(feature = "cli")]
+mod undo;
+#[cfg(feature = "cli")]
+mod velocity;
+#[cfg(feature = "cli")]
+mod wizard;
+
+pub use bucket::Bucket;
+pub use duration::format_duration;
+pub use filter::Filter;
+pub use frequency::{Frequency, Period};
+pub use priority::Priority;
+pub use query::{Query, Term, parse_query};
+pub use status::Status;
+#[cfg(feature = "cli")]
+pub use store::TodoStore;
+pub use tags::Tags;
+
+/// A todo as it is stored, the json export holds these
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Todo {
+  pub body: String,
+  pub id: usize,
+  pub status: Status,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub waiting_on: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub follow_up: Option<NaiveDate>,
+  /// Estimated effort in minutes
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub estimate: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub priority: Option<Priority>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub due: Option<NaiveDate>,
+  pub bucket: Bucket,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub project: Option<String>,
+  /// Left out of reviews until this date
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub snoozed_until: Option<NaiveDate>,
+  /// Part of the current focus set
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub focused: bool,
+  /// Free form markdown, `- [ ]` lines form a checklist
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub notes: Option<String>,
+  /// The todo this is a subtask of
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub parent_id: Option<usize>,
+  #[serde(skip_serializing_if = "Tags::is_empty")]
+  pub tags: Tags,
+  /// When work on the todo is planned to start
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub scheduled: Option<NaiveDate>,
+  /// Listed before everything else
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub pinned: bool,
+  /// Pointless after this date, the todo expires if it is still open by then
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub expires: Option<NaiveDate>,
+  /// Makes the todo a habit, completing it checks in for the day instead
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub habit: Option<Frequency>,
+  /// Where the todo sits in the manual order, lower comes first
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub position: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub created_at: Option<DateTime<Utc>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub updated_at: Option<DateTime<Utc>>,
+  /// Stays the same across exports, imports and devices, unlike the id
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub uuid: Option<String>,
+  /// What the todo is about elsewhere, like an issue url, a ticket key or an email's
+  /// message-id
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reference: Option<String>,
 }
 
 impl PartialEq for Todo {
   fn eq(&self, other: &Self) -> bool {
-    (self.id == other.id) && (self.body == other.body) && (self.incomplete == other.incomplete)
+    // Timestamps, the position and the uuid are bookkeeping, todos are the same when their content is
+    let Todo {
+      body,
+      id,
+      status,
+      waiting_on,
+      follow_up,
+      estimate,
+      priority,
+      due,
+      bucket,
+      project,
+      snoozed_until,
+      focused,
+      notes,
+      parent_id,
+      tags,
+      scheduled,
+      pinned,
+      expires,
+      habit,
+      position: _,
+      created_at: _,
+      updated_at: _,
+      uuid: _,
+      reference,
+    } = self;
+    (*body == other.body)
+      && (*id == other.id)
+      && (*status == other.status)
+      && (*waiting_on == other.waiting_on)
+      && (*follow_up == other.follow_up)
+      && (*estimate == other.estimate)
+      && (*priority == other.priority)
+      && (*due == other.due)
+      && (*bucket == other.bucket)
+      && (*project == other.project)
+      && (*snoozed_until == other.snoozed_until)
+      && (*focused == other.focused)
+      && (*notes == other.notes)
+      && (*parent_id == other.parent_id)
+      && (*tags == other.tags)
+      && (*scheduled == other.scheduled)
+      && (*pinned == other.pinned)
+      && (*expires == other.expires)
+      && (*habit == other.habit)
+      && (*reference == other.reference)
+  }
+}
+
+/// Query that every `Todo` is built from, columns are read by name
+#[cfg(feature = "cli")]
+const TODO_SELECT: &str = "SELECT * FROM todos";
+
+/// Pinned todos, then the manual order that `add --top` and `--after` place todos in
+#[cfg(feature = "cli")]
+const MANUAL_ORDER: &str = "ORDER BY pinned DESC, position, id";
+
+/// Marks pinned todos in lists and pickers
+pub const PIN: char = '★';
+
+/// The todos of a document in the json storage format, like `export --json` writes
+pub fn from_json(text: &str) -> Result<Vec<Todo>, Box<dyn Error>> {
+  json::parse(serde_json::from_str(text)?)
+}
+
+/// Todos as a document in the json storage format
+pub fn to_json(todos: &[Todo]) -> Result<String, Box<dyn Error>> {
+  json::render(todos)
+}
+
+/// The todos of org-mode headlines, like `export --org` writes
+pub fn from_org(text: &str) -> Vec<Todo> {
+  org::parse(text)
+}
+
+/// Todos as org-mode headlines
+pub fn to_org(todos: &[Todo]) -> String {
+  org::render(todos)
+}
+
+#[cfg(feature = "cli")]
+impl Todo {
+  fn from_row(row: &Row) -> Result<Todo> {
+    Ok(Todo {
+      id: row.get("id")?,
+      body: row.get("body")?,
+      status: row.get("status")?,
+      waiting_on: row.get("waiting_on")?,
+      follow_up: row.get("follow_up")?,
+      estimate: row.get("estimate")?,
+      priority: row.get("priority")?,
+      due: row.get("due")?,
+      bucket: row.get("bucket")?,
+      project: row.get("project")?,
+      snoozed_until: row.get("snoozed_until")?,
+      focused: row.get("focused")?,
+      notes: row.get("notes")?,
+      parent_id: row.get("parent_id")?,
+      tags: row.get("tags")?,
+      scheduled: row.get("scheduled")?,
+      pinned: row.get("pinned")?,
+      expires: row.get("expires")?,
+      habit: row.get("habit")?,
+      position: row.get("position")?,
+      created_at: row.get("created_at")?,
+      updated_at: row.get("updated_at")?,
+      uuid: row.get("uuid")?,
+      reference: row.get("reference")?,
+    })
   }
 }
 
+/// Attributes given to newly added todos
+#[cfg(feature = "cli")]
+#[derive(clap::Args, Clone, Debug, Default)]
+struct AddOptions {
+  /// Estimated effort, e.g. 90m, 2h or 2h30m
+  #[arg(short, long, value_parser = parse_duration)]
+  estimate: Option<u32>,
+
+  /// How important the todo is
+  #[arg(short, long)]
+  priority: Option<Priority>,
+
+  /// When the todo is due (YYYY-MM-DD, today, tomorrow, 3d, 2w)
+  #[arg(short, long, value_parser = parse_date)]
+  due: Option<NaiveDate>,
+
+  /// Project the todo belongs to, files it as a next action
+  #[arg(long)]
+  project: Option<String>,
+
+  /// Notes for the todo, `- [ ] item` lines become a checklist
+  #[arg(short, long, allow_hyphen_values = true)]
+  notes: Option<String>,
+
+  /// Id of the todo to add these as subtasks of
+  #[arg(long, value_parser = ids::parse_id)]
+  parent: Option<usize>,
+
+  /// Tag the todo, can be given more than once
+  #[arg(short, long = "tag", value_parser = parse_tag)]
+  tags: Vec<String>,
+
+  /// Hide the todo until this date (YYYY-MM-DD, today, tomorrow, 3d, 2w)
+  #[arg(long, alias = "wait-until", value_parser = parse_date)]
+  scheduled: Option<NaiveDate>,
+
+  /// Expire the todo if it is still open after this date (YYYY-MM-DD, today, tomorrow, 3d, 2w)
+  #[arg(long, value_parser = parse_date)]
+  until: Option<NaiveDate>,
+
+  /// Make the todo a habit done this often, e.g. 3/week or daily
+  #[arg(long, value_parser = frequency::parse_frequency)]
+  habit: Option<Frequency>,
+
+  /// What the todo is about elsewhere, like an issue url, a ticket key or a message-id
+  #[arg(long = "ref")]
+  reference: Option<String>,
+
+  /// Put the todo first instead of last
+  #[arg(long, conflicts_with = "after")]
+  top: bool,
+
+  /// Put the todo right after the todo with this id
+  #[arg(long, value_parser = ids::parse_id)]
+  after: Option<usize>,
+}
+
+/// Filters for the list command
+#[cfg(feature = "cli")]
+#[derive(clap::Args, Clone, Debug, Default)]
+struct ListOptions {
+  /// Show only incomplete items
+  #[arg(short, long, conflicts_with = "status")]
+  incomplete: bool,
+
+  /// Show only items with this status
+  #[arg(short, long)]
+  status: Option<Status>,
+
+  /// Show everything, even while focusing on a few todos, working in a project or scheduled
+  /// for later
+  #[arg(short, long)]
+  all: bool,
+
+  /// Show only todos waiting for another open todo
+  #[arg(long, conflicts_with = "unblocked")]
+  blocked: bool,
+
+  /// Show only todos that can be worked on right now
+  #[arg(long)]
+  unblocked: bool,
+
+  /// Render markdown in the bodies, like **bold** and `code`
+  #[arg(short, long)]
+  markdown: bool,
+
+  /// Cut long todos off at the terminal width instead of wrapping them
+  #[arg(long)]
+  truncate: bool,
+
+  /// Show subtasks under their parents
+  #[arg(long, conflicts_with = "columns")]
+  tree: bool,
+
+  /// With --tree, fold done todos whose subtasks are all done too
+  #[arg(long, requires = "tree")]
+  collapse: bool,
+
+  /// Show a table with these columns, e.g. `--columns id,due,body`. Without any the columns
+  /// that fit the terminal are picked.
+  #[arg(long, value_delimiter = ',', num_args = 0..)]
+  columns: Option<Vec<layout::Column>>,
+
+  /// Show only the first this many todos
+  #[arg(short = 'n', long)]
+  limit: Option<usize>,
+
+  /// Write dates like 2024-03-05, in 3 days or both, instead of what the config says
+  #[arg(long)]
+  dates: Option<date::Dates>,
+
+  /// Sort by keys like `priority:desc,due:asc,created:desc` instead of the manual order
+  /// or the `sort` of the config, `manual` keeps to the order of `todo reorder`
+  #[arg(long, value_parser = sort::parse)]
+  sort: Option<sort::Sort>,
+
+  /// List removed todos too, below the others, archived ones were done or cancelled and
+  /// those in the trash were still open
+  #[arg(long, value_delimiter = ',')]
+  include: Vec<removed::Scope>,
+
+  /// Show only the todos of this project, sorted the way it was set to
+  #[arg(long)]
+  project: Option<String>,
+}
+
 /// Simple todo app
+#[cfg(feature = "cli")]
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
-#[command(arg_required_else_help(true))] // TODO: Remove if tui is added
+#[command(
+  after_help = "Anything that isn't a command is added as a todo, a due date and priority can \
+                end it: todo buy milk tomorrow !!"
+)]
 pub struct Args {
   #[command(subcommand)]
   command: Option<Commands>,
+
+  /// Where todos are kept, a `.txt` file is used as todo.txt, `.json` as json and
+  /// `postgres://` urls connect to a shared server
+  #[arg(long, global = true)]
+  db: Option<String>,
+
+  /// Log the SQL that runs, twice to also log every changed row
+  #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+  verbose: u8,
+
+  /// Only read the todos, commands that would change them fail. Files without write
+  /// permission are always opened this way.
+  #[arg(long, global = true)]
+  read_only: bool,
+
+  /// Never prompt, commands without enough arguments fail instead of picking, opening
+  /// the editor or asking to confirm
+  #[arg(long, global = true)]
+  no_input: bool,
+
+  /// Write out what colors and strikethrough show, like `[done]` and `[overdue]`, and list
+  /// columns as labelled lines, for screen readers
+  #[arg(long, global = true)]
+  accessible: bool,
+
+  /// Write glyphs, box drawing and emoji in plain ASCII, for legacy terminals and log files
+  #[arg(long, global = true)]
+  ascii: bool,
+
+  /// Print long output of list, show and search straight to the terminal instead of through
+  /// `$PAGER`
+  #[arg(long, global = true)]
+  no_pager: bool,
 }
 
+#[cfg(feature = "cli")]
 #[derive(Subcommand)]
 enum Commands {
   /// Add one or more todo items
   Add {
     /// The todo to add
     todos: Vec<String>,
+
+    #[command(flatten)]
+    options: AddOptions,
+  },
+
+  /// Change every todo matching a filter expression at once
+  Apply {
+    /// Like `tag:work and (priority:high or due<3d)`, other words match the body
+    filter: String,
+
+    #[command(flatten)]
+    changes: apply::Changes,
+
+    /// Only print what would change
+    #[arg(long)]
+    dry_run: bool,
   },
 
   /// Remove one or more todo items
-  Rm {},
+  Rm {
+    /// Only print what would be removed
+    #[arg(long)]
+    dry_run: bool,
 
-  /// Edit a todo item
-  Edit {},
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    #[command(flatten)]
+    filter: PickerFilter,
+  },
+
+  /// Edit a todo item, or all of them at once as text
+  Edit {
+    /// Edit every todo in the editor, one per line
+    #[arg(short, long)]
+    all: bool,
+
+    /// With --all, only edit incomplete todos
+    #[arg(short, long, requires = "all")]
+    incomplete: bool,
+
+    /// Id or text of the todo, pick interactively when left out. Without a terminal the
+    /// new body is read from stdin.
+    #[arg(conflicts_with = "all")]
+    target: Option<String>,
+  },
 
   /// Toggle the completion state of a todo
-  Toggle {},
+  Toggle {
+    /// Complete parents whose subtasks are then all done
+    #[arg(short, long)]
+    complete_parents: bool,
+
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    #[command(flatten)]
+    filter: PickerFilter,
+  },
+
+  /// Mark todos done
+  Done {
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+  },
+
+  /// Edit the notes of a todo item
+  Notes {
+    /// Id or text of the todo, pick interactively when left out
+    target: Option<String>,
+  },
+
+  /// Show the keys of the terminal ui and the pickers, and bindings that get in each other's
+  /// way
+  Keys {},
+
+  /// Show everything about a todo, its body and notes rendered as markdown
+  Show {
+    /// Id of the todo, pick interactively when left out
+    #[arg(value_parser = ids::parse_id)]
+    id: Option<usize>,
+
+    /// Write dates like 2024-03-05, in 3 days or both, instead of what the config says
+    #[arg(long)]
+    dates: Option<date::Dates>,
+  },
+
+  /// Tick a checklist item in the notes of a todo, or show the checklist. With --fail-on
+  /// it checks for blockers instead, for git hooks.
+  Check {
+    /// Id of the todo
+    #[arg(value_parser = ids::parse_id, required_unless_present = "fail_on")]
+    id: Option<usize>,
+
+    /// Number of the checklist item to toggle
+    item: Option<usize>,
+
+    /// Exit non-zero when open todos match a filter like overdue or tag:blocking,
+    /// `overdue|tag:blocking` fails on either
+    #[arg(long, conflicts_with = "id")]
+    fail_on: Vec<String>,
+  },
+
+  /// Set the status of one or more todo items
+  Status {
+    /// The new status
+    status: Status,
+
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    /// Complete parents whose subtasks are then all done
+    #[arg(short, long)]
+    complete_parents: bool,
+  },
 
   /// List todo items
   List {
-    /// Show only incomplete items
-    #[arg(short, long)]
-    incomplete: bool,
+    #[command(flatten)]
+    options: ListOptions,
+  },
+
+  /// Find todos by the words of their body and notes, `running` finds `run errands` with
+  /// the default tokenizer
+  Search {
+    /// Words that must all be there, `deploy*` for any word starting with deploy
+    #[arg(required_unless_present_any = ["last", "history"])]
+    words: Vec<String>,
+
+    /// Search for the words of the last search again
+    #[arg(long, conflicts_with_all = ["words", "history"])]
+    last: bool,
+
+    /// List the recent searches, latest first
+    #[arg(long, conflicts_with = "words")]
+    history: bool,
+
+    /// Show only the best this many
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+
+    /// Search removed todos too, archived ones were done or cancelled and those in the trash
+    /// were still open
+    #[arg(long, value_delimiter = ',')]
+    include: Vec<removed::Scope>,
   },
 
   /// Remove all completed items
-  Clean {},
+  Clean {
+    /// Only print what would be removed
+    #[arg(long)]
+    dry_run: bool,
+  },
+
+  /// Merge open todos with the same body, case and punctuation aside, into the oldest
+  Dedupe {
+    /// Only print what would be merged
+    #[arg(long)]
+    dry_run: bool,
+  },
+
+  /// Keep the database small by rolling up old history and pruning what is past keeping
+  Maintain {
+    /// Roll up history older than this many days, or a duration like 12w, to a change per
+    /// field and day, operations that old can't be undone anymore
+    #[arg(long, default_value = "90", value_parser = parse_days)]
+    compact_days: i64,
+
+    /// Forget todos removed and snapshots made more than this many days ago, or a duration
+    /// like 52w
+    #[arg(long, value_parser = parse_days)]
+    keep_days: Option<i64>,
+
+    /// Only print what would change
+    #[arg(long)]
+    dry_run: bool,
+  },
+
+  /// Close the gaps removed todos left in the ids, so they stay short. Scripts using the old
+  /// ids have to be updated, uuids don't change.
+  Renumber {
+    /// Only print what would change
+    #[arg(long)]
+    dry_run: bool,
+  },
+
+  /// Mark a todo as waiting on someone or something
+  Wait {
+    /// Who or what the todo is waiting on
+    #[arg(required_unless_present = "clear")]
+    on: Option<String>,
+
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    /// When to follow up (YYYY-MM-DD, today, tomorrow, 3d, 2w)
+    #[arg(short, long, value_parser = parse_date)]
+    follow_up: Option<NaiveDate>,
+
+    /// Stop waiting and make the todos given after it actionable again, pick interactively
+    /// when none are
+    #[arg(short, long, num_args = 0.., value_name = "TARGETS",
+      conflicts_with_all = ["on", "targets", "follow_up"])]
+    clear: Option<Vec<String>>,
+  },
+
+  /// List delegated todos and what they are waiting on
+  Waiting {},
+
+  /// Set the estimated effort of one or more todo items
+  Estimate {
+    /// Estimated effort, e.g. 90m, 2h or 2h30m
+    #[arg(value_parser = parse_duration)]
+    estimate: u32,
+
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+  },
+
+  /// Set the priority of one or more todo items
+  Priority {
+    /// The new priority, leave out to clear it
+    priority: Option<Priority>,
+
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    /// Clear the priority of the todos given after it, pick interactively when none are
+    #[arg(short, long, num_args = 0.., value_name = "TARGETS",
+      conflicts_with_all = ["priority", "targets"])]
+    clear: Option<Vec<String>>,
+  },
+
+  /// Set the due date of one or more todo items
+  Due {
+    /// The new due date (YYYY-MM-DD, today, tomorrow, 3d, 2w), leave out to clear it
+    #[arg(value_parser = parse_date)]
+    due: Option<NaiveDate>,
+
+    /// Ids like 3, a7 or 1-5,8,12, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    /// Clear the due date of the todos given after it, pick interactively when none are
+    #[arg(short, long, num_args = 0.., value_name = "TARGETS",
+      conflicts_with_all = ["due", "targets"])]
+    clear: Option<Vec<String>>,
+  },
+
+  /// Set the date one or more todo items are hidden until, or list and run the jobs
+  /// scheduled in the config
+  #[command(args_conflicts_with_subcommands = true)]
+  Schedule {
+    /// The new date (YYYY-MM-DD, today, tomorrow, 3d, 2w), leave out to clear it
+    #[arg(value_parser = parse_date)]
+    scheduled: Option<NaiveDate>,
+
+    #[command(subcommand)]
+    action: Option<cron::Action>,
+  },
+
+  /// Show how well habits were kept over the last four weeks
+  Habits {},
+
+  /// Show completions per day as a calendar heatmap
+  Heatmap {
+    /// How many weeks back to show, fewer when the terminal is narrow
+    #[arg(short, long, default_value_t = 52)]
+    weeks: usize,
+  },
+
+  /// Show open and done counts and how long todos take, per tag and project
+  Stats {},
+
+  /// Summarize what was added, completed and carried over as markdown, or analyze the history
+  #[command(args_conflicts_with_subcommands = true)]
+  Report {
+    #[command(subcommand)]
+    action: Option<report::Action>,
+
+    #[command(flatten)]
+    period: report::Period,
+
+    /// Write the report to this file instead of printing it
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+  },
+
+  /// Show open todos in an urgent/important grid
+  Matrix {
+    /// Todos due within this many days count as urgent
+    #[arg(short, long, default_value_t = 2)]
+    urgent_days: i64,
+  },
+
+  /// List captured todos that still need processing
+  Inbox {},
+
+  /// List deferred todos, or move todos in and out of the someday bucket
+  Someday {
+    /// Pick todos to defer to someday
+    #[arg(short, long, conflicts_with = "activate")]
+    defer: bool,
+
+    /// Pick someday todos to make actionable again
+    #[arg(short, long)]
+    activate: bool,
+  },
+
+  /// Quickly capture a todo into the inbox from a one line prompt
+  ///
+  /// Meant to be bound to a hotkey, e.g. `alacritty --class capture -e todo capture`
+  Capture {
+    /// Capture this text directly instead of prompting
+    body: Vec<String>,
+  },
+
+  /// Triage the inbox into next actions, projects, someday or waiting
+  Process {},
+
+  /// Walk through stale todos and decide what to do with each
+  Review {
+    /// Todos without activity for this many days count as stale
+    #[arg(short, long, default_value_t = 14)]
+    days: i64,
+  },
+
+  /// Step through the open todos setting priorities, due dates and snoozes a key at a time
+  Triage {},
+
+  /// Focus on a few todos, list shows only those until unfocus
+  Focus {
+    /// Ids like 3, a7 or 1-5,8, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+  },
+
+  /// Stop focusing and list everything again
+  Unfocus {},
+
+  /// Pin todos so they are always listed first
+  Pin {
+    /// Ids like 3, a7 or 1-5,8, or text to match against the body, pick interactively when empty
+    targets: Vec<String>,
+
+    /// Unpin them instead
+    #[arg(short, long)]
+    remove: bool,
+  },
+
+  /// Put todos in a new manual order, the one list follows without --sort
+  Reorder {
+    /// Ids like 3, a7 or 1-5,8 in their new order, drag the open todos around when empty
+    targets: Vec<String>,
+  },
+
+  /// Make a todo depend on others, it is blocked until they are done
+  Depend {
+    /// Id of the todo that has to wait
+    #[arg(value_parser = ids::parse_id)]
+    id: usize,
+
+    /// Ids of the todos it waits for, e.g. 3, a7 or 1-5,8
+    #[arg(required = true, value_parser = ids::parse_id_expression)]
+    on: Vec<String>,
+
+    /// Remove these dependencies instead
+    #[arg(short, long)]
+    remove: bool,
+  },
+
+  /// Pick the tasks for today that fit in the available time
+  Plan {
+    /// Time available today, e.g. 6h
+    #[arg(short, long, value_parser = parse_duration)]
+    capacity: u32,
+  },
+
+  /// Show every change made to a todo
+  History {
+    /// Id of the todo, it may already be deleted
+    #[arg(value_parser = ids::parse_id)]
+    id: usize,
+  },
+
+  /// Revert the last operations after showing what changes
+  Undo {
+    /// How many operations to revert
+    #[arg(short, long, default_value_t = 1, conflicts_with = "to")]
+    last: usize,
+
+    /// Revert every operation back to and including this one
+    #[arg(short, long)]
+    to: Option<usize>,
+
+    /// Show the operations that can be undone
+    #[arg(long, conflicts_with_all = ["last", "to"])]
+    list: bool,
+
+    /// Only show what would be reverted
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Don't ask before reverting
+    #[arg(short, long)]
+    yes: bool,
+  },
+
+  /// Write all todos in another format
+  Export {
+    #[command(flatten)]
+    format: formats::FormatArgs,
+
+    /// File to write to instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Only export the todos matching an expression like `tag:work and incomplete`, as
+    /// `apply` takes it
+    #[arg(long, value_parser = parse_query)]
+    filter: Option<Query>,
+
+    /// Replace bodies, notes, projects, tags and people with placeholders like `Todo 3`,
+    /// to share the todos in a bug report
+    #[arg(long)]
+    redact: bool,
+  },
+
+  /// Add the todos of a file in another format
+  Import {
+    #[command(flatten)]
+    format: formats::FormatArgs,
+
+    file: PathBuf,
+
+    /// Only show what would be imported
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Map columns, preview the todos and decide what to do with likely duplicates
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// What to do with todos that have the uuid, ref or body of one already there. Without
+    /// one todos exported from here are merged back and the others are added.
+    #[arg(long, value_enum)]
+    strategy: Option<strategy::Strategy>,
+
+    /// Leave out todos already there, like `--strategy skip`
+    #[arg(long, conflicts_with = "strategy")]
+    skip_existing: bool,
+  },
+
+  /// Answer `!todo add/list/done` in a team chat
+  Bot {
+    #[command(flatten)]
+    network: bot::Network,
+  },
+
+  /// Import and link GitLab issues
+  Gitlab {
+    #[command(subcommand)]
+    action: gitlab::Action,
+  },
+
+  /// Import the TODO: and FIXME: comments of a codebase, tagged with file and line. Todos
+  /// of comments that are gone since the last scan are completed.
+  Scan {
+    /// The directory to walk
+    #[arg(default_value = ".")]
+    dir: PathBuf,
+
+    /// What to do with new comments a todo has the text of already
+    #[arg(long, value_enum, default_value = "skip")]
+    strategy: strategy::Strategy,
+  },
+
+  /// Run a todo command in the background, as a systemd user service or a launchd agent
+  /// on macOS, e.g. `install-service --user --every 1h slack post` or `install-service
+  /// --user bot --matrix`
+  InstallService {
+    /// Install it for the current user, the only kind there is
+    #[arg(long)]
+    user: bool,
+
+    /// Run the command this often, e.g. 15m or 1h, instead of keeping it running
+    #[arg(long, value_parser = parse_duration)]
+    every: Option<u32>,
+
+    /// Only print the files that would be written
+    #[arg(long)]
+    dry_run: bool,
+
+    /// The todo command to run
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+  },
+
+  /// Keep every command from opening the todos until `todo unlock` is given the passphrase
+  /// of the config, for accounts that others use too
+  Lock {
+    /// Ask for a new passphrase and print its hash for the config instead
+    #[arg(long)]
+    hash: bool,
+  },
+
+  /// Let commands open the todos again
+  Unlock {},
+
+  /// Complete todos from commit messages with a git hook
+  GitHook {
+    #[command(subcommand)]
+    action: githook::Action,
+  },
+
+  /// Import and link Jira issues
+  Jira {
+    #[command(subcommand)]
+    action: jira::Action,
+  },
+
+  /// Share todos in Slack
+  Slack {
+    #[command(subcommand)]
+    action: slack::Action,
+  },
+
+  /// Notify about todos when they fall due
+  Notify {
+    #[command(subcommand)]
+    action: notify::Action,
+  },
+
+  /// Keep todos in step with other tools
+  Sync {
+    #[command(subcommand)]
+    action: sync::Action,
+  },
+
+  /// Set the defaults of a project: tags and a priority for the todos added to it, how it's
+  /// sorted and when its completed todos are archived. Without any they are shown.
+  Project {
+    name: String,
+
+    /// Tag the todos added to the project like this, repeat it for more
+    #[arg(short, long = "tag", value_parser = parse_tag)]
+    tags: Vec<String>,
+
+    /// Priority of the todos added to the project without one
+    #[arg(long)]
+    priority: Option<Priority>,
+
+    /// Sort `list --project` by keys like `priority:desc,due:asc`
+    #[arg(long, value_parser = sort::parse)]
+    sort: Option<sort::Sort>,
+
+    /// Archive done and cancelled todos this many days after they last changed, or a
+    /// duration like 2w
+    #[arg(long, value_parser = parse_days)]
+    archive_after: Option<i64>,
+
+    /// Forget the defaults
+    #[arg(long, conflicts_with_all = ["tags", "priority", "sort", "archive_after"])]
+    clear: bool,
+  },
+
+  /// Work in a project, todos are added to it and listed from it without `--project`.
+  /// `$TODO_LIST` overrides it, for direnv to set per directory.
+  Use {
+    name: Option<String>,
+
+    /// Work in no project
+    #[arg(long, conflicts_with = "name")]
+    none: bool,
+  },
+
+  /// Add, remove, rename, merge and color tags
+  Tag {
+    #[command(subcommand)]
+    action: tags::Action,
+  },
+
+  /// Save, compare and restore copies of all todos
+  Snapshot {
+    #[command(subcommand)]
+    action: snapshot::Action,
+  },
+
+  /// Run a Lua script with the `todo` api, the arguments after it are in `arg`
+  Run {
+    script: PathBuf,
+
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+  },
+
+  /// Words that are not a command, added as a todo. `todo 12 modify due:friday +urgent
+  /// -someday pri:H` changes todo 12 instead.
+  #[command(external_subcommand)]
+  Quick(Vec<String>),
 }
 
+#[cfg(feature = "cli")]
 pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+  let config = Config::load()?;
+  logging::init(args.verbose, config.log_file.as_deref())?;
+  emoji::init(config.emoji);
+  date::init_calendar(&config.date_format, config.week_start);
+  date::init(match &args.command {
+    Some(Commands::List { options }) => options.dates.unwrap_or(config.dates),
+    Some(Commands::Show { dates, .. }) => dates.unwrap_or(config.dates),
+    _ => config.dates,
+  });
+  config.matcher.init();
+  prompt::init(args.no_input);
+  layout::init(args.accessible || config.accessible);
+  ascii::init(args.ascii || config.ascii);
+  pager::init(args.no_pager);
+  let keys = keys::Keys::new(config.keymap, &config.keys)?;
+  keys.init();
+
   // Create connection to db
-  let conn = Connection::open("todos.db")?;
+  let location = args.db.as_ref().or(config.db.as_ref());
+  let storage = storage::from_location(location.map_or("todos.db", |db| db.as_str()))?;
+  // Locked todos aren't opened by anything but unlock
+  let hashed = || {
+    config
+      .passphrase
+      .as_ref()
+      .ok_or("Set a [passphrase] in the config first, todo lock --hash makes one")?
+      .get("passphrase")
+  };
+  match &args.command {
+    Some(Commands::Lock { hash: true }) => return passphrase::new_hash(),
+    Some(Commands::Lock { hash: false }) => return passphrase::lock(&hashed()?, storage.file()),
+    Some(Commands::Unlock {}) => return passphrase::unlock(&hashed()?, storage.file()),
+    _ => passphrase::check(storage.file())?,
+  }
+  // Jobs open the todos themselves, holding a lock meanwhile would keep them waiting
+  if let Some(Commands::Schedule {
+    action: Some(action),
+    ..
+  }) = &args.command
+  {
+    return cron::schedule(
+      action,
+      &config.schedule,
+      location.map_or("todos.db", |db| db.as_str()),
+    );
+  }
+  if let Some(backend) = storage
+    .without_history()
+    .filter(|_| needs_history(&args.command))
+  {
+    return Err(format!("{backend} can't keep history, a SQLite or json file can").into());
+  }
+  let read_only = args.read_only || storage.read_only();
+  // The bot and the ui lock for each change instead, others would wait on them for good
+  let long_running = matches!(&args.command, None | Some(Commands::Bot { .. }));
+  // Released when the command is done, after the changes are saved
+  let mut _lock = match read_only || long_running {
+    true => None,
+    false => lock::Lock::acquire(config.lock_file, storage.file())?,
+  };
+  let mut conn = match read_only {
+    true => storage.open_read_only()?,
+    false => storage.open()?,
+  };
+  logging::trace(&conn);
+  if read_only {
+    if !db::is_current(&conn)? {
+      return Err("The database needs migrating first, open it once without --read-only".into());
+    }
+    conn.pragma_update(None, "query_only", true)?;
+  }
 
   // Setup db system
   create_db(&conn)?;
+  tags::init_colors(&config.tag_colors, &conn)?;
+  if !read_only {
+    expiry::expire(config.expired, &conn)?;
+    project::archive(&conn)?;
+    search::index(config.search_tokenizer, &conn)?;
+  }
 
   // Parse the args
   match &args.command {
-    Some(Commands::Add { todos }) => add(todos.to_vec(), &conn)?,
-    Some(Commands::Rm {}) => {
-      let targets = match multi_find(&conn) {
-        Ok(result) => result,
-        _ => panic!("Something went wrong with selection!"),
+    Some(Commands::Add { todos, options }) => {
+      let todos = todos
+        .iter()
+        .map(|todo| emoji::store(config.emoji, todo))
+        .collect();
+      let options = AddOptions {
+        project: options.project.clone().or(project::current(&conn)?),
+        ..options.clone()
+      };
+      add(todos, &options, &conn)?
+    }
+    Some(Commands::Quick(words)) => match words.as_slice() {
+      // Only ids, `todo fix modify button` is a todo to add
+      [target, command, tokens @ ..] if command == "modify" && ids::is_unambiguous(target) => {
+        modify::modify(target, tokens, &conn)?
+      }
+      _ => {
+        let (body, mut options) = quick(words);
+        options.project = project::current(&conn)?;
+        add(vec![emoji::store(config.emoji, &body)], &options, &conn)?
+      }
+    },
+    Some(Commands::Apply {
+      filter,
+      changes,
+      dry_run,
+    }) => apply::apply(filter, changes, *dry_run, &conn)?,
+    Some(Commands::Rm {
+      dry_run,
+      targets,
+      filter,
+    }) => {
+      let targets = match targets.is_empty() {
+        true => multi_find_filtered("Which todos to remove?", filter, &conn)?,
+        false => find_targets(targets, &conn)?,
       };
-      rm(targets, &conn)?;
+      let bodies = bodies(&targets);
+      if *dry_run {
+        print_dry_run(&bodies);
+      } else if config.confirm.ask("Remove", &bodies)? {
+        rm(targets, &conn)?;
+      }
     }
-    Some(Commands::Toggle {}) => {
-      let targets = match multi_find(&conn) {
-        Ok(result) => result,
-        _ => panic!("Something went wrong with selection!"),
+    Some(Commands::Toggle {
+      complete_parents,
+      targets,
+      filter,
+    }) => {
+      let targets = match targets.is_empty() {
+        true => multi_find_filtered("Which todos to toggle?", filter, &conn)?,
+        false => find_targets(targets, &conn)?,
       };
-      toggle(targets, &conn)?;
+      let targets = habits::check_in(targets, true, &conn)?;
+      toggle(targets.clone(), &conn)?;
+      if *complete_parents {
+        subtasks::complete_parents(&targets, &conn)?;
+      }
     }
-    Some(Commands::Edit {}) => {
-      let target = match fuzzy_find(&conn) {
-        Ok(result) => result,
-        _ => panic!("Something went wrong with selection!"),
+    Some(Commands::Edit {
+      all: true,
+      incomplete,
+      ..
+    }) => {
+      let todos = if *incomplete {
+        collect_todos_incomplete(&conn)?
+      } else {
+        collect_todos_all(&conn)?
+      };
+      batch::edit_all(todos, config.confirm, &conn)?;
+    }
+    Some(Commands::Edit { target, .. }) if !prompt::interactive() => {
+      let Some(target) = target else {
+        return Err(prompt::refusal("pass the id and pipe in the new body").into());
+      };
+      let target = find_one(target, &conn)?;
+      if std::io::stdin().is_terminal() {
+        return Err(prompt::refusal("pipe in the new body").into());
+      }
+      let mut new = String::new();
+      std::io::stdin().read_to_string(&mut new)?;
+      match new.trim() {
+        "" => println!("Empty todo is not acceptable!"),
+        new => edit(target, emoji::store(config.emoji, new), &conn)?,
+      }
+    }
+    Some(Commands::Edit { target, .. }) => {
+      let target = match target {
+        Some(target) => find_one(target, &conn)?,
+        None => fuzzy_find("Which todo to edit?", &conn)?,
       };
       if let Some(new) = Editor::new()
         .edit(&target.body)
         .expect("Editor had issues!")
       {
-        edit(target, new, &conn)?;
+        edit(target, emoji::store(config.emoji, &new), &conn)?;
       } else {
         println!("Empty todo is not acceptable!");
       }
     }
-    Some(Commands::List { incomplete: all }) => list(*all, conn)?,
-    Some(Commands::Clean {}) => clean(conn)?,
-    _ => {}
+    Some(Commands::Done { targets }) => {
+      let targets = if targets.is_empty() {
+        let incomplete = PickerFilter {
+          incomplete: true,
+          ..Default::default()
+        };
+        multi_find_filtered("Which todos are done?", &incomplete, &conn)?
+      } else {
+        find_targets(targets, &conn)?
+      };
+      let targets = habits::check_in(targets, false, &conn)?;
+      set_status(targets, Status::Done, &conn)?;
+    }
+    Some(Commands::Notes { target }) => {
+      prompt::require("notes are only edited in the editor")?;
+      let target = match target {
+        Some(target) => find_one(target, &conn)?,
+        None => fuzzy_find("Which todo's notes to edit?", &conn)?,
+      };
+      let notes = Editor::new()
+        .extension(".md")
+        .edit(target.notes.as_deref().unwrap_or(""))
+        .expect("Editor had issues!");
+      match notes {
+        Some(notes) => set_notes(target, notes, &conn)?,
+        None => println!("Notes left unchanged!"),
+      }
+    }
+    Some(Commands::Show { id, .. }) => {
+      let target = match id {
+        Some(id) => find_targets(&[id.to_string()], &conn)?.remove(0),
+        None => fuzzy_find("Which todo to show?", &conn)?,
+      };
+      let lines = details(&target)
+        .iter()
+        .map(|line| ascii::text(line))
+        .collect::<Vec<String>>();
+      pager::page(&lines)?;
+    }
+    Some(Commands::Check {
+      id: Some(id), item, ..
+    }) => {
+      let target = find_targets(&[id.to_string()], &conn)?.remove(0);
+      checklist::check(target, *item, &conn)?;
+    }
+    Some(Commands::Check { fail_on, .. }) => gate::gate(fail_on, &conn)?,
+    Some(Commands::Status {
+      status,
+      targets,
+      complete_parents,
+    }) => {
+      let targets = find_or_pick(targets, "Which todos get the status?", &conn)?;
+      set_status(targets.clone(), *status, &conn)?;
+      if *complete_parents {
+        subtasks::complete_parents(&targets, &conn)?;
+      }
+    }
+    Some(Commands::List { options }) => {
+      let project = match options.all {
+        true => options.project.clone(),
+        false => options.project.clone().or(project::current(&conn)?),
+      };
+      let project_sort = match &project {
+        Some(name) => project::settings(name, &conn)?.sort,
+        None => None,
+      };
+      let options = ListOptions {
+        project,
+        sort: options
+          .sort
+          .clone()
+          .or(project_sort)
+          .or(config.sort.clone()),
+        ..options.clone()
+      };
+      list(&options, &conn)?
+    }
+    Some(Commands::Search {
+      words,
+      last,
+      history,
+      limit,
+      include,
+    }) => match (last, history) {
+      (_, true) => {
+        for text in searches::recent(searches::SEARCH, &conn)? {
+          println!("{}", text);
+        }
+      }
+      (true, _) => {
+        let recent = searches::recent(searches::SEARCH, &conn)?;
+        let last = recent.first().ok_or("Nothing was searched for yet")?;
+        search::search(std::slice::from_ref(last), *limit, include, &conn)?
+      }
+      _ => search::search(words, *limit, include, &conn)?,
+    },
+    Some(Commands::Clean { dry_run }) => {
+      let bodies = bodies(&collect_todos_completed(&conn)?);
+      if *dry_run {
+        print_dry_run(&bodies);
+      } else if config.confirm.ask("Clean", &bodies)? {
+        clean(&conn)?;
+      }
+    }
+    Some(Commands::Dedupe { dry_run }) => dedupe::dedupe(*dry_run, &conn)?,
+    Some(Commands::Maintain {
+      compact_days,
+      keep_days,
+      dry_run,
+    }) => maintain::maintain(*compact_days, *keep_days, *dry_run, &conn)?,
+    Some(Commands::Renumber { dry_run }) => maintain::renumber(*dry_run, &conn)?,
+    Some(Commands::Wait {
+      on,
+      targets,
+      follow_up,
+      clear,
+    }) => {
+      let targets = match clear.as_ref().unwrap_or(targets) {
+        targets if targets.is_empty() => vec![fuzzy_find("Which todo is waiting?", &conn)?],
+        targets => find_targets(targets, &conn)?,
+      };
+      for target in targets {
+        wait(target, on.clone(), *follow_up, &conn)?;
+      }
+    }
+    Some(Commands::Waiting {}) => waiting(&conn)?,
+    Some(Commands::Estimate { estimate, targets }) => {
+      let targets = find_or_pick(targets, "Which todos to estimate?", &conn)?;
+      set_estimate(targets, *estimate, &conn)?;
+    }
+    Some(Commands::Priority {
+      priority,
+      targets,
+      clear,
+    }) => {
+      let targets = clear.as_ref().unwrap_or(targets);
+      let targets = find_or_pick(targets, "Which todos to prioritize?", &conn)?;
+      set_priority(targets, *priority, &conn)?;
+    }
+    Some(Commands::Due {
+      due,
+      targets,
+      clear,
+    }) => {
+      let targets = clear.as_ref().unwrap_or(targets);
+      let targets = find_or_pick(targets, "Which todos get the due date?", &conn)?;
+      set_due(targets, *due, &conn)?;
+    }
+    Some(Commands::Schedule {
+      action: Some(_), ..
+    }) => unreachable!("Scheduled jobs run before the todos are opened"),
+    Some(Commands::Lock { .. } | Commands::Unlock {}) => {
+      unreachable!("Locking happens before the todos are opened")
+    }
+    Some(Commands::Schedule { scheduled, .. }) => {
+      // Todos scheduled for later are offered too, so they can be brought back early
+      let targets = match prompt::interactive() {
+        true => multi_select("Which todos to schedule?", collect_todos_all(&conn)?)?,
+        false => piped(&conn)?,
+      };
+      set_scheduled(targets, *scheduled, &conn)?;
+    }
+    Some(Commands::Matrix { urgent_days }) => matrix::matrix(*urgent_days, &conn)?,
+    Some(Commands::Inbox {}) => gtd::inbox(&conn)?,
+    Some(Commands::Someday { defer, activate }) => {
+      if *defer || *activate {
+        let targets = multi_find("Which todos to move?", &conn)?;
+        let bucket = if *defer {
+          Bucket::Someday
+        } else {
+          Bucket::Next
+        };
+        gtd::defer(targets, bucket, &conn)?;
+      } else {
+        gtd::someday(&conn)?;
+      }
+    }
+    Some(Commands::Capture { body }) => gtd::capture(body.join(" "), &conn)?,
+    Some(Commands::Process {}) => gtd::process(&conn)?,
+    Some(Commands::Review { days }) => review::review(*days, &conn)?,
+    Some(Commands::Triage {}) => triage::triage(&conn)?,
+    Some(Commands::Focus { targets }) => {
+      let targets = if targets.is_empty() {
+        multi_find("Which todos to focus on?", &conn)?
+      } else {
+        find_targets(targets, &conn)?
+      };
+      focus::focus(targets, &conn)?;
+    }
+    Some(Commands::Unfocus {}) => focus::unfocus(&conn)?,
+    Some(Commands::Habits {}) => habits::habits(&conn)?,
+    Some(Commands::Heatmap { weeks }) => heatmap::heatmap(*weeks, &conn)?,
+    Some(Commands::Stats {}) => stats::stats(&conn)?,
+    Some(Commands::Report {
+      action,
+      period,
+      out,
+    }) => report::report(action.as_ref(), period, out.as_deref(), &conn)?,
+    Some(Commands::Pin { targets, remove }) => {
+      let targets = if targets.is_empty() {
+        let prompt = match remove {
+          true => "Which todos to unpin?",
+          false => "Which todos to pin?",
+        };
+        multi_find(prompt, &conn)?
+      } else {
+        find_targets(targets, &conn)?
+      };
+      pin(targets, !remove, &conn)?
+    }
+    Some(Commands::Reorder { targets }) => {
+      let targets = match targets.is_empty() {
+        true => order::pick(&conn)?,
+        false => find_targets(targets, &conn)?,
+      };
+      order::reorder(targets, &conn)?
+    }
+    Some(Commands::Depend { id, on, remove }) => {
+      let as_targets = |ids: &[usize]| ids.iter().map(usize::to_string).collect::<Vec<String>>();
+      let target = find_targets(&as_targets(&[*id]), &conn)?.remove(0);
+      let on = find_targets(on, &conn)?;
+      if *remove {
+        deps::undepend(&target, &on, &conn)?;
+      } else {
+        deps::depend(&target, &on, &conn)?;
+      }
+    }
+    Some(Commands::Plan { capacity }) => plan::plan(*capacity, &conn)?,
+    Some(Commands::History { id }) => history::history(*id, &conn)?,
+    Some(Commands::Undo { list: true, .. }) => undo::list(&conn)?,
+    Some(Commands::Undo {
+      last,
+      to,
+      list: _,
+      dry_run,
+      yes,
+    }) => undo::undo(*last, *to, *dry_run, *yes, &conn)?,
+    Some(Commands::Export {
+      format,
+      output,
+      filter,
+      redact,
+    }) => formats::export(
+      format.format(),
+      filter.as_ref(),
+      *redact,
+      output.as_deref(),
+      &conn,
+    )?,
+    Some(Commands::Import {
+      format,
+      file,
+      dry_run,
+      interactive,
+      strategy,
+      skip_existing,
+    }) => formats::import(
+      format.format(),
+      file,
+      *dry_run,
+      *interactive,
+      skip_existing
+        .then_some(strategy::Strategy::Skip)
+        .or(*strategy),
+      &conn,
+    )?,
+    Some(Commands::Bot { network }) => bot::bot(network, &config, storage.as_ref())?,
+    Some(Commands::Gitlab { action }) => gitlab::gitlab(action, config.gitlab.as_ref(), &conn)?,
+    Some(Commands::Scan { dir, strategy }) => scan::scan(dir, *strategy, &conn)?,
+    Some(Commands::InstallService {
+      user,
+      every,
+      dry_run,
+      command,
+    }) => service::install(
+      *user,
+      *every,
+      command,
+      *dry_run,
+      location.map_or("todos.db", |db| db.as_str()),
+    )?,
+    Some(Commands::GitHook { action }) => {
+      githook::git_hook(action, location.map_or("todos.db", |db| db.as_str()), &conn)?
+    }
+    Some(Commands::Jira { action }) => jira::jira(action, config.jira.as_ref(), &conn)?,
+    Some(Commands::Slack { action }) => slack::slack(action, config.slack.as_ref(), &conn)?,
+    Some(Commands::Notify { action }) => notify::notify(action, &config.quiet_hours, &conn)?,
+    Some(Commands::Sync { action }) => sync::sync(action, &conn)?,
+    Some(Commands::Project {
+      name,
+      tags,
+      priority,
+      sort,
+      archive_after,
+      clear,
+    }) => {
+      let changes = project::Settings {
+        tags: Tags(tags.clone()),
+        priority: *priority,
+        sort: sort.clone(),
+        archive_days: *archive_after,
+      };
+      project::project(name, changes, *clear, &conn)?
+    }
+    Some(Commands::Use { name, none }) => project::select(name.as_deref(), *none, &conn)?,
+    Some(Commands::Tag { action }) => tags::tag(action, &conn)?,
+    Some(Commands::Snapshot { action }) => snapshot::snapshot(action, &mut conn)?,
+    Some(Commands::Keys {}) => keys::keys(&keys)?,
+    Some(Commands::Run { script, args }) => script::run(script, args, &conn)?,
+    None => {
+      prompt::require("list the todos with `todo list`")?;
+      tui::tui(keys, config.lock_file, storage.file(), &conn)?
+    }
   }
 
-  Ok(())
-}
+  if read_only {
+    return Ok(());
+  }
+  if long_running {
+    _lock = lock::Lock::acquire(config.lock_file, storage.file())?;
+  }
 
-fn create_db(conn: &Connection) -> Result<(), Box<dyn Error>> {
-  conn.execute(
-    "CREATE TABLE IF NOT EXISTS todos (
-            id          INTEGER PRIMARY KEY,
-            body        TEXT NOT NULL,
-            incomplete  BOOL
-        )",
-    (), // empty list of parameters.
-  )?;
+  // Tell issue trackers about linked todos the command completed
+  jira::close_completed(config.jira.as_ref(), &conn)?;
+  gitlab::close_completed(config.gitlab.as_ref(), &conn)?;
+  script::fire(&config.scripts, &conn)?;
+
+  let command = env::args().skip(1).collect::<Vec<String>>().join(" ");
+  undo::record(&command, true, &conn)?;
+  storage.save(&conn)?;
 
   Ok(())
 }
 
+/// Whether the command works with what was done before, the history or recent searches
+#[cfg(feature = "cli")]
+fn needs_history(command: &Option<Commands>) -> bool {
+  matches!(
+    command,
+    Some(
+      Commands::History { .. }
+        | Commands::Undo { .. }
+        | Commands::Search { history: true, .. }
+        | Commands::Search { last: true, .. }
+    )
+  )
+}
+
+#[cfg(feature = "cli")]
 fn collect_todos(query: String, conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
-  let mut stmt = conn.prepare(&query)?;
+  collect_todos_with(&query, [], conn)
+}
+
+/// Todos of a query with parameters, for text from the command line
+#[cfg(feature = "cli")]
+fn collect_todos_with<P: rusqlite::Params>(
+  query: &str,
+  params: P,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(query)?;
   let todos = stmt
-    .query_map([], |row| {
-      Ok(Todo {
-        id: row.get(0)?,
-        body: row.get(1)?,
-        incomplete: row.get(2)?,
-      })
-    })?
-    .into_iter()
-    .filter(|s| s.is_ok())
-    .map(|s| s.unwrap())
+    .query_map(params, Todo::from_row)?
+    .filter_map(|s| s.ok())
     .collect::<Vec<Todo>>();
 
   Ok(todos)
 }
 
+#[cfg(feature = "cli")]
 fn collect_todos_all(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
-  collect_todos("SELECT * FROM todos;".to_string(), &conn)
+  collect_todos(format!("{TODO_SELECT} {MANUAL_ORDER};"), conn)
 }
 
+#[cfg(feature = "cli")]
 fn collect_todos_incomplete(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
-  collect_todos("SELECT * FROM todos where incomplete;".to_string(), &conn)
+  collect_todos(
+    format!("{TODO_SELECT} where status not in ('done', 'cancelled') {MANUAL_ORDER};"),
+    conn,
+  )
 }
 
-fn fuzzy_find(conn: &Connection) -> Result<Todo, Box<dyn Error>> {
-  let todos = collect_todos_all(&conn).unwrap();
-  let todo_strs = todos.iter().map(|s| &s.body).collect::<Vec<&String>>();
+/// Todos scheduled after today are hidden from lists and pickers until the date arrives
+#[cfg(feature = "cli")]
+const SCHEDULED_LATER: &str = "(scheduled is not null and scheduled > date('now', 'localtime'))";
 
-  let target_id = FuzzySelect::with_theme(&ColorfulTheme::default())
-    .with_prompt("Which one to erase?")
-    .default(0)
-    .items(&todo_strs[..])
-    .interact()
-    .unwrap();
+/// A page of the todos pickers offer that match `condition`, and whether more follow
+#[cfg(feature = "cli")]
+fn collect_todos_page(
+  condition: &str,
+  page: usize,
+  conn: &Connection,
+) -> Result<(Vec<Todo>, bool), Box<dyn Error>> {
+  let mut todos = collect_todos(
+    format!(
+      "{TODO_SELECT} where not {SCHEDULED_LATER} and {condition} {MANUAL_ORDER}
+       limit {} offset {};",
+      picker::PAGE + 1,
+      page * picker::PAGE
+    ),
+    conn,
+  )?;
+  let more = todos.len() > picker::PAGE;
+  todos.truncate(picker::PAGE);
+  Ok((todos, more))
+}
+
+/// What `clean` would remove
+#[cfg(feature = "cli")]
+fn collect_todos_completed(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!("{TODO_SELECT} where status in ('done', 'cancelled') {MANUAL_ORDER};"),
+    conn,
+  )
+}
+
+/// Incomplete todos that are waiting, soonest follow up first
+#[cfg(feature = "cli")]
+fn collect_todos_waiting(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} where status is 'waiting'
+       order by follow_up is null, follow_up;"
+    ),
+    conn,
+  )
+}
+
+/// Todos to show for the given list filters
+#[cfg(feature = "cli")]
+fn collect_todos_listed(
+  options: &ListOptions,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let (query, params) = listed_query(options, conn)?;
+  collect_todos_with(&query, rusqlite::params_from_iter(params), conn)
+}
+
+/// The query of the listed todos, with the parameters for the names it has
+#[cfg(feature = "cli")]
+fn listed_query(
+  options: &ListOptions,
+  conn: &Connection,
+) -> Result<(String, Vec<String>), Box<dyn Error>> {
+  let mut conditions = vec![];
+  let mut params = vec![];
+  if let Some(status) = options.status {
+    conditions.push(format!("status is '{}'", status.as_str()));
+  } else if options.incomplete {
+    conditions.push("status not in ('done', 'cancelled')".to_string());
+  }
+  if !options.all && focus::is_focusing(conn)? {
+    conditions.push("focused".to_string());
+  }
+  if !options.all {
+    conditions.push(format!("not {}", SCHEDULED_LATER));
+  }
+  if options.blocked {
+    conditions.push(deps::BLOCKED.to_string());
+  } else if options.unblocked {
+    conditions.push(format!("not {}", deps::BLOCKED));
+  }
+  if let Some(project) = &options.project {
+    params.push(project.clone());
+    conditions.push(format!("project is ?{}", params.len()));
+  }
+
+  let filter = if conditions.is_empty() {
+    String::new()
+  } else {
+    format!(" where {}", conditions.join(" and "))
+  };
+  let limit = match options.limit {
+    Some(limit) => format!(" limit {}", limit),
+    None => String::new(),
+  };
+  let order = match &options.sort {
+    Some(sort) => sort.order_by(),
+    None => MANUAL_ORDER.to_string(),
+  };
+  Ok((format!("{TODO_SELECT}{filter} {order}{limit};"), params))
+}
+
+/// Resolve command line targets, numbers are ids and anything else matches the body
+#[cfg(feature = "cli")]
+fn find_targets(targets: &[String], conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let mut found: Vec<Todo> = vec![];
+
+  for target in targets {
+    let by_id = |ids: &[usize]| -> Result<Vec<Todo>, Box<dyn Error>> {
+      let list = ids.iter().map(usize::to_string).collect::<Vec<_>>();
+      let todos = collect_todos(
+        format!("{TODO_SELECT} where id in ({});", list.join(",")),
+        conn,
+      )?;
+      Ok(
+        ids
+          .iter()
+          .filter_map(|id| todos.iter().find(|todo| todo.id == *id).cloned())
+          .collect(),
+      )
+    };
+    let by_text = || {
+      collect_todos_with(
+        &format!(
+          "{TODO_SELECT} where status not in ('done', 'cancelled')
+             and (instr(lowercase(body), ?1) > 0 or lowercase(reference) = ?1) {MANUAL_ORDER};"
+        ),
+        (target.to_lowercase(),),
+        conn,
+      )
+    };
+    // Text that reads as short ids of existing todos is taken as those ids
+    let by_ids = match ids::parse_ids(target) {
+      Ok(ids) => Some((by_id(&ids)?, ids)),
+      Err(e) if ids::is_id_expression(target) => return Err(e.into()),
+      Err(_) => None,
+    };
+    let matches = match by_ids {
+      Some((todos, ids)) if todos.len() == ids.len() && ids::is_unambiguous(target) => todos,
+      // Every id has to exist before anything is changed
+      Some((todos, ids)) if ids::is_id_expression(target) => {
+        let missing = ids
+          .iter()
+          .find(|id| todos.iter().all(|todo| todo.id != **id));
+        return Err(format!("No todo with id {}", missing.unwrap_or(&0)).into());
+      }
+      // Words like `bed` match the text first, they are short ids only when nothing has them
+      Some((todos, ids)) if todos.len() == ids.len() => {
+        let matches = by_text()?;
+        if !matches.is_empty() {
+          matches
+        } else {
+          log::warn!("No todo has '{}' in it, taking it as short ids", target);
+          todos
+        }
+      }
+      _ => by_text()?,
+    };
+    if matches.is_empty() {
+      return Err(format!("No todo matches '{}'", target).into());
+    }
+    for todo in matches {
+      if !found.contains(&todo) {
+        found.push(todo);
+      }
+    }
+  }
+
+  Ok(found)
+}
+
+/// The one todo `target` finds
+#[cfg(feature = "cli")]
+fn find_one(target: &str, conn: &Connection) -> Result<Todo, Box<dyn Error>> {
+  let mut targets = find_targets(&[target.to_string()], conn)?;
+  match targets.len() {
+    1 => Ok(targets.remove(0)),
+    count => Err(format!("'{}' matches {} todos, pick one by its id", target, count).into()),
+  }
+}
+
+/// Todos piped in for a picker that can't run
+#[cfg(feature = "cli")]
+fn piped(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let stdin = std::io::stdin();
+  if stdin.is_terminal() {
+    return Err(prompt::refusal(picker::PASS_IDS).into());
+  }
+  find_targets(&picker::piped_targets(stdin.lock())?, conn)
+}
+
+#[cfg(feature = "cli")]
+fn fuzzy_find(prompt: &str, conn: &Connection) -> Result<Todo, Box<dyn Error>> {
+  if !prompt::interactive() {
+    let mut todos = piped(conn)?;
+    if todos.len() != 1 {
+      return Err(format!("Pipe in one todo to pick, got {}", todos.len()).into());
+    }
+    return Ok(todos.remove(0));
+  }
+  // Untested segment starts, this part needs interactivity
+  for page in 0.. {
+    let (todos, more) = collect_todos_page("true", page, conn)?;
+    let target_id = picker::pick(prompt, &todos, more, Local::now().date_naive())?;
+    if let Some(todo) = todos.get(target_id) {
+      return Ok(todo.clone());
+    }
+  }
+  unreachable!("pages never run out")
+  // Untested segment ends
+}
 
-  Ok(todos[target_id].clone())
+#[cfg(feature = "cli")]
+fn multi_find(prompt: &str, conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  multi_find_filtered(prompt, &PickerFilter::default(), conn)
 }
 
-fn multi_find(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
-  let todos = collect_todos_all(&conn).unwrap();
-  let todo_strs = todos.iter().map(|s| &s.body).collect::<Vec<&String>>();
+/// The todos the targets name, picked when there are none
+#[cfg(feature = "cli")]
+fn find_or_pick(
+  targets: &[String],
+  prompt: &str,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  match targets.is_empty() {
+    true => multi_find(prompt, conn),
+    false => find_targets(targets, conn),
+  }
+}
+
+#[cfg(feature = "cli")]
+fn multi_find_filtered(
+  prompt: &str,
+  filter: &PickerFilter,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  if !prompt::interactive() {
+    return piped(conn);
+  }
+  let condition = filter
+    .resolve(&format!("not {SCHEDULED_LATER}"), conn)?
+    .condition();
+  // Untested segment starts, this part needs interactivity
+  let mut selected = vec![];
+  for page in 0.. {
+    let (todos, more) = collect_todos_page(&condition, page, conn)?;
+    if !more {
+      selected.extend(multi_select(prompt, todos)?);
+      break;
+    }
+    // Picking the last item goes on to the next page, what was picked stays picked
+    let mut todo_strs = picker::items(&todos, Local::now().date_naive(), layout::picker_width());
+    todo_strs.push(ascii::text(picker::MORE));
+    let target_ids = MultiSelect::with_theme(&ColorfulTheme::default())
+      .with_prompt(prompt)
+      .items(&todo_strs[..])
+      .interact()
+      .unwrap();
+    selected.extend(target_ids.iter().filter_map(|&i| todos.get(i).cloned()));
+    if !target_ids.contains(&todos.len()) {
+      break;
+    }
+  }
+  Ok(selected)
+  // Untested segment ends
+}
+
+#[cfg(feature = "cli")]
+fn multi_select(prompt: &str, todos: Vec<Todo>) -> Result<Vec<Todo>, Box<dyn Error>> {
+  if todos.is_empty() {
+    println!("No todos to pick from!");
+    return Ok(vec![]);
+  }
+  let todo_strs = picker::items(&todos, Local::now().date_naive(), layout::picker_width());
 
   let target_ids = MultiSelect::with_theme(&ColorfulTheme::default())
-    .with_prompt("Which one to edit?")
+    .with_prompt(prompt)
     .items(&todo_strs[..])
     .interact()
     .unwrap();
@@ -175,87 +2011,536 @@ fn multi_find(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
   Ok(todos_selected.clone())
 }
 
-fn add(todos: Vec<String>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+#[cfg(feature = "cli")]
+fn insert(body: &str, options: &AddOptions, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  // Todos filed under a project have already been processed
+  let bucket = if options.project.is_some() {
+    Bucket::Next
+  } else {
+    Bucket::Inbox
+  };
+  let position = order::position(options.top, options.after, conn)?;
+  let mut tags = Tags(options.tags.clone());
+  let mut priority = options.priority;
+  if let Some(project) = &options.project {
+    let defaults = project::settings(project, conn)?;
+    tags.extend(&defaults.tags.0);
+    priority = priority.or(defaults.priority);
+  }
+  // Cached, adding many todos at once compiles it once
+  let mut stmt = conn.prepare_cached(
+    "INSERT INTO todos (body, estimate, priority, due, bucket, project, notes, parent_id, tags,
+       scheduled, expires, habit, position, reference, body_hash)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+  )?;
+  stmt.execute((
+    body,
+    options.estimate,
+    priority,
+    options.due,
+    bucket,
+    &options.project,
+    &options.notes,
+    options.parent,
+    tags,
+    options.scheduled,
+    options.until,
+    options.habit,
+    position,
+    &options.reference,
+    dedupe::body_hash(body),
+  ))?;
+  Ok(())
+}
+
+/// The body and options of `todo buy milk tomorrow !!`, a date and a priority marker at the
+/// end are taken off the body. The first word always stays.
+#[cfg(feature = "cli")]
+fn quick(words: &[String]) -> (String, AddOptions) {
+  let mut options = AddOptions::default();
+  let mut end = words.len();
+  while end > 1 {
+    let word = words[end - 1].as_str();
+    let priority = [Priority::Low, Priority::Medium, Priority::High]
+      .into_iter()
+      .find(|priority| priority.marker() == word);
+    if let Some(priority) = priority
+      && options.priority.is_none()
+    {
+      options.priority = Some(priority);
+    } else if let Ok(due) = parse_date(word)
+      && options.due.is_none()
+    {
+      options.due = Some(due);
+    } else {
+      break;
+    }
+    end -= 1;
+  }
+  (words[..end].join(" "), options)
+}
+
+#[cfg(feature = "cli")]
+fn add(todos: Vec<String>, options: &AddOptions, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  if let Some(parent) = options.parent {
+    find_targets(&[parent.to_string()], conn)?;
+  }
   if todos.is_empty() {
+    prompt::require("pass the todos to add")?;
     // Untested segment starts, this part needs interactivity
     if let Some(new) = Editor::new().edit("").expect("Editor had issues!") {
-      conn.execute(
-        "INSERT INTO todos (body, incomplete) VALUES (?1, true)",
-        (&new,),
-      )?;
+      insert(&new, options, conn)?;
       println!("Added: {}", new);
     } else {
       println!("Nothing added!");
     }
     // Untested segment ends
   } else {
+    let mut options = options.clone();
     for todo in todos {
-      conn.execute(
-        "INSERT INTO todos (body, incomplete) VALUES (?1, true)",
-        (&todo,),
-      )?;
+      insert(&todo, &options, conn)?;
       println!("Added: {}", todo);
+      // The rest follow the first one instead of all landing in the same spot
+      if options.top || options.after.is_some() {
+        options.top = false;
+        options.after = Some(conn.last_insert_rowid() as usize);
+      }
     }
   }
-  Ok(())
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn bodies(todos: &[Todo]) -> Vec<String> {
+  todos.iter().map(|todo| todo.body.clone()).collect()
+}
+
+#[cfg(feature = "cli")]
+fn print_dry_run(bodies: &[String]) {
+  for body in bodies {
+    println!("Would remove todo: {}", body);
+  }
+  println!("Dry run, nothing removed!");
+}
+
+#[cfg(feature = "cli")]
+fn rm(targets: Vec<Todo>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut stmt = conn.prepare_cached("delete from todos where id is ?1")?;
+  for target in targets {
+    stmt.execute((target.id,))?;
+    println!("Removed todo: {}", target.body);
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn edit(target: Todo, new: String, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  conn.execute(
+    "UPDATE todos SET body = ?1, body_hash = ?2 where id is ?3",
+    (&new, dedupe::body_hash(&new), target.id),
+  )?;
+  println!("Updated to: {}", new);
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_notes(target: Todo, notes: String, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let notes = Some(notes).filter(|notes| !notes.trim().is_empty());
+  conn.execute(
+    "UPDATE todos SET notes = ?1 where id is ?2",
+    (&notes, target.id),
+  )?;
+  println!("Updated notes: {}", target.body);
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn toggle(targets: Vec<Todo>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut stmt = conn.prepare_cached("UPDATE todos SET status = ?1 where id is ?2")?;
+  for target in targets {
+    let flipped = if target.status.is_open() {
+      Status::Done
+    } else {
+      Status::Todo
+    };
+    stmt.execute((flipped, target.id))?;
+    println!("Toggled: {}", target.body);
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_status(targets: Vec<Todo>, status: Status, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut stmt = conn.prepare_cached("UPDATE todos SET status = ?1 where id is ?2")?;
+  for target in targets {
+    stmt.execute((status, target.id))?;
+    println!("Marked {}: {}", status, target.body);
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn pin(targets: Vec<Todo>, pinned: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for target in targets {
+    conn.execute(
+      "UPDATE todos SET pinned = ?1 where id is ?2",
+      (pinned, target.id),
+    )?;
+    match pinned {
+      true => println!("Pinned: {}", target.body),
+      false => println!("Unpinned: {}", target.body),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_estimate(
+  targets: Vec<Todo>,
+  estimate: u32,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  for target in targets {
+    conn.execute(
+      "UPDATE todos SET estimate = ?1 where id is ?2",
+      (estimate, target.id),
+    )?;
+    println!("Estimated {}: {}", format_duration(estimate), target.body);
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_priority(
+  targets: Vec<Todo>,
+  priority: Option<Priority>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  for target in targets {
+    conn.execute(
+      "UPDATE todos SET priority = ?1 where id is ?2",
+      (priority, target.id),
+    )?;
+    match priority {
+      Some(priority) => println!("Prioritised {}: {}", priority, target.body),
+      None => println!("Cleared priority: {}", target.body),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_scheduled(
+  targets: Vec<Todo>,
+  scheduled: Option<NaiveDate>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  for target in targets {
+    conn.execute(
+      "UPDATE todos SET scheduled = ?1 where id is ?2",
+      (scheduled, target.id),
+    )?;
+    match scheduled {
+      Some(scheduled) => println!("Scheduled {}: {}", scheduled, target.body),
+      None => println!("Cleared scheduled date: {}", target.body),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_due(
+  targets: Vec<Todo>,
+  due: Option<NaiveDate>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  for target in targets {
+    conn.execute("UPDATE todos SET due = ?1 where id is ?2", (due, target.id))?;
+    match due {
+      Some(due) => println!("Due {}: {}", due, target.body),
+      None => println!("Cleared due date: {}", target.body),
+    }
+  }
+  Ok(())
+}
+
+/// Project, checklist progress, priority marker, habit frequency, due, scheduled and expiry date
+/// shown after an open todo in list output
+#[cfg(feature = "cli")]
+fn annotations(todo: &Todo, today: NaiveDate) -> String {
+  let mut annotations = String::new();
+  if let Some(project) = &todo.project {
+    annotations.push_str(&format!(" {}", style(format!("[{}]", project)).cyan()));
+  }
+  if !todo.tags.is_empty() {
+    annotations.push_str(&format!(" {}", todo.tags.badges()));
+  }
+  if let Some((checked, total)) = todo.notes.as_deref().and_then(checklist::progress) {
+    annotations.push_str(&format!(
+      " {}",
+      style(format!("({}/{})", checked, total)).dim()
+    ));
+  }
+  if let Some(priority) = todo.priority {
+    let marker = match priority {
+      Priority::High => style(priority.marker()).red().bold(),
+      Priority::Medium => style(priority.marker()).yellow(),
+      Priority::Low => style(priority.marker()).dim(),
+    };
+    annotations.push_str(&format!(" {}", marker));
+  }
+  if let Some(habit) = todo.habit {
+    annotations.push_str(&format!(" {}", style(format!("({})", habit)).green()));
+  }
+  if let Some(due) = todo.due {
+    let due = if due < today {
+      style(format!("(overdue {})", date::display(due, today))).red()
+    } else {
+      style(format!("(due {})", date::display(due, today))).dim()
+    };
+    annotations.push_str(&format!(" {}", due));
+  }
+  if let Some(scheduled) = todo.scheduled.filter(|scheduled| *scheduled > today) {
+    annotations.push_str(&format!(
+      " {}",
+      style(format!("(scheduled {})", date::display(scheduled, today))).dim()
+    ));
+  }
+  if let Some(expires) = todo.expires.filter(|_| todo.status.is_open()) {
+    annotations.push_str(&format!(
+      " {}",
+      style(format!("(until {})", date::display(expires, today))).dim()
+    ));
+  }
+  annotations
+}
+
+/// The body, every field that is set and the notes of a todo
+#[cfg(feature = "cli")]
+fn details(todo: &Todo) -> Vec<String> {
+  let today = Local::now().date_naive();
+  let mut lines = vec![
+    format!(
+      "{}. {}",
+      ids::short(todo.id),
+      markdown::inline(&emoji::display(&todo.body))
+    ),
+    String::new(),
+  ];
+  let mut field = |name: &str, value: String| {
+    lines.push(format!(
+      "{} {}",
+      style(format!("{:<10}", name)).dim(),
+      value
+    ));
+  };
+  field("Status", todo.status.to_string());
+  if let Some(on) = &todo.waiting_on {
+    field("Waiting on", on.clone());
+  }
+  if let Some(priority) = todo.priority {
+    field("Priority", priority.to_string());
+  }
+  if let Some(due) = todo.due {
+    field("Due", date::display(due, today));
+  }
+  if let Some(scheduled) = todo.scheduled {
+    field("Scheduled", date::display(scheduled, today));
+  }
+  if let Some(expires) = todo.expires {
+    field("Until", date::display(expires, today));
+  }
+  if let Some(estimate) = todo.estimate {
+    field("Estimate", format_duration(estimate));
+  }
+  if let Some(habit) = todo.habit {
+    field("Habit", habit.to_string());
+  }
+  if let Some(project) = &todo.project {
+    field("Project", project.clone());
+  }
+  if !todo.tags.is_empty() {
+    field("Tags", todo.tags.badges());
+  }
+  if let Some(reference) = &todo.reference {
+    field("Ref", reference.clone());
+  }
+  if let Some(parent) = todo.parent_id {
+    field("Parent", parent.to_string());
+  }
+  if let Some(created) = todo.created_at {
+    let created = created.with_timezone(&Local);
+    field(
+      "Created",
+      date::display_with(
+        format!(
+          "{} {}",
+          date::format(created.date_naive()),
+          created.format("%H:%M")
+        ),
+        created.date_naive(),
+        today,
+      ),
+    );
+  }
+  if let Some(notes) = todo
+    .notes
+    .as_deref()
+    .filter(|notes| !notes.trim().is_empty())
+  {
+    lines.push(String::new());
+    lines.extend(markdown::render(notes).lines().map(str::to_string));
+  }
+  lines
 }
 
-fn rm(targets: Vec<Todo>, conn: &Connection) -> Result<(), Box<dyn Error>> {
-  for target in targets {
-    conn.execute("delete from todos where body is ?1", (&target.body,))?;
-    println!("Removed todo: {}", target.body);
+#[cfg(feature = "cli")]
+fn list(options: &ListOptions, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  if let Ok(todos) = collect_todos_listed(options, conn) {
+    let today = Local::now().date_naive();
+    let markers = deps::Markers::load(conn)?;
+    let progress = subtasks::progress(conn)?;
+    let width = layout::stdout_width();
+    let mut lines = vec![];
+    if let Some(columns) = &options.columns {
+      lines = match layout::accessible() {
+        true => layout::labelled(&todos, columns, today),
+        false => layout::table(&todos, columns, width, today),
+      };
+    } else {
+      let rows = match options.tree {
+        true => subtasks::tree(&todos, options.collapse),
+        false => todos
+          .iter()
+          .map(|todo| subtasks::Row {
+            todo,
+            connectors: String::new(),
+            folded: 0,
+          })
+          .collect(),
+      };
+      let id_width = rows
+        .iter()
+        .map(|row| ids::short(row.todo.id).len())
+        .max()
+        .unwrap_or_default();
+      for row in &rows {
+        let todo = row.todo;
+        let id = format!("{:>id_width$}", ids::short(todo.id));
+        let pin = match todo.pinned {
+          true => format!("{} ", style(PIN).yellow().bold()),
+          false => String::new(),
+        };
+        // Styled statuses keep their own style, so markup is only left out there
+        let body = emoji::display(&todo.body);
+        let body = match (options.markdown, todo.status) {
+          (false, _) => hyperlink::linkify(&body, hyperlink::enabled()),
+          (true, Status::Todo | Status::Waiting) => markdown::inline(&body),
+          (true, _) => markdown::plain(&body),
+        };
+        let output = format!("{}. {}{}{}", id, pin, layout::signals(todo, today), body);
+        let folded = match row.folded {
+          0 => String::new(),
+          folded => format!(" {}", style(format!("(+{} done)", folded)).dim()),
+        };
+        let rollup = match progress.get(&todo.id) {
+          Some((done, total)) => format!(" {}", style(subtasks::bar(*done, *total)).green()),
+          None => String::new(),
+        };
+        let annotations = format!(
+          "{}{}{}",
+          markers.for_todo(todo),
+          rollup,
+          annotations(todo, today)
+        );
+        let line = match todo.status {
+          Status::Todo => format!("{}{}", output, annotations),
+          Status::InProgress => format!("{}{}", style(output).yellow().bold(), annotations),
+          Status::Waiting => {
+            let on = todo.waiting_on.as_deref().unwrap_or("someone");
+            let waiting = style(format!("(waiting on {})", on)).dim();
+            format!("{}{} {}", output, annotations, waiting)
+          }
+          Status::Done => format!("{}{}", style(output).strikethrough(), folded),
+          Status::Cancelled => format!("{}{}", style(output).strikethrough().dim(), folded),
+        };
+        let line = format!("{}{}", style(&row.connectors).dim(), line);
+        // Wrapped lines start under the body
+        let indent = measure_text_width(&format!("{}{}. {}", row.connectors, id, pin));
+        lines.extend(layout::fit(
+          &ascii::text(&line),
+          width,
+          indent,
+          options.truncate,
+        ));
+      }
+    }
+    lines.extend(removed::lines(&removed::collect(&options.include, conn)?));
+    if !options.all && focus::is_focusing(conn)? {
+      lines.push(
+        style("Focusing, use --all to see everything")
+          .dim()
+          .to_string(),
+      );
+    }
+    pager::page(&lines)?;
+  } else {
+    println!("Something went wrong with collecting!");
   }
   Ok(())
 }
 
-fn edit(target: Todo, new: String, conn: &Connection) -> Result<(), Box<dyn Error>> {
+#[cfg(feature = "cli")]
+fn clean(conn: &Connection) -> Result<(), Box<dyn Error>> {
   conn.execute(
-    "UPDATE todos SET body = ?1 where id is ?2",
-    (&new, target.id),
+    "DELETE FROM todos WHERE status in ('done', 'cancelled')",
+    (),
   )?;
-  println!("Updated to: {}", new);
-  Ok(())
-}
-
-fn toggle(targets: Vec<Todo>, conn: &Connection) -> Result<(), Box<dyn Error>> {
-  for target in targets {
-    let flipped = if target.incomplete { false } else { true };
-    conn.execute(
-      "UPDATE todos SET incomplete = ?1 where id is ?2",
-      (flipped, target.id),
-    )?;
-    println!("Toggled: {}", target.body);
-  }
+  println!("Removed all completed todo items!");
   Ok(())
 }
 
-fn list(incomplete: bool, conn: Connection) -> Result<(), Box<dyn Error>> {
-  if let Ok(todos) = if incomplete {
-    collect_todos_incomplete(&conn)
-  } else {
-    collect_todos_all(&conn)
-  } {
-    for (number, todo) in todos.iter().enumerate() {
-      if todo.incomplete {
-        println!("{}. {}", number + 1, todo.body,);
-      } else {
-        let output = format!("{}. {}", number + 1, todo.body);
-        println!("{}", style(output).strikethrough());
-      }
-    }
+/// Set or clear what a todo is waiting on
+#[cfg(feature = "cli")]
+fn wait(
+  target: Todo,
+  on: Option<String>,
+  follow_up: Option<NaiveDate>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let status = if on.is_some() {
+    Status::Waiting
   } else {
-    println!("Something went wrong with collecting!");
+    Status::Todo
+  };
+  conn.execute(
+    "UPDATE todos SET status = ?1, waiting_on = ?2, follow_up = ?3 where id is ?4",
+    (status, &on, follow_up, target.id),
+  )?;
+  match on {
+    Some(on) => println!("Waiting on {}: {}", on, target.body),
+    None => println!("No longer waiting: {}", target.body),
   }
   Ok(())
 }
 
-fn clean(conn: Connection) -> Result<(), Box<dyn Error>> {
-  conn.execute("DELETE FROM todos WHERE incomplete is false", ())?;
-  println!("Removed all completed todo items!");
+#[cfg(feature = "cli")]
+fn waiting(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let today = Local::now().date_naive();
+  for todo in collect_todos_waiting(conn)? {
+    let on = todo.waiting_on.unwrap_or_default();
+    let follow_up = match todo.follow_up {
+      Some(date) if date <= today => style(format!(" (follow up {})", date)).red().bold(),
+      Some(date) => style(format!(" (follow up {})", date)).dim(),
+      None => style(String::new()),
+    };
+    let line = format!("{} → {}{}", style(on).cyan(), todo.body, follow_up);
+    println!("{}", ascii::text(&line));
+  }
   Ok(())
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "cli"))]
 mod tests {
   use super::*;
 
@@ -276,7 +2561,7 @@ mod tests {
 
     // Populate
     _ = conn.execute(
-      "INSERT INTO todos (body, incomplete) VALUES (?1, true)",
+      "INSERT INTO todos (body) VALUES (?1)",
       ("Milk".to_string(),),
     );
 
@@ -288,7 +2573,8 @@ mod tests {
       vec![Todo {
         id: 1,
         body: "Milk".to_string(),
-        incomplete: true
+        status: Status::Todo,
+        ..Default::default()
       }],
       todos
     );
@@ -298,11 +2584,11 @@ mod tests {
     let conn = Connection::open_in_memory().unwrap();
     _ = create_db(&conn);
     _ = conn.execute(
-      "INSERT INTO todos (body, incomplete) VALUES (?1, true)",
+      "INSERT INTO todos (body) VALUES (?1)",
       ("Milk".to_string(),),
     );
     _ = conn.execute(
-      "INSERT INTO todos (body, incomplete) VALUES (?1, true)",
+      "INSERT INTO todos (body) VALUES (?1)",
       ("Carl".to_string(),),
     );
     let todos = collect_todos_all(&conn).unwrap();
@@ -311,12 +2597,14 @@ mod tests {
         Todo {
           id: 1,
           body: "Milk".to_string(),
-          incomplete: true
+          status: Status::Todo,
+          ..Default::default()
         },
         Todo {
           id: 2,
           body: "Carl".to_string(),
-          incomplete: true
+          status: Status::Todo,
+          ..Default::default()
         }
       ],
       todos
@@ -329,7 +2617,7 @@ mod tests {
     _ = create_db(&conn);
 
     // Test function
-    _ = add(vec!["Milk".to_string()], &conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
 
     // Collect
     let todos = collect_todos_all(&conn).unwrap();
@@ -339,7 +2627,8 @@ mod tests {
       vec![Todo {
         id: 1,
         body: "Milk".to_string(),
-        incomplete: true
+        status: Status::Todo,
+        ..Default::default()
       }],
       todos
     );
@@ -348,7 +2637,11 @@ mod tests {
   fn add_multi() {
     let conn = Connection::open_in_memory().unwrap();
     _ = create_db(&conn);
-    _ = add(vec!["Milk".to_string(), "Carl".to_string()], &conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
 
     let todos = collect_todos_all(&conn).unwrap();
     assert_eq!(
@@ -356,12 +2649,14 @@ mod tests {
         Todo {
           id: 1,
           body: "Milk".to_string(),
-          incomplete: true
+          status: Status::Todo,
+          ..Default::default()
         },
         Todo {
           id: 2,
           body: "Carl".to_string(),
-          incomplete: true
+          status: Status::Todo,
+          ..Default::default()
         }
       ],
       todos
@@ -374,14 +2669,19 @@ mod tests {
     _ = create_db(&conn);
 
     // Populate
-    _ = add(vec!["Milk".to_string(), "Carl".to_string()], &conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
 
     // Test function
     _ = rm(
       vec![Todo {
         id: 1,
         body: "Milk".to_string(),
-        incomplete: true,
+        status: Status::Todo,
+        ..Default::default()
       }],
       &conn,
     );
@@ -392,7 +2692,8 @@ mod tests {
       vec![Todo {
         id: 2,
         body: "Carl".to_string(),
-        incomplete: true
+        status: Status::Todo,
+        ..Default::default()
       }],
       todos
     );
@@ -403,6 +2704,7 @@ mod tests {
     _ = create_db(&conn);
     _ = add(
       vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
       &conn,
     );
 
@@ -411,12 +2713,14 @@ mod tests {
         Todo {
           id: 1,
           body: "Milk".to_string(),
-          incomplete: true,
+          status: Status::Todo,
+          ..Default::default()
         },
         Todo {
           id: 3,
           body: "Katia".to_string(),
-          incomplete: true,
+          status: Status::Todo,
+          ..Default::default()
         },
       ],
       &conn,
@@ -426,26 +2730,47 @@ mod tests {
       vec![Todo {
         id: 2,
         body: "Carl".to_string(),
-        incomplete: true
+        status: Status::Todo,
+        ..Default::default()
       }],
       todos
     );
   }
   #[test]
+  fn rm_keeps_the_same_body() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Milk".to_string(), "Bread".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let milk = collect_todos_all(&conn).unwrap().remove(0);
+    rm(vec![milk], &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(vec!["Milk", "Bread"], bodies(&todos));
+    assert_eq!(2, todos[0].id);
+  }
+  #[test]
   fn edit_test() {
     // Prepare db connection
     let conn = Connection::open_in_memory().unwrap();
     _ = create_db(&conn);
 
     // Populate
-    _ = add(vec!["Milk".to_string(), "Carl".to_string()], &conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
 
     // Test function
     _ = edit(
       Todo {
         id: 1,
         body: "Milk".to_string(),
-        incomplete: true,
+        status: Status::Todo,
+        ..Default::default()
       },
       "Baptise".to_string(),
       &conn,
@@ -458,12 +2783,14 @@ mod tests {
         Todo {
           id: 1,
           body: "Baptise".to_string(),
-          incomplete: true,
+          status: Status::Todo,
+          ..Default::default()
         },
         Todo {
           id: 2,
           body: "Carl".to_string(),
-          incomplete: true
+          status: Status::Todo,
+          ..Default::default()
         }
       ],
       todos
@@ -476,14 +2803,19 @@ mod tests {
     _ = create_db(&conn);
 
     // Populate
-    _ = add(vec!["Milk".to_string(), "Carl".to_string()], &conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
 
     // Test function
     _ = toggle(
       vec![Todo {
         id: 1,
         body: "Milk".to_string(),
-        incomplete: true,
+        status: Status::Todo,
+        ..Default::default()
       }],
       &conn,
     );
@@ -495,12 +2827,14 @@ mod tests {
         Todo {
           id: 1,
           body: "Milk".to_string(),
-          incomplete: false,
+          status: Status::Done,
+          ..Default::default()
         },
         Todo {
           id: 2,
           body: "Carl".to_string(),
-          incomplete: true
+          status: Status::Todo,
+          ..Default::default()
         }
       ],
       todos
@@ -510,19 +2844,25 @@ mod tests {
   fn toggle_multi() {
     let conn = Connection::open_in_memory().unwrap();
     _ = create_db(&conn);
-    _ = add(vec!["Milk".to_string(), "Carl".to_string()], &conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
 
     _ = toggle(
       vec![
         Todo {
           id: 1,
           body: "Milk".to_string(),
-          incomplete: true,
+          status: Status::Todo,
+          ..Default::default()
         },
         Todo {
           id: 2,
           body: "Katia".to_string(),
-          incomplete: true,
+          status: Status::Todo,
+          ..Default::default()
         },
       ],
       &conn,
@@ -533,15 +2873,457 @@ mod tests {
         Todo {
           id: 1,
           body: "Milk".to_string(),
-          incomplete: false,
+          status: Status::Done,
+          ..Default::default()
         },
         Todo {
           id: 2,
           body: "Carl".to_string(),
-          incomplete: false
+          status: Status::Done,
+          ..Default::default()
         }
       ],
       todos
     );
   }
+  #[test]
+  fn wait_and_collect_waiting() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    let follow_up = NaiveDate::from_ymd_opt(2024, 3, 1);
+
+    _ = wait(todos[2].clone(), Some("Bob".to_string()), None, &conn);
+    _ = wait(
+      todos[0].clone(),
+      Some("Alice".to_string()),
+      follow_up,
+      &conn,
+    );
+
+    let waiting = collect_todos_waiting(&conn).unwrap();
+    assert_eq!(
+      vec![
+        Todo {
+          id: 1,
+          body: "Milk".to_string(),
+          status: Status::Waiting,
+          waiting_on: Some("Alice".to_string()),
+          follow_up,
+          ..Default::default()
+        },
+        Todo {
+          id: 3,
+          body: "Katia".to_string(),
+          status: Status::Waiting,
+          waiting_on: Some("Bob".to_string()),
+          ..Default::default()
+        }
+      ],
+      waiting
+    );
+  }
+  #[test]
+  fn wait_clear() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    let todo = collect_todos_all(&conn).unwrap().remove(0);
+
+    _ = wait(todo.clone(), Some("Alice".to_string()), None, &conn);
+    _ = wait(todo, None, None, &conn);
+
+    assert!(collect_todos_waiting(&conn).unwrap().is_empty());
+  }
+  #[test]
+  fn set_status_and_collect_incomplete() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+
+    _ = set_status(vec![todos[0].clone()], Status::InProgress, &conn);
+    _ = set_status(vec![todos[1].clone()], Status::Cancelled, &conn);
+
+    let incomplete = collect_todos_incomplete(&conn).unwrap();
+    assert_eq!(
+      vec![
+        Todo {
+          id: 1,
+          body: "Milk".to_string(),
+          status: Status::InProgress,
+          ..Default::default()
+        },
+        Todo {
+          id: 3,
+          body: "Katia".to_string(),
+          status: Status::Todo,
+          ..Default::default()
+        }
+      ],
+      incomplete
+    );
+    assert_eq!(
+      vec![Todo {
+        id: 2,
+        body: "Carl".to_string(),
+        status: Status::Cancelled,
+        ..Default::default()
+      }],
+      collect_todos_listed(
+        &ListOptions {
+          status: Some(Status::Cancelled),
+          ..Default::default()
+        },
+        &conn
+      )
+      .unwrap()
+    );
+  }
+  #[test]
+  fn toggle_reopens_cancelled() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    let todo = collect_todos_all(&conn).unwrap().remove(0);
+    _ = set_status(vec![todo.clone()], Status::Cancelled, &conn);
+
+    _ = toggle(collect_todos_all(&conn).unwrap(), &conn);
+
+    assert_eq!(Status::Todo, collect_todos_all(&conn).unwrap()[0].status);
+  }
+  #[test]
+  fn add_with_estimate() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let options = AddOptions {
+      estimate: Some(90),
+      ..Default::default()
+    };
+    _ = add(vec!["Milk".to_string()], &options, &conn);
+    _ = set_estimate(collect_todos_all(&conn).unwrap(), 120, &conn);
+    _ = add(vec!["Carl".to_string()], &options, &conn);
+
+    let estimates = collect_todos_all(&conn)
+      .unwrap()
+      .iter()
+      .map(|todo| todo.estimate)
+      .collect::<Vec<Option<u32>>>();
+    assert_eq!(vec![Some(120), Some(90)], estimates);
+  }
+  #[test]
+  fn add_with_priority_and_due() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let due = NaiveDate::from_ymd_opt(2024, 3, 1);
+    let options = AddOptions {
+      priority: Some(Priority::High),
+      due,
+      ..Default::default()
+    };
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &options,
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_priority(vec![todos[1].clone()], None, &conn);
+    _ = set_due(vec![todos[1].clone()], None, &conn);
+
+    assert_eq!(
+      vec![
+        Todo {
+          id: 1,
+          body: "Milk".to_string(),
+          priority: Some(Priority::High),
+          due,
+          ..Default::default()
+        },
+        Todo {
+          id: 2,
+          body: "Carl".to_string(),
+          ..Default::default()
+        }
+      ],
+      collect_todos_all(&conn).unwrap()
+    );
+  }
+  #[test]
+  fn quick_add_takes_trailing_dates_and_priorities() {
+    let words = |text: &str| {
+      Args::try_parse_from(format!("todo {}", text).split(' '))
+        .map(|args| match args.command {
+          Some(Commands::Quick(words)) => words,
+          _ => vec![],
+        })
+        .unwrap()
+    };
+    let (body, options) = quick(&words("buy milk 2024-03-01 !!"));
+    assert_eq!("buy milk", body);
+    assert_eq!(NaiveDate::from_ymd_opt(2024, 3, 1), options.due);
+    assert_eq!(Some(Priority::Medium), options.priority);
+
+    // Only the end counts, and the first word is kept
+    let (body, options) = quick(&words("call 2024-03-01 about it"));
+    assert_eq!("call 2024-03-01 about it", body);
+    assert_eq!(None, options.due);
+    assert_eq!("today", quick(&words("today")).0);
+    assert_eq!("pay !", quick(&words("pay ! today !!!")).0);
+    assert!(matches!(
+      Args::try_parse_from(["todo", "list"]).unwrap().command,
+      Some(Commands::List { .. })
+    ));
+  }
+
+  #[test]
+  fn setters_take_targets_without_input() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let prioritize = |text: &str| {
+      let args = Args::try_parse_from(text.split(' ')).unwrap();
+      assert!(args.no_input);
+      let Some(Commands::Priority {
+        priority,
+        targets,
+        clear,
+      }) = args.command
+      else {
+        panic!("not a priority command");
+      };
+      let targets = find_or_pick(&clear.unwrap_or(targets), "", &conn).unwrap();
+      set_priority(targets, priority, &conn).unwrap();
+      collect_todos_all(&conn)
+        .unwrap()
+        .iter()
+        .map(|todo| todo.priority)
+        .collect::<Vec<_>>()
+    };
+    let high = Some(Priority::High);
+    assert_eq!(
+      vec![high, None, high],
+      prioritize("todo --no-input priority high 1,3")
+    );
+    assert_eq!(
+      vec![None, None, high],
+      prioritize("todo --no-input priority --clear milk")
+    );
+    assert!(Args::try_parse_from(["todo", "priority", "high", "--clear", "1"]).is_err());
+  }
+
+  #[test]
+  fn history_commands_are_known() {
+    let needs = |text: &str| needs_history(&Args::try_parse_from(text.split(' ')).unwrap().command);
+    assert!(needs("todo undo --list"));
+    assert!(needs("todo history 1"));
+    assert!(needs("todo search --history"));
+    assert!(!needs("todo search milk"));
+    assert!(!needs("todo list"));
+  }
+
+  #[test]
+  fn notes_and_checklist() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let options = AddOptions {
+      notes: Some("- [ ] milk\n- [ ] eggs".to_string()),
+      ..Default::default()
+    };
+    _ = add(vec!["Groceries".to_string()], &options, &conn);
+    let todo = collect_todos_all(&conn).unwrap().remove(0);
+
+    _ = checklist::check(todo, Some(2), &conn);
+    let todo = collect_todos_all(&conn).unwrap().remove(0);
+    assert_eq!(Some("- [ ] milk\n- [x] eggs"), todo.notes.as_deref());
+    assert!(annotations(&todo, Local::now().date_naive()).contains("(1/2)"));
+
+    _ = set_notes(todo, " \n".to_string(), &conn);
+    assert_eq!(None, collect_todos_all(&conn).unwrap()[0].notes);
+  }
+  #[test]
+  fn collect_todos_completed_matches_clean() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_status(vec![todos[0].clone()], Status::Done, &conn);
+
+    assert_eq!(
+      vec!["Milk".to_string()],
+      bodies(&collect_todos_completed(&conn).unwrap())
+    );
+  }
+
+  #[test]
+  fn find_targets_expands_id_ranges() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let found = find_targets(&["3,1-2".to_string()], &conn).unwrap();
+    assert_eq!(vec!["Katia", "Milk", "Carl"], bodies(&found));
+    assert!(find_targets(&["1-4".to_string()], &conn).is_err());
+    assert_eq!(1, find_targets(&["carl".to_string()], &conn).unwrap().len());
+    conn
+      .execute(
+        "UPDATE todos SET reference = 'SHOP-12' WHERE body = 'Katia'",
+        (),
+      )
+      .unwrap();
+    assert_eq!(
+      vec!["Katia"],
+      bodies(&find_targets(&["shop-12".to_string()], &conn).unwrap())
+    );
+
+    // Short ids work too, text is still looked for when no todo has it as its short id
+    let found = find_targets(&["c,A".to_string()], &conn).unwrap();
+    assert_eq!(vec!["Katia", "Milk"], bodies(&found));
+    let found = find_targets(&["ka".to_string()], &conn).unwrap();
+    assert_eq!(vec!["Katia"], bodies(&found));
+    assert!(find_targets(&["d".to_string()], &conn).is_err());
+
+    // Once the word is a short id too the text still wins
+    conn
+      .execute_batch("INSERT INTO todos (id, body) VALUES (2776, 'Bed'), (2777, 'Make the bed');")
+      .unwrap();
+    assert_eq!("bed", ids::short(2776));
+    let found = find_targets(&["bed".to_string()], &conn).unwrap();
+    assert_eq!(vec!["Bed", "Make the bed"], bodies(&found));
+    let found = find_targets(&["bee".to_string()], &conn).unwrap();
+    assert_eq!(vec!["Make the bed"], bodies(&found));
+  }
+
+  #[test]
+  fn pickers_and_lists_load_pages() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let mut todos = vec!["Émile".to_string()];
+    todos.extend((1..=picker::PAGE).map(|n| format!("Todo {}", n)));
+    _ = add(todos, &AddOptions::default(), &conn);
+
+    let (first, more) = collect_todos_page("true", 0, &conn).unwrap();
+    assert_eq!((picker::PAGE, true), (first.len(), more));
+    let (second, more) = collect_todos_page("true", 1, &conn).unwrap();
+    assert_eq!(vec![format!("Todo {}", picker::PAGE)], bodies(&second));
+    assert!(!more);
+
+    let found = find_targets(&["ÉMILE".to_string()], &conn).unwrap();
+    assert_eq!(vec!["Émile"], bodies(&found));
+    let options = ListOptions {
+      limit: Some(2),
+      ..Default::default()
+    };
+    assert_eq!(2, collect_todos_listed(&options, &conn).unwrap().len());
+  }
+
+  #[test]
+  fn scheduled_todos_hidden_until_the_date() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let today = Local::now().date_naive();
+    let later = AddOptions {
+      scheduled: Some(today + chrono::Days::new(3)),
+      ..Default::default()
+    };
+    _ = add(vec!["Renew passport".to_string()], &later, &conn);
+    let now = AddOptions {
+      scheduled: Some(today),
+      ..Default::default()
+    };
+    _ = add(vec!["Milk".to_string()], &now, &conn);
+
+    let listed = |options: &ListOptions| bodies(&collect_todos_listed(options, &conn).unwrap());
+    assert_eq!(vec!["Milk"], listed(&ListOptions::default()));
+    let all = ListOptions {
+      all: true,
+      ..Default::default()
+    };
+    assert_eq!(vec!["Renew passport", "Milk"], listed(&all));
+    assert_eq!(
+      vec!["Milk"],
+      bodies(&collect_todos_page("true", 0, &conn).unwrap().0)
+    );
+    // Ids given explicitly still find the hidden todos
+    assert_eq!(
+      vec!["Renew passport"],
+      bodies(&find_targets(&["1".to_string()], &conn).unwrap())
+    );
+
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_scheduled(vec![todos[0].clone()], None, &conn);
+    assert_eq!(2, collect_todos_page("true", 0, &conn).unwrap().0.len());
+  }
+
+  #[test]
+  fn pinned_todos_come_first() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = pin(vec![todos[2].clone()], true, &conn);
+    let top = AddOptions {
+      top: true,
+      ..Default::default()
+    };
+    _ = add(vec!["Eggs".to_string()], &top, &conn);
+    assert_eq!(
+      vec!["Katia", "Eggs", "Milk", "Carl"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+
+    _ = pin(vec![todos[2].clone()], false, &conn);
+    assert_eq!(
+      vec!["Eggs", "Milk", "Carl", "Katia"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+  }
+
+  #[test]
+  fn details_show_set_fields_and_notes() {
+    let todo = Todo {
+      id: 3,
+      body: "Fix the **sink**".to_string(),
+      priority: Some(Priority::High),
+      tags: Tags(vec!["home".to_string()]),
+      notes: Some("- [ ] washer\n- [x] wrench".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(
+      vec![
+        "c. Fix the sink",
+        "",
+        "Status     todo",
+        "Priority   high",
+        "Tags       #home",
+        "",
+        "☐ washer",
+        "☑ wrench",
+      ],
+      details(&todo)
+    );
+  }
 }
@@ -0,0 +1,128 @@
+use crate::Todo;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Tie a todo to something outside, `reference` is unique per source
+pub fn link(
+  todo_id: usize,
+  source: &str,
+  reference: &str,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  conn.execute(
+    "INSERT OR REPLACE INTO links (todo_id, source, reference) VALUES (?1, ?2, ?3)",
+    (todo_id, source, reference),
+  )?;
+  Ok(())
+}
+
+/// The todo whose ref is `reference`, integrations link it rather than import it again
+pub fn referenced(reference: &str, conn: &Connection) -> Result<Option<usize>, Box<dyn Error>> {
+  let mut stmt = conn.prepare_cached("SELECT id FROM todos WHERE reference = ?1 ORDER BY id")?;
+  let mut ids = stmt.query_map((reference,), |row| row.get(0))?;
+  Ok(ids.next().transpose()?)
+}
+
+/// Give a todo linked by hand its ref, unless it has one already
+pub fn set_reference(
+  todo_id: usize,
+  reference: &str,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  conn.execute(
+    "UPDATE todos SET reference = ?1 WHERE id = ?2 and reference IS NULL",
+    (reference, todo_id),
+  )?;
+  Ok(())
+}
+
+pub fn is_linked(source: &str, reference: &str, conn: &Connection) -> Result<bool, Box<dyn Error>> {
+  Ok(conn.query_row(
+    "SELECT EXISTS(SELECT 1 FROM links WHERE source = ?1 AND reference = ?2)",
+    (source, reference),
+    |row| row.get(0),
+  )?)
+}
+
+/// Todos and their reference that are still open in the source
+pub fn collect_linked(
+  source: &str,
+  conn: &Connection,
+) -> Result<Vec<(Todo, String)>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT todos.*, links.reference AS linked FROM todos JOIN links ON links.todo_id = todos.id
+     WHERE links.source = ?1 AND NOT links.closed ORDER BY todos.id",
+  )?;
+  let linked = stmt
+    .query_map((source,), |row| {
+      Ok((Todo::from_row(row)?, row.get("linked")?))
+    })?
+    .collect::<Result<_, _>>()?;
+  Ok(linked)
+}
+
+/// Done todos and their reference that the source has not been closed in yet
+pub fn collect_unclosed(
+  source: &str,
+  conn: &Connection,
+) -> Result<Vec<(Todo, String)>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT todos.*, links.reference AS linked FROM todos JOIN links ON links.todo_id = todos.id
+     WHERE links.source = ?1 AND NOT links.closed AND todos.status = 'done'
+     ORDER BY todos.id",
+  )?;
+  let unclosed = stmt
+    .query_map((source,), |row| {
+      Ok((Todo::from_row(row)?, row.get("linked")?))
+    })?
+    .filter_map(|s| s.ok())
+    .collect();
+  Ok(unclosed)
+}
+
+pub fn mark_closed(source: &str, reference: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  conn.execute(
+    "UPDATE links SET closed = true WHERE source = ?1 AND reference = ?2",
+    (source, reference),
+  )?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, add, collect_todos_all, set_status};
+
+  #[test]
+  fn unclosed_links() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    link(todos[0].id, "jira", "SHOP-1", &conn).unwrap();
+    link(todos[1].id, "jira", "SHOP-2", &conn).unwrap();
+    assert!(is_linked("jira", "SHOP-1", &conn).unwrap());
+    assert!(!is_linked("gitlab", "SHOP-1", &conn).unwrap());
+    assert!(collect_unclosed("jira", &conn).unwrap().is_empty());
+
+    _ = set_status(todos.clone(), Status::Done, &conn);
+    mark_closed("jira", "SHOP-2", &conn).unwrap();
+    let unclosed = collect_unclosed("jira", &conn).unwrap();
+    assert_eq!(
+      vec!["SHOP-1"],
+      unclosed.iter().map(|(_, key)| key).collect::<Vec<_>>()
+    );
+
+    // Refs tie todos to issues before they are linked
+    assert_eq!(None, referenced("SHOP-1", &conn).unwrap());
+    set_reference(todos[0].id, "SHOP-1", &conn).unwrap();
+    set_reference(todos[0].id, "SHOP-9", &conn).unwrap();
+    assert_eq!(Some(todos[0].id), referenced("SHOP-1", &conn).unwrap());
+  }
+}
@@ -0,0 +1,248 @@
+use crate::config::LockFile;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{env, process, thread};
+
+/// How long to wait for another command to finish before giving up
+const WAIT: Duration = Duration::from_secs(10);
+
+/// Locks of other machines older than this are left over from a crash
+const STALE: Duration = Duration::from_secs(60 * 60);
+
+/// Filesystems where SQLite's own locking can't be trusted
+const NETWORK: [&str; 8] = [
+  "nfs",
+  "nfs4",
+  "cifs",
+  "smb3",
+  "smbfs",
+  "afs",
+  "9p",
+  "fuse.sshfs",
+];
+
+/// A `<file>.lock` next to the todos, for as long as a command runs. Machines sharing the
+/// file over the network see each other's locks, SQLite can't tell them apart there.
+#[derive(Debug)]
+pub struct Lock {
+  path: PathBuf,
+  holder: String,
+}
+
+/// Who holds a lock, written into the lockfile as `host pid since`
+#[derive(Debug, PartialEq)]
+struct Holder {
+  host: String,
+  pid: u32,
+  since: u64,
+}
+
+impl Holder {
+  fn parse(text: &str) -> Option<Holder> {
+    let mut words = text.split_whitespace();
+    Some(Holder {
+      host: words.next()?.to_string(),
+      pid: words.next()?.parse().ok()?,
+      since: words.next()?.parse().ok()?,
+    })
+  }
+
+  fn render(&self) -> String {
+    format!("{} {} {}\n", self.host, self.pid, self.since)
+  }
+
+  /// Its process is gone, or it is too old to still be running
+  fn is_stale(&self, now: u64, host: &str) -> bool {
+    if self.host == host && !is_running(self.pid) {
+      return true;
+    }
+    now.saturating_sub(self.since) > STALE.as_secs()
+  }
+}
+
+fn hostname() -> String {
+  fs::read_to_string("/proc/sys/kernel/hostname")
+    .ok()
+    .or_else(|| env::var("HOSTNAME").ok())
+    .or_else(|| env::var("COMPUTERNAME").ok())
+    .map(|host| host.trim().to_string())
+    .filter(|host| !host.is_empty() && !host.contains(char::is_whitespace))
+    .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map_or(0, |since| since.as_secs())
+}
+
+/// Only Linux can tell, elsewhere processes count as running until the lock is stale
+fn is_running(pid: u32) -> bool {
+  match cfg!(target_os = "linux") {
+    true => Path::new(&format!("/proc/{}", pid)).exists(),
+    false => true,
+  }
+}
+
+/// Whether `file` is on a network share, only known on Linux
+fn is_on_network(file: &Path) -> bool {
+  let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+    return false;
+  };
+  let directory = file
+    .parent()
+    .filter(|parent| !parent.as_os_str().is_empty());
+  let Ok(directory) = fs::canonicalize(directory.unwrap_or(Path::new("."))) else {
+    return false;
+  };
+  mounts
+    .lines()
+    .filter_map(|line| {
+      let mut fields = line.split_whitespace();
+      let point = fields.nth(1)?.replace("\\040", " ");
+      Some((point, fields.next()?.to_string()))
+    })
+    .filter(|(point, _)| directory.starts_with(point))
+    .max_by_key(|(point, _)| point.len())
+    .is_some_and(|(_, filesystem)| NETWORK.contains(&filesystem.as_str()))
+}
+
+/// Remove the lock at `path` only if it still is the `stale` one read before, another
+/// command may have taken it over since. Moving it aside first keeps two commands from both
+/// removing it.
+fn remove_stale(path: &Path, stale: &str, host: &str) -> bool {
+  let mut aside = path.as_os_str().to_owned();
+  aside.push(format!(".{}.{}", host, process::id()));
+  let aside = PathBuf::from(aside);
+  if fs::rename(path, &aside).is_err() {
+    return false;
+  }
+  let removed = fs::read_to_string(&aside).is_ok_and(|text| text == stale);
+  if !removed {
+    // Put the lock taken over back, unless yet another one is there already
+    _ = fs::hard_link(&aside, path);
+  }
+  _ = fs::remove_file(&aside);
+  removed
+}
+
+impl Lock {
+  /// Lock `file` when the setting asks for it, waiting a while for commands of other
+  /// machines to finish
+  pub fn acquire(setting: LockFile, file: Option<&Path>) -> Result<Option<Lock>, Box<dyn Error>> {
+    let Some(file) = file else {
+      return Ok(None);
+    };
+    let wanted = match setting {
+      LockFile::Auto => is_on_network(file),
+      LockFile::Always => true,
+      LockFile::Never => false,
+    };
+    if !wanted {
+      return Ok(None);
+    }
+    let mut path = file.as_os_str().to_owned();
+    path.push(".lock");
+    Lock::at(PathBuf::from(path), WAIT).map(Some)
+  }
+
+  fn at(path: PathBuf, wait: Duration) -> Result<Lock, Box<dyn Error>> {
+    let host = hostname();
+    let started = SystemTime::now();
+    loop {
+      let holder = Holder {
+        host: host.clone(),
+        pid: process::id(),
+        since: now(),
+      }
+      .render();
+      // Creating the file fails when it exists, also over NFS since version 3
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(mut file) => {
+          file.write_all(holder.as_bytes())?;
+          file.sync_all()?;
+          return Ok(Lock { path, holder });
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(format!("Can't create {}: {}", path.display(), e).into()),
+      }
+
+      // Unreadable while another machine is still writing it
+      let text = fs::read_to_string(&path).unwrap_or_default();
+      let other = Holder::parse(&text);
+      if let Some(other) = &other
+        && other.is_stale(now(), &host)
+      {
+        if remove_stale(&path, &text, &host) {
+          log::warn!(
+            "Removed the stale lock of pid {} on {}",
+            other.pid,
+            other.host
+          );
+        }
+        continue;
+      }
+      if started.elapsed().unwrap_or_default() >= wait {
+        let by = other.map_or(String::new(), |other| {
+          format!(" by pid {} on {}", other.pid, other.host)
+        });
+        return Err(
+          format!(
+            "The todos are locked{}, remove {} if no todo command is running",
+            by,
+            path.display()
+          )
+          .into(),
+        );
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+  }
+}
+
+impl Drop for Lock {
+  fn drop(&mut self) {
+    // Only if it is still ours, another machine may have taken it over as stale
+    if fs::read_to_string(&self.path).is_ok_and(|text| text == self.holder) {
+      _ = fs::remove_file(&self.path);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn locks_exclude_each_other_until_stale() {
+    let path = env::temp_dir().join(format!("todo-lock-{}.db.lock", process::id()));
+    let lock = Lock::at(path.clone(), Duration::ZERO).unwrap();
+    let holder = Holder::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(process::id(), holder.pid);
+    assert!(Lock::at(path.clone(), Duration::ZERO).is_err());
+    drop(lock);
+    assert!(!path.exists());
+
+    // Another machine's process can't be checked, its lock goes stale with age
+    let old = Holder {
+      host: "elsewhere".to_string(),
+      pid: 1,
+      since: now() - STALE.as_secs() - 1,
+    };
+    assert!(old.is_stale(now(), "here"));
+    assert!(!old.is_stale(old.since, "here"));
+    fs::write(&path, old.render()).unwrap();
+    drop(Lock::at(path.clone(), Duration::ZERO).unwrap());
+    assert!(!path.exists());
+
+    // A lock taken over since it was found stale stays
+    let lock = Lock::at(path.clone(), Duration::ZERO).unwrap();
+    assert!(!remove_stale(&path, &old.render(), "here"));
+    assert_eq!(lock.holder, fs::read_to_string(&path).unwrap());
+    drop(lock);
+    assert!(!path.exists());
+  }
+}
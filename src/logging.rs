@@ -0,0 +1,121 @@
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rusqlite::Connection;
+use rusqlite::hooks::Action;
+use rusqlite::trace::{TraceEvent, TraceEventCodes};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{Write, stderr};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Rows changed by the statement that is currently running
+static CHANGED: AtomicUsize = AtomicUsize::new(0);
+
+struct Logger {
+  level: LevelFilter,
+  /// Log file from the config, stderr when there is none
+  file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.level
+  }
+
+  fn log(&self, record: &Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+    let line = format_line(record.level(), record.target(), &record.args().to_string());
+    match &self.file {
+      Some(file) => {
+        if let Ok(mut file) = file.lock() {
+          _ = writeln!(
+            file,
+            "{} {}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S"),
+            line
+          );
+        }
+      }
+      None => _ = writeln!(stderr(), "{}", line),
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+fn format_line(level: Level, target: &str, message: &str) -> String {
+  format!("{:<5} {}: {}", level, target, message)
+}
+
+/// Warnings only by default, `-v` adds SQL with timing and `-vv` every changed row
+fn level(verbose: u8) -> LevelFilter {
+  match verbose {
+    0 => LevelFilter::Warn,
+    1 => LevelFilter::Info,
+    2 => LevelFilter::Debug,
+    _ => LevelFilter::Trace,
+  }
+}
+
+pub fn init(verbose: u8, file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+  let file = match file {
+    Some(path) => Some(Mutex::new(
+      OpenOptions::new().create(true).append(true).open(path)?,
+    )),
+    None => None,
+  };
+  let level = level(verbose);
+  log::set_boxed_logger(Box::new(Logger { level, file }))?;
+  log::set_max_level(level);
+  Ok(())
+}
+
+/// Log every statement run on `conn` when verbose
+pub fn trace(conn: &Connection) {
+  if !log::log_enabled!(target: "sql", Level::Info) {
+    return;
+  }
+  conn.update_hook(Some(|action: Action, _: &str, table: &str, row: i64| {
+    CHANGED.fetch_add(1, Ordering::Relaxed);
+    log::debug!(target: "sql", "{:?} {} row {}", action, table, row);
+  }));
+  conn.trace_v2(
+    TraceEventCodes::SQLITE_TRACE_PROFILE,
+    Some(|event| {
+      if let TraceEvent::Profile(stmt, took) = event {
+        let changed = CHANGED.swap(0, Ordering::Relaxed);
+        log::info!(
+          target: "sql",
+          "{} ({:.2?}, {} rows changed)",
+          stmt.sql().split_whitespace().collect::<Vec<&str>>().join(" "),
+          took,
+          changed
+        );
+      }
+    }),
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn verbosity_levels() {
+    assert_eq!(LevelFilter::Warn, level(0));
+    assert_eq!(LevelFilter::Info, level(1));
+    assert_eq!(LevelFilter::Trace, level(5));
+  }
+
+  #[test]
+  fn format_line_pads_level() {
+    assert_eq!(
+      "INFO  sql: SELECT 1",
+      format_line(Level::Info, "sql", "SELECT 1")
+    );
+  }
+}
@@ -1,6 +1,6 @@
 use clap::Parser;
 use std::error::Error;
-use todo::{run, Args};
+use todo::{Args, run};
 
 fn main() -> Result<(), Box<dyn Error>> {
   let args = Args::parse();
@@ -0,0 +1,389 @@
+use crate::ids::short;
+use crate::snapshot;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Changes of a todo's field on one local day, older than the cutoff `?1`
+const ROLLUP: &str = "CREATE TEMP TABLE rollup AS
+  SELECT todo_id, field, min(id) AS first, max(id) AS last FROM history
+  WHERE changed_at < datetime('now', ?1) and field not in ('created', 'deleted')
+  GROUP BY todo_id, field, date(changed_at, 'localtime') HAVING count(*) > 1";
+
+/// Roll up history older than `days` to one change per field and day, going from the first
+/// old value to the last new one. Operations that old can't be undone anymore. Returns how
+/// many history entries went away.
+fn compact(days: i64, conn: &Connection) -> Result<usize, Box<dyn Error>> {
+  let cutoff = format!("-{} days", days);
+  conn.execute(ROLLUP, (&cutoff,))?;
+  conn.execute(
+    "UPDATE history SET old = (
+       SELECT first.old FROM rollup JOIN history AS first ON first.id = rollup.first
+       WHERE rollup.last = history.id)
+     WHERE id IN (SELECT last FROM rollup)",
+    (),
+  )?;
+  let mut entries = conn.execute(
+    "DELETE FROM history WHERE EXISTS (
+       SELECT 1 FROM rollup WHERE rollup.todo_id = history.todo_id
+         and rollup.field = history.field and history.id >= rollup.first
+         and history.id < rollup.last)",
+    (),
+  )?;
+  // Changed back and forth on the same day
+  entries += conn.execute(
+    "DELETE FROM history WHERE id IN (SELECT last FROM rollup) and old IS new",
+    (),
+  )?;
+  conn.execute("DROP TABLE rollup", ())?;
+
+  conn.execute(
+    "UPDATE operations SET undoable = false WHERE created_at < datetime('now', ?1)",
+    (&cutoff,),
+  )?;
+  Ok(entries)
+}
+
+/// Forget todos removed more than `days` ago, reports lose them too. Returns how many
+/// history entries went away.
+fn prune(days: i64, conn: &Connection) -> Result<usize, Box<dyn Error>> {
+  Ok(conn.execute(
+    "DELETE FROM history WHERE todo_id NOT IN (SELECT id FROM todos) and todo_id IN (
+       SELECT todo_id FROM history GROUP BY todo_id
+       HAVING max(changed_at) < datetime('now', ?1))",
+    (format!("-{} days", days),),
+  )?)
+}
+
+/// Keep the database small after years of use, `keep_days` also prunes removed todos and
+/// snapshots past it
+pub fn maintain(
+  compact_days: i64,
+  keep_days: Option<i64>,
+  dry_run: bool,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  let mut entries = compact(compact_days, &tx)?;
+  println!(
+    "Rolled up {} history entries older than {} days",
+    entries, compact_days
+  );
+  if let Some(days) = keep_days {
+    let pruned = prune(days, &tx)?;
+    println!(
+      "Pruned {} history entries of todos removed over {} days ago",
+      pruned, days
+    );
+    entries += pruned;
+  }
+  let operations = tx.execute(
+    "DELETE FROM operations WHERE id NOT IN
+       (SELECT operation_id FROM history WHERE operation_id IS NOT NULL)",
+    (),
+  )?;
+  println!(
+    "Removed {} operations with nothing left to undo",
+    operations
+  );
+
+  let snapshots = match keep_days {
+    Some(days) => snapshot::prune(days, dry_run, &tx)?,
+    None => vec![],
+  };
+  for name in &snapshots {
+    match dry_run {
+      true => println!("Would remove snapshot: {}", name),
+      false => println!("Removed snapshot: {}", name),
+    }
+  }
+
+  if dry_run {
+    println!("Dry run, nothing changed!");
+    return Ok(());
+  }
+  tx.commit()?;
+  if entries + operations > 0 {
+    // Gives the space back, a file doesn't shrink otherwise
+    conn.execute("VACUUM", ())?;
+  }
+  Ok(())
+}
+
+/// Tables that point at todos by id, with the columns that do
+const REFERENCES: [(&str, &str); 6] = [
+  ("dependencies", "todo_id"),
+  ("dependencies", "depends_on"),
+  ("links", "todo_id"),
+  ("checkins", "todo_id"),
+  ("notifications", "todo_id"),
+  ("history", "todo_id"),
+];
+
+/// Give the todos the ids 1, 2, 3... in the order they were added, so the ids stay short
+/// after many were removed. Uuids stay, and dependencies, subtasks, links, check-ins and
+/// history move along. History of removed todos moves past the last id, so it isn't mixed up
+/// with the todos taking over their ids and `--include` still finds them. Nothing from
+/// before can be undone.
+pub fn renumber(dry_run: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  tx.execute(
+    "CREATE TEMP TABLE renumbered AS
+     SELECT id AS old, row_number() OVER (ORDER BY id) AS new, updated_at, body FROM todos",
+    (),
+  )?;
+  let moved = {
+    let mut stmt = tx.prepare("SELECT old, new, body FROM renumbered WHERE old != new")?;
+    stmt
+      .query_map([], |row| {
+        Ok((
+          row.get::<_, usize>(0)?,
+          row.get::<_, usize>(1)?,
+          row.get::<_, String>(2)?,
+        ))
+      })?
+      .collect::<Result<Vec<_>, _>>()?
+  };
+  if moved.is_empty() {
+    println!("The ids have no gaps, nothing to renumber!");
+    return Ok(());
+  }
+  for (old, new, body) in &moved {
+    match dry_run {
+      true => println!(
+        "Would renumber {} to {}: {}",
+        short(*old),
+        short(*new),
+        body
+      ),
+      false => println!("Renumbered {} to {}: {}", short(*old), short(*new), body),
+    }
+  }
+
+  let removed = tx.execute(
+    "INSERT INTO renumbered (old, new)
+     SELECT todo_id, (SELECT count(*) FROM todos) + row_number() OVER (ORDER BY todo_id)
+     FROM (SELECT DISTINCT todo_id FROM history WHERE todo_id NOT IN (SELECT id FROM todos))",
+    (),
+  )?;
+  if removed > 0 {
+    match dry_run {
+      true => println!(
+        "Would move the history of {} removed todos past the last id",
+        removed
+      ),
+      false => println!(
+        "Moved the history of {} removed todos past the last id",
+        removed
+      ),
+    }
+  }
+  // Moving the todos is logged by the triggers like any change, those entries go again
+  let logged: i64 = tx.query_row("SELECT coalesce(max(id), 0) FROM history", [], |row| {
+    row.get(0)
+  })?;
+  let new = |column: &str| format!("(SELECT new FROM renumbered WHERE old = {})", column);
+  for (table, column) in REFERENCES {
+    tx.execute(
+      &format!("UPDATE {table} SET {column} = {}", new(column)),
+      (),
+    )?;
+  }
+  // Parents gone without any history keep the id they had
+  tx.execute(
+    &format!(
+      "UPDATE history SET old = coalesce({}, old), new = coalesce({}, new)
+       WHERE field = 'parent_id'",
+      new("history.old"),
+      new("history.new")
+    ),
+    (),
+  )?;
+  // Negative on the way so no two todos ever share an id
+  tx.execute(
+    &format!(
+      "UPDATE todos SET id = -{}, parent_id = {}",
+      new("todos.id"),
+      new("todos.parent_id")
+    ),
+    (),
+  )?;
+  tx.execute_batch(
+    "UPDATE todos SET id = -id;
+     UPDATE todos SET updated_at = (SELECT updated_at FROM renumbered WHERE new = todos.id);
+     UPDATE operations SET undoable = false;
+     DROP TABLE renumbered;",
+  )?;
+  tx.execute("DELETE FROM history WHERE id > ?1", (logged,))?;
+  let indexed: bool = tx.query_row(
+    "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'todos_fts')",
+    [],
+    |row| row.get(0),
+  )?;
+  if indexed {
+    tx.execute("INSERT INTO todos_fts(todos_fts) VALUES ('rebuild')", ())?;
+  }
+
+  println!(
+    "Scripts, notes and shell history using the old ids now point at other todos, and \
+     nothing from before can be undone"
+  );
+  if dry_run {
+    println!("Dry run, nothing changed!");
+    return Ok(());
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{removed, undo};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    conn
+  }
+
+  fn history(conn: &Connection) -> Vec<(usize, String, Option<String>, Option<String>)> {
+    let mut stmt = conn
+      .prepare("SELECT todo_id, field, old, new FROM history ORDER BY id")
+      .unwrap();
+    stmt
+      .query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+      })
+      .unwrap()
+      .map(|s| s.unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn old_history_rolls_up_per_day() {
+    let conn = setup();
+    for sql in [
+      "INSERT INTO todos (body) VALUES ('Milk')",
+      "UPDATE todos SET body = 'Oat milk'",
+      "UPDATE todos SET body = 'Soy milk'",
+      "UPDATE todos SET status = 'done'",
+      "UPDATE todos SET status = 'todo'",
+      "INSERT INTO todos (body) VALUES ('Eggs')",
+      "INSERT INTO todos (body) VALUES ('Bread')",
+      "DELETE FROM todos WHERE body = 'Bread'",
+    ] {
+      conn.execute(sql, ()).unwrap();
+      undo::record(sql, true, &conn).unwrap();
+    }
+    conn
+      .execute_batch(
+        "UPDATE history SET changed_at = '2020-01-01 12:00:00';
+         UPDATE operations SET created_at = '2020-01-01 12:00:00';
+         UPDATE todos SET body = 'Brown eggs' WHERE body = 'Eggs';
+         UPDATE todos SET body = 'Eggs' WHERE body = 'Brown eggs';",
+      )
+      .unwrap();
+    undo::record("recent", true, &conn).unwrap();
+
+    maintain(30, None, true, &conn).unwrap();
+    assert_eq!(10, history(&conn).len());
+    maintain(30, None, false, &conn).unwrap();
+    let milk = history(&conn)
+      .into_iter()
+      .filter(|(id, ..)| *id == 1)
+      .map(|(_, field, old, new)| (field, old, new))
+      .collect::<Vec<_>>();
+    let text = |text: &str| Some(text.to_string());
+    assert_eq!(
+      vec![
+        ("created".to_string(), None, text("Milk")),
+        ("body".to_string(), text("Milk"), text("Soy milk")),
+      ],
+      milk
+    );
+    // The recent changes stay as they are
+    assert_eq!(7, history(&conn).len());
+    let operations: usize = conn
+      .query_row(
+        "SELECT count(*) FROM operations WHERE undoable",
+        [],
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(1, operations);
+
+    maintain(30, Some(365), false, &conn).unwrap();
+    assert_eq!(5, history(&conn).len());
+    assert!(history(&conn).iter().all(|(id, ..)| *id != 3));
+  }
+
+  #[test]
+  fn renumbering_closes_gaps() {
+    let conn = setup();
+    conn
+      .execute_batch(
+        "INSERT INTO todos (id, body) VALUES (1, 'Milk'), (4, 'Eggs'), (9, 'Fix sink'),
+           (12, 'Washer');
+         UPDATE todos SET parent_id = 9 WHERE id = 12;
+         INSERT INTO dependencies (todo_id, depends_on) VALUES (9, 4);
+         INSERT INTO links (todo_id, source, reference) VALUES (12, 'jira', 'HOME-1');
+         INSERT INTO todos (body) VALUES ('Bread');
+         DELETE FROM todos WHERE body = 'Bread';",
+      )
+      .unwrap();
+    undo::record("setup", true, &conn).unwrap();
+    let uuid = |id: usize| -> Option<String> {
+      conn
+        .query_row("SELECT uuid FROM todos WHERE id = ?1", (id,), |row| {
+          row.get(0)
+        })
+        .ok()
+    };
+    let washer = uuid(12);
+
+    renumber(true, &conn).unwrap();
+    assert_eq!(washer, uuid(12));
+    renumber(false, &conn).unwrap();
+    let rows = |sql: &str| -> Vec<(usize, Option<usize>)> {
+      let mut stmt = conn.prepare(sql).unwrap();
+      stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|s| s.unwrap())
+        .collect()
+    };
+    assert_eq!(
+      vec![(1, None), (2, None), (3, None), (4, Some(3))],
+      rows("SELECT id, parent_id FROM todos ORDER BY id")
+    );
+    assert_eq!(washer, uuid(4));
+    assert_eq!(
+      vec![(3, Some(2))],
+      rows("SELECT todo_id, depends_on FROM dependencies")
+    );
+    assert_eq!(vec![(4, None)], rows("SELECT todo_id, NULL FROM links"));
+    // History moved along with the todos, the removed one's past them
+    assert_eq!(
+      vec![
+        (1, None),
+        (2, None),
+        (3, None),
+        (4, None),
+        (4, Some(3)),
+        (5, None),
+        (5, None)
+      ],
+      rows("SELECT todo_id, CASE field WHEN 'parent_id' THEN new END FROM history ORDER BY id")
+    );
+    let removed = removed::collect(&[removed::Scope::Trash], &conn).unwrap();
+    assert_eq!(
+      vec!["Bread"],
+      removed
+        .iter()
+        .map(|removed| &removed.todo.body)
+        .collect::<Vec<_>>()
+    );
+    assert!(undo::undo_last(&conn).unwrap().is_none());
+  }
+}
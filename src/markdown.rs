@@ -0,0 +1,307 @@
+use crate::hyperlink;
+use console::style;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+fn parser(text: &str) -> Parser<'_> {
+  Parser::new_ext(
+    text,
+    Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS,
+  )
+}
+
+/// Terminal text built up from markdown events
+#[derive(Default)]
+struct Writer {
+  out: String,
+  /// Keep the markers out but leave the text unstyled
+  plain: bool,
+  /// Line breaks inside paragraphs become spaces
+  single_line: bool,
+  /// Links and urls can be clicked, otherwise the url of a link follows its text
+  hyperlinks: bool,
+  bold: usize,
+  italic: usize,
+  strikethrough: usize,
+  heading: bool,
+  code_block: bool,
+  quotes: usize,
+  /// Url and text so far of every open link
+  links: Vec<(String, String)>,
+  /// Next number of every open list, `None` for bullets
+  lists: Vec<Option<u64>>,
+  /// Bullet of a list item that has no text yet, a task marker replaces it
+  bullet: Option<String>,
+}
+
+impl Writer {
+  /// Quotes and code blocks prefix every line they cover
+  fn line_start(&mut self) {
+    if self.out.is_empty() || self.out.ends_with('\n') {
+      self.out.push_str(&"│ ".repeat(self.quotes));
+      if self.code_block {
+        self.out.push_str("    ");
+      }
+    }
+    if let Some(bullet) = self.bullet.take() {
+      self.out.push_str(&bullet);
+    }
+  }
+
+  fn newline(&mut self) {
+    self.out.push('\n');
+  }
+
+  /// Blocks at the top level are separated by an empty line, inside lists by a line break
+  fn block(&mut self) {
+    if self.out.is_empty() {
+      return;
+    }
+    let breaks = if self.lists.is_empty() { "\n\n" } else { "\n" };
+    while !self.out.ends_with(breaks) {
+      self.newline();
+    }
+  }
+
+  fn text(&mut self, text: &str) {
+    for (index, line) in text.split('\n').enumerate() {
+      if index > 0 {
+        self.newline();
+      }
+      if line.is_empty() {
+        continue;
+      }
+      self.line_start();
+      for (_, link_text) in &mut self.links {
+        link_text.push_str(line);
+      }
+      let line = match self.links.last() {
+        Some((url, _)) if self.hyperlinks => hyperlink::link(line, url),
+        Some(_) => line.to_string(),
+        None => hyperlink::linkify(line, self.hyperlinks),
+      };
+      let mut styled = style(line.as_str());
+      if self.bold > 0 || self.heading {
+        styled = styled.bold();
+      }
+      if self.italic > 0 {
+        styled = styled.italic();
+      }
+      if self.strikethrough > 0 {
+        styled = styled.strikethrough();
+      }
+      if !self.links.is_empty() {
+        styled = styled.blue().underlined();
+      }
+      if self.code_block {
+        styled = styled.dim();
+      }
+      self.push(styled);
+    }
+  }
+
+  fn push(&mut self, styled: console::StyledObject<&str>) {
+    let styled = match self.plain {
+      true => styled.force_styling(false),
+      false => styled,
+    };
+    self.out.push_str(&styled.to_string());
+  }
+
+  fn event(&mut self, event: Event) {
+    match event {
+      Event::Start(tag) => self.start(tag),
+      Event::End(tag) => self.end(tag),
+      Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => self.text(&text),
+      Event::Code(code) => {
+        self.line_start();
+        let styled = match self.plain {
+          true => style(&*code),
+          false => style(&*code).cyan(),
+        };
+        self.push(styled);
+      }
+      Event::SoftBreak if self.single_line => self.text(" "),
+      Event::SoftBreak | Event::HardBreak => self.newline(),
+      Event::Rule => {
+        self.block();
+        self.text(&"─".repeat(20));
+      }
+      Event::TaskListMarker(checked) => {
+        let indent = self.bullet.take().unwrap_or_default();
+        let indent = indent.trim_end_matches("• ");
+        self.bullet = Some(format!("{}{} ", indent, if checked { '☑' } else { '☐' }));
+      }
+      _ => {}
+    }
+  }
+
+  fn start(&mut self, tag: Tag) {
+    match tag {
+      Tag::Paragraph => self.block(),
+      Tag::Heading { .. } => {
+        self.block();
+        self.heading = true;
+      }
+      Tag::BlockQuote(_) => {
+        self.block();
+        self.quotes += 1;
+      }
+      Tag::CodeBlock(_) => {
+        self.block();
+        self.code_block = true;
+      }
+      Tag::List(first) => {
+        if self.lists.is_empty() {
+          self.block();
+        }
+        self.lists.push(first);
+      }
+      Tag::Item => {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+          self.newline();
+        }
+        let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+        let marker = match self.lists.last_mut() {
+          Some(Some(number)) => {
+            *number += 1;
+            format!("{}. ", *number - 1)
+          }
+          _ => "• ".to_string(),
+        };
+        self.bullet = Some(format!("{}{}", indent, marker));
+      }
+      Tag::Emphasis => self.italic += 1,
+      Tag::Strong => self.bold += 1,
+      Tag::Strikethrough => self.strikethrough += 1,
+      Tag::Link { dest_url, .. } => self.links.push((dest_url.to_string(), String::new())),
+      _ => {}
+    }
+  }
+
+  fn end(&mut self, tag: TagEnd) {
+    match tag {
+      TagEnd::Heading(_) => self.heading = false,
+      TagEnd::BlockQuote(_) => self.quotes -= 1,
+      TagEnd::CodeBlock => self.code_block = false,
+      TagEnd::List(_) => _ = self.lists.pop(),
+      TagEnd::Emphasis => self.italic -= 1,
+      TagEnd::Strong => self.bold -= 1,
+      TagEnd::Strikethrough => self.strikethrough -= 1,
+      TagEnd::Link => {
+        if let Some((url, text)) = self.links.pop()
+          && url != text
+          && !url.is_empty()
+          && !self.hyperlinks
+        {
+          let url = format!(" ({})", url);
+          self.push(style(url.as_str()).dim());
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Whether the text is a single paragraph, so it can be formatted on one line
+fn is_inline(text: &str) -> bool {
+  let mut paragraphs = 0;
+  for event in parser(text) {
+    match event {
+      Event::Start(Tag::Paragraph) => paragraphs += 1,
+      Event::Start(
+        Tag::Heading { .. }
+        | Tag::BlockQuote(_)
+        | Tag::CodeBlock(_)
+        | Tag::List(_)
+        | Tag::HtmlBlock
+        | Tag::Table(_),
+      )
+      | Event::Rule => return false,
+      _ => {}
+    }
+  }
+  paragraphs == 1
+}
+
+fn write(text: &str, plain: bool, single_line: bool, hyperlinks: bool) -> String {
+  let mut writer = Writer {
+    plain,
+    single_line,
+    hyperlinks,
+    ..Default::default()
+  };
+  for event in parser(text) {
+    writer.event(event);
+  }
+  writer.out.trim_end().to_string()
+}
+
+/// Bold, italics, inline code and links of a body styled on one line. Bodies that would be
+/// headings, lists or quotes are shown as written.
+pub fn inline(text: &str) -> String {
+  match is_inline(text) {
+    true => write(text, false, true, hyperlink::enabled()),
+    false => text.to_string(),
+  }
+}
+
+/// Like `inline` without the styling, for text that gets a style of its own
+pub fn plain(text: &str) -> String {
+  match is_inline(text) {
+    true => write(text, true, true, hyperlink::enabled()),
+    false => text.to_string(),
+  }
+}
+
+/// Notes with paragraphs, headings, lists, checklists, quotes and code blocks
+pub fn render(text: &str) -> String {
+  write(text, false, false, hyperlink::enabled())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn inline_markup_is_dropped() {
+    assert_eq!(
+      "Fix the sink now, see docs (https://example.com)",
+      plain("Fix the **sink** _now_, see [docs](https://example.com)")
+    );
+    assert_eq!(
+      "Read https://example.com",
+      plain("Read <https://example.com>")
+    );
+    assert_eq!("Call snake_case fn", plain("Call `snake_case` fn"));
+    // Block markdown stays as typed
+    assert_eq!("# 1 thing", plain("# 1 thing"));
+    assert_eq!("- milk", inline("- milk"));
+    assert_eq!("#home chores", inline("#home chores"));
+  }
+
+  #[test]
+  fn links_can_be_clicked() {
+    assert_eq!(
+      format!(
+        "see {} or {}",
+        hyperlink::link("docs", "https://example.com"),
+        hyperlink::link("https://example.org", "https://example.org")
+      ),
+      write(
+        "see [docs](https://example.com) or https://example.org",
+        true,
+        true,
+        true
+      )
+    );
+  }
+
+  #[test]
+  fn notes_keep_their_structure() {
+    let notes = "# Shopping\nFor **Sunday**\n\n- [ ] oat\n- [x] soy\n  1. first\n  2. second\n\n> quoted\n\n```\ncode\n```";
+    assert_eq!(
+      "Shopping\n\nFor Sunday\n\n☐ oat\n☑ soy\n  1. first\n  2. second\n\n│ quoted\n\n    code",
+      render(notes)
+    );
+  }
+}
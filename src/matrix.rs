@@ -0,0 +1,155 @@
+use crate::priority::Priority;
+use crate::{Todo, ascii, collect_todos_incomplete, emoji};
+use chrono::{Local, NaiveDate};
+use console::{Alignment, Term, measure_text_width, pad_str, truncate_str};
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Eisenhower quadrants, in the order they are drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Quadrant {
+  Do,
+  Schedule,
+  Delegate,
+  Drop,
+}
+
+impl Quadrant {
+  fn title(&self) -> &'static str {
+    match self {
+      Quadrant::Do => "Do (urgent, important)",
+      Quadrant::Schedule => "Schedule (important)",
+      Quadrant::Delegate => "Delegate (urgent)",
+      Quadrant::Drop => "Drop",
+    }
+  }
+}
+
+/// Important means high priority, urgent means due within `urgent_days` or overdue
+fn quadrant(todo: &Todo, today: NaiveDate, urgent_days: i64) -> Quadrant {
+  let important = todo.priority == Some(Priority::High);
+  let urgent = todo
+    .due
+    .is_some_and(|due| (due - today).num_days() <= urgent_days);
+
+  match (urgent, important) {
+    (true, true) => Quadrant::Do,
+    (false, true) => Quadrant::Schedule,
+    (true, false) => Quadrant::Delegate,
+    (false, false) => Quadrant::Drop,
+  }
+}
+
+/// Draw a 2×2 grid, each cell listing the bodies of its todos
+fn render(todos: &[Todo], today: NaiveDate, urgent_days: i64, width: usize) -> Vec<String> {
+  let cell = width.saturating_sub(3).max(20) / 2;
+  let bodies = |wanted: Quadrant| {
+    todos
+      .iter()
+      .filter(|todo| quadrant(todo, today, urgent_days) == wanted)
+      .map(|todo| format!(" {}", emoji::display(&todo.body)))
+      .collect::<Vec<String>>()
+  };
+  let header = |left: &str, quadrant: Quadrant, right: &str| {
+    let title =
+      truncate_str(&format!("─ {} ", quadrant.title()), cell, ascii::ellipsis()).to_string();
+    let fill = "─".repeat(cell - measure_text_width(&title));
+    format!("{}{}{}{}", left, title, fill, right)
+  };
+
+  let mut lines = vec![];
+  for (top, (left, right)) in [
+    (true, (Quadrant::Do, Quadrant::Schedule)),
+    (false, (Quadrant::Delegate, Quadrant::Drop)),
+  ] {
+    let (corner_left, middle, corner_right) = if top {
+      ("┌", "┬", "┐")
+    } else {
+      ("├", "┼", "┤")
+    };
+    lines.push(format!(
+      "{}{}",
+      header(corner_left, left, middle),
+      header("", right, corner_right)
+    ));
+
+    let (left, right) = (bodies(left), bodies(right));
+    for row in 0..left.len().max(right.len()).max(1) {
+      let text = |column: &[String]| {
+        let text = column.get(row).map(String::as_str).unwrap_or("");
+        pad_str(text, cell, Alignment::Left, Some(ascii::ellipsis())).to_string()
+      };
+      lines.push(format!("│{}│{}│", text(&left), text(&right)));
+    }
+  }
+  lines.push(format!("└{}┴{}┘", "─".repeat(cell), "─".repeat(cell)));
+  lines
+}
+
+pub fn matrix(urgent_days: i64, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos_incomplete(conn)?;
+  let (_, width) = Term::stdout().size();
+  for line in render(&todos, Local::now().date_naive(), urgent_days, width.into()) {
+    println!("{}", ascii::text(&line));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 1, 30).unwrap()
+  }
+
+  fn todo(body: &str, priority: Option<Priority>, due: Option<NaiveDate>) -> Todo {
+    Todo {
+      body: body.to_string(),
+      priority,
+      due,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn quadrant_from_priority_and_due() {
+    let soon = NaiveDate::from_ymd_opt(2024, 1, 31);
+    let later = NaiveDate::from_ymd_opt(2024, 3, 1);
+    let high = Some(Priority::High);
+    let low = Some(Priority::Low);
+
+    assert_eq!(Quadrant::Do, quadrant(&todo("a", high, soon), today(), 2));
+    assert_eq!(
+      Quadrant::Schedule,
+      quadrant(&todo("b", high, later), today(), 2)
+    );
+    assert_eq!(
+      Quadrant::Schedule,
+      quadrant(&todo("c", high, None), today(), 2)
+    );
+    assert_eq!(
+      Quadrant::Delegate,
+      quadrant(&todo("d", low, soon), today(), 2)
+    );
+    assert_eq!(
+      Quadrant::Drop,
+      quadrant(&todo("e", None, later), today(), 2)
+    );
+  }
+
+  #[test]
+  fn render_keeps_cells_aligned() {
+    let todos = vec![
+      todo("Taxes", Some(Priority::High), Some(today())),
+      todo("A very long body that will not fit in a cell", None, None),
+    ];
+    let lines = render(&todos, today(), 2, 43);
+
+    assert!(lines[1].contains("Taxes"));
+    assert!(lines[3].contains("…"));
+    for line in &lines {
+      assert_eq!(43, measure_text_width(line), "{}", line);
+    }
+  }
+}
@@ -0,0 +1,122 @@
+use crate::apply::Changes;
+use crate::find_targets;
+use crate::ids::short;
+use crate::tags::parse_tag;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// The field a `key:value` token sets, with the short names Taskwarrior uses
+fn field(key: &str) -> Option<&'static str> {
+  match key.to_lowercase().as_str() {
+    "due" => Some("due"),
+    "scheduled" | "sched" => Some("scheduled"),
+    "priority" | "pri" => Some("priority"),
+    "project" | "proj" => Some("project"),
+    "status" => Some("status"),
+    "ref" => Some("reference"),
+    _ => None,
+  }
+}
+
+/// `H`, `M` and `L` as well as the full names
+fn priority(value: &str) -> String {
+  match value.to_lowercase().as_str() {
+    "h" => "high".to_string(),
+    "m" => "medium".to_string(),
+    "l" => "low".to_string(),
+    value => value.to_string(),
+  }
+}
+
+/// Read tokens like `due:friday +urgent -someday pri:H`. An empty value like `due:` clears
+/// the field, and the other words together replace the body.
+pub fn parse_changes(tokens: &[String]) -> Result<Changes, String> {
+  let mut changes = Changes::default();
+  let mut body = vec![];
+  for token in tokens {
+    if let Some(tag) = token.strip_prefix('+') {
+      changes.add_tag.push(parse_tag(tag)?);
+    } else if let Some(tag) = token.strip_prefix('-')
+      && !tag.is_empty()
+    {
+      changes.remove_tag.push(parse_tag(tag)?);
+    } else if let Some((key, value)) = token.split_once(':')
+      && let Some(field) = field(key)
+    {
+      let value = match field {
+        "priority" => priority(value),
+        _ => value.to_string(),
+      };
+      changes.set.push((field.to_string(), value));
+    } else {
+      body.push(token.as_str());
+    }
+  }
+  if !body.is_empty() {
+    changes.set.push(("body".to_string(), body.join(" ")));
+  }
+  Ok(changes)
+}
+
+/// `todo <target> modify <tokens>`, every todo the target finds changes or none does
+pub fn modify(target: &str, tokens: &[String], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let changes = parse_changes(tokens)?;
+  if changes.is_empty() {
+    return Err("Nothing to change, use e.g. due:friday +urgent -someday pri:H".into());
+  }
+  let targets = find_targets(&[target.to_string()], conn)?;
+  let tx = conn.unchecked_transaction()?;
+  for todo in &targets {
+    changes.write(todo, &tx)?;
+    println!("Modified {}: {}", short(todo.id), todo.body);
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::priority::Priority;
+  use crate::tags::Tags;
+  use crate::{AddOptions, add, collect_todos_all};
+
+  fn tokens(text: &str) -> Vec<String> {
+    text.split(' ').map(str::to_string).collect()
+  }
+
+  #[test]
+  fn modify_reads_taskwarrior_tokens() {
+    let changes = parse_changes(&tokens("due:friday +urgent -someday pri:H call 10:30")).unwrap();
+    assert_eq!(vec!["urgent".to_string()], changes.add_tag);
+    assert_eq!(vec!["someday".to_string()], changes.remove_tag);
+    let set = |field: &str, value: &str| (field.to_string(), value.to_string());
+    assert_eq!(
+      vec![
+        set("due", "friday"),
+        set("priority", "high"),
+        set("body", "call 10:30")
+      ],
+      changes.set
+    );
+    assert!(parse_changes(&tokens("+two words")).is_ok());
+    assert!(parse_changes(&tokens("+")).is_err());
+
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let someday = AddOptions {
+      tags: vec!["someday".to_string()],
+      priority: Some(Priority::Low),
+      ..Default::default()
+    };
+    _ = add(vec!["Renew passport".to_string()], &someday, &conn);
+    modify("1", &tokens("+urgent -someday pri: due:tomorrow"), &conn).unwrap();
+    let todo = collect_todos_all(&conn).unwrap().remove(0);
+    assert_eq!(Tags(vec!["urgent".to_string()]), todo.tags);
+    assert_eq!(None, todo.priority);
+    assert!(todo.due.is_some());
+    assert!(modify("1", &tokens("pri:urgent"), &conn).is_err());
+    assert!(modify("1", &[], &conn).is_err());
+  }
+}
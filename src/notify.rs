@@ -0,0 +1,238 @@
+use crate::duration::parse_duration;
+use crate::ids::short;
+use crate::{Todo, collect_todos_with, find_targets};
+use chrono::{Duration, Local, NaiveDateTime, NaiveTime};
+use clap::Subcommand;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::error::Error;
+use std::process::Command;
+
+/// Times are local and stored like `2024-01-01 09:30:00`
+const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A `quiet_hours` entry like `22:00-07:00`, which may run past midnight
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Window {
+  start: NaiveTime,
+  end: NaiveTime,
+}
+
+impl TryFrom<String> for Window {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Window, String> {
+    let time = |time: &str| {
+      NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| format!("{} isn't a time like 22:00", time))
+    };
+    let (start, end) = text
+      .split_once('-')
+      .ok_or_else(|| format!("{} isn't a window like 22:00-07:00", text))?;
+    Ok(Window {
+      start: time(start)?,
+      end: time(end)?,
+    })
+  }
+}
+
+impl Window {
+  fn contains(&self, time: NaiveTime) -> bool {
+    match self.start <= self.end {
+      true => self.start <= time && time < self.end,
+      false => self.start <= time || time < self.end,
+    }
+  }
+}
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Show a desktop notification for each todo that fell due, outside of quiet hours. Meant
+  /// for a `[[schedule]]` job like `cron = "*/5 * * * *"` with `command = "notify send"`.
+  Send {
+    /// Only print the notifications
+    #[arg(long)]
+    dry_run: bool,
+  },
+
+  /// Notify about todos again later
+  Snooze {
+    /// Ids or parts of the text of the todos
+    #[arg(required = true)]
+    targets: Vec<String>,
+
+    /// How long, e.g. 15m, 1h or 1d
+    #[arg(short, long = "for", default_value = "1h", value_parser = parse_duration)]
+    duration: u32,
+  },
+}
+
+/// Open todos due by `now` that weren't notified about for this due date, or whose snooze
+/// ran out
+fn pending(now: NaiveDateTime, conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos_with(
+    "SELECT todos.* FROM todos LEFT JOIN notifications ON notifications.todo_id = todos.id
+     WHERE status not in ('done', 'cancelled') and todos.due <= ?1
+       and (notifications.todo_id is null or notifications.due is not todos.due
+         or notifications.snoozed_until <= ?2)
+     ORDER BY todos.due, todos.id",
+    (now.date().to_string(), now.format(FORMAT).to_string()),
+    conn,
+  )
+}
+
+fn shown(todos: &[Todo], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for todo in todos {
+    conn.execute(
+      "INSERT OR REPLACE INTO notifications (todo_id, due, snoozed_until) VALUES (?1, ?2, null)",
+      (todo.id, todo.due),
+    )?;
+  }
+  Ok(())
+}
+
+fn snooze(todos: &[Todo], until: NaiveDateTime, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for todo in todos {
+    conn.execute(
+      "INSERT OR REPLACE INTO notifications (todo_id, due, snoozed_until) VALUES (?1, ?2, ?3)",
+      (todo.id, todo.due, until.format(FORMAT).to_string()),
+    )?;
+  }
+  Ok(())
+}
+
+// Untested segment starts, this part needs a desktop
+/// `notify-send` or on macOS `osascript`, false when neither could show it
+fn desktop(title: &str, text: &str) -> bool {
+  let status = match cfg!(target_os = "macos") {
+    true => Command::new("osascript")
+      .arg("-e")
+      .arg(format!(
+        "display notification {:?} with title {:?}",
+        text, title
+      ))
+      .status(),
+    false => Command::new("notify-send")
+      .args(["--app-name", "todo", title, text])
+      .status(),
+  };
+  status.is_ok_and(|status| status.success())
+}
+// Untested segment ends
+
+fn send(quiet_hours: &[Window], dry_run: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let now = Local::now().naive_local();
+  let todos = pending(now, conn)?;
+  if todos.is_empty() {
+    println!("Nothing due!");
+    return Ok(());
+  }
+  // Left pending they are all shown once quiet hours end
+  if let Some(window) = quiet_hours
+    .iter()
+    .find(|window| window.contains(now.time()))
+  {
+    println!(
+      "Quiet hours, {} notifications wait until {}",
+      todos.len(),
+      window.end.format("%H:%M")
+    );
+    return Ok(());
+  }
+  for todo in &todos {
+    let title = format!("Due: {}", todo.body);
+    let text = format!("todo notify snooze {} to be reminded later", short(todo.id));
+    if dry_run || !desktop(&title, &text) {
+      println!("{} ({})", title, text);
+    }
+  }
+  if dry_run {
+    println!("Dry run, nothing marked as notified!");
+    return Ok(());
+  }
+  shown(&todos, conn)
+}
+
+pub fn notify(
+  action: &Action,
+  quiet_hours: &[Window],
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Send { dry_run } => send(quiet_hours, *dry_run, conn),
+    Action::Snooze { targets, duration } => {
+      let todos = find_targets(targets, conn)?;
+      let until = Local::now().naive_local() + Duration::minutes(*duration as i64);
+      snooze(&todos, until, conn)?;
+      for todo in &todos {
+        println!("Snoozed until {}: {}", until.format("%H:%M"), todo.body);
+      }
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, collect_todos_all};
+  use chrono::NaiveDate;
+
+  #[test]
+  fn quiet_hours_run_past_midnight() {
+    let night = Window::try_from("22:00-07:00".to_string()).unwrap();
+    let lunch = Window::try_from("12:00 - 13:00".to_string()).unwrap();
+    let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+    assert!(night.contains(time(23)) && night.contains(time(6)));
+    assert!(!night.contains(time(7)) && !night.contains(time(12)));
+    assert!(lunch.contains(time(12)) && !lunch.contains(time(13)));
+    assert!(Window::try_from("22:00".to_string()).is_err());
+  }
+
+  #[test]
+  fn snoozed_notifications_come_back() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let today = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let due = |day| AddOptions {
+      due: Some(NaiveDate::from_ymd_opt(2024, 1, day).unwrap()),
+      ..Default::default()
+    };
+    _ = add(vec!["Pay rent".to_string()], &due(1), &conn);
+    _ = add(vec!["Call mum".to_string()], &due(2), &conn);
+    let at = |hour| today.and_hms_opt(hour, 0, 0).unwrap();
+    let bodies = |now| {
+      pending(now, &conn)
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.body)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec!["Pay rent"], bodies(at(9)));
+    shown(&pending(at(9), &conn).unwrap(), &conn).unwrap();
+    assert!(bodies(at(10)).is_empty());
+
+    snooze(&collect_todos_all(&conn).unwrap()[..1], at(12), &conn).unwrap();
+    assert!(bodies(at(11)).is_empty());
+    assert_eq!(vec!["Pay rent"], bodies(at(12)));
+
+    // A new due date is notified about again
+    conn
+      .execute(
+        "UPDATE todos SET due = '2024-01-01' WHERE body = 'Call mum'",
+        (),
+      )
+      .unwrap();
+    shown(&pending(at(12), &conn).unwrap(), &conn).unwrap();
+    conn
+      .execute(
+        "UPDATE todos SET due = '2023-12-31' WHERE body = 'Pay rent'",
+        (),
+      )
+      .unwrap();
+    assert_eq!(vec!["Pay rent"], bodies(at(13)));
+  }
+}
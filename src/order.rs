@@ -0,0 +1,205 @@
+use crate::{Todo, collect_todos_incomplete, layout, picker, prompt};
+use chrono::Local;
+use dialoguer::Sort;
+use dialoguer::theme::ColorfulTheme;
+use rusqlite::{Connection, OptionalExtension};
+use std::error::Error;
+
+fn bounds(conn: &Connection) -> Result<(Option<f64>, Option<f64>), Box<dyn Error>> {
+  Ok(conn.query_row(
+    "SELECT min(position), max(position) FROM todos",
+    [],
+    |row| Ok((row.get(0)?, row.get(1)?)),
+  )?)
+}
+
+/// Number the todos 1, 2, 3... in their current order, for when there is no room left
+/// between two neighbours
+fn renumber(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  // Ranked up front, a subquery per row would see the rows numbered before it
+  conn.execute(
+    "UPDATE todos SET position = ranked.rank
+     FROM (SELECT id, row_number() OVER (ORDER BY position, id) AS rank FROM todos) AS ranked
+     WHERE todos.id = ranked.id",
+    (),
+  )?;
+  Ok(())
+}
+
+/// Position for a new todo, first with `top` or right after the todo `after`,
+/// `None` appends it
+pub fn position(
+  top: bool,
+  after: Option<usize>,
+  conn: &Connection,
+) -> Result<Option<f64>, Box<dyn Error>> {
+  if top {
+    return Ok(bounds(conn)?.0.map(|first| first - 1.0));
+  }
+  let Some(after) = after else {
+    return Ok(None);
+  };
+  for _ in 0..2 {
+    let position: f64 = conn
+      .query_row(
+        "SELECT position FROM todos WHERE id = ?1",
+        (after,),
+        |row| row.get(0),
+      )
+      .optional()?
+      .ok_or_else(|| format!("No todo with id {}", after))?;
+    let next: Option<f64> = conn.query_row(
+      "SELECT min(position) FROM todos WHERE position > ?1",
+      (position,),
+      |row| row.get(0),
+    )?;
+    let Some(next) = next else {
+      return Ok(None);
+    };
+    let middle = (position + next) / 2.0;
+    if middle > position && middle < next {
+      return Ok(Some(middle));
+    }
+    renumber(conn)?;
+  }
+  Err("Could not find a place for the todo".into())
+}
+
+/// Hand the positions the todos `ids` hold between them out in the order of `ids`, the other
+/// todos stay where they are
+pub fn arrange(ids: &[usize], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let positions = || -> Result<Vec<f64>, Box<dyn Error>> {
+    let mut positions = ids
+      .iter()
+      .map(|id| {
+        conn
+          .query_row("SELECT position FROM todos WHERE id = ?1", (id,), |row| {
+            row.get::<_, f64>(0)
+          })
+          .optional()?
+          .ok_or_else(|| format!("No todo with id {}", id).into())
+      })
+      .collect::<Result<Vec<f64>, Box<dyn Error>>>()?;
+    positions.sort_by(f64::total_cmp);
+    Ok(positions)
+  };
+  let mut sorted = positions()?;
+  // Todos sharing a position would stay in id order whatever they are given
+  if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+    renumber(conn)?;
+    sorted = positions()?;
+  }
+  let tx = conn.unchecked_transaction()?;
+  for (id, position) in ids.iter().zip(sorted) {
+    tx.execute(
+      "UPDATE todos SET position = ?1 WHERE id = ?2",
+      (position, id),
+    )?;
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+/// `todo reorder`, the todos take the places they held between them in the order given
+pub fn reorder(targets: Vec<Todo>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  if targets.len() < 2 {
+    println!("Give at least two todos to reorder!");
+    return Ok(());
+  }
+  arrange(
+    &targets.iter().map(|todo| todo.id).collect::<Vec<_>>(),
+    conn,
+  )?;
+  for (index, todo) in targets.iter().enumerate() {
+    println!("{}. {}", index + 1, todo.body);
+  }
+  Ok(())
+}
+
+// Untested segment starts, this part needs interactivity
+/// The open todos in the order they are dragged to, space picks one up and puts it down
+pub fn pick(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  prompt::require("give the todos to reorder")?;
+  let todos = collect_todos_incomplete(conn)?;
+  let items = picker::items(&todos, Local::now().date_naive(), layout::picker_width());
+  let order = Sort::with_theme(&ColorfulTheme::default())
+    .with_prompt("Move todos with space and the arrows, enter keeps the order")
+    .items(&items)
+    .interact()?;
+  Ok(
+    order
+      .into_iter()
+      .map(|index| todos[index].clone())
+      .collect(),
+  )
+}
+// Untested segment ends
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, bodies, collect_todos_all};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    conn
+  }
+
+  #[test]
+  fn add_on_top_and_after() {
+    let conn = setup();
+    let top = AddOptions {
+      top: true,
+      ..Default::default()
+    };
+    _ = add(vec!["Rent".to_string(), "Bills".to_string()], &top, &conn);
+    let after = AddOptions {
+      after: Some(1),
+      ..Default::default()
+    };
+    _ = add(vec!["Eggs".to_string()], &after, &conn);
+    assert_eq!(
+      vec!["Rent", "Bills", "Milk", "Eggs", "Carl"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+    assert!(position(false, Some(9), &conn).is_err());
+
+    // Bills and Rent trade places, the others stay
+    arrange(&[4, 3], &conn).unwrap();
+    assert_eq!(
+      vec!["Bills", "Rent", "Milk", "Eggs", "Carl"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+    conn.execute("UPDATE todos SET position = 1", ()).unwrap();
+    arrange(&[5, 1], &conn).unwrap();
+    assert_eq!(
+      vec!["Eggs", "Carl", "Rent", "Bills", "Milk"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+    assert!(arrange(&[1, 9], &conn).is_err());
+  }
+
+  #[test]
+  fn renumber_when_out_of_room() {
+    let conn = setup();
+    conn
+      .execute("UPDATE todos SET position = 1 + id * 1e-16", ())
+      .unwrap();
+    let after = AddOptions {
+      after: Some(1),
+      ..Default::default()
+    };
+    _ = add(vec!["Eggs".to_string()], &after, &conn);
+    assert_eq!(
+      vec!["Milk", "Eggs", "Carl"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+  }
+}
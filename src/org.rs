@@ -0,0 +1,356 @@
+use crate::Todo;
+use crate::bucket::Bucket;
+use crate::duration::parse_duration;
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::tags::{Tags, parse_tag};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// Headline keywords, the first one of a status is what gets exported
+const KEYWORDS: [(&str, Status); 7] = [
+  ("TODO", Status::Todo),
+  ("NEXT", Status::Todo),
+  ("STARTED", Status::InProgress),
+  ("WAITING", Status::Waiting),
+  ("DONE", Status::Done),
+  ("CANCELLED", Status::Cancelled),
+  ("CANCELED", Status::Cancelled),
+];
+
+fn keyword(status: Status) -> &'static str {
+  KEYWORDS
+    .iter()
+    .find(|(_, own)| *own == status)
+    .map_or("TODO", |(keyword, _)| keyword)
+}
+
+fn priority_cookie(priority: Priority) -> &'static str {
+  match priority {
+    Priority::High => "[#A]",
+    Priority::Medium => "[#B]",
+    Priority::Low => "[#C]",
+  }
+}
+
+fn parse_priority_cookie(word: &str) -> Option<Priority> {
+  match word {
+    "[#A]" => Some(Priority::High),
+    "[#B]" => Some(Priority::Medium),
+    "[#C]" => Some(Priority::Low),
+    _ => None,
+  }
+}
+
+/// `<2024-03-01 Fri>`
+fn timestamp(day: NaiveDate) -> String {
+  format!("<{}>", day.format("%Y-%m-%d %a"))
+}
+
+/// The date of the timestamp following `keyword:` on a planning line,
+/// times and repeaters after the date are dropped
+fn timestamp_after(line: &str, keyword: &str) -> Option<NaiveDate> {
+  let (_, rest) = line.split_once(&format!("{}:", keyword))?;
+  let rest = rest.trim_start().strip_prefix(['<', '['])?;
+  NaiveDate::parse_from_str(rest.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Org efforts are `h:mm`, plain durations like `90m` are accepted too
+fn parse_effort(value: &str) -> Option<u32> {
+  match value.split_once(':') {
+    Some((hours, minutes)) => Some(hours.parse::<u32>().ok()? * 60 + minutes.parse::<u32>().ok()?),
+    None => parse_duration(value).ok(),
+  }
+}
+
+fn format_effort(minutes: u32) -> String {
+  format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// A headline's stars and the rest of it, `None` for other lines
+fn headline(line: &str) -> Option<(usize, &str)> {
+  let level = line.chars().take_while(|c| *c == '*').count();
+  let rest = line[level..].strip_prefix(' ')?;
+  (level > 0).then_some((level, rest.trim()))
+}
+
+/// Split trailing `:a:b:` tags off a headline title
+fn split_tags(title: &str) -> (&str, Tags) {
+  if let Some((rest, last)) = title.rsplit_once(char::is_whitespace)
+    && last.len() > 1
+    && last.starts_with(':')
+    && last.ends_with(':')
+  {
+    let tags = last
+      .split(':')
+      .filter_map(|tag| parse_tag(tag).ok())
+      .collect();
+    return (rest.trim_end(), Tags(tags));
+  }
+  (title, Tags::default())
+}
+
+enum Node {
+  Project(String),
+  Todo(usize),
+}
+
+/// Headlines with a keyword become todos numbered from 1 in order, nested ones
+/// subtasks, and headlines without one the project of everything below them
+pub fn parse(text: &str) -> Vec<Todo> {
+  let mut todos: Vec<Todo> = vec![];
+  let mut notes: Vec<Vec<&str>> = vec![];
+  let mut outline: Vec<(usize, Node)> = vec![];
+  let mut current = None;
+  let mut in_drawer = false;
+
+  for line in text.lines() {
+    if let Some((level, rest)) = headline(line) {
+      while outline.last().is_some_and(|(own, _)| *own >= level) {
+        outline.pop();
+      }
+      in_drawer = false;
+      let (word, title) = rest.split_once(' ').unwrap_or((rest, ""));
+      let Some((_, status)) = KEYWORDS.iter().find(|(keyword, _)| *keyword == word) else {
+        outline.push((level, Node::Project(split_tags(rest).0.to_string())));
+        current = None;
+        continue;
+      };
+
+      let (priority, title) = match title.split_once(' ') {
+        Some((cookie, rest)) if parse_priority_cookie(cookie).is_some() => {
+          (parse_priority_cookie(cookie), rest)
+        }
+        _ => (parse_priority_cookie(title), title),
+      };
+      let (body, tags) = split_tags(title);
+      let mut todo = Todo {
+        id: todos.len() + 1,
+        body: body.to_string(),
+        status: *status,
+        priority,
+        tags,
+        // Headlines in an agenda are already processed
+        bucket: Bucket::Next,
+        ..Default::default()
+      };
+      for (_, node) in outline.iter().rev() {
+        match node {
+          Node::Todo(parent) if todo.parent_id.is_none() => todo.parent_id = Some(*parent),
+          Node::Project(project) if todo.project.is_none() => todo.project = Some(project.clone()),
+          _ => {}
+        }
+      }
+      outline.push((level, Node::Todo(todo.id)));
+      current = Some(todos.len());
+      todos.push(todo);
+      notes.push(vec![]);
+      continue;
+    }
+
+    let Some(index) = current else {
+      continue;
+    };
+    let todo = &mut todos[index];
+    let trimmed = line.trim();
+    if in_drawer {
+      if trimmed == ":END:" {
+        in_drawer = false;
+      } else if let Some((key, value)) = trimmed
+        .strip_prefix(':')
+        .and_then(|property| property.split_once(':'))
+      {
+        let value = value.trim();
+        match key.to_uppercase().as_str() {
+          "EFFORT" => todo.estimate = parse_effort(value),
+          "WAITING_ON" => todo.waiting_on = Some(value.to_string()),
+          "ID" => todo.uuid = Some(value.to_string()),
+          _ => {}
+        }
+      }
+    } else if trimmed == ":PROPERTIES:" {
+      in_drawer = true;
+    } else if notes[index].is_empty()
+      && ["SCHEDULED:", "DEADLINE:", "CLOSED:"]
+        .iter()
+        .any(|keyword| trimmed.starts_with(keyword))
+    {
+      todo.scheduled = timestamp_after(trimmed, "SCHEDULED").or(todo.scheduled);
+      todo.due = timestamp_after(trimmed, "DEADLINE").or(todo.due);
+    } else {
+      notes[index].push(line);
+    }
+  }
+
+  for (todo, lines) in todos.iter_mut().zip(notes) {
+    todo.notes = notes_from_lines(&lines);
+  }
+  todos
+}
+
+/// The body of a headline without its common indentation and org's `,*` escapes
+fn notes_from_lines(lines: &[&str]) -> Option<String> {
+  let indent = lines
+    .iter()
+    .filter(|line| !line.trim().is_empty())
+    .map(|line| line.len() - line.trim_start().len())
+    .min()?;
+  let notes = lines
+    .iter()
+    .map(|line| {
+      let line = line.get(indent..).unwrap_or("").trim_end();
+      line
+        .strip_prefix(',')
+        .filter(|rest| rest.starts_with('*'))
+        .unwrap_or(line)
+    })
+    .collect::<Vec<&str>>()
+    .join("\n");
+  Some(notes.trim_matches('\n').to_string())
+}
+
+fn render_todo(
+  todo: &Todo,
+  level: usize,
+  children: &HashMap<Option<usize>, Vec<&Todo>>,
+  out: &mut Vec<String>,
+) {
+  let mut headline = vec!["*".repeat(level), keyword(todo.status).to_string()];
+  headline.extend(
+    todo
+      .priority
+      .map(|priority| priority_cookie(priority).to_string()),
+  );
+  headline.push(todo.body.clone());
+  if !todo.tags.is_empty() {
+    headline.push(format!(":{}:", todo.tags.0.join(":")));
+  }
+  out.push(headline.join(" "));
+
+  let mut planning = vec![];
+  if let Some(scheduled) = todo.scheduled {
+    planning.push(format!("SCHEDULED: {}", timestamp(scheduled)));
+  }
+  if let Some(due) = todo.due {
+    planning.push(format!("DEADLINE: {}", timestamp(due)));
+  }
+  if !planning.is_empty() {
+    out.push(planning.join(" "));
+  }
+
+  if todo.estimate.is_some() || todo.waiting_on.is_some() || todo.uuid.is_some() {
+    out.push(":PROPERTIES:".to_string());
+    if let Some(uuid) = &todo.uuid {
+      out.push(format!(":ID: {}", uuid));
+    }
+    if let Some(estimate) = todo.estimate {
+      out.push(format!(":EFFORT: {}", format_effort(estimate)));
+    }
+    if let Some(waiting_on) = &todo.waiting_on {
+      out.push(format!(":WAITING_ON: {}", waiting_on));
+    }
+    out.push(":END:".to_string());
+  }
+
+  if let Some(notes) = todo.notes.as_ref().filter(|notes| !notes.trim().is_empty()) {
+    for line in notes.lines() {
+      // A line starting with a star would be read back as a headline
+      if line.starts_with('*') {
+        out.push(format!(",{}", line));
+      } else {
+        out.push(line.to_string());
+      }
+    }
+  }
+
+  for child in children.get(&Some(todo.id)).into_iter().flatten() {
+    render_todo(child, level + 1, children, out);
+  }
+}
+
+/// Todos without a project first, then a plain headline per project with its todos,
+/// subtasks are nested under their parents
+pub fn render(todos: &[Todo]) -> String {
+  let ids = todos.iter().map(|todo| todo.id).collect::<Vec<usize>>();
+  let mut children: HashMap<Option<usize>, Vec<&Todo>> = HashMap::new();
+  for todo in todos {
+    let parent = todo.parent_id.filter(|parent| ids.contains(parent));
+    children.entry(parent).or_default().push(todo);
+  }
+
+  let mut roots = children.get(&None).cloned().unwrap_or_default();
+  roots.sort_by(|a, b| a.project.cmp(&b.project));
+  let mut out = vec![];
+  let mut project = None;
+  for root in roots {
+    if root.project != project {
+      project = root.project.clone();
+      out.push(format!("* {}", project.as_deref().unwrap_or_default()));
+    }
+    let level = if project.is_some() { 2 } else { 1 };
+    render_todo(root, level, &children, &mut out);
+  }
+  out.into_iter().map(|line| line + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const AGENDA: &str = "#+TITLE: Agenda
+* TODO [#A] Call mom :family:phone:
+DEADLINE: <2024-03-05 Tue>
+* Garden
+** STARTED Build shed
+SCHEDULED: <2024-03-01 Fri 10:00> DEADLINE: <2024-03-09 Sat>
+:PROPERTIES:
+:EFFORT: 1:30
+:END:
+Wood from the shop
+,* not a headline
+*** DONE Buy wood
+** WAITING Get seeds
+:PROPERTIES:
+:WAITING_ON: neighbour
+:END:
+";
+
+  #[test]
+  fn parse_agenda() {
+    let todos = parse(AGENDA);
+    assert_eq!(4, todos.len());
+    assert_eq!(
+      Todo {
+        id: 1,
+        body: "Call mom".to_string(),
+        priority: Some(Priority::High),
+        due: NaiveDate::from_ymd_opt(2024, 3, 5),
+        tags: Tags(vec!["family".to_string(), "phone".to_string()]),
+        bucket: Bucket::Next,
+        ..Default::default()
+      },
+      todos[0]
+    );
+    assert_eq!(Status::InProgress, todos[1].status);
+    assert_eq!(Some("Garden".to_string()), todos[1].project);
+    assert_eq!(NaiveDate::from_ymd_opt(2024, 3, 1), todos[1].scheduled);
+    assert_eq!(Some(90), todos[1].estimate);
+    assert_eq!(
+      Some("Wood from the shop\n* not a headline".to_string()),
+      todos[1].notes
+    );
+    assert_eq!(Some(2), todos[2].parent_id);
+    assert_eq!(Status::Done, todos[2].status);
+    assert_eq!(Some("Garden".to_string()), todos[2].project);
+    assert_eq!(None, todos[3].parent_id);
+    assert_eq!(Some("neighbour".to_string()), todos[3].waiting_on);
+  }
+
+  #[test]
+  fn render_round_trip() {
+    let text = AGENDA
+      .replacen("#+TITLE: Agenda\n", "", 1)
+      .replace(" 10:00", "");
+    assert_eq!(text, render(&parse(&text)));
+  }
+}
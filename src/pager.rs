@@ -0,0 +1,95 @@
+use console::Term;
+use std::env;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether `--no-pager` keeps long output on the terminal
+static NO_PAGER: AtomicBool = AtomicBool::new(false);
+
+pub fn init(no_pager: bool) {
+  NO_PAGER.store(no_pager, Ordering::Relaxed);
+}
+
+/// `$TODO_PAGER`, then `$PAGER` and `less` like git, empty or `cat` pages nothing
+fn command(todo_pager: Option<String>, pager: Option<String>) -> Option<String> {
+  let pager = todo_pager.or(pager).unwrap_or_else(|| "less".to_string());
+  match pager.trim() {
+    "" | "cat" => None,
+    pager => Some(pager.to_string()),
+  }
+}
+
+/// Whether the lines need more rows than the terminal has, one stays for the prompt
+fn overflows(lines: &[String], height: usize) -> bool {
+  lines
+    .iter()
+    .map(|line| line.lines().count().max(1))
+    .sum::<usize>()
+    >= height
+}
+
+/// Print `lines`, through the pager when they don't fit on the terminal. `less` is told to
+/// keep the colors and leave the output on the screen when it quits, as git does.
+pub fn page(lines: &[String]) -> Result<(), Box<dyn Error>> {
+  let term = Term::stdout();
+  let pager = match (NO_PAGER.load(Ordering::Relaxed), term.size_checked()) {
+    (false, Some((height, _))) if overflows(lines, height as usize) => {
+      command(env::var("TODO_PAGER").ok(), env::var("PAGER").ok())
+    }
+    _ => None,
+  };
+  let Some(pager) = pager else {
+    for line in lines {
+      println!("{}", line);
+    }
+    return Ok(());
+  };
+
+  // Untested segment starts, this part needs a terminal
+  let mut command = Command::new("sh");
+  command.arg("-c").arg(&pager).stdin(Stdio::piped());
+  if env::var_os("LESS").is_none() {
+    command.env("LESS", "FRX");
+  }
+  if env::var_os("LV").is_none() {
+    command.env("LV", "-c");
+  }
+  let mut child = command
+    .spawn()
+    .map_err(|e| format!("Can't run the pager {}: {}", pager, e))?;
+  if let Some(mut stdin) = child.stdin.take() {
+    // Quitting the pager early closes its input, that's no error
+    for line in lines {
+      if writeln!(stdin, "{}", line).is_err() {
+        break;
+      }
+    }
+  }
+  child.wait()?;
+  Ok(())
+  // Untested segment ends
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pager_runs_for_output_taller_than_the_terminal() {
+    let some = |pager: &str| Some(pager.to_string());
+    assert_eq!(some("less"), command(None, None));
+    assert_eq!(some("most"), command(None, some("most")));
+    assert_eq!(some("bat -p"), command(some("bat -p"), some("most")));
+    assert_eq!(None, command(some(""), some("most")));
+    assert_eq!(None, command(None, some("cat")));
+
+    let lines = vec![
+      "Status todo".to_string(),
+      "Notes\nfirst\nsecond".to_string(),
+    ];
+    assert!(!overflows(&lines, 5));
+    assert!(overflows(&lines, 4));
+  }
+}
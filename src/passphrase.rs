@@ -0,0 +1,134 @@
+use crate::prompt;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use dialoguer::Password;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An argon2id hash in the PHC format, `$argon2id$v=19$m=...,t=...,p=...$<salt>$<hash>`.
+/// New hashes use the crate's defaults, what OWASP recommends.
+fn hash_with(passphrase: &str, params: Params) -> Result<String, Box<dyn Error>> {
+  let salt = SaltString::generate(&mut OsRng);
+  let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+    .hash_password(passphrase.as_bytes(), &salt)
+    .map_err(|e| e.to_string())?;
+  Ok(hash.to_string())
+}
+
+fn parse(hashed: &str) -> Result<PasswordHash<'_>, Box<dyn Error>> {
+  PasswordHash::new(hashed.trim())
+    .map_err(|e| format!("The passphrase hash isn't an argon2 one: {}", e).into())
+}
+
+/// Whether the passphrase is the one hashed, in constant time
+fn verify(passphrase: &str, hashed: &str) -> Result<bool, Box<dyn Error>> {
+  Ok(
+    Argon2::default()
+      .verify_password(passphrase.as_bytes(), &parse(hashed)?)
+      .is_ok(),
+  )
+}
+
+/// A `<file>.locked` next to the todos while they are locked
+fn marker(file: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+  let file = file.ok_or("Only todos in a file can be locked")?;
+  let mut name = file.as_os_str().to_owned();
+  name.push(".locked");
+  Ok(PathBuf::from(name))
+}
+
+/// Fails while the todos are locked, before anything opens them
+pub fn check(file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+  match file.is_some() && marker(file)?.exists() {
+    true => Err("The todos are locked, run todo unlock first".into()),
+    false => Ok(()),
+  }
+}
+
+// Untested segment starts, this part needs interactivity
+fn ask(prompt: &str) -> Result<String, Box<dyn Error>> {
+  prompt::require("unlock the todos in a terminal")?;
+  Ok(Password::new().with_prompt(prompt).interact()?)
+}
+
+/// `todo lock --hash`, a new passphrase's hash for the config or a keyring
+pub fn new_hash() -> Result<(), Box<dyn Error>> {
+  prompt::require("hash the passphrase in a terminal")?;
+  let passphrase = Password::new()
+    .with_prompt("New passphrase")
+    .with_confirmation("Repeat it", "The passphrases don't match")
+    .interact()?;
+  println!(
+    "[passphrase]\ntoken = \"{}\"",
+    hash_with(&passphrase, Params::default())?
+  );
+  Ok(())
+}
+// Untested segment ends
+
+/// `todo lock`, commands refuse to open the todos until they are unlocked with the passphrase
+/// of the config. Anyone who can delete the `.locked` file gets past it, it keeps others
+/// sharing the account out rather than encrypting anything.
+pub fn lock(hashed: &str, file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+  let marker = marker(file)?;
+  if marker.exists() {
+    println!("Already locked!");
+    return Ok(());
+  }
+  // A lock nobody can open would only be deleted by hand
+  parse(hashed)?;
+  fs::write(&marker, "")?;
+  println!("Locked, todo unlock opens them again");
+  Ok(())
+}
+
+fn unlock_with(passphrase: &str, hashed: &str, file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+  let marker = marker(file)?;
+  if !marker.exists() {
+    println!("Not locked!");
+    return Ok(());
+  }
+  if !verify(passphrase, hashed)? {
+    return Err("Wrong passphrase".into());
+  }
+  fs::remove_file(&marker)?;
+  println!("Unlocked!");
+  Ok(())
+}
+
+/// `todo unlock`, asking for the passphrase
+pub fn unlock(hashed: &str, file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+  if !marker(file)?.exists() {
+    return unlock_with("", hashed, file);
+  }
+  unlock_with(&ask("Passphrase")?, hashed, file)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn only_the_passphrase_unlocks() {
+    let file = env::temp_dir().join(format!("todo-locked-{}.db", std::process::id()));
+    let file = Some(file.as_path());
+    // Little memory and one pass to keep the test quick
+    let hashed = hash_with("open sesame", Params::new(8, 1, 1, None).unwrap()).unwrap();
+    assert!(hashed.starts_with("$argon2id$v=19$m=8,t=1,p=1$"));
+    assert!(verify("open sesame", &hashed).unwrap());
+    assert!(!verify("open sesame!", &hashed).unwrap());
+    assert!(verify("open sesame", "secret").is_err());
+
+    check(file).unwrap();
+    lock(&hashed, file).unwrap();
+    assert!(check(file).is_err());
+    assert!(unlock_with("sesame", &hashed, file).is_err());
+    assert!(check(file).is_err());
+    unlock_with("open sesame", &hashed, file).unwrap();
+    check(file).unwrap();
+    assert!(check(None).is_ok() && lock(&hashed, None).is_err());
+  }
+}
@@ -0,0 +1,401 @@
+use crate::Todo;
+use crate::bucket::Bucket;
+use crate::frequency::parse_frequency;
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::storage::{Storage, collect_todos_stored, open_in_memory, refuse_extras};
+use crate::tags::Tags;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use postgres::{Client, GenericClient, NoTls, Row};
+use rusqlite::Connection;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+/// Arbitrary key for the advisory lock held while the schema is set up
+const SCHEMA_LOCK: i64 = 0x746f646f;
+
+/// Same idea as the SQLite migrations, applied in order and tracked in `todo_schema`
+const MIGRATIONS: &[&str] = &[
+  "CREATE TABLE todos (
+     id BIGSERIAL PRIMARY KEY,
+     body TEXT NOT NULL,
+     status TEXT NOT NULL DEFAULT 'todo',
+     waiting_on TEXT,
+     follow_up DATE,
+     estimate INTEGER,
+     priority SMALLINT,
+     due DATE,
+     bucket TEXT NOT NULL DEFAULT 'inbox',
+     project TEXT,
+     snoozed_until DATE,
+     focused BOOLEAN NOT NULL DEFAULT false,
+     notes TEXT,
+     parent_id BIGINT REFERENCES todos (id) ON DELETE SET NULL,
+     created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+     updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+     version BIGINT NOT NULL DEFAULT 1
+   );
+   CREATE TABLE dependencies (
+     todo_id BIGINT NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+     depends_on BIGINT NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+     PRIMARY KEY (todo_id, depends_on)
+   );",
+  "ALTER TABLE todos ADD COLUMN tags TEXT, ADD COLUMN scheduled DATE;",
+  "ALTER TABLE todos ADD COLUMN position DOUBLE PRECISION;",
+  "ALTER TABLE todos ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT false;",
+  "ALTER TABLE todos ADD COLUMN expires DATE;",
+  "ALTER TABLE todos ADD COLUMN habit TEXT;",
+  "ALTER TABLE todos ADD COLUMN uuid TEXT UNIQUE;
+   UPDATE todos SET uuid = gen_random_uuid()::text;",
+  "ALTER TABLE todos ADD COLUMN reference TEXT;",
+];
+
+/// A list shared through a Postgres server.
+///
+/// Commands run against an in memory copy. Saving writes back only what changed and
+/// refuses to overwrite a todo someone else changed in the meantime, each row carries
+/// a version for that.
+pub struct Postgres {
+  client: RefCell<Client>,
+  /// Versions of the todos as they were loaded
+  versions: RefCell<HashMap<usize, (i64, Todo)>>,
+  dependencies: RefCell<HashSet<(usize, usize)>>,
+}
+
+impl Postgres {
+  pub fn connect(url: &str) -> Result<Postgres, Box<dyn Error>> {
+    let mut client = Client::connect(url, NoTls)?;
+    create_schema(&mut client)?;
+    Ok(Postgres {
+      client: RefCell::new(client),
+      versions: RefCell::new(HashMap::new()),
+      dependencies: RefCell::new(HashSet::new()),
+    })
+  }
+}
+
+fn create_schema(client: &mut Client) -> Result<(), Box<dyn Error>> {
+  let mut tx = client.transaction()?;
+  // Two clients starting at once must not both run the migrations
+  tx.execute("SELECT pg_advisory_xact_lock($1)", &[&SCHEMA_LOCK])?;
+  tx.batch_execute("CREATE TABLE IF NOT EXISTS todo_schema (version INTEGER NOT NULL)")?;
+  let version: i64 = tx
+    .query_one("SELECT count(*) FROM todo_schema", &[])?
+    .get(0);
+  for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+    tx.batch_execute(migration)?;
+    tx.execute(
+      "INSERT INTO todo_schema (version) VALUES ($1)",
+      &[&(index as i32 + 1)],
+    )?;
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+fn priority_from_i16(priority: i16) -> Option<Priority> {
+  match priority {
+    1 => Some(Priority::Low),
+    2 => Some(Priority::Medium),
+    3 => Some(Priority::High),
+    _ => None,
+  }
+}
+
+fn todo_from_row(row: &Row) -> Result<(i64, Todo), Box<dyn Error>> {
+  let status: String = row.try_get("status")?;
+  let bucket: String = row.try_get("bucket")?;
+  let todo = Todo {
+    id: row.try_get::<_, i64>("id")? as usize,
+    body: row.try_get("body")?,
+    status: Status::from_str(&status, true)?,
+    waiting_on: row.try_get("waiting_on")?,
+    follow_up: row.try_get("follow_up")?,
+    estimate: row
+      .try_get::<_, Option<i32>>("estimate")?
+      .map(|estimate| estimate as u32),
+    priority: row
+      .try_get::<_, Option<i16>>("priority")?
+      .and_then(priority_from_i16),
+    due: row.try_get("due")?,
+    bucket: Bucket::from_str(&bucket, true)?,
+    project: row.try_get("project")?,
+    snoozed_until: row.try_get("snoozed_until")?,
+    focused: row.try_get("focused")?,
+    notes: row.try_get("notes")?,
+    parent_id: row
+      .try_get::<_, Option<i64>>("parent_id")?
+      .map(|parent| parent as usize),
+    tags: Tags(
+      row
+        .try_get::<_, Option<String>>("tags")?
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect(),
+    ),
+    scheduled: row.try_get("scheduled")?,
+    pinned: row.try_get("pinned")?,
+    expires: row.try_get("expires")?,
+    habit: row
+      .try_get::<_, Option<String>>("habit")?
+      .map(|habit| parse_frequency(&habit))
+      .transpose()?,
+    position: row.try_get("position")?,
+    created_at: Some(row.try_get("created_at")?),
+    updated_at: Some(row.try_get("updated_at")?),
+    uuid: row.try_get("uuid")?,
+    reference: row.try_get("reference")?,
+  };
+  Ok((row.try_get("version")?, todo))
+}
+
+/// Column values in the order of `COLUMNS`, `parent` is already translated to a server id
+struct Values {
+  body: String,
+  status: String,
+  waiting_on: Option<String>,
+  follow_up: Option<NaiveDate>,
+  estimate: Option<i32>,
+  priority: Option<i16>,
+  due: Option<NaiveDate>,
+  bucket: String,
+  project: Option<String>,
+  snoozed_until: Option<NaiveDate>,
+  focused: bool,
+  notes: Option<String>,
+  parent: Option<i64>,
+  tags: Option<String>,
+  scheduled: Option<NaiveDate>,
+  pinned: bool,
+  expires: Option<NaiveDate>,
+  habit: Option<String>,
+  position: Option<f64>,
+  created_at: DateTime<Utc>,
+  updated_at: DateTime<Utc>,
+  uuid: Option<String>,
+  reference: Option<String>,
+}
+
+const COLUMNS: &str = "body, status, waiting_on, follow_up, estimate, priority, due, bucket,
+  project, snoozed_until, focused, notes, parent_id, tags, scheduled, pinned, expires,
+  habit, position, created_at, updated_at, uuid, reference";
+
+impl Values {
+  fn new(todo: &Todo, parent: Option<i64>) -> Values {
+    Values {
+      body: todo.body.clone(),
+      status: todo.status.as_str().to_string(),
+      waiting_on: todo.waiting_on.clone(),
+      follow_up: todo.follow_up,
+      estimate: todo.estimate.map(|estimate| estimate as i32),
+      priority: todo.priority.map(|priority| priority as i16),
+      due: todo.due,
+      bucket: todo.bucket.as_str().to_string(),
+      project: todo.project.clone(),
+      snoozed_until: todo.snoozed_until,
+      focused: todo.focused,
+      notes: todo.notes.clone(),
+      parent,
+      tags: Some(todo.tags.0.join(" ")).filter(|tags| !tags.is_empty()),
+      scheduled: todo.scheduled,
+      pinned: todo.pinned,
+      expires: todo.expires,
+      habit: todo.habit.map(String::from),
+      position: todo.position,
+      created_at: todo.created_at.unwrap_or_else(Utc::now),
+      updated_at: todo.updated_at.unwrap_or_else(Utc::now),
+      uuid: todo.uuid.clone(),
+      reference: todo.reference.clone(),
+    }
+  }
+
+  fn params(&self) -> [&(dyn postgres::types::ToSql + Sync); 23] {
+    [
+      &self.body,
+      &self.status,
+      &self.waiting_on,
+      &self.follow_up,
+      &self.estimate,
+      &self.priority,
+      &self.due,
+      &self.bucket,
+      &self.project,
+      &self.snoozed_until,
+      &self.focused,
+      &self.notes,
+      &self.parent,
+      &self.tags,
+      &self.scheduled,
+      &self.pinned,
+      &self.expires,
+      &self.habit,
+      &self.position,
+      &self.created_at,
+      &self.updated_at,
+      &self.uuid,
+      &self.reference,
+    ]
+  }
+}
+
+fn conflict(todo: &Todo) -> Box<dyn Error> {
+  format!(
+    "'{}' was changed by someone else in the meantime, nothing saved, run the command again",
+    todo.body
+  )
+  .into()
+}
+
+impl Storage for Postgres {
+  fn open(&self) -> Result<Connection, Box<dyn Error>> {
+    let mut client = self.client.borrow_mut();
+    let mut versions = HashMap::new();
+    for row in client.query("SELECT * FROM todos ORDER BY id", &[])? {
+      let (version, todo) = todo_from_row(&row)?;
+      versions.insert(todo.id, (version, todo));
+    }
+    let dependencies = client
+      .query("SELECT todo_id, depends_on FROM dependencies", &[])?
+      .iter()
+      .map(|row| (row.get::<_, i64>(0) as usize, row.get::<_, i64>(1) as usize))
+      .collect::<HashSet<(usize, usize)>>();
+
+    let mut todos = versions
+      .values()
+      .map(|(_, todo)| todo.clone())
+      .collect::<Vec<Todo>>();
+    todos.sort_by_key(|todo| todo.id);
+    let conn = open_in_memory(&todos)?;
+    refuse_extras("Postgres", &conn)?;
+    for (todo_id, depends_on) in &dependencies {
+      conn.execute(
+        "INSERT INTO dependencies (todo_id, depends_on) VALUES (?1, ?2)",
+        (todo_id, depends_on),
+      )?;
+    }
+    *self.versions.borrow_mut() = versions;
+    *self.dependencies.borrow_mut() = dependencies;
+    Ok(conn)
+  }
+
+  fn save(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let todos = collect_todos_stored(conn)?;
+    let versions = self.versions.borrow();
+    let mut client = self.client.borrow_mut();
+    let mut tx = client.transaction()?;
+
+    // New todos get their id from the server, others may have added todos meanwhile
+    let mut ids = versions
+      .keys()
+      .map(|id| (*id, *id as i64))
+      .collect::<HashMap<usize, i64>>();
+    for todo in todos.iter().filter(|todo| !versions.contains_key(&todo.id)) {
+      let values = Values::new(todo, None);
+      let row = tx.query_one(
+        &format!(
+          "INSERT INTO todos ({COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+             $12, $13, $14, $15, $16, $17, $18, $19, $20,
+             $21, $22, $23) RETURNING id"
+        ),
+        &values.params(),
+      )?;
+      ids.insert(todo.id, row.get(0));
+    }
+
+    for todo in &todos {
+      let parent = todo.parent_id.and_then(|parent| ids.get(&parent).copied());
+      let values = Values::new(todo, parent);
+      match versions.get(&todo.id) {
+        Some((_, loaded)) if loaded == todo && loaded.position == todo.position => continue,
+        Some((version, _)) => {
+          let mut params = values.params().to_vec();
+          let id = todo.id as i64;
+          params.push(&id);
+          params.push(version);
+          let changed = tx.execute(
+            &format!(
+              "UPDATE todos SET ({COLUMNS}) = ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+                 $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23),
+                 version = version + 1
+               WHERE id = $24 and version = $25"
+            ),
+            &params,
+          )?;
+          if changed == 0 {
+            return Err(conflict(todo));
+          }
+        }
+        // Inserted above without a parent, which may only have an id now
+        None if parent.is_some() => {
+          tx.execute(
+            "UPDATE todos SET parent_id = $1 WHERE id = $2",
+            &[&parent, &ids[&todo.id]],
+          )?;
+        }
+        None => {}
+      }
+    }
+
+    let kept = todos.iter().map(|todo| todo.id).collect::<HashSet<usize>>();
+    for (id, (version, loaded)) in versions.iter().filter(|(id, _)| !kept.contains(id)) {
+      let deleted = tx.execute(
+        "DELETE FROM todos WHERE id = $1 and version = $2",
+        &[&(*id as i64), version],
+      )?;
+      if deleted == 0 {
+        return Err(conflict(loaded));
+      }
+    }
+
+    save_dependencies(&mut tx, conn, &ids, &self.dependencies.borrow())?;
+    tx.commit()?;
+    Ok(())
+  }
+
+  fn without_history(&self) -> Option<&'static str> {
+    Some("Postgres")
+  }
+}
+
+fn save_dependencies(
+  tx: &mut impl GenericClient,
+  conn: &Connection,
+  ids: &HashMap<usize, i64>,
+  loaded: &HashSet<(usize, usize)>,
+) -> Result<(), Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT todo_id, depends_on FROM dependencies")?;
+  let current = stmt
+    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .filter_map(|s| s.ok())
+    .collect::<HashSet<(usize, usize)>>();
+
+  for (todo_id, depends_on) in current.difference(loaded) {
+    tx.execute(
+      "INSERT INTO dependencies (todo_id, depends_on) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+      &[&ids[todo_id], &ids[depends_on]],
+    )?;
+  }
+  for (todo_id, depends_on) in loaded.difference(&current) {
+    tx.execute(
+      "DELETE FROM dependencies WHERE todo_id = $1 and depends_on = $2",
+      &[&(*todo_id as i64), &(*depends_on as i64)],
+    )?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn priorities_map_back() {
+    for priority in [Priority::Low, Priority::Medium, Priority::High] {
+      assert_eq!(Some(priority), priority_from_i16(priority as i16));
+    }
+    assert_eq!(None, priority_from_i16(7));
+  }
+}
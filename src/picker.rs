@@ -0,0 +1,438 @@
+use crate::filter::{Accents, Case, Matcher, Mode};
+use crate::status::Status;
+use crate::{Todo, ascii, emoji, keys, layout};
+use chrono::NaiveDate;
+use console::{measure_text_width, pad_str, style};
+use dialoguer::{FuzzySelect, Input, Select, theme::ColorfulTheme};
+use rusqlite::Connection;
+use std::error::Error;
+use std::io::BufRead;
+
+/// Tags wider than this are cut off so the body stays in view
+const TAGS_WIDTH: usize = 24;
+
+/// With more todos than fit on a screen the picker asks for a status first
+const ASK_STATUS_OVER: usize = 20;
+
+/// Todos a picker loads at once, the item after them opens the next page
+pub const PAGE: usize = 500;
+
+/// The item that opens the next page
+pub const MORE: &str = "… more todos";
+
+/// What to do when a picker can't run, in scripts and cron
+pub const PASS_IDS: &str = "pass ids like 3 or 1-5 or pipe them in one per line";
+
+/// Which todos a picker offers
+#[derive(clap::Args, Clone, Debug, Default, PartialEq)]
+pub struct PickerFilter {
+  /// Only offer incomplete todos
+  #[arg(short, long, conflicts_with_all = ["completed", "status"])]
+  pub incomplete: bool,
+
+  /// Only offer completed todos
+  #[arg(long, conflicts_with = "status")]
+  pub completed: bool,
+
+  /// Only offer todos with this status
+  #[arg(long)]
+  pub status: Option<Status>,
+}
+
+impl PickerFilter {
+  fn matches(&self, status: Status) -> bool {
+    if self.incomplete {
+      return status.is_open();
+    }
+    if self.completed {
+      return !status.is_open();
+    }
+    self.status.is_none_or(|own| own == status)
+  }
+
+  /// The SQL condition for the todos to offer
+  pub fn condition(&self) -> String {
+    if self.incomplete {
+      return "status not in ('done', 'cancelled')".to_string();
+    }
+    if self.completed {
+      return "status in ('done', 'cancelled')".to_string();
+    }
+    match self.status {
+      Some(status) => format!("status is '{}'", status.as_str()),
+      None => "true".to_string(),
+    }
+  }
+
+  /// The filter to use, asking for a status inside the picker when nothing was given and
+  /// more todos than fit on a screen match `available`
+  pub fn resolve(
+    &self,
+    available: &str,
+    conn: &Connection,
+  ) -> Result<PickerFilter, Box<dyn Error>> {
+    if *self != PickerFilter::default() {
+      return Ok(self.clone());
+    }
+    let counts = status_counts(available, conn)?;
+    if counts.iter().map(|(_, count)| count).sum::<usize>() <= ASK_STATUS_OVER {
+      return Ok(self.clone());
+    }
+    ask_filter(&counts)
+  }
+}
+
+/// How many todos matching `condition` have each status, counted by SQLite
+fn status_counts(
+  condition: &str,
+  conn: &Connection,
+) -> Result<Vec<(Status, usize)>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(&format!(
+    "SELECT status, count(*) FROM todos WHERE {} GROUP BY status",
+    condition
+  ))?;
+  let counts = stmt
+    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect::<Result<Vec<(Status, usize)>, _>>()?;
+  Ok(counts)
+}
+
+/// Choices for narrowing down a long picker, with a count for each
+fn filter_choices(counts: &[(Status, usize)]) -> Vec<(String, PickerFilter)> {
+  let count = |filter: &PickerFilter| {
+    counts
+      .iter()
+      .filter(|(status, _)| filter.matches(*status))
+      .map(|(_, count)| count)
+      .sum::<usize>()
+  };
+  let mut choices = vec![
+    ("All".to_string(), PickerFilter::default()),
+    (
+      "Incomplete".to_string(),
+      PickerFilter {
+        incomplete: true,
+        ..Default::default()
+      },
+    ),
+  ];
+  for status in Status::ALL {
+    choices.push((
+      ascii::text(&format!("{} {}", status.glyph(), status)),
+      PickerFilter {
+        status: Some(status),
+        ..Default::default()
+      },
+    ));
+  }
+  choices
+    .into_iter()
+    .map(|(label, filter)| (format!("{} ({})", label, count(&filter)), filter))
+    .filter(|(_, filter)| *filter == PickerFilter::default() || count(filter) > 0)
+    .collect()
+}
+
+// Untested segment starts, this part needs interactivity
+fn ask_filter(counts: &[(Status, usize)]) -> Result<PickerFilter, Box<dyn Error>> {
+  let choices = filter_choices(counts);
+  let labels = choices
+    .iter()
+    .map(|(label, _)| label)
+    .collect::<Vec<&String>>();
+  let choice = Select::with_theme(&ColorfulTheme::default())
+    .with_prompt("Show which todos?")
+    .default(1.min(labels.len() - 1))
+    .items(&labels[..])
+    .interact()?;
+  Ok(choices[choice].1.clone())
+}
+// Untested segment ends
+
+/// Targets piped in instead of picked, one per line
+pub fn piped_targets(input: impl BufRead) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut targets = vec![];
+  for line in input.lines() {
+    let line = line?;
+    if !line.trim().is_empty() {
+      targets.push(line.trim().to_string());
+    }
+  }
+  if targets.is_empty() {
+    return Err(format!("Nothing piped in, {}", PASS_IDS).into());
+  }
+  Ok(targets)
+}
+
+/// The todos whose body matches `query`, with their index and the matched letters
+/// highlighted when the matcher asks for it
+fn search(todos: &[Todo], query: &str, matcher: Matcher) -> Vec<(usize, Todo)> {
+  todos
+    .iter()
+    .enumerate()
+    .filter_map(|(index, todo)| {
+      let found = matcher.find(&todo.body, query)?;
+      let body = match matcher.highlight {
+        true => todo
+          .body
+          .chars()
+          .enumerate()
+          .map(|(at, c)| match found.contains(&at) {
+            true => style(c).bold().underlined().to_string(),
+            false => c.to_string(),
+          })
+          .collect(),
+        false => todo.body.clone(),
+      };
+      Some((
+        index,
+        Todo {
+          body,
+          ..todo.clone()
+        },
+      ))
+    })
+    .collect()
+}
+
+// Untested segment starts, this part needs interactivity
+/// Pick one of `todos` by typing, the index right after them is the item for the next page
+pub fn pick(
+  prompt: &str,
+  todos: &[Todo],
+  more: bool,
+  today: NaiveDate,
+) -> Result<usize, Box<dyn Error>> {
+  let matcher = Matcher::current();
+  let with_more = |mut lines: Vec<String>| {
+    if more {
+      lines.push(ascii::text(MORE));
+    }
+    lines
+  };
+  // FuzzySelect always matches skim style with smart case and accents, for anything else the text is
+  // asked for first
+  if matcher.mode == Mode::Fuzzy
+    && matcher.case == Case::Smart
+    && matcher.accents == Accents::Respect
+  {
+    let lines = with_more(items(todos, today, layout::picker_width()));
+    return Ok(
+      FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .vim_mode(keys::picker_vim_mode())
+        .highlight_matches(matcher.highlight)
+        .default(0)
+        .items(&lines[..])
+        .interact()?,
+    );
+  }
+  let query: String = Input::with_theme(&ColorfulTheme::default())
+    .with_prompt(prompt)
+    .allow_empty(true)
+    .interact_text()?;
+  let found = search(todos, &query, matcher);
+  let shown = found
+    .iter()
+    .map(|(_, todo)| todo.clone())
+    .collect::<Vec<Todo>>();
+  let lines = with_more(items(&shown, today, layout::picker_width()));
+  if lines.is_empty() {
+    return Err(format!("No todo matches '{}'", query).into());
+  }
+  let choice = Select::with_theme(&ColorfulTheme::default())
+    .with_prompt(prompt)
+    .default(0)
+    .items(&lines[..])
+    .interact()?;
+  Ok(found.get(choice).map_or(todos.len(), |(index, _)| *index))
+}
+// Untested segment ends
+
+/// Picker lines with aligned columns: id, status glyph, pin, due date, tags and the body,
+/// cut off at `width` when there is one
+pub fn items(todos: &[Todo], today: NaiveDate, width: Option<usize>) -> Vec<String> {
+  let id_width = todos
+    .iter()
+    .map(|todo| todo.id.to_string().len())
+    .max()
+    .unwrap_or(0);
+  let has_pins = todos.iter().any(|todo| todo.pinned);
+  let has_due = todos.iter().any(|todo| todo.due.is_some());
+  let tags_width = todos
+    .iter()
+    .map(|todo| measure_text_width(&todo.tags.to_string()))
+    .max()
+    .unwrap_or(0)
+    .min(TAGS_WIDTH);
+
+  todos
+    .iter()
+    .map(|todo| {
+      let mut columns = vec![
+        format!("{:>width$}", todo.id, width = id_width),
+        todo.status.glyph().to_string(),
+      ];
+      if has_pins {
+        columns.push(match todo.pinned {
+          true => style(crate::PIN).yellow().bold().to_string(),
+          false => " ".to_string(),
+        });
+      }
+      if has_due {
+        let due = todo.due.map(|due| due.to_string()).unwrap_or_default();
+        let due = format!("{:<10}", due);
+        columns.push(match todo.due {
+          Some(day) if day < today && todo.status.is_open() => style(due).red().to_string(),
+          _ => style(due).dim().to_string(),
+        });
+      }
+      if tags_width > 0 {
+        let tags = todo.tags.to_string();
+        let tags = pad_str(
+          &tags,
+          tags_width,
+          console::Alignment::Left,
+          Some(ascii::ellipsis()),
+        );
+        columns.push(style(tags).magenta().to_string());
+      }
+      columns.push(format!(
+        "{}{}",
+        layout::signals(todo, today),
+        emoji::display(&todo.body)
+      ));
+      let item = ascii::text(&columns.join(" "));
+      match width {
+        Some(width) => layout::truncate(&item, width),
+        None => item,
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::storage::insert_todo;
+  use crate::tags::Tags;
+  use crate::{TODO_SELECT, collect_todos};
+
+  fn todo(status: Status) -> Todo {
+    Todo {
+      status,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn filter_todos() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    for todo in [
+      todo(Status::Todo),
+      todo(Status::Done),
+      todo(Status::Waiting),
+    ] {
+      insert_todo(&todo, &conn).unwrap();
+    }
+    let statuses = |filter: PickerFilter| {
+      let filter = filter.resolve("true", &conn).unwrap();
+      collect_todos(
+        format!("{TODO_SELECT} WHERE {} ORDER BY id;", filter.condition()),
+        &conn,
+      )
+      .unwrap()
+      .iter()
+      .map(|todo| todo.status)
+      .collect::<Vec<Status>>()
+    };
+    assert_eq!(
+      vec![Status::Todo, Status::Waiting],
+      statuses(PickerFilter {
+        incomplete: true,
+        ..Default::default()
+      })
+    );
+    assert_eq!(
+      vec![Status::Done],
+      statuses(PickerFilter {
+        completed: true,
+        ..Default::default()
+      })
+    );
+    // Short lists are not narrowed down without a filter
+    assert_eq!(3, statuses(PickerFilter::default()).len());
+  }
+
+  #[test]
+  fn choices_skip_empty_statuses() {
+    let labels = filter_choices(&[(Status::Todo, 1), (Status::Done, 1)])
+      .into_iter()
+      .map(|(label, _)| label)
+      .collect::<Vec<String>>();
+    assert_eq!(
+      vec!["All (2)", "Incomplete (1)", "○ todo (1)", "✔ done (1)"],
+      labels
+    );
+  }
+
+  #[test]
+  fn targets_are_piped_in_without_a_terminal() {
+    let piped = |input: &str| piped_targets(input.as_bytes());
+    assert_eq!(
+      vec!["3".to_string(), "buy milk".to_string()],
+      piped("3\n\n  buy milk \n").unwrap()
+    );
+    assert!(piped("\n").is_err());
+  }
+
+  #[test]
+  fn search_finds_bodies_by_the_matcher() {
+    let todo = |body: &str| Todo {
+      body: body.to_string(),
+      ..Default::default()
+    };
+    let todos = vec![todo("Buy milk"), todo("Call mum"), todo("Milk again")];
+    let substring = Matcher {
+      mode: Mode::Substring,
+      case: Case::Ignore,
+      ..Default::default()
+    };
+    let found = search(&todos, "MILK", substring);
+    assert_eq!(
+      vec![0, 2],
+      found.iter().map(|(i, _)| *i).collect::<Vec<_>>()
+    );
+    let plain = Matcher {
+      highlight: false,
+      ..substring
+    };
+    assert_eq!("Buy milk", search(&todos, "milk", plain)[0].1.body);
+    assert_eq!(3, search(&todos, "", plain).len());
+  }
+
+  #[test]
+  fn items_are_aligned() {
+    console::set_colors_enabled(false);
+    let todos = vec![
+      Todo {
+        id: 9,
+        body: "Milk".to_string(),
+        due: NaiveDate::from_ymd_opt(2024, 3, 5),
+        ..Default::default()
+      },
+      Todo {
+        id: 10,
+        body: "Carl".to_string(),
+        status: Status::Done,
+        tags: Tags(vec!["phone".to_string()]),
+        ..Default::default()
+      },
+    ];
+    assert_eq!(
+      vec![" 9 ○ 2024-03-05        Milk", "10 ✔            #phone Carl"],
+      items(&todos, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), None)
+    );
+  }
+}
@@ -0,0 +1,142 @@
+use crate::deps::BLOCKED;
+use crate::duration::format_duration;
+use crate::{TODO_SELECT, Todo, collect_todos};
+use console::style;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Today's tasks split by whether they fit in the available time
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+  scheduled: Vec<Todo>,
+  skipped: Vec<Todo>,
+  unestimated: Vec<Todo>,
+}
+
+impl Plan {
+  fn scheduled_minutes(&self) -> u32 {
+    self.scheduled.iter().filter_map(|todo| todo.estimate).sum()
+  }
+}
+
+/// Actionable todos that are not blocked, the ones already in progress first
+fn collect_todos_actionable(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} where status in ('todo', 'in-progress') and bucket is not 'someday'
+       and not {BLOCKED}
+       order by status is 'in-progress' desc, id;"
+    ),
+    conn,
+  )
+}
+
+/// Fill the capacity in order, skipping todos that no longer fit
+fn select(todos: Vec<Todo>, capacity: u32) -> Plan {
+  let mut plan = Plan::default();
+  let mut remaining = capacity;
+
+  for todo in todos {
+    match todo.estimate {
+      Some(estimate) if estimate <= remaining => {
+        remaining -= estimate;
+        plan.scheduled.push(todo);
+      }
+      Some(_) => plan.skipped.push(todo),
+      None => plan.unestimated.push(todo),
+    }
+  }
+
+  plan
+}
+
+pub fn plan(capacity: u32, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let plan = select(collect_todos_actionable(conn)?, capacity);
+  let scheduled = plan.scheduled_minutes();
+
+  println!(
+    "Plan for {} ({} scheduled, {} free):",
+    format_duration(capacity),
+    format_duration(scheduled),
+    format_duration(capacity - scheduled)
+  );
+  for (number, todo) in plan.scheduled.iter().enumerate() {
+    let estimate = format_duration(todo.estimate.unwrap_or_default());
+    println!("{}. {} {}", number + 1, todo.body, style(estimate).dim());
+  }
+
+  if !plan.skipped.is_empty() {
+    println!("{}", style("Does not fit:").yellow());
+    for todo in &plan.skipped {
+      let estimate = format_duration(todo.estimate.unwrap_or_default());
+      println!("- {} {}", todo.body, style(estimate).dim());
+    }
+  }
+  if !plan.unestimated.is_empty() {
+    println!("{}", style("Needs an estimate:").yellow());
+    for todo in &plan.unestimated {
+      println!("- {}", todo.body);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, add, set_status};
+
+  fn todo(id: usize, estimate: Option<u32>) -> Todo {
+    Todo {
+      id,
+      body: format!("Task {}", id),
+      estimate,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn select_skips_what_does_not_fit() {
+    let plan = select(
+      vec![
+        todo(1, Some(120)),
+        todo(2, Some(300)),
+        todo(3, None),
+        todo(4, Some(180)),
+      ],
+      360,
+    );
+    assert_eq!(
+      Plan {
+        scheduled: vec![todo(1, Some(120)), todo(4, Some(180))],
+        skipped: vec![todo(2, Some(300))],
+        unestimated: vec![todo(3, None)],
+      },
+      plan
+    );
+    assert_eq!(300, plan.scheduled_minutes());
+  }
+
+  #[test]
+  fn actionable_puts_in_progress_first() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string(), "Katia".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    let todos = crate::collect_todos_all(&conn).unwrap();
+    _ = set_status(vec![todos[0].clone()], Status::Done, &conn);
+    _ = set_status(vec![todos[2].clone()], Status::InProgress, &conn);
+
+    let ids = collect_todos_actionable(&conn)
+      .unwrap()
+      .iter()
+      .map(|todo| todo.id)
+      .collect::<Vec<usize>>();
+    assert_eq!(vec![3, 2], ids);
+  }
+}
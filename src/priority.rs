@@ -0,0 +1,78 @@
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+#[cfg(feature = "cli")]
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How important a todo is, stored as an integer so it sorts naturally
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+  Low = 1,
+  Medium = 2,
+  High = 3,
+}
+
+impl Priority {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Priority::Low => "low",
+      Priority::Medium => "medium",
+      Priority::High => "high",
+    }
+  }
+
+  /// Short marker shown next to the todo in list output
+  pub fn marker(&self) -> &'static str {
+    match self {
+      Priority::Low => "!",
+      Priority::Medium => "!!",
+      Priority::High => "!!!",
+    }
+  }
+}
+
+impl fmt::Display for Priority {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+#[cfg(feature = "cli")]
+impl ToSql for Priority {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(*self as i64))
+  }
+}
+
+#[cfg(feature = "cli")]
+impl FromSql for Priority {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    match value.as_i64()? {
+      1 => Ok(Priority::Low),
+      2 => Ok(Priority::Medium),
+      3 => Ok(Priority::High),
+      other => Err(FromSqlError::OutOfRange(other)),
+    }
+  }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+  use super::*;
+  use rusqlite::Connection;
+
+  #[test]
+  fn priority_sql_round_trip() {
+    let conn = Connection::open_in_memory().unwrap();
+    for priority in [Priority::Low, Priority::Medium, Priority::High] {
+      let back: Priority = conn
+        .query_row("SELECT ?1", (priority,), |row| row.get(0))
+        .unwrap();
+      assert_eq!(priority, back);
+    }
+    assert!(Priority::High > Priority::Low);
+  }
+}
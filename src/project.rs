@@ -0,0 +1,263 @@
+use crate::priority::Priority;
+use crate::sort::{self, Sort};
+use crate::tags::Tags;
+use crate::{TODO_SELECT, collect_todos, undo};
+use console::style;
+use rusqlite::{Connection, OptionalExtension};
+use std::env;
+use std::error::Error;
+
+/// Defaults a project carries, it works like a list of its own
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Settings {
+  /// Tags every todo added to the project gets
+  pub tags: Tags,
+  /// Priority of todos added without one
+  pub priority: Option<Priority>,
+  /// How `list --project` sorts the project without `--sort`
+  pub sort: Option<Sort>,
+  /// Done and cancelled todos are archived this many days after they last changed
+  pub archive_days: Option<i64>,
+}
+
+/// The defaults of `project`, none when nothing was set
+pub fn settings(project: &str, conn: &Connection) -> Result<Settings, Box<dyn Error>> {
+  let row = conn
+    .prepare_cached(
+      "SELECT tags, priority, sort, archive_days FROM list_settings WHERE project = ?1",
+    )?
+    .query_row((project,), |row| {
+      Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get::<_, Option<String>>(2)?,
+        row.get(3)?,
+      ))
+    })
+    .optional()?;
+  let Some((tags, priority, sort, archive_days)) = row else {
+    return Ok(Settings::default());
+  };
+  Ok(Settings {
+    tags,
+    priority,
+    sort: sort.as_deref().map(sort::parse).transpose()?,
+    archive_days,
+  })
+}
+
+fn show(name: &str, settings: &Settings) {
+  if *settings == Settings::default() {
+    println!("{} has no defaults, todo project --help shows them", name);
+    return;
+  }
+  println!("{}", style(format!("Project {}:", name)).bold());
+  let field = |label: &str, value: String| {
+    println!("  {} {}", style(format!("{:<9}", label)).dim(), value);
+  };
+  if !settings.tags.is_empty() {
+    field("Tags", settings.tags.badges());
+  }
+  if let Some(priority) = settings.priority {
+    field("Priority", priority.to_string());
+  }
+  if let Some(sort) = &settings.sort {
+    field("Sort", sort.to_string());
+  }
+  if let Some(days) = settings.archive_days {
+    field("Archive", format!("completed todos after {} days", days));
+  }
+}
+
+/// `todo project`, the defaults given replace the ones set before, without any they are
+/// shown
+pub fn project(
+  name: &str,
+  changes: Settings,
+  clear: bool,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  if clear {
+    conn.execute("DELETE FROM list_settings WHERE project = ?1", (name,))?;
+    println!("Cleared the defaults of {}", name);
+    return Ok(());
+  }
+  let mut settings = settings(name, conn)?;
+  if changes == Settings::default() {
+    show(name, &settings);
+    return Ok(());
+  }
+  if !changes.tags.is_empty() {
+    settings.tags = changes.tags;
+  }
+  settings.priority = changes.priority.or(settings.priority);
+  settings.sort = changes.sort.or(settings.sort);
+  settings.archive_days = changes.archive_days.or(settings.archive_days);
+  conn.execute(
+    "INSERT OR REPLACE INTO list_settings (project, tags, priority, sort, archive_days)
+     VALUES (?1, ?2, ?3, ?4, ?5)",
+    (
+      name,
+      &settings.tags,
+      settings.priority,
+      settings.sort.as_ref().map(Sort::to_string),
+      settings.archive_days,
+    ),
+  )?;
+  show(name, &settings);
+  Ok(())
+}
+
+/// The project `$TODO_LIST` names, direnv can set it per directory, or else the one `todo
+/// use` picked. An empty `TODO_LIST` works in none.
+fn current_of(
+  todo_list: Option<String>,
+  conn: &Connection,
+) -> Result<Option<String>, Box<dyn Error>> {
+  if let Some(name) = todo_list {
+    return Ok(Some(name.trim().to_string()).filter(|name| !name.is_empty()));
+  }
+  Ok(
+    conn
+      .query_row("SELECT project FROM current_list", (), |row| row.get(0))
+      .optional()?,
+  )
+}
+
+/// The project todos are added to and listed from without `--project`
+pub fn current(conn: &Connection) -> Result<Option<String>, Box<dyn Error>> {
+  current_of(env::var("TODO_LIST").ok(), conn)
+}
+
+/// `todo use`, without a name it shows the project worked in
+pub fn select(name: Option<&str>, clear: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  if clear {
+    conn.execute("DELETE FROM current_list", ())?;
+  } else if let Some(name) = name {
+    conn.execute(
+      "INSERT OR REPLACE INTO current_list (id, project) VALUES (1, ?1)",
+      (name,),
+    )?;
+  }
+  let todo_list = env::var("TODO_LIST").ok();
+  let from = match todo_list.is_some() {
+    true => style(" (TODO_LIST decides here)").dim().to_string(),
+    false => String::new(),
+  };
+  match current_of(todo_list, conn)? {
+    Some(name) => println!("Working in {}{}", name, from),
+    None => println!("Not working in a project{}", from),
+  }
+  Ok(())
+}
+
+/// Runs before every command like expiry, archived todos are removed in an operation of
+/// their own and `--include archived` still finds them
+pub fn archive(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos(
+    format!(
+      "{TODO_SELECT} where status in ('done', 'cancelled') and exists (
+         SELECT 1 FROM list_settings WHERE list_settings.project = todos.project
+           and updated_at <= datetime('now', '-' || archive_days || ' days'));"
+    ),
+    conn,
+  )?;
+  if todos.is_empty() {
+    return Ok(());
+  }
+  for todo in todos {
+    conn.execute("DELETE FROM todos where id is ?1", (todo.id,))?;
+    // Runs before every command, stdout is for what the command prints
+    eprintln!("Archived: {}", todo.body);
+  }
+  undo::record("archive", true, conn)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, ListOptions, add, bodies, collect_todos_all, collect_todos_listed};
+
+  #[test]
+  fn projects_bring_their_defaults() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let changes = Settings {
+      tags: Tags(vec!["home".to_string()]),
+      priority: Some(Priority::Low),
+      ..Default::default()
+    };
+    project("House", changes, false, &conn).unwrap();
+    let changes = Settings {
+      sort: Some(sort::parse("body").unwrap()),
+      archive_days: Some(30),
+      ..Default::default()
+    };
+    project("House", changes, false, &conn).unwrap();
+    let house = settings("House", &conn).unwrap();
+    assert_eq!(
+      (Some(Priority::Low), Some(30)),
+      (house.priority, house.archive_days)
+    );
+    assert_eq!("body:asc", house.sort.unwrap().to_string());
+
+    let options = AddOptions {
+      project: Some("House".to_string()),
+      priority: Some(Priority::High),
+      tags: vec!["diy".to_string()],
+      ..Default::default()
+    };
+    _ = add(vec!["Paint shed".to_string()], &options, &conn);
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(
+      (Some(Priority::High), "#diy #home".to_string()),
+      (todos[0].priority, todos[0].tags.to_string())
+    );
+    assert_eq!((None, true), (todos[1].priority, todos[1].tags.is_empty()));
+    let listed = ListOptions {
+      project: Some("House".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(
+      vec!["Paint shed"],
+      bodies(&collect_todos_listed(&listed, &conn).unwrap())
+    );
+    let quoted = ListOptions {
+      project: Some("Bob's' or 1=1 --".to_string()),
+      ..Default::default()
+    };
+    assert!(collect_todos_listed(&quoted, &conn).unwrap().is_empty());
+
+    // Completed a while ago, only those of the project go
+    conn
+      .execute_batch(
+        "UPDATE todos SET status = 'done', updated_at = '2020-01-01 00:00:00';
+         INSERT INTO todos (body, project, status) VALUES ('Fix sink', 'House', 'done');",
+      )
+      .unwrap();
+    archive(&conn).unwrap();
+    assert_eq!(
+      vec!["Milk", "Fix sink"],
+      bodies(&collect_todos_all(&conn).unwrap())
+    );
+    project("House", Settings::default(), true, &conn).unwrap();
+    assert_eq!(Settings::default(), settings("House", &conn).unwrap());
+  }
+
+  #[test]
+  fn todo_list_overrides_the_project_used() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let current = |todo_list: Option<&str>| current_of(todo_list.map(String::from), &conn).unwrap();
+    assert_eq!(None, current(None));
+    select(Some("Work"), false, &conn).unwrap();
+    select(Some("House"), false, &conn).unwrap();
+    assert_eq!(Some("House".to_string()), current(None));
+    assert_eq!(Some("Garden".to_string()), current(Some(" Garden")));
+    assert_eq!(None, current(Some("")));
+    select(None, true, &conn).unwrap();
+    assert_eq!(None, current(None));
+  }
+}
@@ -0,0 +1,58 @@
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether `--no-input` forbids every prompt, for CI and automation
+static NO_INPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn init(no_input: bool) {
+  NO_INPUT.store(no_input, Ordering::Relaxed);
+}
+
+/// Why nothing can be asked, None when pickers, editors and confirmations can run.
+/// Dialoguer reads keys from stdin and draws on stderr.
+fn obstacle() -> Option<&'static str> {
+  if NO_INPUT.load(Ordering::Relaxed) {
+    return Some("--no-input is set");
+  }
+  if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+    return Some("there is no terminal");
+  }
+  None
+}
+
+pub fn interactive() -> bool {
+  obstacle().is_none()
+}
+
+/// The error for a prompt that can't run, saying what to do `instead`
+pub fn refusal(instead: &str) -> String {
+  format!(
+    "Can't prompt since {}, {}",
+    obstacle().unwrap_or("there is no terminal"),
+    instead
+  )
+}
+
+/// Fails with the refusal when nothing can be asked
+pub fn require(instead: &str) -> Result<(), String> {
+  match interactive() {
+    true => Ok(()),
+    false => Err(refusal(instead)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn no_input_refuses_prompts() {
+    init(true);
+    assert!(!interactive());
+    assert_eq!(
+      "Can't prompt since --no-input is set, pass the id",
+      require("pass the id").unwrap_err()
+    );
+    init(false);
+  }
+}
@@ -0,0 +1,140 @@
+use crate::TodoStore;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyString};
+use serde_json::{Map, Number, Value};
+use std::error::Error;
+
+fn py_error(e: Box<dyn Error>) -> PyErr {
+  PyRuntimeError::new_err(e.to_string())
+}
+
+fn to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+  Ok(match value {
+    Value::Null => py.None().into_bound(py),
+    Value::Bool(bool) => PyBool::new(py, *bool).to_owned().into_any(),
+    Value::Number(number) => match number.as_i64() {
+      Some(integer) => integer.into_pyobject(py)?.into_any(),
+      None => number.as_f64().into_pyobject(py)?.into_any(),
+    },
+    Value::String(string) => PyString::new(py, string).into_any(),
+    Value::Array(items) => {
+      let list = PyList::empty(py);
+      for item in items {
+        list.append(to_py(py, item)?)?;
+      }
+      list.into_any()
+    }
+    Value::Object(fields) => {
+      let dict = PyDict::new(py);
+      for (field, value) in fields {
+        dict.set_item(field, to_py(py, value)?)?;
+      }
+      dict.into_any()
+    }
+  })
+}
+
+/// Plain python values as json, dates and datetimes by their iso format
+fn from_py(object: &Bound<'_, PyAny>) -> PyResult<Value> {
+  if object.is_none() {
+    return Ok(Value::Null);
+  }
+  if let Ok(bool) = object.cast::<PyBool>() {
+    return Ok(Value::Bool(bool.is_true()));
+  }
+  if let Ok(integer) = object.extract::<i64>() {
+    return Ok(Value::from(integer));
+  }
+  if let Ok(float) = object.extract::<f64>() {
+    return Ok(Number::from_f64(float).map_or(Value::Null, Value::Number));
+  }
+  if let Ok(string) = object.extract::<String>() {
+    return Ok(Value::String(string));
+  }
+  if let Ok(dict) = object.cast::<PyDict>() {
+    return Ok(Value::Object(fields(Some(dict))?));
+  }
+  if object.hasattr("isoformat")? {
+    return Ok(Value::String(object.call_method0("isoformat")?.extract()?));
+  }
+  if let Ok(items) = object.try_iter() {
+    return Ok(Value::Array(
+      items
+        .map(|item| from_py(&item?))
+        .collect::<PyResult<Vec<Value>>>()?,
+    ));
+  }
+  Err(PyTypeError::new_err(format!(
+    "Can't store a {}",
+    object.get_type().name()?
+  )))
+}
+
+fn fields(dict: Option<&Bound<'_, PyDict>>) -> PyResult<Map<String, Value>> {
+  let mut fields = Map::new();
+  for (field, value) in dict.iter().flat_map(|dict| dict.iter()) {
+    fields.insert(field.extract()?, from_py(&value)?);
+  }
+  Ok(fields)
+}
+
+/// `todo.Store("todos.db")`, todos are dicts like the ones of the json export
+#[pyclass(name = "Store", unsendable)]
+struct Store {
+  store: TodoStore,
+}
+
+#[pymethods]
+impl Store {
+  #[new]
+  fn new(location: &str) -> PyResult<Store> {
+    Ok(Store {
+      store: TodoStore::open(location).map_err(py_error)?,
+    })
+  }
+
+  /// Every todo, in the order `todo list` shows them
+  fn todos<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    to_py(py, &Value::Array(self.store.todos().map_err(py_error)?))
+  }
+
+  fn get<'py>(&self, py: Python<'py>, id: usize) -> PyResult<Bound<'py, PyAny>> {
+    to_py(
+      py,
+      &self.store.get(id).map_err(py_error)?.unwrap_or(Value::Null),
+    )
+  }
+
+  /// `store.add("Milk", due="tomorrow", priority="high", tags=["shop"])`, returns the id
+  #[pyo3(signature = (body, **fields))]
+  fn add(&mut self, body: &str, fields: Option<&Bound<'_, PyDict>>) -> PyResult<usize> {
+    self
+      .store
+      .add(body, self::fields(fields)?)
+      .map_err(py_error)
+  }
+
+  /// `store.update(3, status="done", due=None)`
+  #[pyo3(signature = (id, **fields))]
+  fn update(&mut self, id: usize, fields: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+    self
+      .store
+      .update(id, self::fields(fields)?)
+      .map_err(py_error)
+  }
+
+  fn complete(&mut self, id: usize) -> PyResult<()> {
+    self.store.complete(id).map_err(py_error)
+  }
+
+  fn remove(&mut self, id: usize) -> PyResult<()> {
+    self.store.remove(id).map_err(py_error)
+  }
+}
+
+#[pymodule]
+fn todo(module: &Bound<'_, PyModule>) -> PyResult<()> {
+  module.add_class::<Store>()?;
+  Ok(())
+}
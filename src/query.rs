@@ -0,0 +1,359 @@
+use crate::Todo;
+use crate::bucket::Bucket;
+use crate::date::parse_date_from;
+use crate::filter::{Matcher, normalize};
+use crate::priority::Priority;
+use crate::status::Status;
+use chrono::{Local, NaiveDate};
+use serde::de::DeserializeOwned;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+/// A filter expression like `tag:work and (priority:high or due<3d) and not status:done`,
+/// words on their own match the body
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+  Term(Term),
+  Not(Box<Query>),
+  And(Box<Query>, Box<Query>),
+  Or(Box<Query>, Box<Query>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+  All,
+  Incomplete,
+  Complete,
+  Pinned,
+  Focused,
+  /// Open and due before the date, today when parsed
+  Overdue(NaiveDate),
+  Status(Status),
+  /// None for todos without a priority
+  Priority(Option<Priority>),
+  Bucket(Bucket),
+  Tag(String),
+  Project(Option<String>),
+  /// None for todos without a ref
+  Reference(Option<String>),
+  /// How the date compares to the given one, None for todos without a due date
+  Due(Vec<Ordering>, Option<NaiveDate>),
+  Scheduled(Vec<Ordering>, Option<NaiveDate>),
+  Text(String),
+}
+
+/// A word of the expression, quoted ones are never keywords
+#[derive(Debug)]
+struct Token {
+  text: String,
+  quoted: bool,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = vec![];
+  let mut current: Option<Token> = None;
+  let mut chars = input.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' => {
+        let token = current.get_or_insert(Token {
+          text: String::new(),
+          quoted: true,
+        });
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some(c) => token.text.push(c),
+            None => return Err("Unclosed quote in the filter".to_string()),
+          }
+        }
+      }
+      '(' | ')' => {
+        tokens.extend(current.take());
+        tokens.push(Token {
+          text: c.to_string(),
+          quoted: false,
+        });
+      }
+      c if c.is_whitespace() => tokens.extend(current.take()),
+      c => current
+        .get_or_insert(Token {
+          text: String::new(),
+          quoted: false,
+        })
+        .text
+        .push(c),
+    }
+  }
+  tokens.extend(current);
+  Ok(tokens)
+}
+
+/// A value of one of the named enums like `high` or `in-progress`
+fn named<T: DeserializeOwned>(value: &str, what: &str) -> Result<T, String> {
+  serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+    .map_err(|_| format!("Unknown {} '{}'", what, value))
+}
+
+/// Orderings that `<`, `<=`, `:`, `>=` and `>` accept
+fn orderings(operator: &str) -> Vec<Ordering> {
+  match operator {
+    "<" => vec![Ordering::Less],
+    "<=" => vec![Ordering::Less, Ordering::Equal],
+    ">=" => vec![Ordering::Greater, Ordering::Equal],
+    ">" => vec![Ordering::Greater],
+    _ => vec![Ordering::Equal],
+  }
+}
+
+fn date(value: &str, today: NaiveDate) -> Result<Option<NaiveDate>, String> {
+  match value {
+    "none" => Ok(None),
+    value => parse_date_from(value, today).map(Some),
+  }
+}
+
+fn term(token: Token, today: NaiveDate) -> Result<Term, String> {
+  if token.quoted {
+    return Ok(Term::Text(token.text));
+  }
+  let text = token.text;
+  match text.to_lowercase().as_str() {
+    "all" => return Ok(Term::All),
+    "incomplete" | "open" => return Ok(Term::Incomplete),
+    "complete" | "completed" => return Ok(Term::Complete),
+    "pinned" => return Ok(Term::Pinned),
+    "focused" => return Ok(Term::Focused),
+    "overdue" => return Ok(Term::Overdue(today)),
+    _ => {}
+  }
+
+  let Some(split) = text.find([':', '<', '>']) else {
+    return Ok(Term::Text(text));
+  };
+  let (key, rest) = text.split_at(split);
+  let operator_len = match rest.get(..2) {
+    Some("<=" | ">=") => 2,
+    _ => 1,
+  };
+  let (operator, value) = rest.split_at(operator_len);
+  match (key.to_lowercase().as_str(), operator) {
+    ("due", _) => Ok(Term::Due(orderings(operator), date(value, today)?)),
+    ("scheduled", _) => Ok(Term::Scheduled(orderings(operator), date(value, today)?)),
+    ("status", ":") => Ok(Term::Status(named(value, "status")?)),
+    ("priority", ":") => Ok(Term::Priority(match value {
+      "none" => None,
+      value => Some(named(value, "priority")?),
+    })),
+    ("bucket", ":") => Ok(Term::Bucket(named(value, "bucket")?)),
+    ("tag", ":") => Ok(Term::Tag(value.to_string())),
+    ("project", ":") => Ok(Term::Project(
+      Some(value.to_string()).filter(|p| p != "none"),
+    )),
+    ("ref", ":") => Ok(Term::Reference(
+      Some(value.to_string()).filter(|r| r != "none"),
+    )),
+    // Anything else like `10:30` is part of the body
+    _ => Ok(Term::Text(text)),
+  }
+}
+
+struct Parser {
+  tokens: Peekable<IntoIter<Token>>,
+  today: NaiveDate,
+}
+
+impl Parser {
+  fn keyword(&mut self, keyword: &str) -> bool {
+    self
+      .tokens
+      .next_if(|token| !token.quoted && token.text.eq_ignore_ascii_case(keyword))
+      .is_some()
+  }
+
+  fn or(&mut self) -> Result<Query, String> {
+    let mut query = self.and()?;
+    while self.keyword("or") {
+      query = Query::Or(Box::new(query), Box::new(self.and()?));
+    }
+    Ok(query)
+  }
+
+  /// Terms next to each other have to match both, with or without `and`
+  fn and(&mut self) -> Result<Query, String> {
+    let mut query = self.unary()?;
+    loop {
+      if !self.keyword("and")
+        && self.tokens.peek().is_none_or(|token| {
+          !token.quoted && (token.text == ")" || token.text.eq_ignore_ascii_case("or"))
+        })
+      {
+        return Ok(query);
+      }
+      query = Query::And(Box::new(query), Box::new(self.unary()?));
+    }
+  }
+
+  fn unary(&mut self) -> Result<Query, String> {
+    if self.keyword("not") {
+      return Ok(Query::Not(Box::new(self.unary()?)));
+    }
+    if self.keyword("(") {
+      let query = self.or()?;
+      if !self.keyword(")") {
+        return Err("Missing ) in the filter".to_string());
+      }
+      return Ok(query);
+    }
+    match self.tokens.next() {
+      Some(token) if !token.quoted && token.text == ")" => {
+        Err("Unexpected ) in the filter".to_string())
+      }
+      Some(token) => Ok(Query::Term(term(token, self.today)?)),
+      None => Err("The filter ends too early".to_string()),
+    }
+  }
+}
+
+fn parse_from(input: &str, today: NaiveDate) -> Result<Query, String> {
+  let tokens = tokenize(input)?;
+  if tokens.is_empty() {
+    return Err("Empty filter, use `all` to match every todo".to_string());
+  }
+  let mut parser = Parser {
+    tokens: tokens.into_iter().peekable(),
+    today,
+  };
+  let query = parser.or()?;
+  match parser.tokens.next() {
+    Some(token) => Err(format!("Unexpected '{}' in the filter", token.text)),
+    None => Ok(query),
+  }
+}
+
+/// Dates in the expression are read like `--due`, relative to today
+pub fn parse_query(input: &str) -> Result<Query, String> {
+  parse_from(input, Local::now().date_naive())
+}
+
+fn compare(date: Option<NaiveDate>, orderings: &[Ordering], to: Option<NaiveDate>) -> bool {
+  match (date, to) {
+    (Some(date), Some(to)) => orderings.contains(&date.cmp(&to)),
+    (date, to) => date == to,
+  }
+}
+
+impl Term {
+  fn matches(&self, todo: &Todo) -> bool {
+    match self {
+      Term::All => true,
+      Term::Incomplete => todo.status.is_open(),
+      Term::Complete => !todo.status.is_open(),
+      Term::Pinned => todo.pinned,
+      Term::Focused => todo.focused,
+      Term::Overdue(today) => todo.status.is_open() && todo.due.is_some_and(|due| due < *today),
+      Term::Status(status) => todo.status == *status,
+      Term::Priority(priority) => todo.priority == *priority,
+      Term::Bucket(bucket) => todo.bucket == *bucket,
+      Term::Tag(tag) => todo.tags.contains(tag),
+      Term::Project(project) => match (&todo.project, project) {
+        (Some(own), Some(project)) => own.eq_ignore_ascii_case(project),
+        (own, project) => own == project,
+      },
+      Term::Reference(reference) => match (&todo.reference, reference) {
+        (Some(own), Some(reference)) => own.eq_ignore_ascii_case(reference),
+        (own, reference) => own == reference,
+      },
+      Term::Due(orderings, date) => compare(todo.due, orderings, *date),
+      Term::Scheduled(orderings, date) => compare(todo.scheduled, orderings, *date),
+      Term::Text(text) => {
+        let accents = Matcher::current().accents;
+        let fold = |text: &str| {
+          normalize(text, accents)
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect::<String>()
+            .to_lowercase()
+        };
+        fold(&todo.body).contains(&fold(text))
+      }
+    }
+  }
+}
+
+impl Query {
+  pub fn matches(&self, todo: &Todo) -> bool {
+    match self {
+      Query::Term(term) => term.matches(todo),
+      Query::Not(query) => !query.matches(todo),
+      Query::And(left, right) => left.matches(todo) && right.matches(todo),
+      Query::Or(left, right) => left.matches(todo) || right.matches(todo),
+    }
+  }
+
+  pub fn apply(&self, todos: Vec<Todo>) -> Vec<Todo> {
+    todos
+      .into_iter()
+      .filter(|todo| self.matches(todo))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tags::Tags;
+
+  #[test]
+  fn queries_combine_terms() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+    let todo = |body: &str, status, tags: &[&str], due: Option<u32>| Todo {
+      body: body.to_string(),
+      status,
+      tags: Tags(tags.iter().map(|tag| tag.to_string()).collect()),
+      due: due.and_then(|day| NaiveDate::from_ymd_opt(2024, 3, day)),
+      ..Default::default()
+    };
+    let todos = vec![
+      todo("Write report", Status::Todo, &["work"], Some(4)),
+      todo("Call bank", Status::Done, &["work"], Some(1)),
+      todo("Buy milk", Status::Todo, &["shop"], None),
+    ];
+    let bodies = |input: &str| {
+      parse_from(input, today)
+        .unwrap()
+        .apply(todos.clone())
+        .into_iter()
+        .map(|todo| todo.body)
+        .collect::<Vec<String>>()
+    };
+
+    assert_eq!(vec!["Write report"], bodies("tag:work and incomplete"));
+    assert_eq!(vec!["Write report"], bodies("tag:Work open"));
+    assert_eq!(
+      vec!["Call bank", "Buy milk"],
+      bodies("not (tag:work and due>today)")
+    );
+    assert_eq!(
+      vec!["Call bank", "Buy milk"],
+      bodies("due<=today or due:none")
+    );
+    assert_eq!(vec!["Write report"], bodies("due<1w status:todo"));
+    assert!(bodies("overdue").is_empty());
+    assert_eq!(3, bodies("ref:none").len());
+    assert_eq!(vec!["Buy milk"], bodies("MILK or \"and\""));
+    assert_eq!(3, bodies("all").len());
+
+    for invalid in [
+      "",
+      "(tag:work",
+      "tag:work)",
+      "status:later",
+      "due<someday",
+      "not",
+    ] {
+      assert!(parse_from(invalid, today).is_err(), "{}", invalid);
+    }
+  }
+}
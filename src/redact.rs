@@ -0,0 +1,105 @@
+use crate::Todo;
+use crate::checklist::parse_line;
+use crate::tags::Tags;
+use std::collections::HashMap;
+
+/// Names standing in for what was written, the same text always gets the same name so
+/// duplicates and shared projects or tags still show
+#[derive(Default)]
+struct Names(HashMap<&'static str, HashMap<String, usize>>);
+
+impl Names {
+  /// Numbered by kind in the order they come up, like `Project 2`
+  fn name(&mut self, kind: &'static str, text: &str) -> String {
+    let named = self.0.entry(kind).or_default();
+    let next = named.len() + 1;
+    let number = *named.entry(text.to_string()).or_insert(next);
+    format!("{} {}", kind, number)
+  }
+
+  /// Checklist items stay items with their state, other lines become a placeholder
+  fn notes(&mut self, notes: &str) -> String {
+    notes
+      .lines()
+      .map(|line| match parse_line(line) {
+        Some((checked, text)) => format!(
+          "- [{}] {}",
+          if checked { "x" } else { " " },
+          self.name("Item", text)
+        ),
+        None if line.trim().is_empty() => String::new(),
+        None => self.name("Line", line),
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+}
+
+/// The todos with what they say replaced, the statuses, dates, priorities, subtasks and
+/// counts of everything are kept for bug reports
+pub fn redact(todos: &[Todo]) -> Vec<Todo> {
+  let mut names = Names::default();
+  todos
+    .iter()
+    .map(|todo| Todo {
+      body: names.name("Todo", &todo.body),
+      waiting_on: todo
+        .waiting_on
+        .as_ref()
+        .map(|who| names.name("Someone", who)),
+      project: todo
+        .project
+        .as_ref()
+        .map(|project| names.name("Project", project)),
+      notes: todo.notes.as_ref().map(|notes| names.notes(notes)),
+      tags: Tags(
+        todo
+          .tags
+          .0
+          .iter()
+          .map(|tag| names.name("tag", tag).replace(' ', "-"))
+          .collect(),
+      ),
+      reference: todo
+        .reference
+        .as_ref()
+        .map(|reference| names.name("ref", reference).replace(' ', "-")),
+      ..todo.clone()
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::status::Status;
+
+  #[test]
+  fn redaction_keeps_the_structure() {
+    let todo = |body: &str, project: Option<&str>| Todo {
+      id: 1,
+      body: body.to_string(),
+      project: project.map(str::to_string),
+      tags: Tags(vec!["health".to_string()]),
+      notes: Some("Ask about\n\n- [x] blood test\n- [ ] x-ray".to_string()),
+      ..Default::default()
+    };
+    let todos = [
+      todo("Call the doctor", Some("Health")),
+      Todo {
+        status: Status::Done,
+        ..todo("Pay rent", None)
+      },
+      todo("Call the doctor", Some("Health")),
+    ];
+    let redacted = redact(&todos);
+    assert_eq!(vec!["Todo 1", "Todo 2", "Todo 1"], crate::bodies(&redacted));
+    assert_eq!(Some("Project 1".to_string()), redacted[0].project);
+    assert_eq!(vec!["tag-1"], redacted[1].tags.0);
+    assert_eq!(
+      Some("Line 1\n\n- [x] Item 1\n- [ ] Item 2".to_string()),
+      redacted[1].notes
+    );
+    assert_eq!(Status::Done, redacted[1].status);
+  }
+}
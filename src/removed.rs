@@ -0,0 +1,221 @@
+use crate::filter::Matcher;
+use crate::ids::short;
+use crate::status::Status;
+use crate::{Todo, ascii, emoji};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::ValueEnum;
+use console::style;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Removed todos `--include` brings back into list and search
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Scope {
+  /// Removed once done or cancelled, like `clean` does
+  Archived,
+  /// Removed while still open
+  Trash,
+}
+
+impl Scope {
+  fn of(todo: &Todo) -> Scope {
+    match todo.status.is_open() {
+      true => Scope::Trash,
+      false => Scope::Archived,
+    }
+  }
+
+  fn heading(&self) -> &'static str {
+    match self {
+      Scope::Archived => "Archived:",
+      Scope::Trash => "Trash:",
+    }
+  }
+}
+
+/// Columns of a todo kept when it was removed, the others are whatever the column defaults to
+const KEPT: [&str; 22] = [
+  "body",
+  "status",
+  "waiting_on",
+  "follow_up",
+  "estimate",
+  "priority",
+  "due",
+  "bucket",
+  "project",
+  "snoozed_until",
+  "created_at",
+  "updated_at",
+  "focused",
+  "notes",
+  "parent_id",
+  "tags",
+  "scheduled",
+  "pinned",
+  "expires",
+  "habit",
+  "uuid",
+  "reference",
+];
+
+/// A todo as it was when it was removed
+pub struct Removed {
+  pub todo: Todo,
+  pub removed: NaiveDate,
+}
+
+/// Todos removed and not brought back by undo, latest removal first. Entries from before the
+/// operation log only have the body.
+pub fn collect(scopes: &[Scope], conn: &Connection) -> Result<Vec<Removed>, Box<dyn Error>> {
+  if scopes.is_empty() {
+    return Ok(vec![]);
+  }
+  let columns = KEPT
+    .iter()
+    .map(|column| match *column {
+      "status" => "coalesce(json_extract(row, '$.status'), 'todo') AS status".to_string(),
+      "bucket" => "coalesce(json_extract(row, '$.bucket'), 'inbox') AS bucket".to_string(),
+      "focused" | "pinned" => {
+        format!("coalesce(json_extract(row, '$.{column}'), false) AS {column}")
+      }
+      column => format!("json_extract(row, '$.{column}') AS {column}"),
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+  let mut stmt = conn.prepare(&format!(
+    "SELECT todo_id AS id, NULL AS position, changed_at, {columns} FROM (
+       SELECT history.*, CASE WHEN json_valid(old) THEN old ELSE json_object('body', old) END
+         AS row FROM history WHERE field = 'deleted'
+     ) AS deleted
+     WHERE NOT EXISTS (SELECT 1 FROM todos WHERE uuid = json_extract(row, '$.uuid'))
+       AND deleted.id = (SELECT max(other.id) FROM history AS other WHERE other.field = 'deleted'
+         AND other.todo_id = deleted.todo_id
+         AND (NOT json_valid(other.old) OR json_extract(other.old, '$.uuid')
+           IS json_extract(row, '$.uuid')))
+     ORDER BY deleted.id DESC"
+  ))?;
+  let removed = stmt
+    .query_map([], |row| {
+      Ok(Removed {
+        todo: Todo::from_row(row)?,
+        removed: row
+          .get::<_, DateTime<Utc>>("changed_at")?
+          .with_timezone(&Local)
+          .date_naive(),
+      })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(
+    removed
+      .into_iter()
+      .filter(|removed| scopes.contains(&Scope::of(&removed.todo)))
+      .collect(),
+  )
+}
+
+/// Whether the body or notes have all the words, the way the config matches, `deploy*`
+/// matches like `deploy`
+pub fn matches(removed: &Removed, words: &[String]) -> bool {
+  let text = format!(
+    "{} {}",
+    removed.todo.body,
+    removed.todo.notes.as_deref().unwrap_or("")
+  );
+  words
+    .iter()
+    .flat_map(|words| words.split_whitespace())
+    .all(|word| {
+      Matcher::current()
+        .find(&text, word.trim_end_matches('*'))
+        .is_some()
+    })
+}
+
+/// Lines listing the removed todos dimmed under a heading per scope, below the others
+pub fn lines(removed: &[Removed]) -> Vec<String> {
+  let mut lines = vec![];
+  for scope in [Scope::Archived, Scope::Trash] {
+    let todos = removed
+      .iter()
+      .filter(|removed| Scope::of(&removed.todo) == scope)
+      .collect::<Vec<_>>();
+    if todos.is_empty() {
+      continue;
+    }
+    lines.push(String::new());
+    lines.push(style(scope.heading()).bold().to_string());
+    for Removed { todo, removed } in todos {
+      let status = match todo.status {
+        Status::Todo => String::new(),
+        status => format!("{}, ", status),
+      };
+      let line = format!(
+        "{}. {} ({}removed {})",
+        short(todo.id),
+        emoji::display(&todo.body),
+        status,
+        removed
+      );
+      lines.push(style(ascii::text(&line)).dim().to_string());
+    }
+  }
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::undo;
+
+  #[test]
+  fn removed_todos_come_back_by_scope() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    for sql in [
+      "INSERT INTO todos (body, notes) VALUES ('File taxes', 'Form 1040')",
+      "INSERT INTO todos (body) VALUES ('Call Bob')",
+      "INSERT INTO todos (body) VALUES ('Paint fence')",
+      "UPDATE todos SET status = 'done' WHERE id = 1",
+      "DELETE FROM todos WHERE id in (1, 2)",
+      "DELETE FROM todos WHERE id = 3",
+    ] {
+      conn.execute(sql, ()).unwrap();
+      undo::record(sql, true, &conn).unwrap();
+    }
+    // Undone removals are todos again
+    undo::undo_last(&conn).unwrap();
+    conn
+      .execute(
+        "INSERT INTO history (todo_id, field, old) VALUES (9, 'deleted', 'Old style')",
+        (),
+      )
+      .unwrap();
+
+    let bodies = |scopes: &[Scope]| {
+      collect(scopes, &conn)
+        .unwrap()
+        .into_iter()
+        .map(|removed| removed.todo.body)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(vec!["File taxes"], bodies(&[Scope::Archived]));
+    assert_eq!(vec!["Old style", "Call Bob"], bodies(&[Scope::Trash]));
+    assert!(bodies(&[]).is_empty());
+
+    let archived = collect(&[Scope::Archived], &conn).unwrap();
+    assert_eq!(Status::Done, archived[0].todo.status);
+    let words = |text: &str| vec![text.to_string()];
+    assert!(matches(&archived[0], &words("tax* 1040")));
+    assert!(!matches(&archived[0], &words("taxes bob")));
+    console::set_colors_enabled(false);
+    assert_eq!(
+      format!(
+        "a. File taxes (done, removed {})",
+        Local::now().date_naive()
+      ),
+      lines(&archived)[2]
+    );
+  }
+}
@@ -0,0 +1,277 @@
+use crate::cycle;
+use crate::tags::Tags;
+use crate::velocity;
+use crate::{TODO_SELECT, Todo, collect_todos};
+use chrono::{Days, Local, NaiveDate};
+use clap::Subcommand;
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// How long todos spend in each status, and from creation to done
+  CycleTime {},
+
+  /// Completed todos per week with a rolling average
+  Velocity {
+    /// How many weeks to show
+    #[arg(short, long, default_value_t = 8)]
+    weeks: usize,
+
+    /// Sum the estimates of the completed todos instead of counting them
+    #[arg(short, long)]
+    estimates: bool,
+  },
+}
+
+/// How far back a report looks, a week when nothing is given
+#[derive(clap::Args, Clone, Debug, Default)]
+#[group(multiple = false)]
+pub struct Period {
+  /// Summarize the last 7 days
+  #[arg(long)]
+  week: bool,
+
+  /// Summarize the last 30 days
+  #[arg(long)]
+  month: bool,
+}
+
+impl Period {
+  fn days(&self) -> u64 {
+    if self.month { 30 } else { 7 }
+  }
+}
+
+/// A todo as far as the report is concerned, removed todos are read from their history
+#[derive(Debug, PartialEq)]
+struct Entry {
+  body: String,
+  tags: Tags,
+  project: Option<String>,
+}
+
+/// Todos with a history row of `field` since the start of `since`, optionally only those that
+/// changed to `new`. `current` narrows down the ones that still exist.
+fn collect_entries(
+  field: &str,
+  new: Option<&str>,
+  current: &str,
+  since: NaiveDate,
+  conn: &Connection,
+) -> Result<Vec<Entry>, Box<dyn Error>> {
+  let deleted = |column: &str| {
+    format!(
+      "(SELECT json_extract(old, '$.{column}') FROM history
+        WHERE todo_id = changed.todo_id and field = 'deleted' and json_valid(old)
+        ORDER BY id DESC LIMIT 1)"
+    )
+  };
+  let mut stmt = conn.prepare(&format!(
+    "SELECT coalesce(todos.body, {}), coalesce(todos.tags, {}), coalesce(todos.project, {})
+     FROM (SELECT DISTINCT todo_id FROM history
+           WHERE field = ?1 and (?2 is null or new = ?2) and changed_at >= datetime(?3, 'utc'))
+       AS changed
+     LEFT JOIN todos ON todos.id = changed.todo_id
+     WHERE todos.id is null or {}
+     ORDER BY changed.todo_id",
+    deleted("body"),
+    deleted("tags"),
+    deleted("project"),
+    current
+  ))?;
+  let entries = stmt
+    .query_map((field, new, since.to_string()), |row| {
+      Ok(Entry {
+        body: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+        tags: row.get(1)?,
+        project: row.get(2)?,
+      })
+    })?
+    .collect::<Result<Vec<Entry>, _>>()?;
+  Ok(entries)
+}
+
+/// Open todos that were created before the period started
+fn collect_todos_carried_over(
+  since: NaiveDate,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} where status not in ('done', 'cancelled')
+       and created_at < datetime('{}', 'utc') ORDER BY id;",
+      since
+    ),
+    conn,
+  )
+}
+
+fn entry_line(entry: &Entry) -> String {
+  let mut line = format!("- {}", entry.body);
+  if !entry.tags.is_empty() {
+    line.push_str(&format!(" `{}`", entry.tags));
+  }
+  if let Some(project) = &entry.project {
+    line.push_str(&format!(" _({})_", project));
+  }
+  line
+}
+
+fn section(title: &str, entries: &[Entry]) -> String {
+  let mut text = format!("## {}\n\n", title);
+  if entries.is_empty() {
+    text.push_str("- Nothing\n");
+  }
+  for entry in entries {
+    text.push_str(&entry_line(entry));
+    text.push('\n');
+  }
+  text.push('\n');
+  text
+}
+
+/// Added, completed and open counts per tag, untagged todos are counted together
+fn tag_table(added: &[Entry], completed: &[Entry], open: &[Todo]) -> String {
+  let mut counts: BTreeMap<String, [usize; 3]> = BTreeMap::new();
+  let mut count = |tags: &Tags, column: usize| {
+    if tags.is_empty() {
+      counts.entry("(untagged)".to_string()).or_default()[column] += 1;
+    }
+    for tag in &tags.0 {
+      counts.entry(format!("#{}", tag)).or_default()[column] += 1;
+    }
+  };
+  for entry in added {
+    count(&entry.tags, 0);
+  }
+  for entry in completed {
+    count(&entry.tags, 1);
+  }
+  for todo in open {
+    count(&todo.tags, 2);
+  }
+
+  let mut table =
+    "## By tag\n\n| Tag | Added | Completed | Open |\n| --- | ---: | ---: | ---: |\n".to_string();
+  for (tag, [added, completed, open]) in counts {
+    table.push_str(&format!(
+      "| {} | {} | {} | {} |\n",
+      tag, added, completed, open
+    ));
+  }
+  table
+}
+
+/// The whole report as markdown for the `days` days up to and including `today`
+fn render(today: NaiveDate, days: u64, conn: &Connection) -> Result<String, Box<dyn Error>> {
+  let since = today - Days::new(days - 1);
+  let completed = collect_entries(
+    "status",
+    Some("done"),
+    "todos.status is 'done'",
+    since,
+    conn,
+  )?;
+  let added = collect_entries("created", None, "true", since, conn)?;
+  let carried_over = collect_todos_carried_over(since, conn)?
+    .into_iter()
+    .map(|todo| Entry {
+      body: todo.body,
+      tags: todo.tags,
+      project: todo.project,
+    })
+    .collect::<Vec<Entry>>();
+  let open = collect_todos(
+    format!("{TODO_SELECT} where status not in ('done', 'cancelled');"),
+    conn,
+  )?;
+
+  let mut text = format!(
+    "# Report for the last {} days ({} to {})\n\n",
+    days, since, today
+  );
+  text.push_str(&format!(
+    "**{} completed, {} added, {} carried over**\n\n",
+    completed.len(),
+    added.len(),
+    carried_over.len()
+  ));
+  text.push_str(&section("Completed", &completed));
+  text.push_str(&section("Added", &added));
+  text.push_str(&section("Carried over", &carried_over));
+  text.push_str(&tag_table(&added, &completed, &open));
+  Ok(text)
+}
+
+pub fn report(
+  action: Option<&Action>,
+  period: &Period,
+  out: Option<&Path>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  match action {
+    Some(Action::CycleTime {}) => return cycle::cycle_time(conn),
+    Some(Action::Velocity { weeks, estimates }) => {
+      return velocity::velocity(*weeks, *estimates, conn);
+    }
+    None => {}
+  }
+  let text = render(Local::now().date_naive(), period.days(), conn)?;
+  match out {
+    Some(path) => {
+      fs::write(path, text)?;
+      println!("Wrote report to {}", path.display());
+    }
+    None => print!("{}", text),
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, add, collect_todos_all, rm, set_status};
+
+  #[test]
+  fn report_counts_removed_todos_from_history() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let home = AddOptions {
+      tags: vec!["home".to_string()],
+      ..Default::default()
+    };
+    _ = add(vec!["Pay bills".to_string()], &home, &conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    for moved in [
+      "UPDATE todos SET created_at = datetime('now', '-20 days') WHERE body = 'Carl'",
+      "UPDATE history SET changed_at = datetime('now', '-20 days') WHERE new = 'Carl'",
+    ] {
+      conn.execute(moved, ()).unwrap();
+    }
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_status(
+      vec![todos[0].clone(), todos[1].clone()],
+      Status::Done,
+      &conn,
+    );
+    _ = set_status(vec![todos[1].clone()], Status::Todo, &conn);
+    _ = rm(vec![todos[0].clone()], &conn);
+
+    let text = render(Local::now().date_naive(), 7, &conn).unwrap();
+    assert!(text.contains("**1 completed, 2 added, 1 carried over**"));
+    assert!(text.contains("## Completed\n\n- Pay bills `#home`\n"));
+    assert!(text.contains("## Carried over\n\n- Carl\n"));
+    assert!(text.contains("| #home | 1 | 1 | 0 |\n"));
+    assert!(text.contains("| (untagged) | 1 | 0 | 2 |\n"));
+  }
+}
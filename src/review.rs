@@ -0,0 +1,181 @@
+use crate::date::parse_date;
+use crate::prompt;
+use crate::status::Status;
+use crate::{TODO_SELECT, Todo, rm, set_status};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use console::style;
+use dialoguer::{Input, Select, theme::ColorfulTheme};
+use rusqlite::Connection;
+use std::error::Error;
+
+/// What to do with a stale todo
+#[derive(Debug, PartialEq)]
+enum Decision {
+  Keep,
+  Complete,
+  Snooze(NaiveDate),
+  Delete,
+}
+
+/// Open todos untouched for `days` days or whose snooze ran out, oldest activity first
+fn collect_todos_stale(
+  days: i64,
+  today: NaiveDate,
+  conn: &Connection,
+) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(&format!(
+    "{TODO_SELECT} where status not in ('done', 'cancelled')
+     and ((snoozed_until is null and updated_at <= datetime('now', ?1))
+       or snoozed_until <= ?2)
+     order by updated_at;"
+  ))?;
+  let todos = stmt
+    .query_map((format!("-{} days", days), today), Todo::from_row)?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<Todo>>();
+  Ok(todos)
+}
+
+fn apply(decision: Decision, todo: Todo, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  match decision {
+    // Keeping counts as activity, so the todo is not stale anymore
+    Decision::Keep => {
+      conn.execute(
+        "UPDATE todos SET updated_at = datetime('now'), snoozed_until = null where id is ?1",
+        (todo.id,),
+      )?;
+    }
+    Decision::Complete => set_status(vec![todo], Status::Done, conn)?,
+    Decision::Snooze(until) => {
+      conn.execute(
+        "UPDATE todos SET snoozed_until = ?1 where id is ?2",
+        (until, todo.id),
+      )?;
+      println!("Snoozed until {}: {}", until, todo.body);
+    }
+    Decision::Delete => rm(vec![todo], conn)?,
+  }
+  Ok(())
+}
+
+pub fn review(days: i64, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos_stale(days, Local::now().date_naive(), conn)?;
+  if todos.is_empty() {
+    println!("Nothing has been idle for {} days, all reviewed!", days);
+    return Ok(());
+  }
+
+  prompt::require("review the stale todos in a terminal")?;
+  // Untested segment starts, this part needs interactivity
+  let theme = ColorfulTheme::default();
+  let choices = ["Keep", "Complete", "Snooze", "Delete", "Stop reviewing"];
+  let total = todos.len();
+  for (number, todo) in todos.into_iter().enumerate() {
+    let days_since = |stamp: Option<DateTime<Utc>>| {
+      stamp
+        .map(|stamp| (Utc::now() - stamp).num_days())
+        .unwrap_or_default()
+    };
+    println!(
+      "{} {} {}",
+      style(format!("[{}/{}]", number + 1, total)).dim(),
+      todo.body,
+      style(format!(
+        "(added {} days ago, idle {} days)",
+        days_since(todo.created_at),
+        days_since(todo.updated_at)
+      ))
+      .dim()
+    );
+
+    let decision = match Select::with_theme(&theme)
+      .with_prompt("Keep it?")
+      .default(0)
+      .items(&choices)
+      .interact()?
+    {
+      0 => Decision::Keep,
+      1 => Decision::Complete,
+      2 => {
+        let until: String = Input::with_theme(&theme)
+          .with_prompt("Snooze until")
+          .default("1w".to_string())
+          .validate_with(|input: &String| parse_date(input).map(|_| ()))
+          .interact_text()?;
+        Decision::Snooze(parse_date(&until)?)
+      }
+      3 => Decision::Delete,
+      _ => break,
+    };
+    apply(decision, todo, conn)?;
+  }
+  // Untested segment ends
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = conn.execute_batch(
+      "INSERT INTO todos (body, updated_at) VALUES ('Milk', '2020-01-01 00:00:00');
+       INSERT INTO todos (body) VALUES ('Carl');
+       INSERT INTO todos (body, updated_at) VALUES ('Katia', '2019-01-01 00:00:00');",
+    );
+    conn
+  }
+
+  fn bodies(todos: Vec<Todo>) -> Vec<String> {
+    todos.into_iter().map(|todo| todo.body).collect()
+  }
+
+  fn today() -> NaiveDate {
+    Local::now().date_naive()
+  }
+
+  #[test]
+  fn stale_oldest_first() {
+    let conn = setup();
+    assert_eq!(
+      vec!["Katia", "Milk"],
+      bodies(collect_todos_stale(14, today(), &conn).unwrap())
+    );
+  }
+
+  #[test]
+  fn keep_and_snooze_leave_review() {
+    let conn = setup();
+    let todos = collect_todos_stale(14, today(), &conn).unwrap();
+
+    _ = apply(Decision::Keep, todos[0].clone(), &conn);
+    _ = apply(
+      Decision::Snooze(today().succ_opt().unwrap()),
+      todos[1].clone(),
+      &conn,
+    );
+
+    assert!(collect_todos_stale(14, today(), &conn).unwrap().is_empty());
+    let tomorrow = today().succ_opt().unwrap();
+    assert_eq!(
+      vec!["Milk"],
+      bodies(collect_todos_stale(14, tomorrow, &conn).unwrap())
+    );
+  }
+
+  #[test]
+  fn complete_leaves_review() {
+    let conn = setup();
+    let todos = collect_todos_stale(14, today(), &conn).unwrap();
+
+    _ = apply(Decision::Complete, todos[0].clone(), &conn);
+
+    assert_eq!(
+      vec!["Milk"],
+      bodies(collect_todos_stale(14, today(), &conn).unwrap())
+    );
+  }
+}
@@ -0,0 +1,237 @@
+use crate::links;
+use crate::status::Status;
+use crate::storage::insert_todo;
+use crate::store::set_field;
+use crate::strategy::{self, Strategy, Summary};
+use crate::tags::Tags;
+use crate::wizard::Resolution;
+use crate::{Todo, set_status};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The source of links to scanned comments
+const SCAN: &str = "scan";
+
+const MARKERS: [&str; 2] = ["TODO:", "FIXME:"];
+
+/// What starts a comment in most languages, markers outside of one aren't comments
+const COMMENTS: [&str; 6] = ["//", "#", "/*", "--", ";", "*"];
+
+/// Directories of tools and dependencies rather than of the code itself
+const SKIPPED: [&str; 3] = ["target", "node_modules", "vendor"];
+
+/// A `TODO:` or `FIXME:` comment, the text after the marker is the body
+#[derive(Debug, PartialEq)]
+struct Comment {
+  file: PathBuf,
+  line: usize,
+  text: String,
+}
+
+impl Comment {
+  /// Where the comment is, as a tag like `src/main.rs#L12` since tags can't hold ':'
+  fn tag(&self, root: &Path) -> String {
+    let file = self.file.strip_prefix(root).unwrap_or(&self.file);
+    format!("{}#L{}", file.display(), self.line).replace(char::is_whitespace, "_")
+  }
+}
+
+/// The text after a marker in a comment, markers inside words like `AUTODO:` don't count
+fn comment_text(line: &str) -> Option<String> {
+  MARKERS.iter().find_map(|marker| {
+    let start = line.find(marker)?;
+    let before = &line[..start];
+    if before
+      .chars()
+      .next_back()
+      .is_some_and(|c| c.is_alphanumeric() || c == '_')
+      || !COMMENTS.iter().any(|comment| before.contains(comment))
+    {
+      return None;
+    }
+    // Block comments close on the same line sometimes
+    let text = line[start + marker.len()..]
+      .trim()
+      .trim_end_matches("*/")
+      .trim_end_matches("-->")
+      .trim();
+    Some(text.to_string()).filter(|text| !text.is_empty())
+  })
+}
+
+/// Every comment below `dir`, files that aren't text are skipped
+fn comments(dir: &Path, found: &mut Vec<Comment>) -> Result<(), Box<dyn Error>> {
+  let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+  entries.sort_by_key(|entry| entry.file_name());
+  for entry in entries {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    let path = entry.path();
+    if entry.file_type()?.is_dir() {
+      if !name.starts_with('.') && !SKIPPED.contains(&name.as_ref()) {
+        comments(&path, found)?;
+      }
+      continue;
+    }
+    let Ok(text) = fs::read_to_string(&path) else {
+      continue;
+    };
+    for (index, line) in text.lines().enumerate() {
+      if let Some(text) = comment_text(line) {
+        found.push(Comment {
+          file: path.clone(),
+          line: index + 1,
+          text,
+        });
+      }
+    }
+  }
+  Ok(())
+}
+
+/// The link reference of each comment. File and text stay the same when lines around the
+/// comment change, the same comment twice in a file gets a count.
+fn references(comments: &[Comment]) -> Vec<String> {
+  let mut seen = HashMap::new();
+  comments
+    .iter()
+    .map(|comment| {
+      let reference = format!("{}: {}", comment.file.display(), comment.text);
+      let count = seen.entry(reference.clone()).or_insert(0);
+      *count += 1;
+      match *count {
+        1 => reference,
+        count => format!("{} ({})", reference, count),
+      }
+    })
+    .collect()
+}
+
+/// `todo scan <dir>`, import new comments and complete the todos of comments that are gone.
+/// New comments a todo has the text of already are settled by `strategy`.
+pub fn scan(dir: &Path, strategy: Strategy, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let root = fs::canonicalize(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+  let mut found = vec![];
+  comments(&root, &mut found)?;
+  let references = references(&found);
+
+  let tx = conn.unchecked_transaction()?;
+  let mut linked = links::collect_linked(SCAN, &tx)?
+    .into_iter()
+    .filter(|(_, reference)| Path::new(reference).starts_with(&root))
+    .map(|(todo, reference)| (reference, todo))
+    .collect::<HashMap<String, Todo>>();
+  let mut summary = Summary::default();
+  for (comment, reference) in found.iter().zip(&references) {
+    let tag = comment.tag(&root);
+    let Some(todo) = linked.remove(reference) else {
+      if links::is_linked(SCAN, reference, &tx)? {
+        continue;
+      }
+      let todo = Todo {
+        body: comment.text.clone(),
+        tags: Tags(vec![tag.clone()]),
+        ..Default::default()
+      };
+      match strategy::settle(&todo, strategy, &mut summary, &tx)? {
+        Resolution::Create => {
+          let id = insert_todo(&todo, &tx)?;
+          links::link(id, SCAN, reference, &tx)?;
+          println!("Imported: {} #{}", todo.body, tag);
+        }
+        Resolution::Merge(id) | Resolution::Overwrite(id) => links::link(id, SCAN, reference, &tx)?,
+        Resolution::Skip => {}
+      }
+      continue;
+    };
+    // Moved lines move the tag along
+    if !todo.tags.contains(&tag) {
+      let file = tag.split_once("#L").map_or(tag.as_str(), |(file, _)| file);
+      let mut tags = Tags(
+        todo
+          .tags
+          .0
+          .iter()
+          .filter(|own| !own.starts_with(&format!("{}#L", file)))
+          .cloned()
+          .collect(),
+      );
+      tags.extend(std::slice::from_ref(&tag));
+      set_field(todo.id, "tags", serde_json::to_value(&tags)?, &tx)?;
+    }
+  }
+  for (reference, todo) in linked {
+    if todo.status.is_open() {
+      set_status(vec![todo], Status::Done, &tx)?;
+      summary.updated += 1;
+    }
+    links::mark_closed(SCAN, &reference, &tx)?;
+  }
+  tx.commit()?;
+  match summary.changed() {
+    true => println!("Scanned {}, {}", dir.display(), summary),
+    false => println!("Nothing new in {}!", dir.display()),
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::collect_todos_all;
+  use crate::db::create_db;
+  use std::{env, process};
+
+  #[test]
+  fn rescans_follow_comments_and_complete_gone_ones() {
+    let dir = env::temp_dir().join(format!("todo-scan-{}", process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join("target")).unwrap();
+    let main = dir.join("src/main.rs");
+    fs::write(
+      &main,
+      "// TODO: handle errors\nfn main() {} /* FIXME: exit code */\n// AUTODO: no\nTODO: no\n",
+    )
+    .unwrap();
+    fs::write(dir.join("target/out.rs"), "// TODO: generated\n").unwrap();
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+
+    scan(&dir, Strategy::Skip, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(
+      vec!["handle errors", "exit code"],
+      todos.iter().map(|todo| &todo.body).collect::<Vec<_>>()
+    );
+    assert_eq!(Tags(vec!["src/main.rs#L2".to_string()]), todos[1].tags);
+
+    // The first comment is fixed and the second one moves down a line
+    fs::write(
+      &main,
+      "use std::process;\n\nfn main() {} // FIXME: exit code\n",
+    )
+    .unwrap();
+    scan(&dir, Strategy::Skip, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(2, todos.len());
+    assert_eq!(Status::Done, todos[0].status);
+    assert_eq!(Status::Todo, todos[1].status);
+    assert_eq!(Tags(vec!["src/main.rs#L3".to_string()]), todos[1].tags);
+
+    // A comment with the text of a todo is left to the strategy
+    fs::write(
+      dir.join("src/lib.rs"),
+      "// TODO: Handle errors
+",
+    )
+    .unwrap();
+    scan(&dir, Strategy::Skip, &conn).unwrap();
+    assert_eq!(2, collect_todos_all(&conn).unwrap().len());
+    scan(&dir, Strategy::Duplicate, &conn).unwrap();
+    assert_eq!(3, collect_todos_all(&conn).unwrap().len());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
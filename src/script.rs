@@ -0,0 +1,302 @@
+use crate::filter::Filter;
+use crate::history::Entry;
+use crate::status::Status;
+use crate::{TODO_SELECT, Todo, collect_todos, collect_todos_all, insert, store};
+use mlua::{Function, Lua, LuaSerdeExt, SerializeOptions, Table, Value};
+use rusqlite::Connection;
+use rusqlite::types::Value as Sql;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `todo.version`, raised when a change could break existing scripts
+const API_VERSION: i64 = 1;
+
+/// What scripts can handle with `todo.on`
+const EVENTS: [&str; 4] = ["add", "done", "update", "delete"];
+
+type Handlers = RefCell<Vec<(String, Function)>>;
+
+fn lua_error(e: impl Display) -> mlua::Error {
+  mlua::Error::RuntimeError(e.to_string())
+}
+
+fn to_lua(lua: &Lua, value: &impl Serialize) -> mlua::Result<Value> {
+  lua.to_value_with(value, SerializeOptions::new().serialize_none_to_null(false))
+}
+
+fn find(id: usize, conn: &Connection) -> Result<Option<Todo>, Box<dyn Error>> {
+  Ok(collect_todos(format!("{TODO_SELECT} where id = {};", id), conn)?.pop())
+}
+
+/// `todo.list{status = "done", project = "House", tag = "shop"}`, every todo without a filter
+fn list(lua: &Lua, filter: Option<Table>, conn: &Connection) -> mlua::Result<Value> {
+  let filter = match filter {
+    Some(filter) => lua.from_value::<Filter>(Value::Table(filter))?,
+    None => Filter::default(),
+  };
+  to_lua(
+    lua,
+    &filter.apply(collect_todos_all(conn).map_err(lua_error)?),
+  )
+}
+
+/// The fields of a Lua table as json, `false` stands for nothing since `nil` can't be
+/// stored in a table
+fn fields(
+  lua: &Lua,
+  table: Option<Table>,
+) -> mlua::Result<serde_json::Map<String, serde_json::Value>> {
+  let mut fields = serde_json::Map::new();
+  for pair in table
+    .iter()
+    .flat_map(|table| table.pairs::<String, Value>())
+  {
+    let (field, value) = pair?;
+    let value = match value {
+      Value::Boolean(false) => serde_json::Value::Null,
+      value => lua.from_value(value)?,
+    };
+    fields.insert(field, value);
+  }
+  Ok(fields)
+}
+
+/// `todo.add("Milk", {due = "tomorrow", priority = "high", tags = {"shop"}})`, returns the id
+fn add(lua: &Lua, body: String, table: Option<Table>, conn: &Connection) -> mlua::Result<i64> {
+  let options = store::add_options(fields(lua, table)?).map_err(lua_error)?;
+  insert(&body, &options, conn).map_err(lua_error)?;
+  Ok(conn.last_insert_rowid())
+}
+
+/// `todo.update(3, {status = "done", due = false})`, values are written as given
+fn update(lua: &Lua, id: usize, table: Table, conn: &Connection) -> mlua::Result<()> {
+  for (field, value) in fields(lua, Some(table))? {
+    store::set_field(id, &field, value, conn).map_err(lua_error)?;
+  }
+  Ok(())
+}
+
+/// Run `body` with the `todo` table set up, handlers registered with `todo.on` are collected
+fn with_api<R>(
+  conn: &Connection,
+  body: impl FnOnce(&Lua, &Handlers) -> mlua::Result<R>,
+) -> Result<R, Box<dyn Error>> {
+  let lua = Lua::new();
+  let handlers = Handlers::default();
+  let result = lua.scope(|scope| {
+    let api = lua.create_table()?;
+    api.set("version", API_VERSION)?;
+    api.set(
+      "list",
+      scope.create_function(|lua, filter| list(lua, filter, conn))?,
+    )?;
+    api.set(
+      "get",
+      scope.create_function(|lua, id: usize| match find(id, conn).map_err(lua_error)? {
+        Some(todo) => to_lua(lua, &todo),
+        None => Ok(Value::Nil),
+      })?,
+    )?;
+    api.set(
+      "add",
+      scope.create_function(|lua, (body, fields)| add(lua, body, fields, conn))?,
+    )?;
+    api.set(
+      "update",
+      scope.create_function(|lua, (id, fields)| update(lua, id, fields, conn))?,
+    )?;
+    api.set(
+      "on",
+      scope.create_function(|_, (event, handler): (String, Function)| {
+        if !EVENTS.contains(&event.as_str()) {
+          return Err(lua_error(format!(
+            "Unknown event {}, expected one of {}",
+            event,
+            EVENTS.join(", ")
+          )));
+        }
+        handlers.borrow_mut().push((event, handler));
+        Ok(())
+      })?,
+    )?;
+    lua.globals().set("todo", api)?;
+    body(&lua, &handlers)
+  });
+  // Lua errors carry their traceback in the message
+  Ok(result.map_err(|e| e.to_string())?)
+}
+
+fn exec(lua: &Lua, path: &Path) -> mlua::Result<()> {
+  let source =
+    fs::read_to_string(path).map_err(|e| lua_error(format!("{}: {}", path.display(), e)))?;
+  lua
+    .load(source)
+    .set_name(format!("@{}", path.display()))
+    .exec()
+}
+
+/// Run a script, `arg` holds the arguments given after it
+pub fn run(script: &Path, args: &[String], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  with_api(conn, |lua, _| {
+    lua.globals().set("arg", args.to_vec())?;
+    exec(lua, script)
+  })
+}
+
+/// The event a change of the command that just ran is
+fn event(entry: &Entry) -> &'static str {
+  match (entry.field.as_str(), &entry.new) {
+    ("created", _) => "add",
+    ("deleted", _) => "delete",
+    ("status", Sql::Text(status)) if status == Status::Done.as_str() => "done",
+    _ => "update",
+  }
+}
+
+fn sql_to_lua(lua: &Lua, value: &Sql) -> mlua::Result<Value> {
+  Ok(match value {
+    Sql::Null | Sql::Blob(_) => Value::Nil,
+    Sql::Integer(integer) => Value::Integer(*integer),
+    Sql::Real(real) => Value::Number(*real),
+    Sql::Text(text) => Value::String(lua.create_string(text)?),
+  })
+}
+
+/// Call the handlers of `scripts` for the changes made by the command that just ran,
+/// changes the handlers make don't fire events again
+pub fn fire(scripts: &[PathBuf], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  if scripts.is_empty() {
+    return Ok(());
+  }
+  let mut stmt = conn.prepare("SELECT * FROM history WHERE operation_id IS NULL ORDER BY id")?;
+  let entries = stmt
+    .query_map([], Entry::from_row)?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<Entry>>();
+  if entries.is_empty() {
+    return Ok(());
+  }
+  let todos = entries
+    .iter()
+    .map(|entry| find(entry.todo_id, conn))
+    .collect::<Result<Vec<Option<Todo>>, _>>()?;
+
+  with_api(conn, |lua, handlers| {
+    for script in scripts {
+      exec(lua, script)?;
+    }
+    let handlers = handlers.borrow().clone();
+    for (entry, todo) in entries.iter().zip(&todos) {
+      // A deleted todo is what the history kept of it
+      let deleted = entries
+        .iter()
+        .find(|other| other.todo_id == entry.todo_id && other.field == "deleted");
+      let todo = match (todo, deleted.map(|deleted| &deleted.old)) {
+        (Some(todo), _) => to_lua(lua, todo)?,
+        (None, Some(Sql::Text(old))) => match serde_json::from_str::<serde_json::Value>(old) {
+          Ok(row) => to_lua(lua, &row)?,
+          Err(_) => Value::Nil,
+        },
+        (None, _) => Value::Nil,
+      };
+      let change = lua.create_table()?;
+      change.set("field", entry.field.as_str())?;
+      change.set("old", sql_to_lua(lua, &entry.old)?)?;
+      change.set("new", sql_to_lua(lua, &entry.new)?)?;
+      for (own, handler) in &handlers {
+        if own == event(entry) {
+          handler.call::<()>((todo.clone(), change.clone()))?;
+        }
+      }
+    }
+    Ok(())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, undo};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    conn
+  }
+
+  fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("todo-{}-{}.lua", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+  }
+
+  #[test]
+  fn scripts_query_add_and_update() {
+    let conn = setup();
+    let path = script(
+      "api",
+      r#"
+        assert(todo.version == 1)
+        local id = todo.add(arg[1], {priority = "high", tags = {"shop"}, due = "2024-03-01"})
+        todo.add("Bread")
+        todo.update(id, {status = "done", due = false})
+        local done = todo.list{status = "done", tag = "shop"}
+        assert(#done == 1 and done[1].body == "Milk" and done[1].due == nil)
+        assert(todo.get(id).priority == "high")
+        assert(todo.get(99) == nil)
+      "#,
+    );
+    run(&path, &["Milk".to_string()], &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(2, todos.len());
+    assert_eq!(Status::Done, todos[0].status);
+
+    let path = script("bad", "todo.update(1, {bucket = 'next'})");
+    let e = run(&path, &[], &conn).unwrap_err();
+    assert!(e.to_string().contains("Can't update bucket of a todo"));
+    _ = fs::remove_file(path);
+  }
+
+  #[test]
+  fn handlers_see_the_changes_of_a_command() {
+    let conn = setup();
+    let hooks = vec![script(
+      "hooks",
+      r#"
+        todo.on("add", function(t) todo.update(t.id, {project = "Inbox"}) end)
+        todo.on("done", function(t, change)
+          assert(change.old == "todo")
+          todo.add("Follow up on " .. t.body)
+        end)
+        todo.on("delete", function(t) todo.add("Deleted " .. t.body) end)
+      "#,
+    )];
+    insert("Milk", &AddOptions::default(), &conn).unwrap();
+    fire(&hooks, &conn).unwrap();
+    undo::record("add Milk", true, &conn).unwrap();
+    assert_eq!(
+      Some("Inbox".to_string()),
+      find(1, &conn).unwrap().unwrap().project
+    );
+
+    conn
+      .execute("UPDATE todos SET status = 'done' WHERE id = 1", ())
+      .unwrap();
+    conn.execute("DELETE FROM todos WHERE id = 1", ()).unwrap();
+    fire(&hooks, &conn).unwrap();
+    let bodies = collect_todos_all(&conn)
+      .unwrap()
+      .into_iter()
+      .map(|todo| todo.body)
+      .collect::<Vec<String>>();
+    // Todos added by handlers don't get the project from the add handler
+    assert_eq!(vec!["Follow up on Milk", "Deleted Milk"], bodies);
+    assert_eq!(None, find(2, &conn).unwrap().unwrap().project);
+    _ = fs::remove_file(&hooks[0]);
+  }
+}
@@ -0,0 +1,262 @@
+use crate::Todo;
+use crate::ascii;
+use crate::ids::short;
+use crate::pager;
+use crate::removed::{self, Scope};
+use crate::searches;
+use console::style;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+use std::error::Error;
+
+/// How the full text index splits bodies and notes into words
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tokenizer {
+  /// English stemming, `running` finds `run errands`
+  #[default]
+  Porter,
+  /// Whole words of any language, accents aside
+  Unicode61,
+  /// Any three letters in a row, for languages written without spaces like Chinese
+  Trigram,
+}
+
+impl Tokenizer {
+  fn fts5(&self) -> &'static str {
+    match self {
+      Tokenizer::Porter => "porter unicode61 remove_diacritics 2",
+      Tokenizer::Unicode61 => "unicode61 remove_diacritics 2",
+      Tokenizer::Trigram => "trigram",
+    }
+  }
+}
+
+/// Keep `todos_fts` in step with the todos, made again when the tokenizer or the columns
+/// change. SQLite builds without FTS5 go without it.
+pub fn index(tokenizer: Tokenizer, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let tokenize = format!("tokenize='{}'", tokenizer.fts5());
+  let existing: Option<String> = conn
+    .query_row(
+      "SELECT sql FROM sqlite_master WHERE name = 'todos_fts'",
+      [],
+      |row| row.get(0),
+    )
+    .optional()?;
+  let columns = "fts5(body, notes, reference,";
+  if existing.is_some_and(|sql| sql.contains(&tokenize) && sql.contains(columns)) {
+    return Ok(());
+  }
+  let tx = conn.unchecked_transaction()?;
+  let made = tx.execute_batch(&format!(
+    "DROP TABLE IF EXISTS todos_fts;
+     DROP TRIGGER IF EXISTS todos_fts_created;
+     DROP TRIGGER IF EXISTS todos_fts_deleted;
+     DROP TRIGGER IF EXISTS todos_fts_updated;
+     CREATE VIRTUAL TABLE todos_fts USING {columns} content='todos', content_rowid='id',
+       {tokenize});
+     INSERT INTO todos_fts(todos_fts) VALUES ('rebuild');
+     CREATE TRIGGER todos_fts_created AFTER INSERT ON todos BEGIN
+       INSERT INTO todos_fts (rowid, body, notes, reference)
+       VALUES (NEW.id, NEW.body, NEW.notes, NEW.reference);
+     END;
+     CREATE TRIGGER todos_fts_deleted AFTER DELETE ON todos BEGIN
+       INSERT INTO todos_fts (todos_fts, rowid, body, notes, reference)
+       VALUES ('delete', OLD.id, OLD.body, OLD.notes, OLD.reference);
+     END;
+     CREATE TRIGGER todos_fts_updated AFTER UPDATE OF body, notes, reference ON todos BEGIN
+       INSERT INTO todos_fts (todos_fts, rowid, body, notes, reference)
+       VALUES ('delete', OLD.id, OLD.body, OLD.notes, OLD.reference);
+       INSERT INTO todos_fts (rowid, body, notes, reference)
+       VALUES (NEW.id, NEW.body, NEW.notes, NEW.reference);
+     END;"
+  ));
+  match made {
+    Ok(()) => tx.commit()?,
+    Err(e) => log::warn!("No full text index, SQLite lacks FTS5: {}", e),
+  }
+  Ok(())
+}
+
+/// What FTS5 puts around matched words, styled when printed
+const START: char = '\u{2}';
+const END: char = '\u{3}';
+
+/// A todo that matched, with the matched words marked in the body and a fragment of the
+/// notes when they matched too
+struct Found {
+  todo: Todo,
+  body: String,
+  notes: Option<String>,
+}
+
+/// Every word as a phrase so punctuation isn't FTS syntax, `deploy*` still matches prefixes
+fn fts_query(words: &[String]) -> String {
+  words
+    .iter()
+    .flat_map(|words| words.split_whitespace())
+    .map(|word| match word.strip_suffix('*') {
+      Some(prefix) => format!("\"{}\"*", prefix.replace('"', "\"\"")),
+      None => format!("\"{}\"", word.replace('"', "\"\"")),
+    })
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+/// Todos whose body, notes or ref have all the words, by BM25 relevance with the body counting
+/// double
+fn find(words: &[String], limit: usize, conn: &Connection) -> Result<Vec<Found>, Box<dyn Error>> {
+  let query = fts_query(words);
+  if query.is_empty() {
+    return Err("Nothing to search for".into());
+  }
+  let stmt = conn.prepare(
+    "SELECT todos.*,
+       highlight(todos_fts, 0, char(2), char(3)) AS found_body,
+       snippet(todos_fts, 1, char(2), char(3), '…', 8) AS found_notes
+     FROM todos_fts JOIN todos ON todos.id = todos_fts.rowid
+     WHERE todos_fts MATCH ?1 ORDER BY bm25(todos_fts, 2.0, 1.0), todos.id LIMIT ?2",
+  );
+  let mut stmt = match stmt {
+    Err(e) if e.to_string().contains("no such table") => {
+      return Err(
+        "There is no full text index yet, open the todos once without --read-only".into(),
+      );
+    }
+    stmt => stmt?,
+  };
+  let found = stmt
+    .query_map((query, limit), |row| {
+      Ok(Found {
+        todo: Todo::from_row(row)?,
+        body: row.get("found_body")?,
+        notes: row
+          .get::<_, Option<String>>("found_notes")?
+          .filter(|notes| notes.contains(START)),
+      })
+    })?
+    .collect::<Result<_, _>>()?;
+  Ok(found)
+}
+
+/// The marked words bold in the color of the rest
+fn highlight(text: &str, color: fn(String) -> String) -> String {
+  text
+    .split(START)
+    .enumerate()
+    .map(|(index, part)| match (index, part.split_once(END)) {
+      (0, _) | (_, None) => color(part.to_string()),
+      (_, Some((found, rest))) => format!(
+        "{}{}",
+        style(found).bold().yellow(),
+        color(rest.to_string())
+      ),
+    })
+    .collect()
+}
+
+/// `todo search`, with the words the tokenizer of the config makes of them. Removed todos
+/// aren't indexed, the words are matched the way filters are there.
+pub fn search(
+  words: &[String],
+  limit: usize,
+  include: &[Scope],
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let found = find(words, limit, conn)?;
+  let gone = removed::collect(include, conn)?
+    .into_iter()
+    .filter(|gone| removed::matches(gone, words))
+    .take(limit)
+    .collect::<Vec<_>>();
+  // Read-only todos keep no history
+  _ = searches::record(searches::SEARCH, &words.join(" "), conn);
+  if found.is_empty() && gone.is_empty() {
+    println!("Nothing found!");
+  }
+  let dim = |text: String| style(text).dim().to_string();
+  let mut lines = vec![];
+  for Found { todo, body, notes } in found {
+    let id = short(todo.id);
+    lines.push(match todo.status.is_open() {
+      true => format!("{}. {}", id, highlight(&body, |text| text)),
+      false => format!(
+        "{} {}",
+        dim(format!("{}.", id)),
+        highlight(&format!("{} {}", body, todo.status), dim)
+      ),
+    });
+    if let Some(notes) = notes {
+      lines.push(format!(
+        "{:width$}  {}",
+        "",
+        highlight(&ascii::text(&notes), dim),
+        width = id.len()
+      ));
+    }
+  }
+  lines.extend(removed::lines(&gone));
+  pager::page(&lines)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add};
+
+  #[test]
+  fn stemming_finds_other_forms() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Run errands".to_string(), "Paint the fence".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    index(Tokenizer::Porter, &conn).unwrap();
+    conn
+      .execute(
+        "UPDATE todos SET notes = 'Buy fresh paint' WHERE id = 1",
+        (),
+      )
+      .unwrap();
+    let bodies = |words: &[&str]| {
+      let words = words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+      find(&words, 10, &conn)
+        .unwrap()
+        .into_iter()
+        .map(|found| found.todo.body)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(vec!["Run errands"], bodies(&["running"]));
+    // The body counts more than the notes
+    assert_eq!(
+      vec!["Paint the fence", "Run errands"],
+      bodies(&["painting"])
+    );
+    assert_eq!(vec!["Paint the fence"], bodies(&["fen*", "\"paint"]));
+    conn
+      .execute("UPDATE todos SET reference = 'SHOP-12' WHERE id = 2", ())
+      .unwrap();
+    assert_eq!(vec!["Paint the fence"], bodies(&["shop-12"]));
+
+    let marked = |text: &str| text.replace(START, "[").replace(END, "]");
+    let found = find(&["paint".to_string()], 10, &conn).unwrap();
+    assert_eq!("[Paint] the fence", marked(&found[0].body));
+    assert_eq!(None, found[0].notes);
+    assert_eq!(
+      Some("Buy fresh [paint]".to_string()),
+      found[1].notes.as_deref().map(marked)
+    );
+    console::set_colors_enabled(false);
+    assert_eq!("Paint the fence", highlight(&found[0].body, |text| text));
+
+    // Without stemming only whole words are found
+    index(Tokenizer::Unicode61, &conn).unwrap();
+    assert!(bodies(&["running"]).is_empty());
+    assert_eq!(vec!["Paint the fence"], bodies(&["SHOP-12"]));
+    conn.execute("DELETE FROM todos WHERE id = 2", ()).unwrap();
+    assert_eq!(vec!["Run errands"], bodies(&["paint"]));
+  }
+}
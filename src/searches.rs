@@ -0,0 +1,73 @@
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Words given to `todo search`
+pub const SEARCH: &str = "search";
+
+/// Text typed into the filter bar of the terminal ui
+pub const FILTER: &str = "filter";
+
+/// How many of each kind are remembered
+const KEEP: usize = 50;
+
+/// Remember `text` as the latest of its kind, typing it again moves it to the front
+pub fn record(kind: &str, text: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let text = text.trim();
+  if text.is_empty() {
+    return Ok(());
+  }
+  conn.execute(
+    "INSERT INTO searches (kind, text) VALUES (?1, ?2)
+     ON CONFLICT (kind, text) DO UPDATE SET id = (SELECT max(id) + 1 FROM searches)",
+    (kind, text),
+  )?;
+  conn.execute(
+    "DELETE FROM searches WHERE kind = ?1 AND id NOT IN
+       (SELECT id FROM searches WHERE kind = ?1 ORDER BY id DESC LIMIT ?2)",
+    (kind, KEEP),
+  )?;
+  Ok(())
+}
+
+/// The searches of a kind, latest first
+pub fn recent(kind: &str, conn: &Connection) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT text FROM searches WHERE kind = ?1 ORDER BY id DESC")?;
+  let recent = stmt
+    .query_map((kind,), |row| row.get(0))?
+    .collect::<Result<_, _>>()?;
+  Ok(recent)
+}
+
+/// The latest search starting with what is typed, to complete it with
+pub fn complete<'a>(typed: &str, recent: &'a [String]) -> Option<&'a String> {
+  recent
+    .iter()
+    .find(|text| text.len() > typed.len() && text.starts_with(typed))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+
+  #[test]
+  fn searches_are_recalled_latest_first() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    for text in ["milk", "paint fence", " ", "milk", "rent"] {
+      record(SEARCH, text, &conn).unwrap();
+    }
+    record(FILTER, "house", &conn).unwrap();
+    let recent = recent(SEARCH, &conn).unwrap();
+    assert_eq!(vec!["rent", "milk", "paint fence"], recent);
+    assert_eq!(Some(&"paint fence".to_string()), complete("pa", &recent));
+    assert_eq!(None, complete("milk", &recent));
+
+    for index in 0..KEEP {
+      record(FILTER, &index.to_string(), &conn).unwrap();
+    }
+    let filters = super::recent(FILTER, &conn).unwrap();
+    assert_eq!(KEEP, filters.len());
+    assert_eq!("49", filters[0]);
+  }
+}
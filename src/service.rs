@@ -0,0 +1,243 @@
+use crate::duration::format_duration;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs};
+
+/// How the command runs in the background
+#[derive(Debug, PartialEq)]
+enum Schedule {
+  /// Started again every so many minutes, like `slack post`
+  Every(u32),
+  /// Kept running and restarted when it fails, like `bot`
+  Daemon,
+}
+
+/// A unit or agent to write, named after the command like `todo-slack-post`
+struct Service {
+  name: String,
+  /// This binary and its arguments, `--db` included
+  args: Vec<String>,
+  schedule: Schedule,
+}
+
+/// One argument of an `ExecStart=` line, quoted when it has spaces or quotes and with `%`
+/// doubled since systemd expands specifiers
+fn systemd_arg(arg: &str) -> String {
+  let arg = arg.replace('%', "%%");
+  match arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\') {
+    true => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+    false => arg,
+  }
+}
+
+fn xml(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+impl Service {
+  fn new(exe: &Path, db: &str, command: &[String], every: Option<u32>) -> Service {
+    let name = command
+      .iter()
+      .take_while(|word| !word.starts_with('-'))
+      .map(|word| word.replace(|c: char| !c.is_ascii_alphanumeric(), ""))
+      .filter(|word| !word.is_empty())
+      .fold("todo".to_string(), |name, word| name + "-" + &word);
+    let mut args = vec![
+      exe.display().to_string(),
+      "--db".to_string(),
+      db.to_string(),
+    ];
+    args.extend(command.iter().cloned());
+    Service {
+      name,
+      args,
+      schedule: every.map_or(Schedule::Daemon, Schedule::Every),
+    }
+  }
+
+  /// The `.service` file and for commands run every so often the `.timer` starting it
+  fn systemd(&self) -> Vec<(String, String)> {
+    let exec = self
+      .args
+      .iter()
+      .map(|arg| systemd_arg(arg))
+      .collect::<Vec<String>>()
+      .join(" ");
+    let description = format!("Description=todo {}", self.args[3..].join(" "));
+    match self.schedule {
+      Schedule::Every(minutes) => vec![
+        (
+          format!("{}.service", self.name),
+          format!("[Unit]\n{description}\n\n[Service]\nType=oneshot\nExecStart={exec}\n"),
+        ),
+        (
+          format!("{}.timer", self.name),
+          format!(
+            "[Unit]\n{description} every {}\n\n[Timer]\nOnBootSec=1min\nOnUnitActiveSec={}min\n\n\
+             [Install]\nWantedBy=timers.target\n",
+            format_duration(minutes),
+            minutes
+          ),
+        ),
+      ],
+      Schedule::Daemon => vec![(
+        format!("{}.service", self.name),
+        format!(
+          "[Unit]\n{description}\n\n[Service]\nExecStart={exec}\nRestart=on-failure\n\
+           RestartSec=30\n\n[Install]\nWantedBy=default.target\n"
+        ),
+      )],
+    }
+  }
+
+  /// A launchd agent, which runs the command on an interval or keeps it alive
+  fn launchd(&self) -> (String, String) {
+    let args = self
+      .args
+      .iter()
+      .map(|arg| format!("    <string>{}</string>\n", xml(arg)))
+      .collect::<String>();
+    let schedule = match self.schedule {
+      Schedule::Every(minutes) => format!(
+        "  <key>StartInterval</key>\n  <integer>{}</integer>\n",
+        minutes * 60
+      ),
+      Schedule::Daemon => "  <key>KeepAlive</key>\n  <true/>\n".to_string(),
+    };
+    let label = format!("org.{}", self.name);
+    (
+      format!("{}.plist", label),
+      format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n  <key>Label</key>\n  <string>{}</string>\n  \
+         <key>ProgramArguments</key>\n  <array>\n{}  </array>\n  <key>RunAtLoad</key>\n  \
+         <true/>\n{}</dict>\n</plist>\n",
+        label, args, schedule
+      ),
+    )
+  }
+}
+
+fn home() -> Result<PathBuf, Box<dyn Error>> {
+  Ok(PathBuf::from(env::var_os("HOME").ok_or("HOME isn't set")?))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
+  let status = Command::new(program)
+    .args(args)
+    .status()
+    .map_err(|e| format!("Can't run {}: {}", program, e))?;
+  if !status.success() {
+    return Err(format!("{} {} failed", program, args.join(" ")).into());
+  }
+  Ok(())
+}
+
+/// `todo install-service --user`, a systemd user unit or on macOS a launchd agent that runs
+/// `command` on the same todos as this command
+pub fn install(
+  user: bool,
+  every: Option<u32>,
+  command: &[String],
+  dry_run: bool,
+  db: &str,
+) -> Result<(), Box<dyn Error>> {
+  if !user && !cfg!(target_os = "macos") {
+    return Err("Only user services are supported, use --user".into());
+  }
+  if every == Some(0) {
+    return Err("Use an interval of at least a minute".into());
+  }
+  // The service starts in another directory
+  let db = match fs::canonicalize(db) {
+    Ok(path) => path.display().to_string(),
+    Err(_) => db.to_string(),
+  };
+  let service = Service::new(&env::current_exe()?, &db, command, every);
+
+  let (dir, files) = match cfg!(target_os = "macos") {
+    true => (
+      home()?.join("Library/LaunchAgents"),
+      vec![service.launchd()],
+    ),
+    false => (
+      env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .map_or_else(|| home().map(|home| home.join(".config")), Ok)?
+        .join("systemd/user"),
+      service.systemd(),
+    ),
+  };
+  for (name, text) in &files {
+    let path = dir.join(name);
+    match dry_run {
+      true => println!("Would write {}:\n{}", path.display(), text),
+      false => {
+        fs::create_dir_all(&dir)?;
+        fs::write(&path, text)?;
+        println!("Wrote {}", path.display());
+      }
+    }
+  }
+  if dry_run {
+    println!("Dry run, nothing installed!");
+    return Ok(());
+  }
+
+  // Untested segment starts, this part needs a service manager
+  match cfg!(target_os = "macos") {
+    true => run(
+      "launchctl",
+      &["load", "-w", &dir.join(&files[0].0).display().to_string()],
+    )?,
+    false => {
+      run("systemctl", &["--user", "daemon-reload"])?;
+      // The timer starts the service, enabling that alone would run it once
+      let unit = &files.last().map_or(String::new(), |(name, _)| name.clone());
+      run("systemctl", &["--user", "enable", "--now", unit])?;
+    }
+  }
+  println!("Enabled {}", service.name);
+  Ok(())
+  // Untested segment ends
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn services_run_the_command_on_the_same_todos() {
+    let words = |text: &str| text.split(' ').map(str::to_string).collect::<Vec<_>>();
+    let exe = Path::new("/usr/bin/todo");
+    let timer = Service::new(exe, "/home/me/my todos.db", &words("slack post"), Some(90));
+    let units = timer.systemd();
+    assert_eq!("todo-slack-post.service", units[0].0);
+    assert!(
+      units[0]
+        .1
+        .contains("ExecStart=/usr/bin/todo --db \"/home/me/my todos.db\" slack post\n")
+    );
+    assert_eq!("todo-slack-post.timer", units[1].0);
+    assert!(units[1].1.contains("OnUnitActiveSec=90min"));
+
+    let daemon = Service::new(exe, "50%.db", &words("bot --matrix"), None);
+    let units = daemon.systemd();
+    assert_eq!(1, units.len());
+    assert!(
+      units[0]
+        .1
+        .contains("--db 50%%.db bot --matrix\nRestart=on-failure")
+    );
+    let (name, plist) = daemon.launchd();
+    assert_eq!("org.todo-bot.plist", name);
+    assert!(plist.contains("<string>--matrix</string>\n  </array>"));
+    assert!(plist.contains("<key>KeepAlive</key>"));
+  }
+}
@@ -0,0 +1,131 @@
+use crate::status::Status;
+use crate::{TODO_SELECT, Todo, collect_todos, config, find_targets};
+use chrono::{Local, NaiveDate};
+use clap::Subcommand;
+use rusqlite::Connection;
+use serde_json::json;
+use std::error::Error;
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Post a summary of todos to the configured webhook, the overdue ones by default
+  Post {
+    /// Ids or parts of the text of the todos to post instead
+    targets: Vec<String>,
+
+    /// Only show the message
+    #[arg(long)]
+    dry_run: bool,
+  },
+}
+
+fn collect_todos_overdue(today: NaiveDate, conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} WHERE status not in ('done', 'cancelled') and due < '{}' ORDER BY due, id;",
+      today
+    ),
+    conn,
+  )
+}
+
+/// Slack flavoured markdown, one bullet per todo
+fn message(title: &str, todos: &[Todo], today: NaiveDate) -> String {
+  let mut lines = vec![format!("*{}* ({})", title, todos.len())];
+  for todo in todos {
+    let mut line = format!("• {}", todo.body);
+    if let Some(project) = &todo.project {
+      line.push_str(&format!(" _[{}]_", project));
+    }
+    if let Some(due) = todo.due {
+      let days = (today - due).num_days();
+      if days > 0 {
+        line.push_str(&format!(" — due {}, {} days overdue", due, days));
+      } else {
+        line.push_str(&format!(" — due {}", due));
+      }
+    }
+    if todo.status != Status::Todo {
+      line.push_str(&format!(" `{}`", todo.status));
+    }
+    lines.push(line);
+  }
+  lines.join("\n")
+}
+
+fn post(
+  targets: &[String],
+  dry_run: bool,
+  config: Option<&config::Slack>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let today = Local::now().date_naive();
+  let (title, todos) = if targets.is_empty() {
+    ("Overdue todos", collect_todos_overdue(today, conn)?)
+  } else {
+    ("Todos", find_targets(targets, conn)?)
+  };
+  if todos.is_empty() {
+    println!("Nothing to post!");
+    return Ok(());
+  }
+  let text = message(title, &todos, today);
+  if dry_run {
+    println!("{}", text);
+    println!("Dry run, nothing posted!");
+    return Ok(());
+  }
+  let config = config.ok_or("Add a [slack] table with the webhook url to the config first")?;
+  ureq::post(&config.webhook).send_json(json!({ "text": text }))?;
+  println!("Posted {} todos to Slack", todos.len());
+  Ok(())
+}
+
+pub fn slack(
+  action: &Action,
+  config: Option<&config::Slack>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Post { targets, dry_run } => post(targets, *dry_run, config, conn),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add};
+
+  #[test]
+  fn overdue_message() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+    let due = |day| AddOptions {
+      due: NaiveDate::from_ymd_opt(2024, 3, day),
+      ..Default::default()
+    };
+    _ = add(vec!["Milk".to_string()], &due(7), &conn);
+    _ = add(vec!["Eggs".to_string()], &due(12), &conn);
+    _ = add(
+      vec!["Rent".to_string()],
+      &AddOptions {
+        project: Some("Home".to_string()),
+        ..due(1)
+      },
+      &conn,
+    );
+
+    let todos = collect_todos_overdue(today, &conn).unwrap();
+    assert_eq!(
+      "*Overdue todos* (2)\n\
+       • Rent _[Home]_ — due 2024-03-01, 9 days overdue\n\
+       • Milk — due 2024-03-07, 3 days overdue",
+      message("Overdue todos", &todos, today)
+    );
+    // Only the dry run works without a webhook
+    assert!(post(&[], true, None, &conn).is_ok());
+    assert!(post(&[], false, None, &conn).is_err());
+  }
+}
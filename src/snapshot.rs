@@ -0,0 +1,340 @@
+use crate::db::create_db;
+use crate::prompt;
+use crate::{TODO_SELECT, Todo, collect_todos};
+use clap::Subcommand;
+use console::style;
+use dialoguer::Confirm;
+use rusqlite::{Connection, MAIN_DB};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Save a copy of everything under a name
+  Create { name: String },
+
+  /// Show what changed since a snapshot
+  Diff { name: String },
+
+  /// Replace everything with a snapshot
+  Restore {
+    name: String,
+
+    /// Don't ask before restoring
+    #[arg(short, long)]
+    yes: bool,
+  },
+
+  /// Show the saved snapshots
+  List {},
+}
+
+/// Differences between the todos of a snapshot and the current ones
+#[derive(Debug, Default, PartialEq)]
+struct Diff {
+  added: Vec<Todo>,
+  removed: Vec<Todo>,
+  /// Current todo and the fields that differ from the snapshot
+  changed: Vec<(Todo, Vec<&'static str>)>,
+}
+
+/// Snapshots live next to the database, `todos.db` keeps them in `todos.snapshots`
+fn snapshot_dir(conn: &Connection) -> Result<PathBuf, Box<dyn Error>> {
+  let path = conn
+    .path()
+    .filter(|path| !path.is_empty())
+    .ok_or("Snapshots need a database file")?;
+  Ok(Path::new(path).with_extension("snapshots"))
+}
+
+fn snapshot_path(dir: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+  if name.is_empty()
+    || !name
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+  {
+    return Err(
+      format!(
+        "Invalid snapshot name '{}', use letters, digits, - and _",
+        name
+      )
+      .into(),
+    );
+  }
+  Ok(dir.join(name).with_extension("db"))
+}
+
+fn existing_snapshot(dir: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+  let path = snapshot_path(dir, name)?;
+  if !path.exists() {
+    return Err(format!("No snapshot named '{}'", name).into());
+  }
+  Ok(path)
+}
+
+fn create(dir: &Path, name: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let path = snapshot_path(dir, name)?;
+  if path.exists() {
+    return Err(format!("Snapshot '{}' already exists", name).into());
+  }
+  fs::create_dir_all(dir)?;
+  conn.execute("VACUUM INTO ?1", (path.to_string_lossy(),))?;
+  println!("Created snapshot: {}", name);
+  Ok(())
+}
+
+/// Todos of a snapshot, migrated in memory in case it is from an older version
+fn collect_todos_snapshot(path: &Path) -> Result<Vec<Todo>, Box<dyn Error>> {
+  let mut snapshot = Connection::open_in_memory()?;
+  snapshot.restore(MAIN_DB, path, None::<fn(rusqlite::backup::Progress)>)?;
+  create_db(&snapshot)?;
+  collect_todos(format!("{TODO_SELECT};"), &snapshot)
+}
+
+fn changed_fields(before: &Todo, after: &Todo) -> Vec<&'static str> {
+  [
+    ("body", before.body != after.body),
+    ("status", before.status != after.status),
+    ("waiting on", before.waiting_on != after.waiting_on),
+    ("follow up", before.follow_up != after.follow_up),
+    ("estimate", before.estimate != after.estimate),
+    ("priority", before.priority != after.priority),
+    ("due", before.due != after.due),
+    ("bucket", before.bucket != after.bucket),
+    ("project", before.project != after.project),
+    ("snoozed", before.snoozed_until != after.snoozed_until),
+    ("focus", before.focused != after.focused),
+    ("notes", before.notes != after.notes),
+    ("parent", before.parent_id != after.parent_id),
+    ("tags", before.tags != after.tags),
+    ("scheduled", before.scheduled != after.scheduled),
+    ("pinned", before.pinned != after.pinned),
+    ("expires", before.expires != after.expires),
+    ("habit", before.habit != after.habit),
+    ("ref", before.reference != after.reference),
+  ]
+  .into_iter()
+  .filter_map(|(field, changed)| changed.then_some(field))
+  .collect()
+}
+
+fn diff(snapshot: Vec<Todo>, current: Vec<Todo>) -> Diff {
+  let mut before = snapshot
+    .into_iter()
+    .map(|todo| (todo.id, todo))
+    .collect::<HashMap<usize, Todo>>();
+  let mut diff = Diff::default();
+  for todo in current {
+    match before.remove(&todo.id) {
+      None => diff.added.push(todo),
+      Some(old) if old != todo => {
+        let fields = changed_fields(&old, &todo);
+        diff.changed.push((todo, fields));
+      }
+      Some(_) => {}
+    }
+  }
+  let mut removed = before.into_values().collect::<Vec<Todo>>();
+  removed.sort_by_key(|todo| todo.id);
+  diff.removed = removed;
+  diff
+}
+
+fn print_diff(diff: &Diff) {
+  if *diff == Diff::default() {
+    println!("No changes since the snapshot!");
+  }
+  for todo in &diff.added {
+    println!(
+      "{}",
+      style(format!("+ [{}] {}", todo.id, todo.body)).green()
+    );
+  }
+  for todo in &diff.removed {
+    println!("{}", style(format!("- [{}] {}", todo.id, todo.body)).red());
+  }
+  for (todo, fields) in &diff.changed {
+    println!(
+      "{}",
+      style(format!(
+        "~ [{}] {} ({})",
+        todo.id,
+        todo.body,
+        fields.join(", ")
+      ))
+      .yellow()
+    );
+  }
+}
+
+fn show_diff(dir: &Path, name: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let snapshot = collect_todos_snapshot(&existing_snapshot(dir, name)?)?;
+  let current = collect_todos(format!("{TODO_SELECT};"), conn)?;
+  print_diff(&diff(snapshot, current));
+  Ok(())
+}
+
+/// Replace the whole database, history included, with the snapshot
+fn restore(dir: &Path, name: &str, yes: bool, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+  let path = existing_snapshot(dir, name)?;
+  show_diff(dir, name, conn)?;
+  if !yes {
+    prompt::require("pass --yes to restore without asking")?;
+  }
+
+  // Untested segment starts, this part needs interactivity
+  if !yes
+    && !Confirm::new()
+      .with_prompt(format!("Undo these changes by restoring '{}'?", name))
+      .interact()?
+  {
+    println!("Cancelled, nothing changed!");
+    return Ok(());
+  }
+  // Untested segment ends
+
+  conn.restore(MAIN_DB, &path, None::<fn(rusqlite::backup::Progress)>)?;
+  create_db(conn)?;
+  println!("Restored snapshot: {}", name);
+  Ok(())
+}
+
+/// Names of the snapshots in `dir`, sorted
+fn names(dir: &Path) -> Vec<String> {
+  let mut names = match fs::read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|extension| extension == "db"))
+      .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+      .collect::<Vec<String>>(),
+    Err(_) => vec![],
+  };
+  names.sort();
+  names
+}
+
+fn list(dir: &Path) -> Result<(), Box<dyn Error>> {
+  let names = names(dir);
+  if names.is_empty() {
+    println!("No snapshots yet!");
+  }
+  for name in names {
+    println!("{}", name);
+  }
+  Ok(())
+}
+
+/// Remove the snapshots made more than `days` ago, returns their names. A database in
+/// memory has none.
+pub fn prune(days: i64, dry_run: bool, conn: &Connection) -> Result<Vec<String>, Box<dyn Error>> {
+  let Ok(dir) = snapshot_dir(conn) else {
+    return Ok(vec![]);
+  };
+  let cutoff = SystemTime::now() - Duration::from_secs(days.max(0) as u64 * 24 * 60 * 60);
+  let mut pruned = vec![];
+  for name in names(&dir) {
+    let path = snapshot_path(&dir, &name)?;
+    if fs::metadata(&path)?.modified()? < cutoff {
+      if !dry_run {
+        fs::remove_file(&path)?;
+      }
+      pruned.push(name);
+    }
+  }
+  Ok(pruned)
+}
+
+pub fn snapshot(action: &Action, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+  let dir = snapshot_dir(conn)?;
+  match action {
+    Action::Create { name } => create(&dir, name, conn),
+    Action::Diff { name } => show_diff(&dir, name, conn),
+    Action::Restore { name, yes } => restore(&dir, name, *yes, conn),
+    Action::List {} => list(&dir),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::status::Status;
+  use crate::{AddOptions, add, collect_todos_all, rm, set_status};
+  use std::env;
+
+  /// A file database in its own temporary directory, snapshots need a real file
+  fn setup(test: &str) -> (PathBuf, Connection) {
+    let dir = env::temp_dir().join(format!("todo-snapshot-{}-{}", test, std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let conn = Connection::open(dir.join("todos.db")).unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    (dir, conn)
+  }
+
+  #[test]
+  fn snapshot_dir_follows_database() {
+    let (dir, conn) = setup("dir");
+    assert_eq!(dir.join("todos.snapshots"), snapshot_dir(&conn).unwrap());
+    assert!(snapshot_dir(&Connection::open_in_memory().unwrap()).is_err());
+    assert!(snapshot_path(&dir, "../escape").is_err());
+  }
+
+  #[test]
+  fn diff_and_restore() {
+    let (dir, mut conn) = setup("restore");
+    let snapshots = snapshot_dir(&conn).unwrap();
+    create(&snapshots, "before", &conn).unwrap();
+    assert!(create(&snapshots, "before", &conn).is_err());
+
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = rm(vec![todos[0].clone()], &conn);
+    _ = set_status(vec![todos[1].clone()], Status::Done, &conn);
+    _ = add(vec!["Bread".to_string()], &AddOptions::default(), &conn);
+
+    let diff = diff(
+      collect_todos_snapshot(&snapshot_path(&snapshots, "before").unwrap()).unwrap(),
+      collect_todos_all(&conn).unwrap(),
+    );
+    assert_eq!(vec!["Bread"], bodies(&diff.added));
+    assert_eq!(vec!["Milk"], bodies(&diff.removed));
+    assert_eq!(vec!["status"], diff.changed[0].1);
+
+    restore(&snapshots, "before", true, &mut conn).unwrap();
+    assert_eq!(todos, collect_todos_all(&conn).unwrap());
+    _ = fs::remove_dir_all(dir);
+  }
+
+  #[test]
+  fn prune_removes_old_snapshots() {
+    let (dir, conn) = setup("prune");
+    let snapshots = snapshot_dir(&conn).unwrap();
+    create(&snapshots, "old", &conn).unwrap();
+    create(&snapshots, "new", &conn).unwrap();
+    let year_ago = SystemTime::now() - Duration::from_secs(365 * 24 * 60 * 60);
+    fs::File::options()
+      .write(true)
+      .open(snapshot_path(&snapshots, "old").unwrap())
+      .unwrap()
+      .set_modified(year_ago)
+      .unwrap();
+
+    assert_eq!(vec!["old"], prune(30, true, &conn).unwrap());
+    assert_eq!(vec!["new", "old"], names(&snapshots));
+    assert_eq!(vec!["old"], prune(30, false, &conn).unwrap());
+    assert_eq!(vec!["new"], names(&snapshots));
+    _ = fs::remove_dir_all(dir);
+  }
+
+  fn bodies(todos: &[Todo]) -> Vec<&str> {
+    todos.iter().map(|todo| todo.body.as_str()).collect()
+  }
+}
@@ -0,0 +1,151 @@
+use crate::status::Status;
+use serde::Deserialize;
+use std::fmt;
+
+/// What todos can be sorted by
+const KEYS: [&str; 11] = [
+  "manual",
+  "priority",
+  "due",
+  "scheduled",
+  "created",
+  "updated",
+  "status",
+  "project",
+  "estimate",
+  "body",
+  "id",
+];
+
+/// Keys like `priority:desc,due:asc,created:desc`, the first one counts most. Pinned todos
+/// still come first and the manual order settles ties.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Sort(Vec<(&'static str, bool)>);
+
+impl TryFrom<String> for Sort {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Sort, String> {
+    parse(&text)
+  }
+}
+
+/// `--sort`, a key without a direction sorts ascending
+pub fn parse(text: &str) -> Result<Sort, String> {
+  let mut keys = vec![];
+  for part in text
+    .split(',')
+    .map(str::trim)
+    .filter(|part| !part.is_empty())
+  {
+    let (key, direction) = part.split_once(':').unwrap_or((part, "asc"));
+    let key = KEYS
+      .into_iter()
+      .find(|known| known.eq_ignore_ascii_case(key.trim()))
+      .ok_or_else(|| format!("Can't sort by {}, only by {}", key, KEYS.join(", ")))?;
+    let descending = match direction.trim().to_lowercase().as_str() {
+      "asc" => false,
+      "desc" => true,
+      direction => return Err(format!("{} isn't asc or desc", direction)),
+    };
+    keys.push((key, descending));
+  }
+  match keys.is_empty() {
+    true => Err("Sort by at least one key like due:asc".to_string()),
+    false => Ok(Sort(keys)),
+  }
+}
+
+impl fmt::Display for Sort {
+  /// The keys as `--sort` takes them
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let keys = self
+      .0
+      .iter()
+      .map(|(key, descending)| format!("{}:{}", key, if *descending { "desc" } else { "asc" }))
+      .collect::<Vec<_>>();
+    f.write_str(&keys.join(","))
+  }
+}
+
+impl Sort {
+  fn column(key: &str) -> String {
+    match key {
+      "manual" => "position".to_string(),
+      "created" => "created_at".to_string(),
+      "updated" => "updated_at".to_string(),
+      "body" => "body COLLATE NOCASE".to_string(),
+      // In the order work goes through them
+      "status" => {
+        let ranks = Status::ALL
+          .iter()
+          .enumerate()
+          .map(|(rank, status)| format!(" WHEN '{}' THEN {}", status.as_str(), rank))
+          .collect::<String>();
+        format!("CASE status{} END", ranks)
+      }
+      key => key.to_string(),
+    }
+  }
+
+  /// The ORDER BY clause, todos without a value go last either way
+  pub fn order_by(&self) -> String {
+    let keys = self
+      .0
+      .iter()
+      .map(|(key, descending)| {
+        let direction = if *descending { "DESC" } else { "ASC" };
+        format!("{} {} NULLS LAST, ", Sort::column(key), direction)
+      })
+      .collect::<String>();
+    format!("ORDER BY pinned DESC, {}position, id", keys)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::priority::Priority;
+  use crate::{AddOptions, TODO_SELECT, add, bodies, collect_todos};
+  use chrono::NaiveDate;
+  use rusqlite::Connection;
+
+  #[test]
+  fn sorting_by_several_keys() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let day = |day| NaiveDate::from_ymd_opt(2024, 3, day);
+    for (body, priority, due) in [
+      ("Milk", None, day(2)),
+      ("Rent", Some(Priority::High), day(5)),
+      ("Taxes", Some(Priority::High), day(1)),
+      ("Call", Some(Priority::Low), None),
+    ] {
+      let options = AddOptions {
+        priority,
+        due,
+        ..Default::default()
+      };
+      _ = add(vec![body.to_string()], &options, &conn);
+    }
+    let sorted = |text: &str| {
+      let sort = parse(text).unwrap();
+      bodies(&collect_todos(format!("{TODO_SELECT} {}", sort.order_by()), &conn).unwrap())
+    };
+    assert_eq!(
+      vec!["Taxes", "Rent", "Call", "Milk"],
+      sorted("priority:desc,due:asc")
+    );
+    assert_eq!(vec!["Taxes", "Milk", "Rent", "Call"], sorted("due"));
+    assert_eq!(vec!["Rent", "Milk", "Taxes", "Call"], sorted("DUE:desc"));
+    assert_eq!(vec!["Call", "Milk", "Rent", "Taxes"], sorted("body"));
+    assert_eq!(vec!["Call", "Taxes", "Rent", "Milk"], sorted("manual:desc"));
+    assert_eq!(
+      "due:desc,body:asc",
+      parse("DUE:desc, body").unwrap().to_string()
+    );
+    assert!(parse("size").is_err() && parse("due:up").is_err() && parse("").is_err());
+  }
+}
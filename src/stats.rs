@@ -0,0 +1,241 @@
+use crate::status::Status;
+use crate::{Todo, collect_todos_all};
+use chrono::{DateTime, Utc};
+use console::{measure_text_width, pad_str};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+/// When each todo was last marked done, from its history
+pub fn collect_completed_at(
+  conn: &Connection,
+) -> Result<HashMap<usize, DateTime<Utc>>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT todo_id, max(changed_at) FROM history
+     WHERE field = 'status' and new = 'done' GROUP BY todo_id",
+  )?;
+  let completed = stmt
+    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect::<Result<HashMap<usize, DateTime<Utc>>, _>>()?;
+  Ok(completed)
+}
+
+fn days_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+  (to - from).num_seconds() as f64 / 86_400.0
+}
+
+/// Counts and durations of one tag or project
+#[derive(Debug, Default, PartialEq)]
+struct Group {
+  open: usize,
+  done: usize,
+  /// Days since creation of the open todos
+  ages: Vec<f64>,
+  /// Days from creation to completion of the done todos
+  completions: Vec<f64>,
+}
+
+impl Group {
+  fn add(&mut self, todo: &Todo, completed_at: Option<&DateTime<Utc>>, now: DateTime<Utc>) {
+    match todo.status {
+      Status::Done => {
+        self.done += 1;
+        let completed = completed_at.copied().or(todo.updated_at);
+        if let (Some(created), Some(completed)) = (todo.created_at, completed) {
+          self.completions.push(days_between(created, completed));
+        }
+      }
+      Status::Cancelled => {}
+      _ => {
+        self.open += 1;
+        if let Some(created) = todo.created_at {
+          self.ages.push(days_between(created, now));
+        }
+      }
+    }
+  }
+}
+
+fn average(days: &[f64]) -> String {
+  if days.is_empty() {
+    return "-".to_string();
+  }
+  format!("{:.1}d", days.iter().sum::<f64>() / days.len() as f64)
+}
+
+/// Todos grouped by the names `key` gives them, a todo can be in several groups
+fn breakdown(
+  todos: &[Todo],
+  completed_at: &HashMap<usize, DateTime<Utc>>,
+  now: DateTime<Utc>,
+  key: impl Fn(&Todo) -> Vec<String>,
+) -> BTreeMap<String, Group> {
+  let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+  for todo in todos {
+    for name in key(todo) {
+      groups
+        .entry(name)
+        .or_default()
+        .add(todo, completed_at.get(&todo.id), now);
+    }
+  }
+  groups
+}
+
+/// Aligned table with a header naming what the rows are
+fn table(title: &str, groups: &BTreeMap<String, Group>) -> Vec<String> {
+  let header = [title, "Open", "Done", "Avg age", "Avg completion"];
+  let mut rows = vec![header.map(str::to_string).to_vec()];
+  for (name, group) in groups {
+    rows.push(vec![
+      name.clone(),
+      group.open.to_string(),
+      group.done.to_string(),
+      average(&group.ages),
+      average(&group.completions),
+    ]);
+  }
+  let widths = (0..header.len())
+    .map(|column| {
+      rows
+        .iter()
+        .map(|row| measure_text_width(&row[column]))
+        .max()
+        .unwrap_or(0)
+    })
+    .collect::<Vec<usize>>();
+  rows
+    .iter()
+    .map(|row| {
+      row
+        .iter()
+        .enumerate()
+        .map(|(column, cell)| {
+          // Names to the left, numbers to the right
+          let alignment = match column {
+            0 => console::Alignment::Left,
+            _ => console::Alignment::Right,
+          };
+          pad_str(cell, widths[column], alignment, None).to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("  ")
+        .trim_end()
+        .to_string()
+    })
+    .collect()
+}
+
+fn render(
+  todos: &[Todo],
+  completed_at: &HashMap<usize, DateTime<Utc>>,
+  now: DateTime<Utc>,
+) -> Vec<String> {
+  let mut total = Group::default();
+  for todo in todos {
+    total.add(todo, completed_at.get(&todo.id), now);
+  }
+  let mut lines = vec![format!(
+    "{} open, {} done, open for {} on average, done after {}",
+    total.open,
+    total.done,
+    average(&total.ages),
+    average(&total.completions)
+  )];
+
+  let by_tag = breakdown(todos, completed_at, now, |todo| {
+    if todo.tags.is_empty() {
+      return vec!["(untagged)".to_string()];
+    }
+    todo.tags.0.iter().map(|tag| format!("#{}", tag)).collect()
+  });
+  let by_project = breakdown(todos, completed_at, now, |todo| {
+    vec![todo.project.clone().unwrap_or("(no project)".to_string())]
+  });
+  for (title, groups) in [("Tag", by_tag), ("Project", by_project)] {
+    lines.push(String::new());
+    lines.extend(table(title, &groups));
+  }
+  lines
+}
+
+pub fn stats(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos_all(conn)?;
+  if todos.is_empty() {
+    println!("No todos yet!");
+    return Ok(());
+  }
+  for line in render(&todos, &collect_completed_at(conn)?, Utc::now()) {
+    println!("{}", line);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tags::Tags;
+  use chrono::TimeZone;
+
+  fn day(day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap()
+  }
+
+  #[test]
+  fn breakdown_per_tag_and_project() {
+    let todos = vec![
+      Todo {
+        id: 1,
+        body: "Pay bills".to_string(),
+        status: Status::Done,
+        tags: Tags(vec!["home".to_string()]),
+        created_at: Some(day(1)),
+        ..Default::default()
+      },
+      Todo {
+        id: 2,
+        body: "Fix sink".to_string(),
+        tags: Tags(vec!["home".to_string()]),
+        project: Some("House".to_string()),
+        created_at: Some(day(2)),
+        ..Default::default()
+      },
+      Todo {
+        id: 3,
+        body: "Milk".to_string(),
+        created_at: Some(day(5)),
+        ..Default::default()
+      },
+    ];
+    let completed_at = HashMap::from([(1, day(4))]);
+    let lines = render(&todos, &completed_at, day(10));
+    assert_eq!(
+      vec![
+        "2 open, 1 done, open for 6.5d on average, done after 3.0d",
+        "",
+        "Tag         Open  Done  Avg age  Avg completion",
+        "#home          1     1     8.0d            3.0d",
+        "(untagged)     1     0     5.0d               -",
+        "",
+        "Project       Open  Done  Avg age  Avg completion",
+        "(no project)     1     1     5.0d            3.0d",
+        "House            1     0     8.0d               -",
+      ],
+      lines
+    );
+  }
+
+  #[test]
+  fn completion_times_come_from_history() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = crate::db::create_db(&conn);
+    _ = crate::add(
+      vec!["Milk".to_string()],
+      &crate::AddOptions::default(),
+      &conn,
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = crate::set_status(todos, Status::Done, &conn);
+    assert!(collect_completed_at(&conn).unwrap().contains_key(&1));
+  }
+}
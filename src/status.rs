@@ -0,0 +1,104 @@
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+#[cfg(feature = "cli")]
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Lifecycle state of a todo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+  #[default]
+  Todo,
+  InProgress,
+  Waiting,
+  Done,
+  Cancelled,
+}
+
+impl Status {
+  pub const ALL: [Status; 5] = [
+    Status::Todo,
+    Status::InProgress,
+    Status::Waiting,
+    Status::Done,
+    Status::Cancelled,
+  ];
+
+  /// Name used in the database and on the command line
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Status::Todo => "todo",
+      Status::InProgress => "in-progress",
+      Status::Waiting => "waiting",
+      Status::Done => "done",
+      Status::Cancelled => "cancelled",
+    }
+  }
+
+  /// Single character shown in pickers
+  pub fn glyph(&self) -> char {
+    match self {
+      Status::Todo => '○',
+      Status::InProgress => '◐',
+      Status::Waiting => '◷',
+      Status::Done => '✔',
+      Status::Cancelled => '✘',
+    }
+  }
+
+  /// Whether the todo still needs attention
+  pub fn is_open(&self) -> bool {
+    !matches!(self, Status::Done | Status::Cancelled)
+  }
+}
+
+impl fmt::Display for Status {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+#[cfg(feature = "cli")]
+impl ToSql for Status {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(self.as_str()))
+  }
+}
+
+#[cfg(feature = "cli")]
+impl FromSql for Status {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    let text = value.as_str()?;
+    Status::ALL
+      .into_iter()
+      .find(|status| status.as_str() == text)
+      .ok_or_else(|| FromSqlError::Other(format!("Unknown status '{}'", text).into()))
+  }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+  use super::*;
+  use rusqlite::Connection;
+
+  #[test]
+  fn status_sql_round_trip() {
+    let conn = Connection::open_in_memory().unwrap();
+    for status in Status::ALL {
+      let back: Status = conn
+        .query_row("SELECT ?1", (status,), |row| row.get(0))
+        .unwrap();
+      assert_eq!(status, back);
+    }
+  }
+
+  #[test]
+  fn status_unknown_text() {
+    let conn = Connection::open_in_memory().unwrap();
+    let result: rusqlite::Result<Status> = conn.query_row("SELECT 'later'", [], |row| row.get(0));
+    assert!(result.is_err());
+  }
+}
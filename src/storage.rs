@@ -0,0 +1,219 @@
+use crate::db::create_db;
+use crate::dedupe;
+use crate::json::JsonFile;
+use crate::todotxt::TodoTxt;
+use crate::{TODO_SELECT, Todo, collect_todos};
+use rusqlite::{Connection, OpenFlags, params};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Where todos are kept, commands always run against the SQLite connection it opens
+pub trait Storage {
+  fn open(&self) -> Result<Connection, Box<dyn Error>>;
+
+  /// Write back what the commands changed through `conn`
+  fn save(&self, conn: &Connection) -> Result<(), Box<dyn Error>>;
+
+  /// The file the todos are kept in, if they are in one
+  fn file(&self) -> Option<&Path> {
+    None
+  }
+
+  /// Whether the file exists and has no write permission, commands then only read it
+  fn read_only(&self) -> bool {
+    self
+      .file()
+      .and_then(|file| fs::metadata(file).ok())
+      .is_some_and(|metadata| metadata.permissions().readonly())
+  }
+
+  /// What to call the backend when it has no place for the history, undo and `todo history`
+  /// are refused then
+  fn without_history(&self) -> Option<&'static str> {
+    None
+  }
+
+  /// Open without ever writing to the location, backends read into memory are never
+  /// written until `save`
+  fn open_read_only(&self) -> Result<Connection, Box<dyn Error>> {
+    self.open()
+  }
+}
+
+/// The default, a SQLite database file that is written as commands run
+pub struct Sqlite {
+  path: PathBuf,
+}
+
+impl Storage for Sqlite {
+  fn open(&self) -> Result<Connection, Box<dyn Error>> {
+    let conn = Connection::open(&self.path)?;
+    Ok(conn)
+  }
+
+  fn save(&self, _conn: &Connection) -> Result<(), Box<dyn Error>> {
+    Ok(())
+  }
+
+  fn file(&self) -> Option<&Path> {
+    Some(&self.path)
+  }
+
+  fn open_read_only(&self) -> Result<Connection, Box<dyn Error>> {
+    Connection::open_with_flags(&self.path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+      .map_err(|e| format!("Can't open {} read-only: {}", self.path.display(), e).into())
+  }
+}
+
+/// Pick the storage from the location, `postgres://` urls are a server,
+/// `.txt` files are todo.txt, `.json` json and anything else SQLite
+pub fn from_location(location: &str) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+  if location.starts_with("postgres://") || location.starts_with("postgresql://") {
+    return postgres(location);
+  }
+  let path = PathBuf::from(location);
+  Ok(
+    match path.extension().and_then(|extension| extension.to_str()) {
+      Some("txt") => Box::new(TodoTxt::new(path)),
+      Some("json") => Box::new(JsonFile::new(path)),
+      _ => Box::new(Sqlite { path }),
+    },
+  )
+}
+
+#[cfg(feature = "postgres")]
+fn postgres(url: &str) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+  Ok(Box::new(crate::pg::Postgres::connect(url)?))
+}
+
+#[cfg(not(feature = "postgres"))]
+fn postgres(_url: &str) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+  Err("Postgres support is not built in, build with `--features postgres`".into())
+}
+
+/// Tables beside the todos, with what they keep
+const EXTRAS: [(&str, &str); 8] = [
+  ("checkins", "habit check-ins"),
+  ("tag_colors", "tag colors"),
+  ("list_settings", "project defaults"),
+  ("current_list", "the project picked with `todo use`"),
+  ("searches", "recent searches"),
+  ("notifications", "notification snoozes"),
+  ("links", "links to issues"),
+  ("collapsed", "collapsed groups"),
+];
+
+/// Make writes to the tables a backend has no place for fail, instead of being lost on save
+pub fn refuse_extras(backend: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for (table, what) in EXTRAS {
+    conn.execute_batch(&format!(
+      "CREATE TEMP TRIGGER refuse_{table} BEFORE INSERT ON {table} BEGIN
+         SELECT RAISE(ABORT, '{backend} can''t keep {what}, a SQLite or json file can');
+       END;"
+    ))?;
+  }
+  Ok(())
+}
+
+/// An in memory database to load a file backend into
+pub fn open_in_memory(todos: &[Todo]) -> Result<Connection, Box<dyn Error>> {
+  let conn = Connection::open_in_memory()?;
+  create_db(&conn)?;
+  for todo in todos {
+    insert_todo(todo, &conn)?;
+    // Files don't always know when a todo was made, keep it unknown instead of now
+    if todo.created_at.is_none() || todo.updated_at.is_none() {
+      conn.execute(
+        "UPDATE todos SET created_at = ?1, updated_at = ?2 WHERE id = ?3",
+        (todo.created_at, todo.updated_at, todo.id),
+      )?;
+    }
+  }
+  // Loading is not a change, history only covers what happens afterwards
+  conn.execute("DELETE FROM history", ())?;
+  Ok(conn)
+}
+
+/// Insert every field of `todo`, an id of 0 gets a new one, returns the id it is stored under
+pub fn insert_todo(todo: &Todo, conn: &Connection) -> Result<usize, Box<dyn Error>> {
+  // Cached, files and imports insert every todo through here
+  let mut stmt = conn.prepare_cached(
+    "INSERT INTO todos (id, body, status, waiting_on, follow_up, estimate, priority, due,
+         bucket, project, snoozed_until, focused, notes, parent_id, tags, scheduled, pinned,
+         expires, habit, position, created_at, updated_at, uuid, reference, body_hash)
+       VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+         ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
+  )?;
+  stmt.execute(params![
+    (todo.id != 0).then_some(todo.id),
+    todo.body,
+    todo.status,
+    todo.waiting_on,
+    todo.follow_up,
+    todo.estimate,
+    todo.priority,
+    todo.due,
+    todo.bucket,
+    todo.project,
+    todo.snoozed_until,
+    todo.focused,
+    todo.notes,
+    todo.parent_id,
+    todo.tags,
+    todo.scheduled,
+    todo.pinned,
+    todo.expires,
+    todo.habit,
+    todo.position,
+    todo.created_at,
+    todo.updated_at,
+    todo.uuid,
+    todo.reference,
+    dedupe::body_hash(&todo.body),
+  ])?;
+  Ok(conn.last_insert_rowid() as usize)
+}
+
+pub fn collect_todos_stored(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(format!("{TODO_SELECT} ORDER BY id;"), conn)
+}
+
+/// Replace `path` with `contents` unless they are the same already,
+/// through a temporary file so a crash never leaves half a list behind
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+  if fs::read_to_string(path).is_ok_and(|current| current == contents) {
+    return Ok(());
+  }
+  let mut temporary = path.as_os_str().to_owned();
+  temporary.push(".tmp");
+  let mut file = File::create(&temporary)?;
+  file.write_all(contents.as_bytes())?;
+  file.sync_all()?;
+  fs::rename(&temporary, path)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn read_only_files_are_only_read() {
+    let path = env::temp_dir().join(format!("todo-read-only-{}.db", std::process::id()));
+    let storage = from_location(path.to_str().unwrap()).unwrap();
+    assert!(!storage.read_only());
+    create_db(&storage.open().unwrap()).unwrap();
+    assert!(!storage.read_only());
+
+    let mut permissions = fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&path, permissions).unwrap();
+    assert!(storage.read_only());
+    let conn = storage.open_read_only().unwrap();
+    assert!(conn.execute("DELETE FROM todos", ()).is_err());
+    _ = fs::remove_file(path);
+  }
+}
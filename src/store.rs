@@ -0,0 +1,212 @@
+use crate::date::parse_date;
+use crate::db::create_db;
+use crate::dedupe;
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::storage::{self, Storage};
+use crate::tags::Tags;
+use crate::{AddOptions, TODO_SELECT, Todo, collect_todos, collect_todos_all, insert, undo};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use rusqlite::types::{ToSql, Value as Sql};
+use serde_json::{Map, Value};
+use std::error::Error;
+
+/// Fields that can be set to null to clear them
+const CLEARABLE: [&str; 6] = [
+  "priority",
+  "due",
+  "scheduled",
+  "project",
+  "notes",
+  "reference",
+];
+
+fn date(value: Value) -> Result<Option<NaiveDate>, Box<dyn Error>> {
+  match value {
+    Value::Null => Ok(None),
+    value => Ok(Some(parse_date(&serde_json::from_value::<String>(value)?)?)),
+  }
+}
+
+/// Options of a new todo from fields named like in the json export, dates can also be
+/// written like `tomorrow` or `3d`
+pub fn add_options(fields: Map<String, Value>) -> Result<AddOptions, Box<dyn Error>> {
+  let mut options = AddOptions::default();
+  for (field, value) in fields {
+    match field.as_str() {
+      "priority" => options.priority = serde_json::from_value(value)?,
+      "due" => options.due = date(value)?,
+      "scheduled" => options.scheduled = date(value)?,
+      "project" => options.project = serde_json::from_value(value)?,
+      "notes" => options.notes = serde_json::from_value(value)?,
+      "tags" => options.tags = serde_json::from_value(value)?,
+      "estimate" => options.estimate = serde_json::from_value(value)?,
+      "parent_id" => options.parent = serde_json::from_value(value)?,
+      "reference" => options.reference = serde_json::from_value(value)?,
+      field => return Err(format!("Can't add a todo with {}", field).into()),
+    }
+  }
+  Ok(options)
+}
+
+/// Write one field of a todo as given, without what commands do on top like checking in
+/// habits
+pub fn set_field(
+  id: usize,
+  field: &str,
+  value: Value,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let hash = value
+    .as_str()
+    .filter(|_| field == "body")
+    .map(dedupe::body_hash);
+  let value: Box<dyn ToSql> = match (field, value) {
+    (field, Value::Null) if CLEARABLE.contains(&field) => Box::new(Sql::Null),
+    ("body" | "project" | "notes" | "reference", value) => {
+      Box::new(serde_json::from_value::<String>(value)?)
+    }
+    ("status", value) => Box::new(serde_json::from_value::<Status>(value)?),
+    ("priority", value) => Box::new(serde_json::from_value::<Priority>(value)?),
+    ("due" | "scheduled", value) => Box::new(date(value)?),
+    ("tags", value) => Box::new(serde_json::from_value::<Tags>(value)?),
+    (field, _) => return Err(format!("Can't update {} of a todo", field).into()),
+  };
+  let changed = conn.execute(
+    &format!("UPDATE todos SET {} = ?1 WHERE id = ?2", field),
+    (value, id),
+  )?;
+  if changed == 0 {
+    return Err(format!("No todo with id {}", id).into());
+  }
+  if let Some(hash) = hash {
+    conn.execute("UPDATE todos SET body_hash = ?1 WHERE id = ?2", (hash, id))?;
+  }
+  Ok(())
+}
+
+fn find(id: usize, conn: &Connection) -> Result<Option<Todo>, Box<dyn Error>> {
+  Ok(collect_todos(format!("{TODO_SELECT} where id = {};", id), conn)?.pop())
+}
+
+/// A todo database opened the way the command line opens `location`, for programs that
+/// embed the app. Todos come and go as json objects like the ones of the json export, and
+/// every change is saved and can be undone like a command.
+pub struct TodoStore {
+  storage: Box<dyn Storage>,
+  conn: Connection,
+}
+
+impl TodoStore {
+  pub fn open(location: &str) -> Result<TodoStore, Box<dyn Error>> {
+    let storage = storage::from_location(location)?;
+    let conn = storage.open()?;
+    create_db(&conn)?;
+    Ok(TodoStore { storage, conn })
+  }
+
+  /// Every todo, in the order `list` shows them
+  pub fn todos(&self) -> Result<Vec<Value>, Box<dyn Error>> {
+    collect_todos_all(&self.conn)?
+      .iter()
+      .map(|todo| Ok(serde_json::to_value(todo)?))
+      .collect()
+  }
+
+  pub fn get(&self, id: usize) -> Result<Option<Value>, Box<dyn Error>> {
+    Ok(
+      find(id, &self.conn)?
+        .map(serde_json::to_value)
+        .transpose()?,
+    )
+  }
+
+  /// Add a todo, returns its id
+  pub fn add(&mut self, body: &str, fields: Map<String, Value>) -> Result<usize, Box<dyn Error>> {
+    let options = add_options(fields)?;
+    insert(body, &options, &self.conn)?;
+    let id = self.conn.last_insert_rowid() as usize;
+    self.commit(&format!("store add {}", id))?;
+    Ok(id)
+  }
+
+  /// Set the fields of a todo, null clears the ones that can be empty
+  pub fn update(&mut self, id: usize, fields: Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    let tx = self.conn.unchecked_transaction()?;
+    for (field, value) in fields {
+      set_field(id, &field, value, &tx)?;
+    }
+    tx.commit()?;
+    self.commit(&format!("store update {}", id))
+  }
+
+  pub fn complete(&mut self, id: usize) -> Result<(), Box<dyn Error>> {
+    set_field(id, "status", Value::from(Status::Done.as_str()), &self.conn)?;
+    self.commit(&format!("store done {}", id))
+  }
+
+  pub fn remove(&mut self, id: usize) -> Result<(), Box<dyn Error>> {
+    if self
+      .conn
+      .execute("DELETE FROM todos WHERE id = ?1", (id,))?
+      == 0
+    {
+      return Err(format!("No todo with id {}", id).into());
+    }
+    self.commit(&format!("store rm {}", id))
+  }
+
+  fn commit(&self, command: &str) -> Result<(), Box<dyn Error>> {
+    undo::record(command, true, &self.conn)?;
+    self.storage.save(&self.conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn fields(value: Value) -> Map<String, Value> {
+    match value {
+      Value::Object(fields) => fields,
+      _ => Map::new(),
+    }
+  }
+
+  #[test]
+  fn store_saves_every_change() {
+    let path = std::env::temp_dir().join(format!("todo-store-{}.json", std::process::id()));
+    let location = path.to_str().unwrap();
+    let mut store = TodoStore::open(location).unwrap();
+    let id = store
+      .add(
+        "Milk",
+        fields(json!({"priority": "high", "due": "2024-03-01", "tags": ["shop"]})),
+      )
+      .unwrap();
+    store.add("Bread", Map::new()).unwrap();
+    store
+      .update(id, fields(json!({"body": "Oat milk", "due": null})))
+      .unwrap();
+    store.complete(id).unwrap();
+    assert!(store.remove(9).is_err());
+    assert!(
+      store
+        .add("Eggs", fields(json!({"bucket": "next"})))
+        .is_err()
+    );
+
+    // A json file is only written on save, so reopening shows what was saved
+    let store = TodoStore::open(location).unwrap();
+    let todo = store.get(id).unwrap().unwrap();
+    assert_eq!(json!("Oat milk"), todo["body"]);
+    assert_eq!(json!("done"), todo["status"]);
+    assert_eq!(json!("high"), todo["priority"]);
+    assert_eq!(json!(["shop"]), todo["tags"]);
+    assert_eq!(None, todo.get("due"));
+    assert_eq!(2, store.todos().unwrap().len());
+    _ = std::fs::remove_file(path);
+  }
+}
@@ -0,0 +1,196 @@
+use crate::dedupe;
+use crate::storage::insert_todo;
+use crate::wizard::{self, Resolution};
+use crate::{TODO_SELECT, Todo, collect_todos, links};
+use clap::ValueEnum;
+use rusqlite::Connection;
+use std::error::Error;
+use std::fmt;
+
+/// What an import does with a todo that matches one already there by uuid, ref or body
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+  /// Leave the existing todo alone and the incoming one out
+  Skip,
+  /// Replace the fields of the existing todo with the incoming ones
+  Overwrite,
+  /// Fill in what the existing todo lacks, the tags of both are kept
+  Merge,
+  /// Add the incoming todo anyway
+  Duplicate,
+}
+
+impl Strategy {
+  /// What happens to an incoming todo matching the existing todo `id`
+  pub fn resolve(self, id: usize) -> Resolution {
+    match self {
+      Strategy::Skip => Resolution::Skip,
+      Strategy::Overwrite => Resolution::Overwrite(id),
+      Strategy::Merge => Resolution::Merge(id),
+      Strategy::Duplicate => Resolution::Create,
+    }
+  }
+}
+
+/// How many incoming todos were created, updated and skipped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+  pub created: usize,
+  pub updated: usize,
+  pub skipped: usize,
+}
+
+impl Summary {
+  pub fn count(&mut self, resolution: &Resolution) {
+    match resolution {
+      Resolution::Create => self.created += 1,
+      Resolution::Merge(_) | Resolution::Overwrite(_) => self.updated += 1,
+      Resolution::Skip => self.skipped += 1,
+    }
+  }
+
+  /// Whether any todo was created or updated
+  pub fn changed(&self) -> bool {
+    self.created + self.updated > 0
+  }
+}
+
+impl fmt::Display for Summary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} created, {} updated, {} skipped",
+      self.created, self.updated, self.skipped
+    )
+  }
+}
+
+/// Fill in or replace the todo `id` with the incoming one, as the resolution says
+fn update(
+  id: usize,
+  todo: &Todo,
+  resolution: Resolution,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let into = collect_todos(format!("{TODO_SELECT} WHERE id = {};", id), conn)?
+    .pop()
+    .ok_or("The todo is gone")?;
+  match resolution {
+    Resolution::Overwrite(_) => wizard::overwrite(&into, todo, conn)?,
+    _ => wizard::merge(&into, todo, conn)?,
+  }
+  println!("Updated: {}", into.body);
+  Ok(())
+}
+
+/// Settle a todo found in files, like a task or a comment, that no todo is linked to yet.
+/// The todo with the same body is the one it matches. Matches are updated here, the caller
+/// adds and links new todos itself.
+pub fn settle(
+  todo: &Todo,
+  strategy: Strategy,
+  summary: &mut Summary,
+  conn: &Connection,
+) -> Result<Resolution, Box<dyn Error>> {
+  let resolution = match dedupe::find_same(&todo.body, conn)? {
+    Some(id) => strategy.resolve(id),
+    None => Resolution::Create,
+  };
+  summary.count(&resolution);
+  if let Resolution::Merge(id) | Resolution::Overwrite(id) = resolution {
+    update(id, todo, resolution, conn)?;
+  }
+  Ok(resolution)
+}
+
+/// Settle an issue pulled from a tracker, the todo linked to it or with its ref is the one it
+/// matches. The issue stays linked to the todo it ends up in.
+pub fn pull(
+  source: &str,
+  reference: &str,
+  todo: &Todo,
+  strategy: Strategy,
+  summary: &mut Summary,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let linked = conn
+    .prepare_cached("SELECT todo_id FROM links WHERE source = ?1 AND reference = ?2")?
+    .query_map((source, reference), |row| row.get(0))?
+    .next()
+    .transpose()?;
+  let existing = match linked {
+    Some(id) => Some(id),
+    None => links::referenced(reference, conn)?,
+  };
+  let resolution = match existing {
+    Some(id) => strategy.resolve(id),
+    None => Resolution::Create,
+  };
+  summary.count(&resolution);
+  match resolution {
+    Resolution::Create => {
+      let id = insert_todo(todo, conn)?;
+      links::link(id, source, reference, conn)?;
+      println!("Imported: {}", todo.body);
+    }
+    Resolution::Merge(id) | Resolution::Overwrite(id) => {
+      update(id, todo, resolution, conn)?;
+      links::link(id, source, reference, conn)?;
+    }
+    Resolution::Skip => {
+      if let (None, Some(id)) = (linked, existing) {
+        links::link(id, source, reference, conn)?;
+        println!("Linked {} to the todo with its ref", reference);
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::priority::Priority;
+  use crate::{bodies, collect_todos_all};
+
+  #[test]
+  fn pulled_issues_follow_the_strategy() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let issue = |body: &str, priority| Todo {
+      body: body.to_string(),
+      priority,
+      reference: Some("SHOP-1".to_string()),
+      ..Default::default()
+    };
+    let mut summary = Summary::default();
+    for (body, strategy) in [
+      ("Fix cart", Strategy::Skip),
+      ("Fix the cart", Strategy::Skip),
+      ("Fix cart", Strategy::Merge),
+      ("Fix it", Strategy::Overwrite),
+      ("Fix it", Strategy::Duplicate),
+    ] {
+      let todo = issue(body, Some(Priority::High));
+      pull("jira", "SHOP-1", &todo, strategy, &mut summary, &conn).unwrap();
+    }
+    assert_eq!(
+      Summary {
+        created: 2,
+        updated: 2,
+        skipped: 1,
+      },
+      summary
+    );
+    assert_eq!("2 created, 2 updated, 1 skipped", summary.to_string());
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(vec!["Fix it", "Fix it"], bodies(&todos));
+    assert_eq!(Some(Priority::High), todos[0].priority);
+    // The issue moved on to the duplicate
+    let linked: usize = conn
+      .query_row("SELECT todo_id FROM links", (), |row| row.get(0))
+      .unwrap();
+    assert_eq!(todos[1].id, linked);
+  }
+}
@@ -0,0 +1,255 @@
+use crate::Todo;
+use crate::status::Status;
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+/// Width of the progress bar in list output
+const BAR_WIDTH: usize = 5;
+
+/// Done and total subtasks per parent id, cancelled subtasks do not count
+pub fn progress(conn: &Connection) -> Result<HashMap<usize, (usize, usize)>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT parent_id, sum(status is 'done'), count(*) FROM todos
+     WHERE parent_id IS NOT NULL and status is not 'cancelled'
+     GROUP BY parent_id",
+  )?;
+  let progress = stmt
+    .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+    .filter_map(|s| s.ok())
+    .collect::<HashMap<usize, (usize, usize)>>();
+  Ok(progress)
+}
+
+/// `2/5 ██░░░ 40%`
+pub fn bar(done: usize, total: usize) -> String {
+  let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or_default();
+  let percent = (done * 100).checked_div(total).unwrap_or_default();
+  format!(
+    "{}/{} {}{} {}%",
+    done,
+    total,
+    "█".repeat(filled),
+    "░".repeat(BAR_WIDTH - filled),
+    percent
+  )
+}
+
+/// Complete open parents of `targets` whose subtasks are all done, walking up the tree
+pub fn complete_parents(targets: &[Todo], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut parents = targets
+    .iter()
+    .filter_map(|todo| todo.parent_id)
+    .collect::<Vec<usize>>();
+
+  while let Some(parent) = parents.pop() {
+    // Parents that are already closed are left alone
+    let open_parent: Option<(usize, Option<usize>)> = conn
+      .query_row(
+        "SELECT
+           (SELECT count(*) FROM todos WHERE parent_id = ?1 and status not in ('done', 'cancelled')),
+           parent_id
+         FROM todos WHERE id = ?1 and status not in ('done', 'cancelled')",
+        (parent,),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+      )
+      .optional()?;
+
+    if let Some((0, grandparent)) = open_parent {
+      let body: String = conn.query_row(
+        "UPDATE todos SET status = ?1 WHERE id = ?2 RETURNING body",
+        (Status::Done, parent),
+        |row| row.get(0),
+      )?;
+      println!("All subtasks done, completed: {}", body);
+      parents.extend(grandparent);
+    }
+  }
+  Ok(())
+}
+
+/// A listed todo with the connectors that draw its place in the tree
+#[derive(Debug, PartialEq)]
+pub struct Row<'a> {
+  pub todo: &'a Todo,
+  pub connectors: String,
+  /// Done subtasks folded into this todo
+  pub folded: usize,
+}
+
+/// Walks the listed todos depth first, keeping their order among siblings
+struct Tree<'a> {
+  children: HashMap<usize, Vec<&'a Todo>>,
+  collapse: bool,
+  seen: HashSet<usize>,
+  rows: Vec<Row<'a>>,
+}
+
+impl<'a> Tree<'a> {
+  fn descendants(&self, todo: &Todo, found: &mut Vec<&'a Todo>) {
+    for child in self.children.get(&todo.id).into_iter().flatten() {
+      if !found.iter().any(|own| own.id == child.id) {
+        found.push(child);
+        self.descendants(child, found);
+      }
+    }
+  }
+
+  fn visit(&mut self, todo: &'a Todo, connectors: String, indent: &str) {
+    if !self.seen.insert(todo.id) {
+      return;
+    }
+    let mut below = vec![];
+    self.descendants(todo, &mut below);
+    if self.collapse
+      && !below.is_empty()
+      && !todo.status.is_open()
+      && below.iter().all(|own| !own.status.is_open())
+    {
+      self.seen.extend(below.iter().map(|own| own.id));
+      self.rows.push(Row {
+        todo,
+        connectors,
+        folded: below.len(),
+      });
+      return;
+    }
+    self.rows.push(Row {
+      todo,
+      connectors,
+      folded: 0,
+    });
+    let children = self.children.get(&todo.id).cloned().unwrap_or_default();
+    for (index, child) in children.iter().enumerate() {
+      let (connector, next) = match index + 1 == children.len() {
+        true => ("└─ ", "   "),
+        false => ("├─ ", "│  "),
+      };
+      self.visit(
+        child,
+        format!("{}{}", indent, connector),
+        &format!("{}{}", indent, next),
+      );
+    }
+  }
+}
+
+/// Subtasks right under their parents. Parents that are not listed leave their subtasks at
+/// the top, with `collapse` done todos hide their subtasks when those are all done too.
+pub fn tree(todos: &[Todo], collapse: bool) -> Vec<Row<'_>> {
+  let listed = todos.iter().map(|todo| todo.id).collect::<HashSet<usize>>();
+  let mut tree = Tree {
+    children: HashMap::new(),
+    collapse,
+    seen: HashSet::new(),
+    rows: vec![],
+  };
+  let mut roots = vec![];
+  for todo in todos {
+    match todo.parent_id.filter(|parent| listed.contains(parent)) {
+      Some(parent) => tree.children.entry(parent).or_default().push(todo),
+      None => roots.push(todo),
+    }
+  }
+  // Todos in a loop of parents have no root, they are listed at the end
+  for todo in roots.into_iter().chain(todos) {
+    tree.visit(todo, String::new(), "");
+  }
+  tree.rows
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, collect_todos_all, set_status};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(vec!["Trip".to_string()], &AddOptions::default(), &conn);
+    let options = AddOptions {
+      parent: Some(1),
+      ..Default::default()
+    };
+    _ = add(
+      vec!["Tickets".to_string(), "Hotel".to_string()],
+      &options,
+      &conn,
+    );
+    conn
+  }
+
+  #[test]
+  fn bar_rendering() {
+    assert_eq!("2/5 ██░░░ 40%", bar(2, 5));
+    assert_eq!("3/3 █████ 100%", bar(3, 3));
+    assert_eq!("0/0 ░░░░░ 0%", bar(0, 0));
+  }
+
+  #[test]
+  fn progress_counts_children() {
+    let conn = setup();
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_status(vec![todos[1].clone()], Status::Done, &conn);
+
+    assert_eq!(Some(&(1, 2)), progress(&conn).unwrap().get(&1));
+  }
+
+  #[test]
+  fn add_rejects_unknown_parent() {
+    let conn = setup();
+    let options = AddOptions {
+      parent: Some(9),
+      ..Default::default()
+    };
+    assert!(add(vec!["Visa".to_string()], &options, &conn).is_err());
+  }
+
+  #[test]
+  fn complete_parents_when_all_done() {
+    let conn = setup();
+    let todos = collect_todos_all(&conn).unwrap();
+
+    _ = set_status(vec![todos[1].clone()], Status::Done, &conn);
+    _ = complete_parents(&todos[1..2], &conn);
+    assert_eq!(Status::Todo, collect_todos_all(&conn).unwrap()[0].status);
+
+    _ = set_status(vec![todos[2].clone()], Status::Cancelled, &conn);
+    _ = complete_parents(&todos[2..3], &conn);
+    assert_eq!(Status::Done, collect_todos_all(&conn).unwrap()[0].status);
+  }
+
+  #[test]
+  fn tree_draws_connectors_and_folds_done_subtrees() {
+    let todo = |id: usize, parent_id: Option<usize>, status: Status| Todo {
+      id,
+      body: id.to_string(),
+      parent_id,
+      status,
+      ..Default::default()
+    };
+    let todos = vec![
+      todo(1, None, Status::Todo),
+      todo(2, Some(1), Status::Done),
+      todo(3, Some(2), Status::Done),
+      todo(4, Some(1), Status::Todo),
+      todo(5, Some(4), Status::Todo),
+      todo(6, Some(9), Status::Todo),
+    ];
+    let rows = |collapse: bool| {
+      tree(&todos, collapse)
+        .iter()
+        .map(|row| format!("{}{} {}", row.connectors, row.todo.id, row.folded))
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(
+      vec!["1 0", "├─ 2 0", "│  └─ 3 0", "└─ 4 0", "   └─ 5 0", "6 0"],
+      rows(false)
+    );
+    assert_eq!(
+      vec!["1 0", "├─ 2 1", "└─ 4 0", "   └─ 5 0", "6 0"],
+      rows(true)
+    );
+  }
+}
@@ -0,0 +1,326 @@
+use crate::bucket::Bucket;
+use crate::dedupe;
+use crate::status::Status;
+use crate::storage::{insert_todo, write_atomic};
+use crate::strategy::{self, Strategy, Summary};
+use crate::wizard::Resolution;
+use crate::{TODO_SELECT, Todo, collect_todos, links};
+use clap::Subcommand;
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Links of synced todos use this source, their reference is `<file>:<line>`
+const MARKDOWN: &str = "markdown";
+
+#[derive(Subcommand)]
+pub enum Action {
+  /// Import `- [ ]` tasks from the markdown files of a vault and check off completed ones
+  Markdown {
+    /// Directory of the vault, searched recursively
+    #[arg(long)]
+    dir: PathBuf,
+
+    /// What to do with new tasks a todo has the text of already
+    #[arg(long, value_enum, default_value = "skip")]
+    strategy: Strategy,
+  },
+}
+
+/// A checkbox item found in a markdown file, lines count from 1
+#[derive(Debug, PartialEq)]
+struct Task {
+  path: PathBuf,
+  line: usize,
+  done: bool,
+  text: String,
+}
+
+/// Whether a line is a task and its text, `- [x] Milk` is a done `Milk`
+fn parse_task(line: &str) -> Option<(bool, &str)> {
+  let rest = line.trim_start().strip_prefix(['-', '*', '+'])?;
+  let rest = rest.strip_prefix(' ')?;
+  let done = match rest.get(..3)? {
+    "[ ]" => false,
+    "[x]" | "[X]" => true,
+    _ => return None,
+  };
+  let text = rest[3..].trim();
+  (!text.is_empty()).then_some((done, text))
+}
+
+/// Tasks of one file, skipping fenced code blocks
+fn parse_tasks(path: &Path, text: &str) -> Vec<Task> {
+  let mut tasks = vec![];
+  let mut in_code = false;
+  for (number, line) in text.lines().enumerate() {
+    if line.trim_start().starts_with("```") {
+      in_code = !in_code;
+    } else if !in_code && let Some((done, text)) = parse_task(line) {
+      tasks.push(Task {
+        path: path.to_path_buf(),
+        line: number + 1,
+        done,
+        text: text.to_string(),
+      });
+    }
+  }
+  tasks
+}
+
+/// Markdown files under `dir` in a stable order, hidden directories like `.obsidian` are skipped
+fn markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+  let mut entries = fs::read_dir(dir)?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .collect::<Vec<PathBuf>>();
+  entries.sort();
+  for path in entries {
+    let hidden = path
+      .file_name()
+      .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    if hidden {
+      continue;
+    }
+    if path.is_dir() {
+      markdown_files(&path, files)?;
+    } else if path.extension().is_some_and(|extension| extension == "md") {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+fn scan(dir: &Path) -> Result<Vec<Task>, Box<dyn Error>> {
+  let mut files = vec![];
+  markdown_files(dir, &mut files)?;
+  let mut tasks = vec![];
+  for path in files {
+    tasks.extend(parse_tasks(&path, &fs::read_to_string(&path)?));
+  }
+  Ok(tasks)
+}
+
+fn reference(path: &Path, line: usize) -> String {
+  format!("{}:{}", path.display(), line)
+}
+
+fn link(todo_id: usize, task: &Task, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  links::link(todo_id, MARKDOWN, &reference(&task.path, task.line), conn)
+}
+
+/// A synced todo and the file and line it was last seen at
+struct Linked {
+  todo: Todo,
+  path: PathBuf,
+  line: usize,
+}
+
+fn collect_linked(conn: &Connection) -> Result<Vec<Linked>, Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT todo_id, reference FROM links WHERE source = ?1")?;
+  let references = stmt
+    .query_map((MARKDOWN,), |row| {
+      Ok((row.get::<_, usize>(0)?, row.get::<_, String>(1)?))
+    })?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<(usize, String)>>();
+  let mut linked = vec![];
+  for (id, reference) in references {
+    let Some((path, line)) = reference.rsplit_once(':') else {
+      continue;
+    };
+    let Ok(line) = line.parse() else {
+      continue;
+    };
+    let todo = collect_todos(format!("{TODO_SELECT} WHERE id = {id};"), conn)?;
+    if let Some(todo) = todo.into_iter().next() {
+      linked.push(Linked {
+        todo,
+        path: PathBuf::from(path),
+        line,
+      });
+    }
+  }
+  Ok(linked)
+}
+
+/// Check off the given lines of a file, keeping everything else byte for byte
+fn check_off(path: &Path, lines: &HashSet<usize>) -> Result<(), Box<dyn Error>> {
+  let text = fs::read_to_string(path)?;
+  let checked = text
+    .split_inclusive('\n')
+    .enumerate()
+    .map(|(number, line)| {
+      if lines.contains(&(number + 1)) {
+        line.replacen("[ ]", "[x]", 1)
+      } else {
+        line.to_string()
+      }
+    })
+    .collect::<String>();
+  write_atomic(path, &checked)
+}
+
+/// Tasks checked in the vault complete their todo and completed todos are checked in the
+/// vault, completion wins when the two disagree. New open tasks become todos, or are settled
+/// by `strategy` when a todo has their text.
+pub fn markdown(dir: &Path, strategy: Strategy, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let dir = dir
+    .canonicalize()
+    .map_err(|e| format!("{}: {}", dir.display(), e))?;
+  let tasks = scan(&dir)?;
+  let tx = conn.unchecked_transaction()?;
+  let mut claimed = HashSet::new();
+  let mut check: BTreeMap<PathBuf, HashSet<usize>> = BTreeMap::new();
+  let mut summary = Summary::default();
+  let mut checked = 0;
+
+  let linked = collect_linked(&tx)?
+    .into_iter()
+    .filter(|linked| linked.path.starts_with(&dir))
+    .collect::<Vec<Linked>>();
+  // Links are written again where their task is now
+  for Linked { path, line, .. } in &linked {
+    tx.execute(
+      "DELETE FROM links WHERE source = ?1 AND reference = ?2",
+      (MARKDOWN, reference(path, *line)),
+    )?;
+  }
+
+  for Linked { todo, path, line } in linked {
+    // Lines move as notes are edited, look for the same text elsewhere in the file then.
+    // Case and punctuation aside, the todo may have been merged into one written otherwise.
+    let hash = dedupe::body_hash(&todo.body);
+    let found = tasks
+      .iter()
+      .enumerate()
+      .filter(|(index, task)| {
+        task.path == path && dedupe::body_hash(&task.text) == hash && !claimed.contains(index)
+      })
+      .min_by_key(|(_, task)| task.line.abs_diff(line));
+    let Some((index, task)) = found else {
+      println!("Unlinked, no longer in the vault: {}", todo.body);
+      continue;
+    };
+    claimed.insert(index);
+    link(todo.id, task, &tx)?;
+    if task.done && todo.status.is_open() {
+      tx.execute(
+        "UPDATE todos SET status = ?1 WHERE id = ?2",
+        (Status::Done, todo.id),
+      )?;
+      println!("Completed from the vault: {}", todo.body);
+      summary.updated += 1;
+    } else if !task.done && !todo.status.is_open() {
+      check
+        .entry(task.path.clone())
+        .or_default()
+        .insert(task.line);
+      println!("Checked off in the vault: {}", todo.body);
+      checked += 1;
+    }
+  }
+
+  for (index, task) in tasks.iter().enumerate() {
+    if task.done || claimed.contains(&index) {
+      continue;
+    }
+    let todo = Todo {
+      body: task.text.clone(),
+      bucket: Bucket::Inbox,
+      ..Default::default()
+    };
+    match strategy::settle(&todo, strategy, &mut summary, &tx)? {
+      Resolution::Create => {
+        link(insert_todo(&todo, &tx)?, task, &tx)?;
+        println!(
+          "Imported from {}:{}: {}",
+          task.path.strip_prefix(&dir).unwrap_or(&task.path).display(),
+          task.line,
+          task.text
+        );
+      }
+      Resolution::Merge(id) | Resolution::Overwrite(id) => link(id, task, &tx)?,
+      Resolution::Skip => {}
+    }
+  }
+
+  // Files are only touched once the database side is safe
+  tx.commit()?;
+  for (path, lines) in check {
+    check_off(&path, &lines)?;
+  }
+  match summary.changed() || checked > 0 {
+    true => println!("Synced the vault, {}, {} checked off", summary, checked),
+    false => println!("Nothing new in the vault!"),
+  }
+  Ok(())
+}
+
+pub fn sync(action: &Action, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Markdown { dir, strategy } => markdown(dir, *strategy, conn),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{collect_todos_all, set_status};
+  use std::env;
+
+  #[test]
+  fn parse_tasks_skips_code() {
+    let path = Path::new("note.md");
+    let text = "# Shopping\n- [ ] Milk\n  * [x] Eggs\n```\n- [ ] not a task\n```\n- [link](x)\n";
+    assert_eq!(
+      vec![
+        Task {
+          path: path.to_path_buf(),
+          line: 2,
+          done: false,
+          text: "Milk".to_string(),
+        },
+        Task {
+          path: path.to_path_buf(),
+          line: 3,
+          done: true,
+          text: "Eggs".to_string(),
+        },
+      ],
+      parse_tasks(path, text)
+    );
+  }
+
+  #[test]
+  fn sync_both_ways() {
+    let dir = env::temp_dir().join(format!("todo-vault-{}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join(".obsidian")).unwrap();
+    fs::write(dir.join(".obsidian/templates.md"), "- [ ] Template\n").unwrap();
+    let note = dir.join("note.md");
+    fs::write(&note, "- [ ] Milk\n- [ ] Eggs\n- [x] Bread\n").unwrap();
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+
+    markdown(&dir, Strategy::Skip, &conn).unwrap();
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(vec!["Milk", "Eggs"], crate::bodies(&todos));
+
+    // Complete one on each side, and move the other one down a line
+    _ = set_status(vec![todos[0].clone()], Status::Done, &conn);
+    fs::write(&note, "# Today\n- [ ] Milk\n- [x] Eggs\n- [x] Bread\n").unwrap();
+    markdown(&dir, Strategy::Skip, &conn).unwrap();
+
+    assert_eq!(
+      "# Today\n- [x] Milk\n- [x] Eggs\n- [x] Bread\n",
+      fs::read_to_string(&note).unwrap()
+    );
+    let todos = collect_todos_all(&conn).unwrap();
+    assert!(todos.iter().all(|todo| todo.status == Status::Done));
+    _ = fs::remove_dir_all(dir);
+  }
+}
@@ -0,0 +1,311 @@
+use crate::Todo;
+use crate::date::parse_date;
+use crate::duration::{format_duration, parse_duration};
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::tags::parse_tag;
+use clap::ValueEnum;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+/// What a column of an imported table fills in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+  Body,
+  Status,
+  Due,
+  Priority,
+  Tags,
+  Project,
+  Notes,
+  Estimate,
+  Skip,
+}
+
+impl Field {
+  pub const ALL: [Field; 9] = [
+    Field::Body,
+    Field::Status,
+    Field::Due,
+    Field::Priority,
+    Field::Tags,
+    Field::Project,
+    Field::Notes,
+    Field::Estimate,
+    Field::Skip,
+  ];
+
+  /// The field a column name most likely means, columns nobody recognizes are skipped
+  pub fn guess(column: &str) -> Field {
+    match column
+      .trim()
+      .to_lowercase()
+      .replace(['_', '-'], " ")
+      .as_str()
+    {
+      "body" | "title" | "task" | "name" | "todo" | "summary" | "content" | "subject" => {
+        Field::Body
+      }
+      "status" | "state" | "done" | "completed" | "complete" => Field::Status,
+      "due" | "due date" | "due on" | "deadline" => Field::Due,
+      "priority" | "pri" | "importance" => Field::Priority,
+      "tags" | "tag" | "labels" | "label" | "contexts" => Field::Tags,
+      "project" | "list" | "category" => Field::Project,
+      "notes" | "note" | "description" | "details" => Field::Notes,
+      "estimate" | "effort" | "duration" => Field::Estimate,
+      _ => Field::Skip,
+    }
+  }
+}
+
+impl fmt::Display for Field {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Field::Body => "body",
+      Field::Status => "status",
+      Field::Due => "due",
+      Field::Priority => "priority",
+      Field::Tags => "tags",
+      Field::Project => "project",
+      Field::Notes => "notes",
+      Field::Estimate => "estimate",
+      Field::Skip => "(skip)",
+    })
+  }
+}
+
+/// Rows of named columns, from a CSV file or a JSON array of objects
+#[derive(Debug, Default, PartialEq)]
+pub struct Table {
+  pub columns: Vec<String>,
+  pub rows: Vec<Vec<String>>,
+}
+
+/// The first line names the columns
+pub fn parse_csv(text: &str) -> Result<Table, Box<dyn Error>> {
+  let mut reader = csv::ReaderBuilder::new()
+    .flexible(true)
+    .from_reader(text.as_bytes());
+  let columns = reader
+    .headers()?
+    .iter()
+    .map(str::to_string)
+    .collect::<Vec<String>>();
+  let mut rows = vec![];
+  for record in reader.records() {
+    let mut row = record?.iter().map(str::to_string).collect::<Vec<String>>();
+    row.resize(columns.len(), String::new());
+    rows.push(row);
+  }
+  Ok(Table { columns, rows })
+}
+
+/// Text of a JSON value, lists are joined with spaces
+fn json_text(value: &Value) -> String {
+  match value {
+    Value::Null => String::new(),
+    Value::String(text) => text.clone(),
+    Value::Array(values) => values
+      .iter()
+      .map(json_text)
+      .collect::<Vec<String>>()
+      .join(" "),
+    value => value.to_string(),
+  }
+}
+
+/// Every key of the objects becomes a column, keys of one object come sorted by name
+pub fn parse_json(objects: &[Value]) -> Result<Table, Box<dyn Error>> {
+  let mut table = Table::default();
+  for object in objects {
+    let object = object
+      .as_object()
+      .ok_or("Expected a list of objects, one per todo")?;
+    for key in object.keys() {
+      if !table.columns.contains(key) {
+        table.columns.push(key.clone());
+      }
+    }
+  }
+  for object in objects {
+    let row = table
+      .columns
+      .iter()
+      .map(|column| object.get(column).map(json_text).unwrap_or_default())
+      .collect();
+    table.rows.push(row);
+  }
+  Ok(table)
+}
+
+fn parse_status(value: &str) -> Option<Status> {
+  match value.to_lowercase().as_str() {
+    "" | "false" | "no" | "0" | "open" => Some(Status::Todo),
+    "x" | "true" | "yes" | "1" | "completed" | "complete" => Some(Status::Done),
+    "canceled" => Some(Status::Cancelled),
+    value => Status::from_str(value, true).ok(),
+  }
+}
+
+fn parse_priority(value: &str) -> Option<Priority> {
+  match value.to_lowercase().as_str() {
+    "a" | "h" | "1" => Some(Priority::High),
+    "b" | "m" | "2" => Some(Priority::Medium),
+    "c" | "l" | "3" => Some(Priority::Low),
+    value => Priority::from_str(value, true).ok(),
+  }
+}
+
+/// Minutes, like `90`, or a duration like `1h30m`
+fn parse_estimate(value: &str) -> Option<u32> {
+  value
+    .parse::<u32>()
+    .ok()
+    .filter(|minutes| *minutes > 0)
+    .or_else(|| parse_duration(value).ok())
+}
+
+impl Table {
+  pub fn guess_mapping(&self) -> Vec<Field> {
+    let mut mapping = self
+      .columns
+      .iter()
+      .map(|column| Field::guess(column))
+      .collect::<Vec<Field>>();
+    // Only the first column of a kind is used, the others would overwrite it
+    for index in 0..mapping.len() {
+      if mapping[..index].contains(&mapping[index]) {
+        mapping[index] = Field::Skip;
+      }
+    }
+    mapping
+  }
+
+  /// A todo per row with a body, numbered from 1. Values that can't be read are left out.
+  pub fn todos(&self, mapping: &[Field]) -> Vec<Todo> {
+    let mut todos = vec![];
+    for row in &self.rows {
+      let mut todo = Todo::default();
+      for (value, field) in row.iter().zip(mapping) {
+        let value = value.trim();
+        match field {
+          Field::Body => todo.body = value.to_string(),
+          Field::Status => todo.status = parse_status(value).unwrap_or_default(),
+          // Timestamps count from their date
+          Field::Due => todo.due = parse_date(value.get(..10).unwrap_or(value)).ok(),
+          Field::Priority => todo.priority = parse_priority(value),
+          Field::Tags => {
+            let tags = value
+              .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+              .filter_map(|tag| parse_tag(tag).ok())
+              .collect::<Vec<String>>();
+            todo.tags.extend(&tags);
+          }
+          Field::Project => todo.project = Some(value.to_string()).filter(|p| !p.is_empty()),
+          Field::Notes => todo.notes = Some(value.to_string()).filter(|n| !n.is_empty()),
+          Field::Estimate => todo.estimate = parse_estimate(value),
+          Field::Skip => {}
+        }
+      }
+      if !todo.body.is_empty() {
+        todo.id = todos.len() + 1;
+        todos.push(todo);
+      }
+    }
+    todos
+  }
+}
+
+/// Columns `Table::guess_mapping` reads back
+pub fn render_csv(todos: &[Todo]) -> Result<String, Box<dyn Error>> {
+  let mut writer = csv::Writer::from_writer(vec![]);
+  writer.write_record([
+    "body", "status", "due", "priority", "tags", "project", "notes", "estimate",
+  ])?;
+  for todo in todos {
+    writer.write_record([
+      todo.body.clone(),
+      todo.status.to_string(),
+      todo.due.map(|due| due.to_string()).unwrap_or_default(),
+      todo
+        .priority
+        .map(|priority| priority.to_string())
+        .unwrap_or_default(),
+      todo.tags.0.join(" "),
+      todo.project.clone().unwrap_or_default(),
+      todo.notes.clone().unwrap_or_default(),
+      todo.estimate.map(format_duration).unwrap_or_default(),
+    ])?;
+  }
+  Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tags::Tags;
+  use chrono::NaiveDate;
+
+  #[test]
+  fn csv_columns_are_guessed() {
+    let table = parse_csv(
+      "Title,Due Date,Labels,Done,Title,Random\n\
+       Call mom,2024-03-05T10:00:00,\"phone, family\",no,ignored,1\n\
+       ,2024-03-06,,,,\n\
+       Pay bills,,,x\n",
+    )
+    .unwrap();
+    let mapping = table.guess_mapping();
+    assert_eq!(
+      vec![
+        Field::Body,
+        Field::Due,
+        Field::Tags,
+        Field::Status,
+        Field::Skip,
+        Field::Skip
+      ],
+      mapping
+    );
+    let todos = table.todos(&mapping);
+    assert_eq!(2, todos.len());
+    assert_eq!("Call mom", todos[0].body);
+    assert_eq!(NaiveDate::from_ymd_opt(2024, 3, 5), todos[0].due);
+    assert_eq!("#phone #family", todos[0].tags.to_string());
+    assert_eq!((2, Status::Done), (todos[1].id, todos[1].status));
+  }
+
+  #[test]
+  fn json_objects_become_rows() {
+    let objects: Vec<Value> = serde_json::from_str(
+      r#"[{"name": "Milk", "priority": "high", "estimate": 30},
+          {"name": "Carl", "tags": ["home", "errands"], "extra": null}]"#,
+    )
+    .unwrap();
+    let table = parse_json(&objects).unwrap();
+    assert_eq!(
+      vec!["estimate", "name", "priority", "extra", "tags"],
+      table.columns
+    );
+    let todos = table.todos(&table.guess_mapping());
+    assert_eq!(Some(Priority::High), todos[0].priority);
+    assert_eq!(Some(30), todos[0].estimate);
+    assert_eq!("#home #errands", todos[1].tags.to_string());
+    assert!(parse_json(&[Value::from(1)]).is_err());
+  }
+
+  #[test]
+  fn csv_round_trip() {
+    let todos = vec![Todo {
+      id: 1,
+      body: "Pay, then file, bills".to_string(),
+      status: Status::InProgress,
+      estimate: Some(90),
+      tags: Tags(vec!["home".to_string()]),
+      ..Default::default()
+    }];
+    let table = parse_csv(&render_csv(&todos).unwrap()).unwrap();
+    assert_eq!(todos, table.todos(&table.guess_mapping()));
+  }
+}
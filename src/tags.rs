@@ -0,0 +1,637 @@
+#[cfg(feature = "cli")]
+use crate::{Todo, collect_todos_all, find_targets};
+#[cfg(feature = "cli")]
+use clap::Subcommand;
+#[cfg(feature = "cli")]
+use console::style;
+#[cfg(feature = "cli")]
+use rusqlite::Connection;
+#[cfg(feature = "cli")]
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cli")]
+use std::collections::BTreeMap;
+#[cfg(feature = "cli")]
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "cli")]
+use std::sync::OnceLock;
+
+/// Colors of tags from the config and `todo tag color`, by lowercase tag
+#[cfg(feature = "cli")]
+static COLORS: OnceLock<BTreeMap<String, Color>> = OnceLock::new();
+
+#[cfg(feature = "cli")]
+#[derive(Subcommand)]
+pub enum Action {
+  /// Tag todos
+  Add {
+    /// Ids like 3, a7 or 1-5,8, or part of the text of the todos
+    target: String,
+
+    #[arg(required = true, value_parser = parse_tag)]
+    tags: Vec<String>,
+  },
+
+  /// Take tags off todos
+  Remove {
+    /// Ids like 3, a7 or 1-5,8, or part of the text of the todos
+    target: String,
+
+    #[arg(required = true, value_parser = parse_tag)]
+    tags: Vec<String>,
+  },
+
+  /// Show every tag with how many open todos and how many in all have it, most used first
+  List {},
+
+  /// Rename a tag on every todo, its color goes along
+  Rename {
+    #[arg(value_parser = parse_tag)]
+    from: String,
+
+    #[arg(value_parser = parse_tag)]
+    to: String,
+  },
+
+  /// Fold tags into another one on every todo, todos that had several have it once
+  Merge {
+    #[arg(required = true, value_parser = parse_tag)]
+    from: Vec<String>,
+
+    #[arg(long, value_parser = parse_tag)]
+    into: String,
+  },
+
+  /// Take tags off every todo and forget their color
+  Rm {
+    #[arg(required_unless_present = "unused", value_parser = parse_tag)]
+    tags: Vec<String>,
+
+    /// Forget the colors of tags no todo has anymore
+    #[arg(long)]
+    unused: bool,
+  },
+
+  /// Show a tag as a badge of its own color, without a color it is plain again. Lists the
+  /// colors when no tag is given.
+  Color {
+    #[arg(value_parser = parse_tag)]
+    tag: Option<String>,
+
+    /// A name like green or bright-blue, or a number of the 256 terminal colors
+    color: Option<Color>,
+  },
+}
+
+/// Labels of a todo, stored space separated in insertion order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tags(pub Vec<String>);
+
+/// A single tag, a leading `#` is dropped and org's `:` or whitespace are not allowed
+pub fn parse_tag(input: &str) -> Result<String, String> {
+  let tag = input.trim().trim_start_matches('#');
+  if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ':') {
+    return Err(format!(
+      "Invalid tag '{}', tags are single words without ':'",
+      input
+    ));
+  }
+  Ok(tag.to_string())
+}
+
+impl Tags {
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn contains(&self, tag: &str) -> bool {
+    self.0.iter().any(|own| own.eq_ignore_ascii_case(tag))
+  }
+
+  /// Add tags that are not there yet
+  pub fn extend<'a>(&mut self, tags: impl IntoIterator<Item = &'a String>) {
+    for tag in tags {
+      if !self.contains(tag) {
+        self.0.push(tag.clone());
+      }
+    }
+  }
+
+  pub fn remove(&mut self, tag: &str) {
+    self.0.retain(|own| !own.eq_ignore_ascii_case(tag));
+  }
+
+  /// The tags with any of `from` replaced by `to`, in the place of the first of them
+  pub fn replace(&self, from: &[String], to: &str) -> Tags {
+    let mut tags = Tags::default();
+    for tag in &self.0 {
+      match from.iter().any(|from| from.eq_ignore_ascii_case(tag)) {
+        true => tags.extend(&[to.to_string()]),
+        false => tags.extend([tag]),
+      }
+    }
+    tags
+  }
+}
+
+/// The basic terminal colors by name, their number is their place
+#[cfg(feature = "cli")]
+const NAMES: [&str; 8] = [
+  "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// One of the 256 terminal colors, written as a name like `green` or `bright-blue` or as
+/// its number
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub u8);
+
+#[cfg(feature = "cli")]
+impl TryFrom<String> for Color {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Color, String> {
+    text.parse()
+  }
+}
+
+#[cfg(feature = "cli")]
+impl std::str::FromStr for Color {
+  type Err = String;
+
+  fn from_str(text: &str) -> Result<Color, String> {
+    let text = text.trim().to_lowercase();
+    let (offset, name) = match text.strip_prefix("bright-") {
+      Some(name) => (8, name),
+      None => (0, text.as_str()),
+    };
+    match NAMES.iter().position(|known| *known == name) {
+      Some(index) => Ok(Color(offset + index as u8)),
+      None => text.parse().map(Color).map_err(|_| {
+        format!(
+          "Unknown color {}, use {}, bright- ones or 0 to 255",
+          text,
+          NAMES.join(", ")
+        )
+      }),
+    }
+  }
+}
+
+#[cfg(feature = "cli")]
+impl fmt::Display for Color {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.0 {
+      index @ 0..8 => f.write_str(NAMES[index as usize]),
+      index @ 8..16 => write!(f, "bright-{}", NAMES[index as usize - 8]),
+      index => write!(f, "{}", index),
+    }
+  }
+}
+
+#[cfg(feature = "cli")]
+impl Color {
+  /// Text that can be read on the color, light on the dark ones
+  pub fn ink(&self) -> u8 {
+    match self.0 {
+      0 | 1 | 4 | 5 | 8 | 16..=33 | 52..=57 | 88..=93 | 232..=243 => 15,
+      _ => 0,
+    }
+  }
+}
+
+/// Colors set with `todo tag color`
+#[cfg(feature = "cli")]
+fn collect_colors(conn: &Connection) -> Result<BTreeMap<String, Color>, Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT tag, color FROM tag_colors ORDER BY tag")?;
+  let colors = stmt
+    .query_map([], |row| {
+      Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?
+    .filter_map(|row| row.ok())
+    .filter_map(|(tag, color)| Some((tag.to_lowercase(), color.parse().ok()?)))
+    .collect();
+  Ok(colors)
+}
+
+/// Colors of the config with those of `todo tag color` over them, once the todos are open
+#[cfg(feature = "cli")]
+pub fn init_colors(
+  config: &BTreeMap<String, Color>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let mut colors = config
+    .iter()
+    .map(|(tag, color)| (tag.trim_start_matches('#').to_lowercase(), *color))
+    .collect::<BTreeMap<_, _>>();
+  colors.extend(collect_colors(conn)?);
+  _ = COLORS.set(colors);
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+pub fn color(tag: &str) -> Option<Color> {
+  COLORS.get()?.get(&tag.to_lowercase()).copied()
+}
+
+#[cfg(feature = "cli")]
+fn badge(tag: &str, color: Option<Color>) -> String {
+  let text = style(format!("#{}", tag));
+  match color {
+    Some(color) => text.on_color256(color.0).color256(color.ink()),
+    None => text.magenta(),
+  }
+  .to_string()
+}
+
+#[cfg(feature = "cli")]
+impl Tags {
+  /// The tags as badges in their colors, magenta without one
+  pub fn badges(&self) -> String {
+    self
+      .0
+      .iter()
+      .map(|tag| badge(tag, color(tag)))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+}
+
+#[cfg(feature = "cli")]
+fn set_color(tag: &str, color: Option<Color>, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  match color {
+    Some(color) => {
+      conn.execute(
+        "INSERT OR REPLACE INTO tag_colors (tag, color) VALUES (?1, ?2)",
+        (tag, color.to_string()),
+      )?;
+      println!("Colored {}", badge(tag, Some(color)));
+    }
+    None => {
+      conn.execute("DELETE FROM tag_colors WHERE tag = ?1", (tag,))?;
+      println!("Uncolored {}", badge(tag, None));
+    }
+  }
+  Ok(())
+}
+
+/// Change the tags of every todo with `change`, returns how many todos changed
+#[cfg(feature = "cli")]
+fn retag(change: impl Fn(&Tags) -> Tags, conn: &Connection) -> Result<usize, Box<dyn Error>> {
+  let mut changed = 0;
+  for todo in collect_todos_all(conn)? {
+    let tags = change(&todo.tags);
+    if tags != todo.tags {
+      conn.execute(
+        "UPDATE todos SET tags = ?1 where id is ?2",
+        (&tags, todo.id),
+      )?;
+      changed += 1;
+    }
+  }
+  Ok(changed)
+}
+
+/// A tag as first written with how many open todos and how many todos have it
+#[cfg(feature = "cli")]
+type Usage = (String, usize, usize);
+
+/// Every tag in use, most used first
+#[cfg(feature = "cli")]
+fn usage(conn: &Connection) -> Result<Vec<Usage>, Box<dyn Error>> {
+  let mut usage: Vec<Usage> = vec![];
+  for todo in collect_todos_all(conn)? {
+    for tag in &todo.tags.0 {
+      let open = usize::from(todo.status.is_open());
+      match usage
+        .iter_mut()
+        .find(|(own, ..)| own.eq_ignore_ascii_case(tag))
+      {
+        Some((_, opened, total)) => (*opened, *total) = (*opened + open, *total + 1),
+        None => usage.push((tag.clone(), open, 1)),
+      }
+    }
+  }
+  usage.sort_by(|a, b| {
+    b.2
+      .cmp(&a.2)
+      .then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase()))
+  });
+  Ok(usage)
+}
+
+#[cfg(feature = "cli")]
+fn list(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let usage = usage(conn)?;
+  if usage.is_empty() {
+    println!("No todo has a tag yet!");
+  }
+  let width = usage.iter().map(|(tag, ..)| tag.chars().count() + 1).max();
+  for (tag, open, total) in usage {
+    let padding = " ".repeat(width.unwrap_or_default() - tag.chars().count() - 1);
+    println!(
+      "{}{}  {} open, {} in all",
+      badge(&tag, color(&tag)),
+      padding,
+      open,
+      total
+    );
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn rename(from: &str, to: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let usage = usage(conn)?;
+  let used = |tag: &str| usage.iter().any(|(own, ..)| own.eq_ignore_ascii_case(tag));
+  if !used(from) {
+    return Err(format!("No todo is tagged #{}", from).into());
+  }
+  if used(to) && !from.eq_ignore_ascii_case(to) {
+    return Err(format!("#{} is in use already, merge into it instead", to).into());
+  }
+  let tx = conn.unchecked_transaction()?;
+  let changed = retag(|tags| tags.replace(&[from.to_string()], to), &tx)?;
+  tx.execute(
+    "UPDATE OR REPLACE tag_colors SET tag = ?1 WHERE tag = ?2",
+    (to, from),
+  )?;
+  tx.commit()?;
+  println!("Renamed #{} to #{} on {} todos", from, to, changed);
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn merge(from: &[String], into: &str, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  let changed = retag(|tags| tags.replace(from, into), &tx)?;
+  for tag in from.iter().filter(|tag| !tag.eq_ignore_ascii_case(into)) {
+    tx.execute("DELETE FROM tag_colors WHERE tag = ?1", (tag,))?;
+  }
+  tx.commit()?;
+  let from = Tags(from.to_vec());
+  println!("Merged {} into #{} on {} todos", from, into, changed);
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn rm(tags: &[String], unused: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  if !tags.is_empty() {
+    let changed = retag(
+      |own| {
+        let mut own = own.clone();
+        for tag in tags {
+          own.remove(tag);
+        }
+        own
+      },
+      &tx,
+    )?;
+    for tag in tags {
+      tx.execute("DELETE FROM tag_colors WHERE tag = ?1", (tag,))?;
+    }
+    println!("Took {} off {} todos", Tags(tags.to_vec()), changed);
+  }
+  if unused {
+    let usage = usage(&tx)?;
+    let mut forgotten = 0;
+    for tag in collect_colors(&tx)?.into_keys() {
+      if !usage.iter().any(|(own, ..)| own.eq_ignore_ascii_case(&tag)) {
+        forgotten += tx.execute("DELETE FROM tag_colors WHERE tag = ?1", (&tag,))?;
+      }
+    }
+    println!("Forgot the colors of {} unused tags", forgotten);
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn set_tags(todo: &Todo, tags: &Tags, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  conn.execute("UPDATE todos SET tags = ?1 where id is ?2", (tags, todo.id))?;
+  if tags.is_empty() {
+    println!("Untagged: {}", todo.body);
+  } else {
+    println!("Tagged {}: {}", tags, todo.body);
+  }
+  Ok(())
+}
+
+#[cfg(feature = "cli")]
+pub fn tag(action: &Action, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  match action {
+    Action::Add { target, tags } => {
+      for todo in find_targets(std::slice::from_ref(target), conn)? {
+        let mut own = todo.tags.clone();
+        own.extend(tags);
+        set_tags(&todo, &own, conn)?;
+      }
+    }
+    Action::Remove { target, tags } => {
+      for todo in find_targets(std::slice::from_ref(target), conn)? {
+        let mut own = todo.tags.clone();
+        for tag in tags {
+          own.remove(tag);
+        }
+        set_tags(&todo, &own, conn)?;
+      }
+    }
+    Action::List {} => list(conn)?,
+    Action::Rename { from, to } => rename(from, to, conn)?,
+    Action::Merge { from, into } => merge(from, into, conn)?,
+    Action::Rm { tags, unused } => rm(tags, *unused, conn)?,
+    Action::Color {
+      tag: Some(tag),
+      color,
+    } => set_color(tag, *color, conn)?,
+    Action::Color { tag: None, .. } => {
+      let colors = COLORS.get().cloned().unwrap_or_default();
+      if colors.is_empty() {
+        println!("No tags have a color yet!");
+      }
+      for (tag, color) in colors {
+        println!("{} {}", badge(&tag, Some(color)), color);
+      }
+    }
+  }
+  Ok(())
+}
+
+impl fmt::Display for Tags {
+  /// `#home #errands`
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let tags = self
+      .0
+      .iter()
+      .map(|tag| format!("#{}", tag))
+      .collect::<Vec<String>>();
+    write!(f, "{}", tags.join(" "))
+  }
+}
+
+#[cfg(feature = "cli")]
+impl ToSql for Tags {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    if self.is_empty() {
+      return Ok(ToSqlOutput::from(rusqlite::types::Null));
+    }
+    Ok(ToSqlOutput::from(self.0.join(" ")))
+  }
+}
+
+#[cfg(feature = "cli")]
+impl FromSql for Tags {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    match value {
+      ValueRef::Null => Ok(Tags::default()),
+      value => Ok(Tags(
+        value
+          .as_str()?
+          .split_whitespace()
+          .map(str::to_string)
+          .collect(),
+      )),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_tags() {
+    assert_eq!(Ok("home".to_string()), parse_tag("#home"));
+    assert!(parse_tag("two words").is_err());
+    assert!(parse_tag("a:b").is_err());
+    assert!(parse_tag("#").is_err());
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn tag_add_and_remove() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = crate::db::create_db(&conn);
+    let options = crate::AddOptions {
+      tags: vec!["home".to_string()],
+      ..Default::default()
+    };
+    _ = crate::add(vec!["Milk".to_string()], &options, &conn);
+    let tags = |conn: &Connection| crate::collect_todos_all(conn).unwrap()[0].tags.to_string();
+    assert_eq!("#home", tags(&conn));
+
+    let add = Action::Add {
+      target: "milk".to_string(),
+      tags: vec!["errands".to_string()],
+    };
+    _ = tag(&add, &conn);
+    assert_eq!("#home #errands", tags(&conn));
+
+    let remove = Action::Remove {
+      target: "1".to_string(),
+      tags: vec!["home".to_string(), "errands".to_string()],
+    };
+    _ = tag(&remove, &conn);
+    assert_eq!("", tags(&conn));
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn colors_by_name_or_number() {
+    assert_eq!(Ok(Color(2)), "Green".parse());
+    assert_eq!(Ok(Color(12)), "bright-blue".parse());
+    assert_eq!(Ok(Color(208)), "208".parse());
+    assert!("teal".parse::<Color>().is_err() && "256".parse::<Color>().is_err());
+    assert_eq!("bright-blue", Color(12).to_string());
+    assert_eq!((15, 0), (Color(4).ink(), Color(3).ink()));
+
+    let conn = Connection::open_in_memory().unwrap();
+    _ = crate::db::create_db(&conn);
+    set_color("Shopping", Some(Color(2)), &conn).unwrap();
+    set_color("work", Some(Color(1)), &conn).unwrap();
+    set_color("WORK", None, &conn).unwrap();
+    let config = BTreeMap::from([
+      ("#home".to_string(), Color(5)),
+      ("shopping".to_string(), Color(3)),
+    ]);
+    init_colors(&config, &conn).unwrap();
+    assert_eq!(
+      (Some(Color(2)), Some(Color(5)), None),
+      (color("SHOPPING"), color("home"), color("work"))
+    );
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn rename_merge_and_rm_every_todo() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = crate::db::create_db(&conn);
+    for (body, tags) in [
+      ("Milk", "shop home"),
+      ("Rent", "Home bills"),
+      ("Eggs", "shop groceries"),
+    ] {
+      conn
+        .execute(
+          "INSERT INTO todos (body, tags) VALUES (?1, ?2)",
+          (body, tags),
+        )
+        .unwrap();
+    }
+    conn
+      .execute("UPDATE todos SET status = 'done' WHERE body = 'Eggs'", ())
+      .unwrap();
+    let tags = |conn: &Connection| {
+      collect_todos_all(conn)
+        .unwrap()
+        .iter()
+        .map(|todo| todo.tags.to_string())
+        .collect::<Vec<_>>()
+    };
+    let text = |text: &str| text.to_string();
+    assert_eq!(
+      vec![
+        (text("home"), 2, 2),
+        (text("shop"), 1, 2),
+        (text("bills"), 1, 1),
+        (text("groceries"), 0, 1)
+      ],
+      usage(&conn).unwrap()
+    );
+
+    set_color("shop", Some(Color(2)), &conn).unwrap();
+    set_color("old", Some(Color(3)), &conn).unwrap();
+    rename("shop", "errands", &conn).unwrap();
+    assert!(rename("errands", "HOME", &conn).is_err() && rename("nope", "x", &conn).is_err());
+    assert_eq!(
+      vec!["#errands #home", "#Home #bills", "#errands #groceries"],
+      tags(&conn)
+    );
+    assert_eq!(
+      Some(&Color(2)),
+      collect_colors(&conn).unwrap().get("errands")
+    );
+
+    merge(&[text("errands"), text("groceries")], "shopping", &conn).unwrap();
+    assert_eq!(
+      vec!["#shopping #home", "#Home #bills", "#shopping"],
+      tags(&conn)
+    );
+    rm(&[text("home")], true, &conn).unwrap();
+    assert_eq!(vec!["#shopping", "#bills", "#shopping"], tags(&conn));
+    assert!(collect_colors(&conn).unwrap().is_empty());
+  }
+
+  #[test]
+  fn extend_and_remove_ignore_case() {
+    let mut tags = Tags(vec!["Home".to_string()]);
+    tags.extend(&["home".to_string(), "errands".to_string()]);
+    assert_eq!("#Home #errands", tags.to_string());
+    tags.remove("HOME");
+    assert_eq!(Tags(vec!["errands".to_string()]), tags);
+  }
+}
@@ -0,0 +1,460 @@
+use crate::bucket::Bucket;
+use crate::duration::{format_duration, parse_duration};
+use crate::frequency::parse_frequency;
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::storage::{Storage, open_in_memory, refuse_extras, write_atomic};
+use crate::tags::parse_tag;
+use crate::{Todo, collect_todos_all};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A plain todo.txt file, ids are line numbers and @contexts are tags. Lines are kept in
+/// the manual order.
+/// Notes and dependencies have no place in the format and are not kept, habit check-ins and
+/// the other settings beside the todos are refused.
+pub struct TodoTxt {
+  path: PathBuf,
+}
+
+impl TodoTxt {
+  pub fn new(path: PathBuf) -> TodoTxt {
+    TodoTxt { path }
+  }
+}
+
+impl Storage for TodoTxt {
+  fn open(&self) -> Result<Connection, Box<dyn Error>> {
+    // Only a missing file is an empty list, saving over one that can't be read would lose it
+    let text = match fs::read_to_string(&self.path) {
+      Ok(text) => text,
+      Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(format!("{}: {}", self.path.display(), e).into()),
+    };
+    let conn = open_in_memory(&parse(&text))?;
+    refuse_extras("todo.txt files", &conn)?;
+    Ok(conn)
+  }
+
+  fn save(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    write_atomic(&self.path, &render(&collect_todos_all(conn)?))
+  }
+
+  fn file(&self) -> Option<&Path> {
+    Some(&self.path)
+  }
+
+  fn without_history(&self) -> Option<&'static str> {
+    Some("todo.txt files")
+  }
+}
+
+/// Names are a single word in todo.txt, spaces are written as `_` and underscores as `\_`
+fn escape_name(name: &str) -> String {
+  name
+    .replace('\\', "\\\\")
+    .replace('_', "\\_")
+    .replace(' ', "_")
+}
+
+fn unescape_name(word: &str) -> String {
+  let mut name = String::new();
+  let mut chars = word.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => name.push(chars.next().unwrap_or('\\')),
+      '_' => name.push(' '),
+      c => name.push(c),
+    }
+  }
+  name
+}
+
+fn parse_day(word: &str) -> Option<NaiveDate> {
+  NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+fn priority_from_letter(letter: char) -> Option<Priority> {
+  match letter {
+    'A' => Some(Priority::High),
+    'B' => Some(Priority::Medium),
+    'C'..='Z' => Some(Priority::Low),
+    _ => None,
+  }
+}
+
+fn priority_letter(priority: Priority) -> char {
+  match priority {
+    Priority::High => 'A',
+    Priority::Medium => 'B',
+    Priority::Low => 'C',
+  }
+}
+
+/// `(A)` at the start of a line
+fn parse_priority(word: &str) -> Option<Priority> {
+  let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+  let mut chars = letter.chars();
+  match (chars.next(), chars.next()) {
+    (Some(letter), None) => priority_from_letter(letter),
+    _ => None,
+  }
+}
+
+/// Apply a `key:value` extension, false when the word is just part of the body
+fn apply_tag(todo: &mut Todo, key: &str, value: &str) -> bool {
+  match key {
+    "due" => parse_day(value).map(|due| todo.due = Some(due)).is_some(),
+    "est" => parse_duration(value)
+      .map(|estimate| todo.estimate = Some(estimate))
+      .is_ok(),
+    "status" => Status::from_str(value, true)
+      .map(|status| todo.status = status)
+      .is_ok(),
+    "pri" => value
+      .chars()
+      .next()
+      .and_then(priority_from_letter)
+      .map(|priority| todo.priority = Some(priority))
+      .is_some(),
+    "waiting" => {
+      todo.waiting_on = Some(unescape_name(value));
+      true
+    }
+    "follow" => parse_day(value)
+      .map(|day| todo.follow_up = Some(day))
+      .is_some(),
+    "bucket" => {
+      todo.bucket = match value {
+        "inbox" => Bucket::Inbox,
+        "next" => Bucket::Next,
+        "someday" => Bucket::Someday,
+        _ => return false,
+      };
+      true
+    }
+    "habit" => parse_frequency(value)
+      .map(|habit| todo.habit = Some(habit))
+      .is_ok(),
+    "until" => parse_day(value)
+      .map(|day| todo.expires = Some(day))
+      .is_some(),
+    "t" => parse_day(value)
+      .map(|day| todo.scheduled = Some(day))
+      .is_some(),
+    "snooze" => parse_day(value)
+      .map(|day| todo.snoozed_until = Some(day))
+      .is_some(),
+    "focus" if value == "yes" => {
+      todo.focused = true;
+      true
+    }
+    "pin" if value == "yes" => {
+      todo.pinned = true;
+      true
+    }
+    "uuid" if !value.is_empty() => {
+      todo.uuid = Some(value.to_string());
+      true
+    }
+    "ref" if !value.is_empty() => {
+      todo.reference = Some(value.to_string());
+      true
+    }
+    "parent" => value
+      .parse()
+      .map(|parent| todo.parent_id = Some(parent))
+      .is_ok(),
+    _ => false,
+  }
+}
+
+fn parse_line(line: &str, id: usize) -> Todo {
+  let mut todo = Todo {
+    id,
+    position: Some(id as f64),
+    // Plain todo.txt tasks are actionable, only new ones start in the inbox
+    bucket: Bucket::Next,
+    ..Default::default()
+  };
+  let mut words = line.split_whitespace().peekable();
+
+  let created = if words.next_if_eq(&"x").is_some() {
+    todo.status = Status::Done;
+    // Completion date comes first, then the creation date
+    words.next_if(|word| parse_day(word).is_some());
+    words.next_if(|word| parse_day(word).is_some())
+  } else {
+    if let Some(word) = words.next_if(|word| parse_priority(word).is_some()) {
+      todo.priority = parse_priority(word);
+    }
+    words.next_if(|word| parse_day(word).is_some())
+  };
+  todo.created_at = created
+    .and_then(parse_day)
+    .and_then(|day| day.and_hms_opt(0, 0, 0))
+    .map(|time| DateTime::<Utc>::from_naive_utc_and_offset(time, Utc));
+
+  let mut body = vec![];
+  for word in words {
+    if let Some(project) = word.strip_prefix('+')
+      && !project.is_empty()
+      && todo.project.is_none()
+    {
+      todo.project = Some(unescape_name(project));
+      continue;
+    }
+    if let Some(context) = word.strip_prefix('@')
+      && let Ok(tag) = parse_tag(context)
+    {
+      todo.tags.extend(&[tag]);
+      continue;
+    }
+    if let Some((key, value)) = word.split_once(':')
+      && apply_tag(&mut todo, key, value)
+    {
+      continue;
+    }
+    body.push(word);
+  }
+  todo.body = body.join(" ");
+  todo
+}
+
+pub fn parse(text: &str) -> Vec<Todo> {
+  text
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(number, line)| parse_line(line, number + 1))
+    .collect()
+}
+
+/// `parents` maps stored ids to the line numbers they are written at
+fn render_line(todo: &Todo, parents: &HashMap<usize, usize>) -> String {
+  let mut words = vec![];
+  let created = todo
+    .created_at
+    .map(|created| created.date_naive().to_string());
+  let done = matches!(todo.status, Status::Done | Status::Cancelled);
+  if done {
+    words.push("x".to_string());
+    words.extend(
+      todo
+        .updated_at
+        .filter(|_| created.is_some())
+        .map(|updated| updated.date_naive().to_string()),
+    );
+  } else if let Some(priority) = todo.priority {
+    words.push(format!("({})", priority_letter(priority)));
+  }
+  words.extend(created);
+  words.push(todo.body.clone());
+
+  if let Some(project) = &todo.project {
+    words.push(format!("+{}", escape_name(project)));
+  }
+  words.extend(todo.tags.0.iter().map(|tag| format!("@{}", tag)));
+  if done && let Some(priority) = todo.priority {
+    words.push(format!("pri:{}", priority_letter(priority)));
+  }
+  if !matches!(todo.status, Status::Todo | Status::Done) {
+    words.push(format!("status:{}", todo.status));
+  }
+  if let Some(due) = todo.due {
+    words.push(format!("due:{}", due));
+  }
+  if let Some(estimate) = todo.estimate {
+    words.push(format!("est:{}", format_duration(estimate)));
+  }
+  if let Some(waiting_on) = &todo.waiting_on {
+    words.push(format!("waiting:{}", escape_name(waiting_on)));
+  }
+  if let Some(follow_up) = todo.follow_up {
+    words.push(format!("follow:{}", follow_up));
+  }
+  match todo.bucket {
+    Bucket::Inbox => words.push("bucket:inbox".to_string()),
+    Bucket::Someday => words.push("bucket:someday".to_string()),
+    Bucket::Next => {}
+  }
+  if let Some(scheduled) = todo.scheduled {
+    words.push(format!("t:{}", scheduled));
+  }
+  if let Some(habit) = todo.habit {
+    words.push(format!("habit:{}", String::from(habit)));
+  }
+  if let Some(expires) = todo.expires {
+    words.push(format!("until:{}", expires));
+  }
+  if let Some(snoozed_until) = todo.snoozed_until {
+    words.push(format!("snooze:{}", snoozed_until));
+  }
+  if todo.focused {
+    words.push("focus:yes".to_string());
+  }
+  if todo.pinned {
+    words.push("pin:yes".to_string());
+  }
+  if let Some(parent) = todo.parent_id.and_then(|parent| parents.get(&parent)) {
+    words.push(format!("parent:{}", parent));
+  }
+  if let Some(uuid) = &todo.uuid {
+    words.push(format!("uuid:{}", uuid));
+  }
+  // Words end at spaces, a ref with one doesn't fit
+  if let Some(reference) = todo
+    .reference
+    .as_ref()
+    .filter(|reference| !reference.contains(char::is_whitespace))
+  {
+    words.push(format!("ref:{}", reference));
+  }
+  words.join(" ")
+}
+
+/// Todos one per line in the given order, their line numbers are the ids next time
+pub fn render(todos: &[Todo]) -> String {
+  let lines = todos
+    .iter()
+    .enumerate()
+    .map(|(number, todo)| (todo.id, number + 1))
+    .collect::<HashMap<usize, usize>>();
+  todos
+    .iter()
+    .map(|todo| render_line(todo, &lines) + "\n")
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tags::Tags;
+  use crate::{AddOptions, add, searches, set_status};
+  use std::env;
+
+  #[test]
+  fn parse_todo_txt() {
+    let todos = parse(
+      "(A) 2024-03-01 Call mom @phone +Family due:2024-03-05\n\n\
+       x 2024-03-02 2024-03-01 Pay bills pri:B\n\
+       Someday maybe time:10:30 bucket:someday",
+    );
+    assert_eq!(
+      vec![
+        Todo {
+          body: "Call mom".to_string(),
+          id: 1,
+          tags: Tags(vec!["phone".to_string()]),
+          position: Some(1.0),
+          priority: Some(Priority::High),
+          project: Some("Family".to_string()),
+          due: NaiveDate::from_ymd_opt(2024, 3, 5),
+          bucket: Bucket::Next,
+          ..Default::default()
+        },
+        Todo {
+          body: "Pay bills".to_string(),
+          id: 3,
+          position: Some(3.0),
+          status: Status::Done,
+          priority: Some(Priority::Medium),
+          bucket: Bucket::Next,
+          ..Default::default()
+        },
+        Todo {
+          body: "Someday maybe time:10:30".to_string(),
+          id: 4,
+          position: Some(4.0),
+          bucket: Bucket::Someday,
+          ..Default::default()
+        },
+      ],
+      todos
+    );
+    assert_eq!(
+      Some("2024-03-01".to_string()),
+      todos[1].created_at.map(|day| day.date_naive().to_string())
+    );
+  }
+
+  #[test]
+  fn render_round_trip() {
+    let text = "(A) 2024-03-01 Call mom +Family @phone due:2024-03-05 est:30m\n\
+                Book hotel status:in-progress waiting:travel_agent follow:2024-03-04 t:2024-03-02 \
+                parent:1 ref:https://example.com/issues/7\n";
+    let todos = parse(text);
+    assert_eq!(text, render(&todos));
+    assert_eq!(Some(1), todos[1].parent_id);
+    assert_eq!(Some("travel agent".to_string()), todos[1].waiting_on);
+    let waiting = Todo {
+      body: "Deploy".to_string(),
+      waiting_on: Some("ops_team or C:\\ bob".to_string()),
+      project: Some("Side project_2".to_string()),
+      ..Default::default()
+    };
+    let text = render(std::slice::from_ref(&waiting));
+    assert!(text.contains(" waiting:ops\\_team_or_C:\\\\_bob"));
+    assert!(text.contains(" +Side_project\\_2 "));
+    assert_eq!(waiting.waiting_on, parse(&text)[0].waiting_on);
+    assert_eq!(waiting.project, parse(&text)[0].project);
+    assert_eq!(NaiveDate::from_ymd_opt(2024, 3, 2), todos[1].scheduled);
+  }
+
+  #[test]
+  fn storage_writes_back() {
+    let path = env::temp_dir().join(format!("todo-{}.txt", std::process::id()));
+    fs::write(&path, "Milk\n\n(B) Eggs\n").unwrap();
+    let storage = TodoTxt::new(path.clone());
+
+    let conn = storage.open().unwrap();
+    _ = add(vec!["Bread".to_string()], &AddOptions::default(), &conn);
+    let todos = collect_todos_all(&conn).unwrap();
+    _ = set_status(vec![todos[0].clone()], Status::Done, &conn);
+    storage.save(&conn).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert!(lines[0].starts_with("x ") && lines[0].contains(" Milk uuid:"));
+    assert!(lines[1].starts_with("(B) Eggs uuid:"));
+    assert!(lines[2].contains(" Bread bucket:inbox uuid:"));
+
+    // Todos keep their uuid across loads
+    let conn = storage.open().unwrap();
+    let uuids = |todos: Vec<Todo>| todos.into_iter().map(|todo| todo.uuid).collect::<Vec<_>>();
+    assert_eq!(uuids(todos), uuids(collect_todos_all(&conn).unwrap()));
+    let top = AddOptions {
+      top: true,
+      ..Default::default()
+    };
+    _ = add(vec!["Jam".to_string()], &top, &conn);
+    storage.save(&conn).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(
+      text
+        .lines()
+        .next()
+        .unwrap()
+        .contains(" Jam bucket:inbox uuid:")
+    );
+    assert!(TodoTxt::new(env::temp_dir()).open().is_err());
+    // Settings the format has no place for are refused rather than lost
+    let error = conn
+      .execute(
+        "INSERT INTO tag_colors (tag, color) VALUES ('home', 'green')",
+        (),
+      )
+      .unwrap_err();
+    assert!(error.to_string().contains("can't keep tag colors"));
+    let error = searches::record(searches::SEARCH, "milk", &conn).unwrap_err();
+    assert!(error.to_string().contains("can't keep recent searches"));
+    assert_eq!(Some("todo.txt files"), storage.without_history());
+    _ = fs::remove_file(path);
+  }
+}
@@ -0,0 +1,166 @@
+use crate::date::parse_date;
+use crate::priority::Priority;
+use crate::prompt;
+use crate::{
+  SCHEDULED_LATER, TODO_SELECT, Todo, annotations, collect_todos, set_due, set_priority,
+  set_scheduled,
+};
+use chrono::{Days, Local, NaiveDate};
+use console::{Key, Term, style};
+use dialoguer::{Input, theme::ColorfulTheme};
+use rusqlite::Connection;
+use std::error::Error;
+
+/// What a key does to the todo being triaged
+#[derive(Debug, PartialEq)]
+enum Decision {
+  Priority(Option<Priority>),
+  Due(Option<NaiveDate>),
+  /// Type the due date
+  AskDue,
+  /// Hide the todo until then
+  Snooze(NaiveDate),
+  AskSnooze,
+  Next,
+  Stop,
+}
+
+/// Shown once before the first todo
+const KEYS: &str = "h m l priority, - none | t o w due today, tomorrow, in a week, d on a \
+                    date, x none | s snooze a week, S until | enter next, q stop";
+
+/// Open todos that are listed, the ones left alone longest first
+fn collect_todos_untriaged(conn: &Connection) -> Result<Vec<Todo>, Box<dyn Error>> {
+  collect_todos(
+    format!(
+      "{TODO_SELECT} where status not in ('done', 'cancelled') and not {SCHEDULED_LATER}
+       order by updated_at, id;"
+    ),
+    conn,
+  )
+}
+
+fn decide(key: &Key, today: NaiveDate) -> Option<Decision> {
+  let in_days = |days| today.checked_add_days(Days::new(days));
+  let decision = match key {
+    Key::Char('h') => Decision::Priority(Some(Priority::High)),
+    Key::Char('m') => Decision::Priority(Some(Priority::Medium)),
+    Key::Char('l') => Decision::Priority(Some(Priority::Low)),
+    Key::Char('-') => Decision::Priority(None),
+    Key::Char('t') => Decision::Due(Some(today)),
+    Key::Char('o') => Decision::Due(in_days(1)),
+    Key::Char('w') => Decision::Due(in_days(7)),
+    Key::Char('d') => Decision::AskDue,
+    Key::Char('x') => Decision::Due(None),
+    Key::Char('s') => Decision::Snooze(in_days(7)?),
+    Key::Char('S') => Decision::AskSnooze,
+    Key::Enter | Key::Char(' ') => Decision::Next,
+    Key::Char('q') | Key::Escape => Decision::Stop,
+    _ => return None,
+  };
+  Some(decision)
+}
+
+/// Priority and due dates keep the todo up for more keys, a snoozed todo is done with
+fn apply(decision: Decision, todo: &Todo, conn: &Connection) -> Result<bool, Box<dyn Error>> {
+  let todos = vec![todo.clone()];
+  match decision {
+    Decision::Priority(priority) => set_priority(todos, priority, conn)?,
+    Decision::Due(due) => set_due(todos, due, conn)?,
+    Decision::Snooze(until) => {
+      set_scheduled(todos, Some(until), conn)?;
+      return Ok(true);
+    }
+    Decision::AskDue | Decision::AskSnooze | Decision::Stop => {}
+    Decision::Next => return Ok(true),
+  }
+  Ok(false)
+}
+
+/// `todo triage`, a key press at a time through the open todos
+pub fn triage(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let todos = collect_todos_untriaged(conn)?;
+  if todos.is_empty() {
+    println!("Nothing to triage!");
+    return Ok(());
+  }
+
+  prompt::require("triage the todos in a terminal")?;
+  // Untested segment starts, this part needs interactivity
+  let term = Term::stdout();
+  let today = Local::now().date_naive();
+  let ask = |prompt: &str, default: &str| -> Result<NaiveDate, Box<dyn Error>> {
+    let date: String = Input::with_theme(&ColorfulTheme::default())
+      .with_prompt(prompt)
+      .default(default.to_string())
+      .validate_with(|input: &String| parse_date(input).map(|_| ()))
+      .interact_text()?;
+    Ok(parse_date(&date)?)
+  };
+  println!("{}", style(KEYS).dim());
+  let total = todos.len();
+  for (number, todo) in todos.into_iter().enumerate() {
+    println!(
+      "{} {}{}",
+      style(format!("[{}/{}]", number + 1, total)).dim(),
+      todo.body,
+      annotations(&todo, today)
+    );
+    loop {
+      let decision = match decide(&term.read_key()?, today) {
+        None => continue,
+        Some(Decision::Stop) => return Ok(()),
+        Some(Decision::AskDue) => Decision::Due(Some(ask("Due", "tomorrow")?)),
+        Some(Decision::AskSnooze) => Decision::Snooze(ask("Snooze until", "1w")?),
+        Some(decision) => decision,
+      };
+      if apply(decision, &todo, conn)? {
+        break;
+      }
+    }
+  }
+  println!("All triaged!");
+  Ok(())
+  // Untested segment ends
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, bodies, collect_todos_all};
+
+  #[test]
+  fn keys_set_priority_due_and_snooze() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    _ = add(
+      vec!["Milk".to_string(), "Rent".to_string()],
+      &AddOptions::default(),
+      &conn,
+    );
+    conn
+      .execute(
+        "UPDATE todos SET updated_at = '2020-01-01 00:00:00' WHERE id = 2",
+        (),
+      )
+      .unwrap();
+    let todos = collect_todos_untriaged(&conn).unwrap();
+    assert_eq!(vec!["Rent", "Milk"], bodies(&todos));
+
+    let today = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+    let key = |c| decide(&Key::Char(c), today).unwrap();
+    assert!(!apply(key('h'), &todos[0], &conn).unwrap());
+    assert!(!apply(key('o'), &todos[0], &conn).unwrap());
+    assert!(apply(key('s'), &todos[1], &conn).unwrap());
+    assert_eq!(Some(Decision::Next), decide(&Key::Enter, today));
+    assert_eq!(None, decide(&Key::Char('?'), today));
+
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(
+      (Some(Priority::High), NaiveDate::from_ymd_opt(2024, 3, 6)),
+      (todos[1].priority, todos[1].due)
+    );
+    assert_eq!(NaiveDate::from_ymd_opt(2024, 3, 12), todos[0].scheduled);
+  }
+}
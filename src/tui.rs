@@ -0,0 +1,808 @@
+use crate::ascii;
+use crate::config::LockFile;
+use crate::filter::text_matches;
+use crate::keys::{self, Action, Keys};
+use crate::lock::Lock;
+use crate::order;
+use crate::searches::{self, FILTER};
+use crate::status::Status;
+use crate::tags;
+use crate::{ListOptions, Todo, collect_todos_listed, details, emoji, undo};
+use ratatui::Frame;
+use ratatui::crossterm::event::{
+  self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+  MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout, Margin, Position, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::symbols::border;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+/// Rows moved by page up and down
+const PAGE: isize = 10;
+
+/// Rows moved by a turn of the scroll wheel
+const SCROLL: isize = 3;
+
+/// Borders of `--ascii`
+const ASCII_BORDER: border::Set = border::Set {
+  top_left: "+",
+  top_right: "+",
+  bottom_left: "+",
+  bottom_right: "+",
+  vertical_left: "|",
+  vertical_right: "|",
+  horizontal_top: "-",
+  horizontal_bottom: "-",
+};
+
+/// What todos are grouped by, groups can be collapsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Grouping {
+  #[default]
+  Project,
+  Tag,
+  None,
+}
+
+impl Grouping {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Grouping::Project => "project",
+      Grouping::Tag => "tag",
+      Grouping::None => "none",
+    }
+  }
+
+  fn next(self) -> Grouping {
+    match self {
+      Grouping::Project => Grouping::Tag,
+      Grouping::Tag => Grouping::None,
+      Grouping::None => Grouping::Project,
+    }
+  }
+
+  /// Groups a todo is in, with several tags it is in several groups
+  fn names(&self, todo: &Todo) -> Vec<String> {
+    match self {
+      Grouping::Project => vec![todo.project.clone().unwrap_or("(no project)".to_string())],
+      Grouping::Tag if todo.tags.is_empty() => vec!["(untagged)".to_string()],
+      Grouping::Tag => todo.tags.0.iter().map(|tag| format!("#{}", tag)).collect(),
+      Grouping::None => vec![],
+    }
+  }
+}
+
+/// What an action is called in the hints
+const HINTS: [(Action, &str); 8] = [
+  (Action::Toggle, "done"),
+  (Action::Collapse, "fold"),
+  (Action::Filter, "filter"),
+  (Action::Pane, "details"),
+  (Action::Group, "group"),
+  (Action::Undo, "undo"),
+  (Action::Mouse, "mouse"),
+  (Action::Quit, "quit"),
+];
+
+#[derive(Debug, PartialEq)]
+enum Row {
+  Group {
+    name: String,
+    count: usize,
+    collapsed: bool,
+  },
+  /// Index into the todos
+  Todo(usize),
+}
+
+struct App {
+  todos: Vec<Todo>,
+  grouping: Grouping,
+  /// Grouping and name of every collapsed group
+  collapsed: HashSet<(String, String)>,
+  filter: String,
+  /// Keys go to the filter bar
+  filtering: bool,
+  /// Earlier filters, latest first, and which one up and down recalled
+  filters: Vec<String>,
+  recalled: Option<usize>,
+  pane: bool,
+  /// Clicks and the scroll wheel are handled by the app
+  mouse: bool,
+  keys: Keys,
+  /// What the last action did, shown instead of the hints until the next key
+  message: Option<String>,
+  selected: usize,
+  rows: Vec<Row>,
+  /// Scroll position of the list as last drawn
+  list: ListState,
+  /// Where the list was last drawn, to find the row under a click
+  list_area: Rect,
+}
+
+impl App {
+  fn new(todos: Vec<Todo>, collapsed: HashSet<(String, String)>, keys: Keys) -> App {
+    let mut app = App {
+      todos,
+      grouping: Grouping::default(),
+      collapsed,
+      filter: String::new(),
+      filtering: false,
+      filters: vec![],
+      recalled: None,
+      pane: false,
+      mouse: true,
+      keys,
+      message: None,
+      selected: 0,
+      rows: vec![],
+      list: ListState::default(),
+      list_area: Rect::default(),
+    };
+    app.build_rows();
+    app
+  }
+
+  fn is_collapsed(&self, name: &str) -> bool {
+    self
+      .collapsed
+      .contains(&(self.grouping.as_str().to_string(), name.to_string()))
+  }
+
+  fn build_rows(&mut self) {
+    let matching = (0..self.todos.len())
+      .filter(|index| text_matches(&self.todos[*index], &self.filter))
+      .collect::<Vec<usize>>();
+    let mut rows = vec![];
+    if self.grouping == Grouping::None {
+      rows.extend(matching.into_iter().map(Row::Todo));
+    } else {
+      let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+      for index in matching {
+        for name in self.grouping.names(&self.todos[index]) {
+          groups.entry(name).or_default().push(index);
+        }
+      }
+      for (name, indexes) in groups {
+        let collapsed = self.is_collapsed(&name);
+        rows.push(Row::Group {
+          name,
+          count: indexes.len(),
+          collapsed,
+        });
+        if !collapsed {
+          rows.extend(indexes.into_iter().map(Row::Todo));
+        }
+      }
+    }
+    self.rows = rows;
+    self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+  }
+
+  fn selected_todo(&self) -> Option<&Todo> {
+    match self.rows.get(self.selected) {
+      Some(Row::Todo(index)) => self.todos.get(*index),
+      _ => None,
+    }
+  }
+
+  fn move_by(&mut self, delta: isize) {
+    let last = self.rows.len().saturating_sub(1) as isize;
+    self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+  }
+
+  /// The group of the selected row, a todo is in the group above it
+  fn selected_group(&self) -> Option<(usize, &str)> {
+    self.rows[..=self.selected.min(self.rows.len().saturating_sub(1))]
+      .iter()
+      .enumerate()
+      .rev()
+      .find_map(|(index, row)| match row {
+        Row::Group { name, .. } => Some((index, name.as_str())),
+        Row::Todo(_) => None,
+      })
+  }
+
+  /// Collapse or expand the selected group and remember it for the next session
+  fn set_collapsed(&mut self, collapsed: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let Some((index, name)) = self.selected_group() else {
+      return Ok(());
+    };
+    let key = (self.grouping.as_str().to_string(), name.to_string());
+    if collapsed {
+      conn.execute(
+        "INSERT OR IGNORE INTO collapsed (grouping, name) VALUES (?1, ?2)",
+        (&key.0, &key.1),
+      )?;
+      self.collapsed.insert(key);
+    } else {
+      conn.execute(
+        "DELETE FROM collapsed WHERE grouping = ?1 and name = ?2",
+        (&key.0, &key.1),
+      )?;
+      self.collapsed.remove(&key);
+    }
+    self.selected = index;
+    self.build_rows();
+    Ok(())
+  }
+
+  fn toggle(&mut self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    if let Some(Row::Group { collapsed, .. }) = self.rows.get(self.selected) {
+      return self.set_collapsed(!collapsed, conn);
+    }
+    let Some(todo) = self.selected_todo() else {
+      return Ok(());
+    };
+    let (status, command) = match todo.status {
+      Status::Done => (Status::Todo, "reopen"),
+      _ => (Status::Done, "done"),
+    };
+    conn.execute(
+      "UPDATE todos SET status = ?1 WHERE id = ?2",
+      (status, todo.id),
+    )?;
+    undo::record(&format!("tui {} {}", command, todo.id), true, conn)?;
+    self.reload(conn)
+  }
+
+  fn delete(&mut self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let Some(todo) = self.selected_todo() else {
+      return Ok(());
+    };
+    let (id, body) = (todo.id, todo.body.clone());
+    conn.execute("DELETE FROM todos WHERE id = ?1", (id,))?;
+    undo::record(&format!("tui rm {}", id), true, conn)?;
+    self.message = Some(format!("Removed todo: {}", body));
+    self.reload(conn)
+  }
+
+  /// Swap the selected todo with its neighbour in the group, the manual order is kept for
+  /// the list and the next session. Pinned todos stay above the others.
+  fn move_todo(&mut self, delta: isize, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let (Some(Row::Todo(index)), Some(Row::Todo(other))) = (
+      self.rows.get(self.selected),
+      self
+        .selected
+        .checked_add_signed(delta)
+        .and_then(|neighbour| self.rows.get(neighbour)),
+    ) else {
+      return Ok(());
+    };
+    let (todo, other) = (&self.todos[*index], &self.todos[*other]);
+    if todo.pinned != other.pinned {
+      return Ok(());
+    }
+    let id = todo.id;
+    match delta < 0 {
+      true => order::arrange(&[id, other.id], conn)?,
+      false => order::arrange(&[other.id, id], conn)?,
+    }
+    undo::record(&format!("tui move {}", id), true, conn)?;
+    let group = self.selected_group().map_or(0, |(index, _)| index);
+    self.reload(conn)?;
+    if let Some(row) = self
+      .rows
+      .iter()
+      .skip(group)
+      .position(|row| matches!(row, Row::Todo(index) if self.todos[*index].id == id))
+    {
+      self.selected = group + row;
+    }
+    Ok(())
+  }
+
+  fn undo(&mut self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    self.message = Some(match undo::undo_last(conn)? {
+      Some(command) => format!("Undid: {}", command),
+      None => "Nothing to undo!".to_string(),
+    });
+    self.reload(conn)
+  }
+
+  fn reload(&mut self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    self.todos = collect_todos_listed(&ListOptions::default(), conn)?;
+    self.build_rows();
+    Ok(())
+  }
+
+  /// Returns false once the app should quit
+  fn apply(&mut self, action: Action, conn: &Connection) -> Result<bool, Box<dyn Error>> {
+    match action {
+      Action::Up => self.move_by(-1),
+      Action::Down => self.move_by(1),
+      Action::PageUp => self.move_by(-PAGE),
+      Action::PageDown => self.move_by(PAGE),
+      Action::Top => self.selected = 0,
+      Action::Bottom => self.move_by(self.rows.len() as isize),
+      Action::Toggle => self.toggle(conn)?,
+      Action::Collapse => self.set_collapsed(true, conn)?,
+      Action::Expand => self.set_collapsed(false, conn)?,
+      Action::Filter => self.filtering = true,
+      Action::Pane => self.pane = !self.pane,
+      Action::Group => {
+        self.grouping = self.grouping.next();
+        self.selected = 0;
+        self.build_rows();
+      }
+      Action::Mouse => self.mouse = !self.mouse,
+      Action::Delete => self.delete(conn)?,
+      Action::MoveUp => self.move_todo(-1, conn)?,
+      Action::MoveDown => self.move_todo(1, conn)?,
+      Action::Undo => self.undo(conn)?,
+      Action::Quit => return Ok(false),
+    }
+    Ok(true)
+  }
+
+  /// Typing in the filter bar, enter keeps the filter and escape clears it. Up and down go
+  /// through earlier filters, tab completes to the latest one starting with what is typed.
+  fn type_filter(&mut self, key: KeyEvent, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    match key.code {
+      KeyCode::Char(c) => self.filter.push(c),
+      KeyCode::Backspace => _ = self.filter.pop(),
+      KeyCode::Tab => match searches::complete(&self.filter, &self.filters) {
+        Some(filter) => self.filter = filter.clone(),
+        None => return Ok(()),
+      },
+      KeyCode::Up | KeyCode::Down => {
+        let recalled = match (key.code, self.recalled) {
+          (KeyCode::Up, None) => Some(0),
+          (KeyCode::Up, Some(index)) => Some((index + 1).min(self.filters.len() - 1)),
+          (_, Some(index)) => index.checked_sub(1),
+          (_, None) => None,
+        };
+        self.recalled = recalled.filter(|index| *index < self.filters.len());
+        self.filter = self
+          .recalled
+          .map_or(String::new(), |index| self.filters[index].clone());
+      }
+      KeyCode::Enter => {
+        self.filtering = false;
+        // Read-only todos keep no history
+        _ = searches::record(FILTER, &self.filter, conn);
+        self.filters = searches::recent(FILTER, conn)?;
+      }
+      KeyCode::Esc => {
+        self.filter.clear();
+        self.filtering = false;
+      }
+      _ => return Ok(()),
+    }
+    if key.code != KeyCode::Up && key.code != KeyCode::Down {
+      self.recalled = None;
+    }
+    self.selected = 0;
+    self.build_rows();
+    Ok(())
+  }
+
+  /// Shortcuts for the bar at the bottom, with the first key of each action
+  fn hints(&self) -> String {
+    HINTS
+      .iter()
+      .filter_map(|(action, hint)| {
+        let keys = *self.keys.bound(*action).first()?;
+        Some(format!("{} {}", keys::display(keys), hint))
+      })
+      .collect::<Vec<String>>()
+      .join("  ")
+  }
+
+  /// Index of the row drawn at a position of the terminal
+  fn row_at(&self, column: u16, row: u16) -> Option<usize> {
+    let inner = self.list_area.inner(Margin::new(1, 1));
+    if !inner.contains(Position::new(column, row)) {
+      return None;
+    }
+    let index = self.list.offset() + (row - inner.y) as usize;
+    Some(index).filter(|index| *index < self.rows.len())
+  }
+
+  /// Whether a column is on the checkbox of a todo or the arrow of a group
+  fn on_marker(&self, index: usize, column: u16) -> bool {
+    let marker = match (&self.rows[index], self.grouping) {
+      (Row::Todo(_), Grouping::None) | (Row::Group { .. }, _) => 0,
+      (Row::Todo(_), _) => 2,
+    };
+    let inner = self.list_area.inner(Margin::new(1, 1));
+    (column - inner.x)
+      .checked_sub(marker)
+      .is_some_and(|offset| offset < 2)
+  }
+
+  /// A click selects a row, on a checkbox it completes or reopens the todo and on an arrow
+  /// it folds the group
+  fn click(&mut self, event: MouseEvent, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    match event.kind {
+      MouseEventKind::ScrollDown => self.move_by(SCROLL),
+      MouseEventKind::ScrollUp => self.move_by(-SCROLL),
+      MouseEventKind::Down(MouseButton::Left) => {
+        if let Some(index) = self.row_at(event.column, event.row) {
+          self.selected = index;
+          if self.on_marker(index, event.column) {
+            self.toggle(conn)?;
+          }
+        }
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  fn row_line(&self, row: &Row) -> Line<'static> {
+    match row {
+      Row::Group {
+        name,
+        count,
+        collapsed,
+      } => Line::from(vec![
+        Span::raw(ascii::text(if *collapsed { "▸ " } else { "▾ " })),
+        Span::raw(name.clone()).bold(),
+        Span::raw(format!(" ({})", count)).dim(),
+      ]),
+      Row::Todo(index) => {
+        let todo = &self.todos[*index];
+        let indent = match self.grouping {
+          Grouping::None => "",
+          _ => "  ",
+        };
+        let body = Span::raw(emoji::display(&todo.body));
+        let body = match todo.status {
+          Status::InProgress => body.yellow().bold(),
+          Status::Done => body.crossed_out(),
+          Status::Cancelled => body.crossed_out().dim(),
+          Status::Todo | Status::Waiting => body,
+        };
+        let mut spans = vec![
+          Span::raw(ascii::text(&format!("{}{} ", indent, todo.status.glyph()))),
+          body,
+        ];
+        if let Some(priority) = todo.priority {
+          spans.push(Span::raw(format!(" {}", priority.marker())).red());
+        }
+        if let Some(due) = todo.due {
+          spans.push(Span::raw(format!(" (due {})", due)).dim());
+        }
+        for tag in &todo.tags.0 {
+          let badge = Span::raw(format!("#{}", tag));
+          spans.push(Span::raw(" "));
+          spans.push(match tags::color(tag) {
+            Some(color) => badge
+              .bg(ratatui::style::Color::Indexed(color.0))
+              .fg(ratatui::style::Color::Indexed(color.ink())),
+            None => badge.magenta(),
+          });
+        }
+        Line::from(spans)
+      }
+    }
+  }
+
+  fn draw(&mut self, frame: &mut Frame) {
+    let [main, bar] =
+      Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let (list_area, pane_area) = match self.pane {
+      true => {
+        let [list, pane] =
+          Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+        (list, Some(pane))
+      }
+      false => (main, None),
+    };
+
+    let items = self
+      .rows
+      .iter()
+      .map(|row| ListItem::new(self.row_line(row)))
+      .collect::<Vec<ListItem>>();
+    let title = match self.mouse {
+      true => format!(" Todos by {} ", self.grouping.as_str()),
+      false => format!(" Todos by {}, mouse off ", self.grouping.as_str()),
+    };
+    let list = List::new(items)
+      .block(block(title))
+      .highlight_style(Style::new().reversed());
+    self.list.select(Some(self.selected));
+    self.list_area = list_area;
+    frame.render_stateful_widget(list, list_area, &mut self.list);
+
+    if let Some(area) = pane_area {
+      let text = match self.selected_todo() {
+        Some(todo) => ascii::text(&details(todo).join("\n")),
+        None => String::new(),
+      };
+      let pane = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(block(" Details ".to_string()));
+      frame.render_widget(pane, area);
+    }
+
+    let bar_text = match (self.filtering, &self.message, self.filter.is_empty()) {
+      (true, _, _) => Line::from(format!("/{}", self.filter)),
+      (false, Some(message), _) => Line::from(message.clone()),
+      (false, None, false) => Line::from(format!("Filter: {}", self.filter)).dim(),
+      (false, None, true) => Line::from(self.hints()).dim(),
+    };
+    frame.render_widget(Paragraph::new(bar_text), bar);
+  }
+}
+
+fn block(title: String) -> Block<'static> {
+  let block = Block::default().borders(Borders::ALL).title(title);
+  match ascii::enabled() {
+    true => block.border_set(ASCII_BORDER),
+    false => block,
+  }
+}
+
+fn collect_collapsed(conn: &Connection) -> Result<HashSet<(String, String)>, Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT grouping, name FROM collapsed")?;
+  let collapsed = stmt
+    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect::<Result<HashSet<(String, String)>, _>>()?;
+  Ok(collapsed)
+}
+
+/// Browse the listed todos grouped by project or tag, `file` is locked while a key or click
+/// is handled
+pub fn tui(
+  keys: Keys,
+  lock_file: LockFile,
+  file: Option<&Path>,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let mut app = App::new(
+    collect_todos_listed(&ListOptions::default(), conn)?,
+    collect_collapsed(conn)?,
+    keys,
+  );
+  app.filters = searches::recent(FILTER, conn)?;
+  // Untested segment starts, this part needs interactivity
+  // The details are drawn by the terminal ui, escape codes would show up as text
+  console::set_colors_enabled(false);
+  let mut terminal = ratatui::try_init()?;
+  let result = (|| -> Result<(), Box<dyn Error>> {
+    execute!(io::stdout(), EnableMouseCapture)?;
+    loop {
+      terminal.draw(|frame| app.draw(frame))?;
+      let event = event::read()?;
+      let _lock = Lock::acquire(lock_file, file)?;
+      let key = match event {
+        Event::Key(key) if key.kind == KeyEventKind::Press => key,
+        Event::Mouse(event) if app.mouse => {
+          app.click(event, conn)?;
+          continue;
+        }
+        _ => continue,
+      };
+      let mouse = app.mouse;
+      app.message = None;
+      if app.filtering {
+        app.type_filter(key, conn)?;
+      } else if let Some(action) = app.keys.press(key)
+        && !app.apply(action, conn)?
+      {
+        return Ok(());
+      }
+      match (mouse, app.mouse) {
+        (false, true) => execute!(io::stdout(), EnableMouseCapture)?,
+        (true, false) => execute!(io::stdout(), DisableMouseCapture)?,
+        _ => {}
+      }
+    }
+  })();
+  _ = execute!(io::stdout(), DisableMouseCapture);
+  ratatui::restore();
+  result
+  // Untested segment ends
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Keymap;
+  use crate::db::create_db;
+  use crate::{AddOptions, add, bodies};
+  use ratatui::Terminal;
+  use ratatui::backend::TestBackend;
+  use std::collections::BTreeMap;
+
+  fn setup() -> (Connection, App) {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let house = AddOptions {
+      project: Some("House".to_string()),
+      ..Default::default()
+    };
+    _ = add(
+      vec!["Fix sink".to_string(), "Paint shed".to_string()],
+      &house,
+      &conn,
+    );
+    _ = add(vec!["Milk".to_string()], &AddOptions::default(), &conn);
+    _ = undo::record("add", true, &conn);
+    let app = App::new(
+      collect_todos_listed(&ListOptions::default(), &conn).unwrap(),
+      collect_collapsed(&conn).unwrap(),
+      Keys::new(Keymap::Default, &BTreeMap::new()).unwrap(),
+    );
+    (conn, app)
+  }
+
+  fn lines(app: &App) -> Vec<String> {
+    app
+      .rows
+      .iter()
+      .map(|row| app.row_line(row).to_string())
+      .collect()
+  }
+
+  #[test]
+  fn groups_fold_and_stay_folded() {
+    let (conn, mut app) = setup();
+    assert_eq!(
+      vec![
+        "▾ (no project) (1)",
+        "  ○ Milk",
+        "▾ House (2)",
+        "  ○ Fix sink",
+        "  ○ Paint shed"
+      ],
+      lines(&app)
+    );
+
+    // Folding from a todo folds the group it is in
+    app.selected = 4;
+    app.apply(Action::Collapse, &conn).unwrap();
+    assert_eq!(2, app.selected);
+    assert_eq!("▸ House (2)", lines(&app)[2]);
+    assert_eq!(3, app.rows.len());
+
+    let reopened = App::new(
+      app.todos.clone(),
+      collect_collapsed(&conn).unwrap(),
+      Keys::new(Keymap::Default, &BTreeMap::new()).unwrap(),
+    );
+    assert_eq!(3, reopened.rows.len());
+    app.apply(Action::Toggle, &conn).unwrap();
+    assert_eq!(5, app.rows.len());
+  }
+
+  #[test]
+  fn filter_and_toggle() {
+    let (conn, mut app) = setup();
+    app.apply(Action::Filter, &conn).unwrap();
+    let typed = |app: &mut App, code| app.type_filter(KeyEvent::from(code), &conn).unwrap();
+    for c in "shed".chars() {
+      typed(&mut app, KeyCode::Char(c));
+    }
+    typed(&mut app, KeyCode::Enter);
+    assert!(!app.filtering);
+    assert_eq!(vec!["▾ House (1)", "  ○ Paint shed"], lines(&app));
+
+    // Earlier filters come back with up and tab
+    typed(&mut app, KeyCode::Esc);
+    typed(&mut app, KeyCode::Up);
+    assert_eq!("shed", app.filter);
+    typed(&mut app, KeyCode::Down);
+    assert_eq!("", app.filter);
+    typed(&mut app, KeyCode::Char('s'));
+    typed(&mut app, KeyCode::Tab);
+    assert_eq!("shed", app.filter);
+    typed(&mut app, KeyCode::Enter);
+
+    app.apply(Action::Down, &conn).unwrap();
+    app.apply(Action::Toggle, &conn).unwrap();
+    assert_eq!(Status::Done, app.selected_todo().unwrap().status);
+
+    typed(&mut app, KeyCode::Esc);
+    app.apply(Action::Group, &conn).unwrap();
+    assert_eq!(Grouping::Tag, app.grouping);
+    assert_eq!("▾ (untagged) (3)", lines(&app)[0]);
+  }
+
+  #[test]
+  fn todos_move_within_their_group() {
+    let (conn, mut app) = setup();
+    app.selected = 3;
+    app.apply(Action::MoveDown, &conn).unwrap();
+    assert_eq!(4, app.selected);
+    assert_eq!(vec!["  ○ Paint shed", "  ○ Fix sink"], lines(&app)[3..]);
+    // Nothing to swap with past the end or over the group
+    app.apply(Action::MoveDown, &conn).unwrap();
+    app.selected = 1;
+    app.apply(Action::MoveUp, &conn).unwrap();
+    assert_eq!(1, app.selected);
+
+    // The order is kept for the list
+    let listed = collect_todos_listed(&ListOptions::default(), &conn).unwrap();
+    assert_eq!(vec!["Paint shed", "Fix sink", "Milk"], bodies(&listed));
+  }
+
+  #[test]
+  fn draws_the_detail_pane() {
+    let (conn, mut app) = setup();
+    app.apply(Action::Down, &conn).unwrap();
+    app.apply(Action::Pane, &conn).unwrap();
+    let mut terminal = Terminal::new(TestBackend::new(60, 8)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let screen = format!("{:?}", terminal.backend().buffer());
+    assert!(screen.contains(" Details "));
+    assert!(screen.contains("c. Milk"));
+  }
+
+  #[test]
+  fn vim_keys_delete_and_undo() {
+    let (conn, mut app) = setup();
+    app.keys = Keys::new(Keymap::Vim, &BTreeMap::new()).unwrap();
+    let press = |app: &mut App, keys: &str| {
+      for c in keys.chars() {
+        if let Some(action) = app.keys.press(KeyEvent::from(KeyCode::Char(c))) {
+          app.apply(action, &conn).unwrap();
+        }
+      }
+    };
+
+    press(&mut app, "G");
+    assert_eq!(4, app.selected);
+    press(&mut app, "gg");
+    assert_eq!(0, app.selected);
+    press(&mut app, "jx");
+    assert_eq!(Status::Done, app.selected_todo().unwrap().status);
+
+    // A sequence that goes nowhere is dropped, `gdd` deletes
+    press(&mut app, "gdd");
+    assert_eq!(Some("Removed todo: Milk".to_string()), app.message);
+    assert_eq!(3, app.rows.len());
+    press(&mut app, "u");
+    assert_eq!(Some("Undid: tui rm 3".to_string()), app.message);
+    press(&mut app, "u");
+    assert_eq!(Status::Todo, app.todos[2].status);
+    assert_eq!(
+      "x done  h fold  / filter  tab details  gr group  u undo  m mouse  q quit",
+      app.hints()
+    );
+  }
+
+  #[test]
+  fn clicks_select_and_complete() {
+    let (conn, mut app) = setup();
+    let mut terminal = Terminal::new(TestBackend::new(40, 8)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let click = |column, row| MouseEvent {
+      kind: MouseEventKind::Down(MouseButton::Left),
+      column,
+      row,
+      modifiers: event::KeyModifiers::NONE,
+    };
+
+    // The first row of the list is on the second line, below the border
+    app.click(click(20, 4), &conn).unwrap();
+    assert_eq!(3, app.selected);
+    assert_eq!(Status::Todo, app.selected_todo().unwrap().status);
+    app.click(click(3, 4), &conn).unwrap();
+    assert_eq!(Status::Done, app.selected_todo().unwrap().status);
+
+    // Clicking the arrow folds a group, clicks below the rows do nothing
+    app.click(click(1, 3), &conn).unwrap();
+    assert_eq!(3, app.rows.len());
+    app.click(click(5, 6), &conn).unwrap();
+    assert_eq!(2, app.selected);
+
+    let scroll = MouseEvent {
+      kind: MouseEventKind::ScrollUp,
+      ..click(0, 0)
+    };
+    app.click(scroll, &conn).unwrap();
+    assert_eq!(0, app.selected);
+  }
+}
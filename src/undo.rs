@@ -0,0 +1,345 @@
+use crate::history::{Entry, format_entry};
+use crate::prompt;
+use chrono::{DateTime, Local, Utc};
+use console::style;
+use dialoguer::Confirm;
+use rusqlite::types::Value;
+use rusqlite::{Connection, Row};
+use std::error::Error;
+
+/// Columns an undo may write back, history entries naming anything else are refused
+const FIELDS: &[&str] = &[
+  "body",
+  "status",
+  "waiting_on",
+  "follow_up",
+  "estimate",
+  "priority",
+  "due",
+  "bucket",
+  "project",
+  "snoozed_until",
+  "focused",
+  "notes",
+  "parent_id",
+  "tags",
+  "scheduled",
+  "pinned",
+  "expires",
+  "habit",
+  "reference",
+];
+
+/// How many operations `--list` shows
+const LIST_LIMIT: usize = 10;
+
+#[derive(Debug)]
+struct Operation {
+  id: usize,
+  command: String,
+  created_at: DateTime<Utc>,
+}
+
+impl Operation {
+  fn from_row(row: &Row) -> Result<Operation, rusqlite::Error> {
+    Ok(Operation {
+      id: row.get("id")?,
+      command: row.get("command")?,
+      created_at: row.get("created_at")?,
+    })
+  }
+
+  fn header(&self) -> String {
+    format!(
+      "#{} {}  {}",
+      self.id,
+      self
+        .created_at
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M"),
+      self.command
+    )
+  }
+}
+
+/// Group the history written since the last operation under `command`
+pub fn record(command: &str, undoable: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let pending: bool = conn.query_row(
+    "SELECT EXISTS(SELECT 1 FROM history WHERE operation_id IS NULL)",
+    [],
+    |row| row.get(0),
+  )?;
+  if pending {
+    conn.execute(
+      "INSERT INTO operations (command, undoable) VALUES (?1, ?2)",
+      (command, undoable),
+    )?;
+    conn.execute(
+      "UPDATE history SET operation_id = last_insert_rowid() WHERE operation_id IS NULL",
+      (),
+    )?;
+  }
+  Ok(())
+}
+
+/// Operations that can still be undone, newest first, stopping at `limit` or at `to`
+fn collect_operations(
+  limit: Option<usize>,
+  to: Option<usize>,
+  conn: &Connection,
+) -> Result<Vec<Operation>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT * FROM operations WHERE undoable and not undone and id >= ?1
+     ORDER BY id DESC LIMIT ?2",
+  )?;
+  let operations = stmt
+    .query_map(
+      (to.unwrap_or(0), limit.map_or(-1, |limit| limit as i64)),
+      Operation::from_row,
+    )?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<Operation>>();
+
+  if let Some(to) = to
+    && operations.last().is_none_or(|operation| operation.id != to)
+  {
+    return Err(format!("Operation {} can't be undone", to).into());
+  }
+  Ok(operations)
+}
+
+fn collect_entries(operation: &Operation, conn: &Connection) -> Result<Vec<Entry>, Box<dyn Error>> {
+  let mut stmt = conn.prepare("SELECT * FROM history WHERE operation_id = ?1 ORDER BY id")?;
+  let entries = stmt
+    .query_map((operation.id,), Entry::from_row)?
+    .filter_map(|s| s.ok())
+    .collect::<Vec<Entry>>();
+  Ok(entries)
+}
+
+/// Put a deleted todo back with its old id, dependencies are not restored
+fn restore(entry: &Entry, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let Value::Text(old) = &entry.old else {
+    return Err(format!("Can't restore todo {}", entry.todo_id).into());
+  };
+  let taken: bool = conn.query_row(
+    "SELECT EXISTS(SELECT 1 FROM todos WHERE id = ?1)",
+    (entry.todo_id,),
+    |row| row.get(0),
+  )?;
+  if taken {
+    return Err(format!("Id {} is used by another todo now", entry.todo_id).into());
+  }
+
+  // Entries from before the operation log only have the body
+  let Ok(serde_json::Value::Object(row)) = serde_json::from_str(old) else {
+    conn.execute(
+      "INSERT INTO todos (id, body) VALUES (?1, ?2)",
+      (entry.todo_id, old),
+    )?;
+    return Ok(());
+  };
+  let columns = row
+    .keys()
+    .filter(|column| {
+      FIELDS.contains(&column.as_str())
+        || ["created_at", "updated_at", "uuid"].contains(&column.as_str())
+    })
+    .collect::<Vec<&String>>();
+  let values = columns
+    .iter()
+    .map(|column| format!("json_extract(?2, '$.{}')", column))
+    .collect::<Vec<String>>();
+  conn.execute(
+    &format!(
+      "INSERT INTO todos (id, {}) VALUES (?1, {})",
+      columns
+        .iter()
+        .map(|column| column.as_str())
+        .collect::<Vec<&str>>()
+        .join(", "),
+      values.join(", ")
+    ),
+    (entry.todo_id, old),
+  )?;
+  Ok(())
+}
+
+fn revert(entry: &Entry, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  match entry.field.as_str() {
+    "created" => {
+      conn.execute("DELETE FROM todos WHERE id = ?1", (entry.todo_id,))?;
+    }
+    "deleted" => restore(entry, conn)?,
+    field if FIELDS.contains(&field) => {
+      let changed = conn.execute(
+        &format!("UPDATE todos SET {} = ?1 WHERE id = ?2", field),
+        (&entry.old, entry.todo_id),
+      )?;
+      if changed == 0 {
+        return Err(format!("Todo {} does not exist anymore", entry.todo_id).into());
+      }
+    }
+    field => return Err(format!("Don't know how to undo a change to {}", field).into()),
+  }
+  Ok(())
+}
+
+/// Revert `operations` newest first, the undo itself is recorded but can't be undone
+fn apply(operations: &[Operation], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let tx = conn.unchecked_transaction()?;
+  for operation in operations {
+    for entry in collect_entries(operation, &tx)?.iter().rev() {
+      revert(entry, &tx)?;
+    }
+    tx.execute(
+      "UPDATE operations SET undone = true WHERE id = ?1",
+      (operation.id,),
+    )?;
+  }
+  let ids = operations
+    .iter()
+    .map(|operation| format!("#{}", operation.id))
+    .collect::<Vec<String>>();
+  record(&format!("undo {}", ids.join(" ")), false, &tx)?;
+  tx.commit()?;
+  Ok(())
+}
+
+fn preview(operations: &[Operation], conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for operation in operations {
+    println!("{}", style(operation.header()).bold());
+    for entry in collect_entries(operation, conn)? {
+      println!("  [{}] {}", entry.todo_id, format_entry(&entry));
+    }
+  }
+  Ok(())
+}
+
+/// Show the operations that can be undone
+pub fn list(conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let operations = collect_operations(Some(LIST_LIMIT), None, conn)?;
+  if operations.is_empty() {
+    println!("Nothing to undo!");
+  }
+  for operation in operations {
+    println!("{}", operation.header());
+  }
+  Ok(())
+}
+
+/// Revert the newest operation without asking, returns the command it came from
+pub fn undo_last(conn: &Connection) -> Result<Option<String>, Box<dyn Error>> {
+  let operations = collect_operations(Some(1), None, conn)?;
+  apply(&operations, conn)?;
+  Ok(
+    operations
+      .first()
+      .map(|operation| operation.command.clone()),
+  )
+}
+
+/// Revert the last `last` operations, or every one back to and including `to`
+pub fn undo(
+  last: usize,
+  to: Option<usize>,
+  dry_run: bool,
+  yes: bool,
+  conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+  let limit = if to.is_some() { None } else { Some(last) };
+  let operations = collect_operations(limit, to, conn)?;
+  if operations.is_empty() {
+    println!("Nothing to undo!");
+    return Ok(());
+  }
+
+  println!("Reverting {} operations:", operations.len());
+  preview(&operations, conn)?;
+  if dry_run {
+    println!("Dry run, nothing reverted!");
+    return Ok(());
+  }
+  if !yes {
+    prompt::require("pass --yes to revert without asking")?;
+  }
+
+  // Untested segment starts, this part needs interactivity
+  if !yes && !Confirm::new().with_prompt("Revert?").interact()? {
+    println!("Cancelled, nothing changed!");
+    return Ok(());
+  }
+  // Untested segment ends
+
+  apply(&operations, conn)?;
+  println!("Reverted {} operations!", operations.len());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::status::Status;
+  use crate::{AddOptions, Todo, add, collect_todos_all, rm, set_status};
+
+  fn setup() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let options = AddOptions {
+      estimate: Some(30),
+      ..Default::default()
+    };
+    _ = add(vec!["Milk".to_string()], &options, &conn);
+    _ = record("add Milk", true, &conn);
+    _ = set_status(collect_todos_all(&conn).unwrap(), Status::Done, &conn);
+    _ = record("status done", true, &conn);
+    _ = rm(collect_todos_all(&conn).unwrap(), &conn);
+    _ = record("rm", true, &conn);
+    conn
+  }
+
+  #[test]
+  fn undo_last_restores_deleted_todo() {
+    let conn = setup();
+    _ = undo(1, None, false, true, &conn);
+
+    let todos = collect_todos_all(&conn).unwrap();
+    assert_eq!(
+      vec![Todo {
+        body: "Milk".to_string(),
+        id: 1,
+        status: Status::Done,
+        estimate: Some(30),
+        ..Default::default()
+      }],
+      todos
+    );
+    assert!(todos[0].created_at.is_some());
+  }
+
+  #[test]
+  fn undo_to_reverts_back_to_operation() {
+    let conn = setup();
+    _ = undo(1, Some(2), false, true, &conn);
+    assert_eq!(Status::Todo, collect_todos_all(&conn).unwrap()[0].status);
+
+    // Undone operations and the undo itself are skipped from now on
+    _ = undo(1, None, false, true, &conn);
+    assert!(collect_todos_all(&conn).unwrap().is_empty());
+    assert!(collect_operations(Some(1), None, &conn).unwrap().is_empty());
+  }
+
+  #[test]
+  fn undo_to_unknown_operation() {
+    let conn = setup();
+    assert!(undo(1, Some(9), false, true, &conn).is_err());
+  }
+
+  #[test]
+  fn dry_run_changes_nothing() {
+    let conn = setup();
+    _ = undo(3, None, true, true, &conn);
+    assert!(collect_todos_all(&conn).unwrap().is_empty());
+  }
+}
@@ -0,0 +1,172 @@
+use crate::ascii;
+use crate::date;
+use crate::duration::format_duration;
+use chrono::{DateTime, Days, Local, NaiveDate, Utc};
+use console::style;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Weeks the rolling average spans, the shown week included
+const ROLLING_WEEKS: usize = 4;
+
+/// Width of the bar of the busiest week
+const BAR_WIDTH: usize = 30;
+
+/// Local day a todo was completed on and its estimate in minutes
+type Completion = (NaiveDate, Option<u32>);
+
+/// Every done todo, removed ones included
+fn collect_completions(conn: &Connection) -> Result<Vec<Completion>, Box<dyn Error>> {
+  let mut stmt = conn.prepare(
+    "SELECT max(history.changed_at), CASE WHEN todos.id is null THEN
+       (SELECT json_extract(old, '$.estimate') FROM history AS deleted
+        WHERE deleted.todo_id = history.todo_id and field = 'deleted' and json_valid(old)
+        ORDER BY id DESC LIMIT 1)
+       ELSE todos.estimate END
+     FROM history LEFT JOIN todos ON todos.id = history.todo_id
+     WHERE field = 'status' and new = 'done' and (todos.id is null or todos.status is 'done')
+     GROUP BY history.todo_id",
+  )?;
+  let completions = stmt
+    .query_map([], |row| {
+      let at: DateTime<Utc> = row.get(0)?;
+      Ok((at.with_timezone(&Local).date_naive(), row.get(1)?))
+    })?
+    .collect::<Result<Vec<Completion>, _>>()?;
+  Ok(completions)
+}
+
+/// Completed todos, or their estimated minutes, per week by its first day
+fn per_week(completions: &[Completion], estimates: bool) -> HashMap<NaiveDate, u32> {
+  let mut weeks = HashMap::new();
+  for (day, estimate) in completions {
+    let amount = match estimates {
+      true => estimate.unwrap_or(0),
+      false => 1,
+    };
+    *weeks.entry(date::start_of_week(*day)).or_default() += amount;
+  }
+  weeks
+}
+
+/// A line per week, oldest first, ending with the week of `today`
+fn render(
+  completions: &[Completion],
+  today: NaiveDate,
+  weeks: usize,
+  estimates: bool,
+) -> Vec<String> {
+  let totals = per_week(completions, estimates);
+  let start = date::start_of_week(today);
+  let total = |back: usize| {
+    totals
+      .get(&(start - Days::new(7 * back as u64)))
+      .copied()
+      .unwrap_or(0)
+  };
+  let format = |amount: u32| match estimates {
+    true => format_duration(amount),
+    false => amount.to_string(),
+  };
+  let busiest = (0..weeks).map(total).max().unwrap_or(0).max(1);
+
+  let mut lines = vec![format!(
+    "{:<12}{:>8}{:>9}",
+    "Week of",
+    if estimates { "Effort" } else { "Done" },
+    format!("Avg({})", ROLLING_WEEKS)
+  )];
+  for back in (0..weeks).rev() {
+    let amount = total(back);
+    let rolling = (back..back + ROLLING_WEEKS).map(total).sum::<u32>();
+    let rolling = match estimates {
+      true => format_duration(rolling / ROLLING_WEEKS as u32),
+      false => format!("{:.1}", rolling as f64 / ROLLING_WEEKS as f64),
+    };
+    let bar = "█".repeat(amount as usize * BAR_WIDTH / busiest as usize);
+    let mut line = format!(
+      "{:<12}{:>8}{:>9} {}",
+      date::format(start - Days::new(7 * back as u64)),
+      format(amount),
+      rolling,
+      style(bar).green()
+    );
+    if back == 0 {
+      line.push_str(&style(" (so far)").dim().to_string());
+    }
+    lines.push(line.trim_end().to_string());
+  }
+  lines
+}
+
+pub fn velocity(weeks: usize, estimates: bool, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  for line in render(
+    &collect_completions(conn)?,
+    Local::now().date_naive(),
+    weeks.max(1),
+    estimates,
+  ) {
+    println!("{}", ascii::text(&line));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn day(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+  }
+
+  #[test]
+  fn weekly_totals_and_rolling_average() {
+    let completions = vec![
+      (day(2, 20), Some(60)),
+      (day(2, 21), None),
+      (day(2, 26), Some(30)),
+      (day(3, 1), Some(90)),
+      (day(3, 3), None),
+      (day(3, 4), Some(120)),
+    ];
+    // A Wednesday
+    let lines = render(&completions, day(3, 6), 3, false);
+    assert_eq!(
+      vec![
+        "Week of         Done   Avg(4)",
+        "2024-02-19         2      0.5 ████████████████████",
+        "2024-02-26         3      1.2 ██████████████████████████████",
+        "2024-03-04         1      1.5 ██████████ (so far)",
+      ],
+      lines
+    );
+
+    let lines = render(&completions, day(3, 6), 1, true);
+    assert!(lines[1].starts_with("2024-03-04        2h    1h15m"));
+  }
+
+  #[test]
+  fn completions_come_from_history() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = crate::db::create_db(&conn);
+    let options = crate::AddOptions {
+      estimate: Some(30),
+      ..Default::default()
+    };
+    _ = crate::add(
+      vec!["Milk".to_string(), "Carl".to_string()],
+      &options,
+      &conn,
+    );
+    let todos = crate::collect_todos_all(&conn).unwrap();
+    _ = crate::set_status(todos.clone(), crate::status::Status::Done, &conn);
+    _ = crate::rm(vec![todos[0].clone()], &conn);
+    let estimates = collect_completions(&conn)
+      .unwrap()
+      .into_iter()
+      .map(|(_, estimate)| estimate)
+      .collect::<Vec<Option<u32>>>();
+    assert_eq!(vec![Some(30), Some(30)], estimates);
+  }
+}
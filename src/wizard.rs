@@ -0,0 +1,378 @@
+use crate::formats::Parsed;
+use crate::strategy::Strategy;
+use crate::table::{Field, Table};
+use crate::{Todo, annotations, ascii, dedupe, links};
+use chrono::Local;
+use dialoguer::{Confirm, Select, theme::ColorfulTheme};
+use rusqlite::{Connection, params};
+use std::error::Error;
+
+/// Todos shown before asking to go on
+const PREVIEW_ROWS: usize = 5;
+
+/// Values shown next to each column
+const SAMPLES: usize = 3;
+
+/// What happens to an imported todo
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+  Create,
+  /// Fill in the existing todo with this id
+  Merge(usize),
+  /// Replace the fields of the existing todo with this id
+  Overwrite(usize),
+  Skip,
+}
+
+/// The id of the existing todo that `todo` was exported from, if any
+pub fn same_uuid(todo: &Todo, conn: &Connection) -> Result<Option<usize>, Box<dyn Error>> {
+  match &todo.uuid {
+    Some(uuid) => dedupe::find_uuid(uuid, conn),
+    None => Ok(None),
+  }
+}
+
+/// The id of the existing todo `todo` likely duplicates, the same uuid or ref is a sure
+/// match and otherwise the body is compared
+pub fn find_existing(todo: &Todo, conn: &Connection) -> Result<Option<usize>, Box<dyn Error>> {
+  if let Some(id) = same_uuid(todo, conn)? {
+    return Ok(Some(id));
+  }
+  if let Some(reference) = &todo.reference
+    && let Some(id) = links::referenced(reference, conn)?
+  {
+    return Ok(Some(id));
+  }
+  dedupe::find_same(&todo.body, conn)
+}
+
+/// For every imported todo the id of the existing todo it likely duplicates
+pub fn find_duplicates(
+  todos: &[Todo],
+  conn: &Connection,
+) -> Result<Vec<Option<usize>>, Box<dyn Error>> {
+  todos.iter().map(|todo| find_existing(todo, conn)).collect()
+}
+
+/// Fill in what `into` lacks from `todo`, the tags of both are kept
+pub fn merge(into: &Todo, todo: &Todo, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut tags = into.tags.clone();
+  tags.extend(&todo.tags.0);
+  let mut stmt = conn.prepare_cached(
+    "UPDATE todos SET due = coalesce(due, ?1), priority = coalesce(priority, ?2),
+       project = coalesce(project, ?3), notes = coalesce(notes, ?4),
+       estimate = coalesce(estimate, ?5), tags = ?6 WHERE id is ?7",
+  )?;
+  stmt.execute((
+    todo.due,
+    todo.priority,
+    &todo.project,
+    &todo.notes,
+    todo.estimate,
+    &tags,
+    into.id,
+  ))?;
+  Ok(())
+}
+
+/// Replace what `into` has with the fields of `todo`, the tags too. Only the id, uuid,
+/// parent, position and when it was created stay those of `into`.
+pub fn overwrite(into: &Todo, todo: &Todo, conn: &Connection) -> Result<(), Box<dyn Error>> {
+  let mut stmt = conn.prepare_cached(
+    "UPDATE todos SET body = ?1, status = ?2, waiting_on = ?3, follow_up = ?4, estimate = ?5,
+       priority = ?6, due = ?7, bucket = ?8, project = ?9, snoozed_until = ?10, focused = ?11,
+       notes = ?12, tags = ?13, scheduled = ?14, pinned = ?15, expires = ?16, habit = ?17,
+       reference = ?18, body_hash = ?19 WHERE id is ?20",
+  )?;
+  stmt.execute(params![
+    todo.body,
+    todo.status,
+    todo.waiting_on,
+    todo.follow_up,
+    todo.estimate,
+    todo.priority,
+    todo.due,
+    todo.bucket,
+    todo.project,
+    todo.snoozed_until,
+    todo.focused,
+    todo.notes,
+    todo.tags,
+    todo.scheduled,
+    todo.pinned,
+    todo.expires,
+    todo.habit,
+    todo.reference,
+    dedupe::body_hash(&todo.body),
+    into.id,
+  ])?;
+  Ok(())
+}
+
+/// A line per column with the field it fills and a few of its values
+fn column_lines(table: &Table, mapping: &[Field]) -> Vec<String> {
+  let width = table
+    .columns
+    .iter()
+    .map(|column| column.chars().count())
+    .max()
+    .unwrap_or(0);
+  table
+    .columns
+    .iter()
+    .zip(mapping)
+    .enumerate()
+    .map(|(index, (column, field))| {
+      let samples = table
+        .rows
+        .iter()
+        .map(|row| row[index].trim())
+        .filter(|value| !value.is_empty())
+        .take(SAMPLES)
+        .collect::<Vec<&str>>();
+      let line = format!(
+        "{:<width$} → {:<8}  e.g. {}",
+        column,
+        field.to_string(),
+        samples.join(", ")
+      );
+      ascii::text(line.trim_end())
+    })
+    .collect()
+}
+
+fn todo_line(todo: &Todo) -> String {
+  ascii::text(&format!(
+    "{} {}{}",
+    todo.status.glyph(),
+    todo.body,
+    annotations(todo, Local::now().date_naive())
+  ))
+}
+
+// Untested segment starts, this part needs interactivity
+fn ask_mapping(table: &Table, theme: &ColorfulTheme) -> Result<Vec<Field>, Box<dyn Error>> {
+  let mut mapping = table.guess_mapping();
+  loop {
+    let mut items = vec!["Use this mapping".to_string()];
+    items.extend(column_lines(table, &mapping));
+    let choice = Select::with_theme(theme)
+      .with_prompt("Pick a column to change what it fills in")
+      .default(0)
+      .items(&items)
+      .interact()?;
+    if choice == 0 {
+      if mapping.contains(&Field::Body) {
+        return Ok(mapping);
+      }
+      println!("One column has to be the body!");
+      continue;
+    }
+    let column = choice - 1;
+    let field = Select::with_theme(theme)
+      .with_prompt(format!("Column {}", table.columns[column]))
+      .default(
+        Field::ALL
+          .iter()
+          .position(|f| *f == mapping[column])
+          .unwrap_or(0),
+      )
+      .items(&Field::ALL)
+      .interact()?;
+    mapping[column] = Field::ALL[field];
+  }
+}
+
+fn ask_resolution(
+  todo: &Todo,
+  existing: &Todo,
+  theme: &ColorfulTheme,
+) -> Result<Resolution, Box<dyn Error>> {
+  println!("Imported: {}", todo_line(todo));
+  println!("Existing: {}", todo_line(existing));
+  let choice = Select::with_theme(theme)
+    .with_prompt("This looks like a duplicate")
+    .default(0)
+    .items(&[
+      "Merge into the existing todo",
+      "Overwrite the existing todo",
+      "Skip",
+      "Create anyway",
+    ])
+    .interact()?;
+  Ok(match choice {
+    0 => Resolution::Merge(existing.id),
+    1 => Resolution::Overwrite(existing.id),
+    2 => Resolution::Skip,
+    _ => Resolution::Create,
+  })
+}
+
+/// Ask how columns map to fields, show the first todos and ask what to do about each likely
+/// duplicate of an `existing` todo, unless a `strategy` settles them all
+pub fn wizard(
+  parsed: Parsed,
+  existing: &[Todo],
+  strategy: Option<Strategy>,
+  conn: &Connection,
+) -> Result<Vec<(Todo, Resolution)>, Box<dyn Error>> {
+  let theme = ColorfulTheme::default();
+  let todos = match parsed {
+    Parsed::Todos(todos) => todos,
+    Parsed::Table(table) => table.todos(&ask_mapping(&table, &theme)?),
+  };
+  if todos.is_empty() {
+    return Ok(vec![]);
+  }
+
+  println!("{} todos to import:", todos.len());
+  for todo in todos.iter().take(PREVIEW_ROWS) {
+    println!("  {}", todo_line(todo));
+  }
+  if todos.len() > PREVIEW_ROWS {
+    println!("  and {} more", todos.len() - PREVIEW_ROWS);
+  }
+  if !Confirm::with_theme(&theme)
+    .with_prompt("Import them?")
+    .default(true)
+    .interact()?
+  {
+    return Ok(vec![]);
+  }
+
+  let duplicates = find_duplicates(&todos, conn)?;
+  let mut plan = vec![];
+  for (todo, duplicate) in todos.into_iter().zip(duplicates) {
+    let resolution = match duplicate.and_then(|id| existing.iter().find(|own| own.id == id)) {
+      Some(own) => match strategy {
+        Some(strategy) => strategy.resolve(own.id),
+        None => ask_resolution(&todo, own, &theme)?,
+      },
+      None => Resolution::Create,
+    };
+    plan.push((todo, resolution));
+  }
+  Ok(plan)
+}
+// Untested segment ends
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::db::create_db;
+  use crate::priority::Priority;
+  use crate::storage::insert_todo;
+  use crate::tags::Tags;
+  use crate::{AddOptions, add, collect_todos_all};
+
+  #[test]
+  fn duplicates_ignore_case_and_punctuation() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let uuid = Some("e3b0c442-98fc-4c14-9afb-f4c8996fb924".to_string());
+    for (id, body, uuid) in [(4, "Call mom", None), (7, "call  MOM!", uuid.clone())] {
+      let todo = Todo {
+        id,
+        body: body.to_string(),
+        uuid,
+        ..Default::default()
+      };
+      insert_todo(&todo, &conn).unwrap();
+    }
+    let todos = vec![
+      Todo {
+        body: "Call Mom.".to_string(),
+        ..Default::default()
+      },
+      Todo {
+        body: "Call dad".to_string(),
+        ..Default::default()
+      },
+      Todo {
+        body: "Call mom again".to_string(),
+        uuid,
+        ..Default::default()
+      },
+    ];
+    assert_eq!(
+      vec![Some(4), None, Some(7)],
+      find_duplicates(&todos, &conn).unwrap()
+    );
+  }
+
+  #[test]
+  fn merge_fills_in_missing_fields() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let options = AddOptions {
+      priority: Some(Priority::Low),
+      tags: vec!["home".to_string()],
+      ..Default::default()
+    };
+    _ = add(vec!["Pay bills".to_string()], &options, &conn);
+    let existing = collect_todos_all(&conn).unwrap();
+    let imported = Todo {
+      body: "pay bills".to_string(),
+      priority: Some(Priority::High),
+      project: Some("House".to_string()),
+      tags: Tags(vec!["money".to_string(), "home".to_string()]),
+      ..Default::default()
+    };
+    merge(&existing[0], &imported, &conn).unwrap();
+    let merged = &collect_todos_all(&conn).unwrap()[0];
+    assert_eq!("Pay bills", merged.body);
+    assert_eq!(Some(Priority::Low), merged.priority);
+    assert_eq!(Some("House".to_string()), merged.project);
+    assert_eq!("#home #money", merged.tags.to_string());
+  }
+
+  #[test]
+  fn overwrite_replaces_every_field() {
+    let conn = Connection::open_in_memory().unwrap();
+    _ = create_db(&conn);
+    let existing = Todo {
+      body: "Pay bills".to_string(),
+      waiting_on: Some("Bob".to_string()),
+      pinned: true,
+      reference: Some("HOME-1".to_string()),
+      ..Default::default()
+    };
+    let id = insert_todo(&existing, &conn).unwrap();
+    let existing = collect_todos_all(&conn).unwrap().remove(0);
+    let imported = Todo {
+      body: "Pay the bills".to_string(),
+      priority: Some(Priority::High),
+      ..Default::default()
+    };
+    overwrite(&existing, &imported, &conn).unwrap();
+    let overwritten = collect_todos_all(&conn).unwrap().remove(0);
+    assert_eq!(
+      Todo {
+        id,
+        uuid: existing.uuid,
+        created_at: overwritten.created_at,
+        updated_at: overwritten.updated_at,
+        ..imported
+      },
+      overwritten
+    );
+  }
+
+  #[test]
+  fn columns_show_their_field_and_samples() {
+    let table = Table {
+      columns: vec!["Title".to_string(), "Due date".to_string()],
+      rows: vec![
+        vec!["Milk".to_string(), String::new()],
+        vec!["Carl".to_string(), "2024-03-05".to_string()],
+      ],
+    };
+    assert_eq!(
+      vec![
+        "Title    → body      e.g. Milk, Carl",
+        "Due date → due       e.g. 2024-03-05",
+      ],
+      column_lines(&table, &table.guess_mapping())
+    );
+  }
+}